@@ -0,0 +1,32 @@
+/// Platform backend that reports which application has focus
+pub trait ActiveWindow {
+    /// Name of the windowing system, for log messages
+    fn backend(&self) -> &'static str;
+
+    /// `(app_name, window_class)` of the focused window, if one can be determined
+    fn active_window(&self) -> Option<(String, String)>;
+}
+
+/// Connect to the windowing system of the platform this was built for
+#[cfg(target_os = "linux")]
+pub fn connect() -> Result<Box<dyn ActiveWindow>, String> {
+    Ok(Box::new(crate::x11_window::X11Window::connect()?))
+}
+
+/// Connect to the windowing system of the platform this was built for
+#[cfg(target_os = "macos")]
+pub fn connect() -> Result<Box<dyn ActiveWindow>, String> {
+    Ok(Box::new(crate::macos_window::MacWindow))
+}
+
+/// Connect to the windowing system of the platform this was built for
+#[cfg(target_os = "windows")]
+pub fn connect() -> Result<Box<dyn ActiveWindow>, String> {
+    Ok(Box::new(crate::windows_window::WindowsWindow))
+}
+
+/// Connect to the windowing system of the platform this was built for
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn connect() -> Result<Box<dyn ActiveWindow>, String> {
+    Err("Active window tracking is only supported on Linux (X11), macOS and Windows".to_string())
+}
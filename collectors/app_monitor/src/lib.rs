@@ -0,0 +1,10 @@
+// Platform backends that report which application has focus; the `app_monitor`
+// binary polls them and publishes focus changes as events.
+
+pub mod active_window;
+#[cfg(target_os = "macos")]
+pub mod macos_window;
+#[cfg(target_os = "windows")]
+pub mod windows_window;
+#[cfg(target_os = "linux")]
+pub mod x11_window;
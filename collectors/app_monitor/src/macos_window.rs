@@ -0,0 +1,52 @@
+use crate::active_window::ActiveWindow;
+use core_foundation::base::{CFType, TCFType};
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::CFNumber;
+use core_foundation::string::CFString;
+use core_graphics::window::{
+    copy_window_info, kCGNullWindowID, kCGWindowLayer, kCGWindowListExcludeDesktopElements,
+    kCGWindowListOptionOnScreenOnly, kCGWindowOwnerName,
+};
+
+/// Focused application from the on-screen window list (`CGWindowListCopyWindowInfo`)
+///
+/// The list is ordered front to back, so the first window on the normal layer
+/// belongs to the frontmost app. Owner names need no Accessibility or Screen
+/// Recording permission. macOS has no `WM_CLASS`, so the owner name is reported
+/// as both the app name and the window class.
+pub struct MacWindow;
+
+impl ActiveWindow for MacWindow {
+    fn backend(&self) -> &'static str {
+        "the macOS window server"
+    }
+    
+    fn active_window(&self) -> Option<(String, String)> {
+        let windows = copy_window_info(
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+        )?;
+        
+        for window in windows.iter() {
+            // Entries are borrowed from the array, hence the get rule
+            let info: CFDictionary<CFString, CFType> =
+                unsafe { CFDictionary::wrap_under_get_rule(*window as CFDictionaryRef) };
+            
+            // Layer 0 holds normal app windows; menu bar, dock and overlays sit above it
+            let layer = info
+                .find(unsafe { CFString::wrap_under_get_rule(kCGWindowLayer) })
+                .and_then(|value| value.downcast::<CFNumber>())
+                .and_then(|number| number.to_i32());
+            if layer != Some(0) {
+                continue;
+            }
+            
+            let owner = info
+                .find(unsafe { CFString::wrap_under_get_rule(kCGWindowOwnerName) })
+                .and_then(|value| value.downcast::<CFString>())?
+                .to_string();
+            return Some((owner.clone(), owner));
+        }
+        None
+    }
+}
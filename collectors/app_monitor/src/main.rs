@@ -1,126 +1,203 @@
+use app_monitor::active_window;
+use common::{
+    drain_wal, event_bus, record_event, watch_control_blocking, Config, EventBusKind, EventClock,
+    EventWal, Heartbeat, RedisLink, HEARTBEAT_INTERVAL_SECS,
+};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH, Duration};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::thread;
-use x11rb::connection::Connection;
-use x11rb::protocol::xproto::*;
-use x11rb::rust_connection::RustConnection;
+
+/// Prefix for this collector's log lines
+const LABEL: &str = "App Monitor";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct AppEvent {
     #[serde(rename = "type")]
     event_type: String,
     ts: u128,
+    dev_mode: bool,  // Set while SECLYZER_DEV_MODE is on, so self-testing is filtered out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,  // USER_ID of this host's user, so extractors keep users apart
     app_name: String,
     window_class: String,
     event: String,  // "focus"
 }
 
-fn get_active_window_info(conn: &RustConnection, screen_num: usize) -> Option<(String, String)> {
-    let screen = &conn.setup().roots[screen_num];
-    
-    // Get the _NET_ACTIVE_WINDOW property
-    let net_active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW")
-        .ok()?
-        .reply()
-        .ok()?
-        .atom;
-    
-    let active_window = conn.get_property(
-        false,
-        screen.root,
-        net_active_window,
-        AtomEnum::WINDOW,
-        0,
-        1,
-    ).ok()?.reply().ok()?;
-    
-    if active_window.value.is_empty() {
-        return None;
-    }
-    
-    let window_id = u32::from_ne_bytes(active_window.value[0..4].try_into().ok()?);
-    
-    // Get WM_CLASS property
-    let wm_class_atom = conn.intern_atom(false, b"WM_CLASS")
-        .ok()?
-        .reply()
-        .ok()?
-        .atom;
-    
-    let wm_class = conn.get_property(
-        false,
-        window_id,
-        wm_class_atom,
-        AtomEnum::STRING,
-        0,
-        1024,
-    ).ok()?.reply().ok()?;
-    
-    let class_str = String::from_utf8_lossy(&wm_class.value);
-    let parts: Vec<&str> = class_str.split('\0').filter(|s| !s.is_empty()).collect();
-    
-    let app_name = parts.get(0).unwrap_or(&"Unknown").to_string();
-    let window_class = parts.get(1).unwrap_or(&"Unknown").to_string();
-    
-    Some((app_name, window_class))
+/// Announce on the heartbeat channel that this collector is still running, and whether it's paused
+fn send_heartbeat(
+    con: &mut redis::Connection,
+    channel: &str,
+    source: &str,
+    paused: bool,
+) -> redis::RedisResult<()> {
+    let json = serde_json::to_string(&Heartbeat::now(source).with_paused(paused))
+        .expect("Failed to serialize heartbeat");
+    con.publish(channel, json)
 }
 
 fn main() {
     println!("[App Monitor] Starting...");
     
-    // Connect to Redis
-    let redis_client = redis::Client::open("redis://127.0.0.1:6379/")
+    // Connect to the event bus; if it's down, events wait in the WAL until it's back
+    let config = Config::load().expect("Invalid configuration");
+    let redis_client = redis::Client::open(config.redis_url())
         .expect("Failed to connect to Redis");
-    let mut con = redis_client.get_connection()
-        .expect("Failed to get Redis connection");
+    let mut bus = event_bus::connect_blocking(&config, redis_client.clone())
+        .expect("Failed to set up the event bus");
+    if config.event_bus == EventBusKind::Kafka {
+        println!("[App Monitor] Publishing events to Kafka at {}", config.kafka_brokers);
+    } else if config.event_bus == EventBusKind::Unix {
+        println!(
+            "[App Monitor] Publishing events over sockets in {}, or Redis when nothing listens",
+            config.socket_dir().unwrap_or_default().display()
+        );
+    } else if bus.ready() {
+        println!("[App Monitor] Connected to Redis at {}", config.redis_url_redacted());
+    } else {
+        eprintln!(
+            "[App Monitor] Redis at {} unavailable; buffering events in the WAL",
+            config.redis_url_redacted()
+        );
+    }
     
-    println!("[App Monitor] Connected to Redis");
+    let compress = config.compress_events;
+    // Signed when a key is configured, so extractors can tell these from injected events
+    let signer = config.event_signer();
+    let channel = config.event_channel();
+    let heartbeat_channel = config.heartbeat_channel();
+    // Monotonic after startup, so time spent in an app can't come out negative
+    let clock = EventClock::new();
+    let dev_mode = config.dev_mode;
+    // Filtered apps are never reported, not even as a placeholder
+    let app_filter = config.app_filter();
+    if !app_filter.is_open() {
+        println!("[App Monitor] Leaving apps out per APP_ALLOWLIST/APP_DENYLIST");
+    }
+    if dev_mode {
+        println!("[App Monitor] Dev mode enabled; events are tagged dev_mode=true");
+    }
+    let user_id = config.user_id.clone();
+    if let Some(user_id) = &user_id {
+        println!("[App Monitor] Events are stamped user_id={}", user_id);
+    }
+    
+    // Replay whatever a previous run logged but never got acknowledged
+    let mut wal = EventWal::open(config.wal_path("app"), config.wal_max_bytes)
+        .expect("Failed to open WAL");
+    if wal.pending() > 0 {
+        println!(
+            "[App Monitor] Replaying {} undelivered events from {}",
+            wal.pending(),
+            wal.path().display()
+        );
+        drain_wal(&mut wal, bus.as_mut(), &channel, compress, LABEL);
+    }
     
-    // Connect to X11
-    let (conn, screen_num) = match RustConnection::connect(None) {
-        Ok(conn) => conn,
+    // Connect to the windowing system (X11, the macOS window server or the Windows desktop)
+    let windows = match active_window::connect() {
+        Ok(windows) => windows,
         Err(e) => {
-            eprintln!("[App Monitor] Failed to connect to X11: {:?}", e);
-            eprintln!("[App Monitor] Make sure you're running on X11 (not Wayland)");
+            eprintln!("[App Monitor] {}", e);
             return;
         }
     };
     
-    println!("[App Monitor] Connected to X11");
+    println!("[App Monitor] Connected to {}", windows.backend());
+    
+    // Pause/resume commands arrive on their own connection; the flag gates polling
+    let paused = Arc::new(AtomicBool::new(false));
+    let control_flag = paused.clone();
+    let control_client = redis_client.clone();
+    let control_channel = config.control_channel();
+    thread::spawn(move || loop {
+        let result = watch_control_blocking(&control_client, &control_channel, |message| {
+            match message {
+                Ok(command) => {
+                    let Some(pause) = command.pauses() else {
+                        return;
+                    };
+                    if control_flag.swap(pause, Ordering::SeqCst) != pause {
+                        println!("[App Monitor] {}", if pause { "Paused" } else { "Resumed" });
+                    }
+                }
+                Err(e) => eprintln!("[App Monitor] Ignoring malformed control message: {}", e),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("[App Monitor] Control channel unavailable, retrying: {:?}", e);
+        }
+        thread::sleep(Duration::from_secs(1));
+    });
+    
+    // Finish the current poll and exit cleanly on Ctrl+C / SIGTERM
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let handler_flag = shutdown.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .expect("Failed to install signal handler");
+    
     println!("[App Monitor] Monitoring active window (Ctrl+C to stop)");
     
+    // Heartbeats go to Redis whichever bus carries the events
+    let mut link = RedisLink::new(redis_client);
     let mut last_app: Option<String> = None;
+    let heartbeat_every = Duration::from_secs(HEARTBEAT_INTERVAL_SECS);
+    let mut next_heartbeat = Instant::now();
     
-    loop {
-        if let Some((app_name, window_class)) = get_active_window_info(&conn, screen_num) {
-            // Only send event if app changed
-            if last_app.as_ref() != Some(&app_name) {
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_micros();
+    while !shutdown.load(Ordering::SeqCst) {
+        if Instant::now() >= next_heartbeat {
+            let is_paused = paused.load(Ordering::SeqCst);
+            if let Some(con) = link.connection() {
+                if let Err(e) = send_heartbeat(con, &heartbeat_channel, "app", is_paused) {
+                    eprintln!("[App Monitor] Failed to send heartbeat: {:?}", e);
+                    link.reset();
+                }
+            }
+            next_heartbeat = Instant::now() + heartbeat_every;
+        }
+        
+        // Nothing is read while paused; the focused app is reported again on resume
+        if paused.load(Ordering::SeqCst) {
+            last_app = None;
+        } else if let Some((app_name, window_class)) = windows.active_window() {
+            // Only send event if app changed; focus on a filtered app leaves the last
+            // reported one in place, so coming back to it isn't a new switch
+            if last_app.as_ref() != Some(&app_name)
+                && app_filter.allows(&app_name, Some(&window_class))
+            {
+                let timestamp = clock.now_micros();
                 
                 let app_event = AppEvent {
                     event_type: "app".to_string(),
                     ts: timestamp,
+                    dev_mode,
+                    user_id: user_id.clone(),
                     app_name: app_name.clone(),
                     window_class,
                     event: "focus".to_string(),
                 };
                 
-                let json = serde_json::to_string(&app_event)
-                    .expect("Failed to serialize event");
+                let json = match &signer {
+                    Some(signer) => signer.sign(&app_event),
+                    None => serde_json::to_string(&app_event),
+                }
+                .expect("Failed to serialize event");
                 
-                let _: () = con.publish("seclyzer:events", json)
-                    .expect("Failed to publish to Redis");
+                record_event(&mut wal, &json, LABEL);
                 
                 println!("[App Monitor] App switched to: {}", app_name);
                 last_app = Some(app_name);
             }
         }
         
+        // Also drains a backlog left by an outage when the focused app hasn't changed
+        drain_wal(&mut wal, bus.as_mut(), &channel, compress, LABEL);
+        
         // Poll every 500ms
         thread::sleep(Duration::from_millis(500));
     }
+    
+    println!("[App Monitor] Shutting down");
 }
@@ -0,0 +1,70 @@
+use crate::active_window::ActiveWindow;
+use std::path::Path;
+use windows_sys::Win32::Foundation::CloseHandle;
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetClassNameW, GetForegroundWindow, GetWindowThreadProcessId,
+};
+
+/// Focused window from `GetForegroundWindow` and the image name of the process owning it
+///
+/// The app name is the executable's file stem (`chrome` for `chrome.exe`) and the
+/// window class is the Win32 class the window was registered with. Limited query
+/// access is enough to read the image name, so no elevation is needed except for
+/// processes running as another user, which report no active window.
+pub struct WindowsWindow;
+
+impl ActiveWindow for WindowsWindow {
+    fn backend(&self) -> &'static str {
+        "the Windows desktop"
+    }
+    
+    fn active_window(&self) -> Option<(String, String)> {
+        let window = unsafe { GetForegroundWindow() };
+        if window == 0 {
+            // Nothing has focus, e.g. while the desktop is locked or switching
+            return None;
+        }
+        
+        let mut pid = 0u32;
+        unsafe { GetWindowThreadProcessId(window, &mut pid) };
+        if pid == 0 {
+            return None;
+        }
+        let image = process_image_name(pid)?;
+        let app_name = Path::new(&image)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())?;
+        
+        let mut class = [0u16; 256];
+        let len = unsafe { GetClassNameW(window, class.as_mut_ptr(), class.len() as i32) };
+        let window_class = if len > 0 {
+            String::from_utf16_lossy(&class[..len as usize])
+        } else {
+            "Unknown".to_string()
+        };
+        
+        Some((app_name, window_class))
+    }
+}
+
+/// Full path of the executable a process was started from
+pub fn process_image_name(pid: u32) -> Option<String> {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process == 0 {
+        return None;
+    }
+
+    // Long-path aware, so allow more than MAX_PATH
+    let mut path = [0u16; 1024];
+    let mut len = path.len() as u32;
+    let ok = unsafe {
+        QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len)
+    };
+    unsafe { CloseHandle(process) };
+
+    (ok != 0).then(|| String::from_utf16_lossy(&path[..len as usize]))
+}
@@ -0,0 +1,77 @@
+use crate::active_window::ActiveWindow;
+use common::parse_wm_class;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+/// Focused window from `_NET_ACTIVE_WINDOW` and its `WM_CLASS`
+pub struct X11Window {
+    conn: RustConnection,
+    screen_num: usize,
+}
+
+impl X11Window {
+    pub fn connect() -> Result<Self, String> {
+        let (conn, screen_num) = RustConnection::connect(None).map_err(|e| {
+            format!(
+                "Failed to connect to X11 ({:?}); make sure you're running on X11, not Wayland",
+                e
+            )
+        })?;
+        Ok(X11Window { conn, screen_num })
+    }
+}
+
+impl ActiveWindow for X11Window {
+    fn backend(&self) -> &'static str {
+        "X11"
+    }
+    
+    fn active_window(&self) -> Option<(String, String)> {
+        let conn = &self.conn;
+        let screen = &conn.setup().roots[self.screen_num];
+        
+        // Get the _NET_ACTIVE_WINDOW property
+        let net_active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW")
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        
+        let active_window = conn.get_property(
+            false,
+            screen.root,
+            net_active_window,
+            AtomEnum::WINDOW,
+            0,
+            1,
+        ).ok()?.reply().ok()?;
+        
+        if active_window.value.is_empty() {
+            return None;
+        }
+        
+        let window_id = u32::from_ne_bytes(active_window.value[0..4].try_into().ok()?);
+        
+        // Get WM_CLASS property; any type, as some clients set it as UTF8_STRING
+        let wm_class_atom = conn.intern_atom(false, b"WM_CLASS")
+            .ok()?
+            .reply()
+            .ok()?
+            .atom;
+        
+        let wm_class = conn.get_property(
+            false,
+            window_id,
+            wm_class_atom,
+            AtomEnum::ANY,
+            0,
+            1024,
+        ).ok()?.reply().ok()?;
+        
+        Some(
+            parse_wm_class(&wm_class.value)
+                .unwrap_or_else(|| ("Unknown".to_string(), "Unknown".to_string())),
+        )
+    }
+}
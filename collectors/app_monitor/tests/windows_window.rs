@@ -0,0 +1,22 @@
+// The Windows backend against the desktop of the machine running the tests; only
+// built on Windows:
+//
+//     cargo test --test windows_window
+#![cfg(target_os = "windows")]
+
+use app_monitor::active_window::ActiveWindow;
+use app_monitor::windows_window::{process_image_name, WindowsWindow};
+use windows_sys::Win32::System::Threading::GetCurrentProcessId;
+
+#[test]
+fn resolves_own_image_name() {
+    let image = process_image_name(unsafe { GetCurrentProcessId() })
+        .expect("Failed to read this process's image name");
+    assert!(image.to_lowercase().ends_with(".exe"), "{}", image);
+}
+
+#[test]
+fn active_window_does_not_panic() {
+    // CI runners may have no interactive desktop, so any answer is fine
+    let _ = WindowsWindow.active_window();
+}
@@ -1,63 +1,233 @@
-use rdev::{listen, EventType};
+use rdev::{listen, EventType, Key};
+use common::{
+    drain_wal, event_bus, keymap, record_event, watch_control_blocking, Config, EventBusKind,
+    EventClock, EventWal, Heartbeat, RedisLink, HEARTBEAT_INTERVAL_SECS,
+};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Prefix for this collector's log lines
+const LABEL: &str = "Keyboard Collector";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct KeyboardEvent {
     #[serde(rename = "type")]
     event_type: String,
     ts: u128,  // Timestamp in microseconds
+    dev_mode: bool,  // Set while SECLYZER_DEV_MODE is on, so self-testing is filtered out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,  // USER_ID of this host's user, so extractors keep users apart
     key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<u32>,  // X11 keycode of the physical position, for layout-independent features
     event: String,  // "press" or "release"
 }
 
+/// Physical position of a key; rdev hands over the raw keycode only for keys it can't name
+fn key_code(key: Key, name: &str) -> Option<u32> {
+    match key {
+        Key::Unknown(code) => Some(code),
+        _ => keymap::code_for_key(name),
+    }
+}
+
+/// Name and position to publish for a key; privacy mode sends only its category
+fn published_key(key: Key, privacy_mode: bool) -> (String, Option<u32>) {
+    let name = format!("{:?}", key);  // Use debug format to get key name
+    if privacy_mode {
+        return (keymap::privacy_key(&name).to_string(), None);
+    }
+    let code = key_code(key, &name);
+    (name, code)
+}
+
+/// Announce on the heartbeat channel that this collector is still running, and whether it's paused
+fn send_heartbeat(
+    con: &mut redis::Connection,
+    channel: &str,
+    source: &str,
+    paused: bool,
+) -> redis::RedisResult<()> {
+    let json = serde_json::to_string(&Heartbeat::now(source).with_paused(paused))
+        .expect("Failed to serialize heartbeat");
+    con.publish(channel, json)
+}
+
 fn main() {
     println!("[Keyboard Collector] Starting...");
     
-    // Connect to Redis
-    let redis_client = redis::Client::open("redis://127.0.0.1:6379/")
+    // Connect to the event bus; if it's down, events wait in the WAL until it's back
+    let config = Config::load().expect("Invalid configuration");
+    let redis_client = redis::Client::open(config.redis_url())
         .expect("Failed to connect to Redis");
-    let mut con = redis_client.get_connection()
-        .expect("Failed to get Redis connection");
+    let mut bus = event_bus::connect_blocking(&config, redis_client.clone())
+        .expect("Failed to set up the event bus");
+    if config.event_bus == EventBusKind::Kafka {
+        println!("[Keyboard Collector] Publishing events to Kafka at {}", config.kafka_brokers);
+    } else if config.event_bus == EventBusKind::Unix {
+        println!(
+            "[Keyboard Collector] Publishing events over sockets in {}, or Redis when nothing listens",
+            config.socket_dir().unwrap_or_default().display()
+        );
+    } else if bus.ready() {
+        println!("[Keyboard Collector] Connected to Redis at {}", config.redis_url_redacted());
+    } else {
+        eprintln!(
+            "[Keyboard Collector] Redis at {} unavailable; buffering events in the WAL",
+            config.redis_url_redacted()
+        );
+    }
+    
+    let compress = config.compress_events;
+    // With a signing key set, every event carries an HMAC the extractors can check
+    let signer = config.event_signer();
+    let channel = config.event_channel();
+    let heartbeat_channel = config.heartbeat_channel();
+    // Anchored to the wall clock once, so an NTP step can't make events run backwards
+    let clock = EventClock::new();
+    let dev_mode = config.dev_mode;
+    if dev_mode {
+        println!("[Keyboard Collector] Dev mode enabled; events are tagged dev_mode=true");
+    }
+    let user_id = config.user_id.clone();
+    if let Some(user_id) = &user_id {
+        println!("[Keyboard Collector] Events are stamped user_id={}", user_id);
+    }
+    let privacy_mode = config.privacy_mode;
+    if privacy_mode {
+        println!("[Keyboard Collector] Privacy mode enabled; only key categories are published");
+    }
+    
+    // Replay whatever a previous run logged but never got acknowledged
+    let mut wal = EventWal::open(config.wal_path("keyboard"), config.wal_max_bytes)
+        .expect("Failed to open WAL");
+    if wal.pending() > 0 {
+        println!(
+            "[Keyboard Collector] Replaying {} undelivered events from {}",
+            wal.pending(),
+            wal.path().display()
+        );
+        drain_wal(&mut wal, bus.as_mut(), &channel, compress, LABEL);
+    }
+    let outbox = Arc::new(Mutex::new((wal, bus)));
+    
+    // Pause/resume commands arrive on their own connection; the flag gates publishing
+    let paused = Arc::new(AtomicBool::new(false));
+    let control_flag = paused.clone();
+    let control_client = redis_client.clone();
+    let control_channel = config.control_channel();
+    thread::spawn(move || loop {
+        let result = watch_control_blocking(&control_client, &control_channel, |message| {
+            match message {
+                Ok(command) => {
+                    let Some(pause) = command.pauses() else {
+                        return;
+                    };
+                    if control_flag.swap(pause, Ordering::SeqCst) != pause {
+                        println!("[Keyboard Collector] {}", if pause { "Paused" } else { "Resumed" });
+                    }
+                }
+                Err(e) => eprintln!("[Keyboard Collector] Ignoring malformed control message: {}", e),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("[Keyboard Collector] Control channel unavailable, retrying: {:?}", e);
+        }
+        thread::sleep(Duration::from_secs(1));
+    });
+    
+    // Stop publishing and exit cleanly on Ctrl+C / SIGTERM
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let handler_flag = shutdown.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .expect("Failed to install signal handler");
     
-    println!("[Keyboard Collector] Connected to Redis");
     println!("[Keyboard Collector] Listening for keyboard events (Ctrl+C to stop)");
     
-    // Start listening to keyboard events
-    if let Err(error) = listen(move |event| {
-        match event.event_type {
-            EventType::KeyPress(key) | EventType::KeyRelease(key) => {
-                // Get current timestamp in microseconds
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_micros();
+    // Start listening to keyboard events; rdev::listen never returns, so it gets
+    // its own thread while main waits for shutdown
+    let listener_flag = shutdown.clone();
+    let listener_paused = paused.clone();
+    let listener_outbox = outbox.clone();
+    let listener_channel = channel.clone();
+    thread::spawn(move || {
+        let callback_flag = listener_flag.clone();
+        if let Err(error) = listen(move |event| {
+            if callback_flag.load(Ordering::Relaxed) || listener_paused.load(Ordering::Relaxed) {
+                return;
+            }
+            
+            match event.event_type {
+                EventType::KeyPress(key) | EventType::KeyRelease(key) => {
+                    // Get current timestamp in microseconds
+                    let timestamp = clock.now_micros();
                 
-                let event_name = match event.event_type {
-                    EventType::KeyPress(_) => "press",
-                    EventType::KeyRelease(_) => "release",
-                    _ => "unknown",
-                };
+                    let event_name = match event.event_type {
+                        EventType::KeyPress(_) => "press",
+                        EventType::KeyRelease(_) => "release",
+                        _ => "unknown",
+                    };
                 
-                let keyboard_event = KeyboardEvent {
-                    event_type: "keystroke".to_string(),
-                    ts: timestamp,
-                    key: format!("{:?}", key),  // Use debug format to get key name
-                    event: event_name.to_string(),
-                };
+                    let (key, code) = published_key(key, privacy_mode);
+                    let keyboard_event = KeyboardEvent {
+                        event_type: "keystroke".to_string(),
+                        ts: timestamp,
+                        dev_mode,
+                        user_id: user_id.clone(),
+                        key,
+                        code,
+                        event: event_name.to_string(),
+                    };
                 
-                // Serialize to JSON
-                let json = serde_json::to_string(&keyboard_event)
+                    // Serialize to JSON
+                    let json = match &signer {
+                        Some(signer) => signer.sign(&keyboard_event),
+                        None => serde_json::to_string(&keyboard_event),
+                    }
                     .expect("Failed to serialize event");
                 
-                // Publish to Redis channel
-                let _: () = con.publish("seclyzer:events", json)
-                    .expect("Failed to publish to Redis");
+                    // Log first, then publish whatever Redis will take
+                    let mut outbox = listener_outbox.lock().expect("WAL lock poisoned");
+                    let (wal, bus) = &mut *outbox;
+                    record_event(wal, &json, LABEL);
+                    drain_wal(wal, bus.as_mut(), &listener_channel, compress, LABEL);
+                }
+                _ => {}
+            }
+        }) {
+            eprintln!("[Keyboard Collector] Error: {:?}", error);
+            listener_flag.store(true, Ordering::SeqCst);
+        }
+    });
+    
+    // The event bus is shared with the listener thread, so heartbeats get their own connection
+    let mut heartbeat_link = RedisLink::new(redis_client);
+    let heartbeat_every = Duration::from_secs(HEARTBEAT_INTERVAL_SECS);
+    let mut next_heartbeat = Instant::now();
+    
+    while !shutdown.load(Ordering::SeqCst) {
+        if Instant::now() >= next_heartbeat {
+            let is_paused = paused.load(Ordering::SeqCst);
+            if let Some(con) = heartbeat_link.connection() {
+                if let Err(e) = send_heartbeat(con, &heartbeat_channel, "keyboard", is_paused) {
+                    eprintln!("[Keyboard Collector] Failed to send heartbeat: {:?}", e);
+                    heartbeat_link.reset();
+                }
             }
-            _ => {}
+            next_heartbeat = Instant::now() + heartbeat_every;
+        }
+        
+        // Keep draining a backlog left by an outage even when no new events arrive
+        {
+            let mut outbox = outbox.lock().expect("WAL lock poisoned");
+            let (wal, bus) = &mut *outbox;
+            drain_wal(wal, bus.as_mut(), &channel, compress, LABEL);
         }
-    }) {
-        eprintln!("[Keyboard Collector] Error: {:?}", error);
+        thread::sleep(Duration::from_millis(100));
     }
+    println!("[Keyboard Collector] Shutting down");
 }
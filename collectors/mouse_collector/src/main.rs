@@ -1,97 +1,250 @@
 use rdev::{listen, EventType};
+use common::{
+    drain_wal, event_bus, record_event, watch_control_blocking, Config, EventBusKind, EventClock,
+    EventWal, Heartbeat, RedisLink, HEARTBEAT_INTERVAL_SECS,
+};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Prefix for this collector's log lines
+const LABEL: &str = "Mouse Collector";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct MouseEvent {
     #[serde(rename = "type")]
     event_type: String,
     ts: u128,  // Timestamp in microseconds
+    dev_mode: bool,  // Set while SECLYZER_DEV_MODE is on, so self-testing is filtered out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,  // USER_ID of this host's user, so extractors keep users apart
     x: Option<f64>,
     y: Option<f64>,
     event: String,  // "move", "click", "release", "scroll"
     button: Option<String>,
-    scroll_delta: Option<i64>,
+    scroll_delta: Option<f64>,  // f64 to match RawEvent; high-resolution devices send fractions
+}
+
+/// Announce on the heartbeat channel that this collector is still running, and whether it's paused
+fn send_heartbeat(
+    con: &mut redis::Connection,
+    channel: &str,
+    source: &str,
+    paused: bool,
+) -> redis::RedisResult<()> {
+    let json = serde_json::to_string(&Heartbeat::now(source).with_paused(paused))
+        .expect("Failed to serialize heartbeat");
+    con.publish(channel, json)
 }
 
 fn main() {
     println!("[Mouse Collector] Starting...");
     
-    // Connect to Redis
-    let redis_client = redis::Client::open("redis://127.0.0.1:6379/")
+    // Connect to the event bus; if it's down, events wait in the WAL until it's back
+    let config = Config::load().expect("Invalid configuration");
+    let redis_client = redis::Client::open(config.redis_url())
         .expect("Failed to connect to Redis");
-    let mut con = redis_client.get_connection()
-        .expect("Failed to get Redis connection");
+    let mut bus = event_bus::connect_blocking(&config, redis_client.clone())
+        .expect("Failed to set up the event bus");
+    if config.event_bus == EventBusKind::Kafka {
+        println!("[Mouse Collector] Publishing events to Kafka at {}", config.kafka_brokers);
+    } else if config.event_bus == EventBusKind::Unix {
+        println!(
+            "[Mouse Collector] Publishing events over sockets in {}, or Redis when nothing listens",
+            config.socket_dir().unwrap_or_default().display()
+        );
+    } else if bus.ready() {
+        println!("[Mouse Collector] Connected to Redis at {}", config.redis_url_redacted());
+    } else {
+        eprintln!(
+            "[Mouse Collector] Redis at {} unavailable; buffering events in the WAL",
+            config.redis_url_redacted()
+        );
+    }
     
-    println!("[Mouse Collector] Connected to Redis");
-    println!("[Mouse Collector] Listening for mouse events (Ctrl+C to stop)");
+    let compress = config.compress_events;
+    // Events are HMAC-signed if a key is configured (see REQUIRE_SIGNED_EVENTS)
+    let signer = config.event_signer();
+    let channel = config.event_channel();
+    let heartbeat_channel = config.heartbeat_channel();
+    // Monotonic after startup, so movement deltas stay non-negative across NTP steps
+    let clock = EventClock::new();
+    let dev_mode = config.dev_mode;
+    if dev_mode {
+        println!("[Mouse Collector] Dev mode enabled; events are tagged dev_mode=true");
+    }
+    let user_id = config.user_id.clone();
+    if let Some(user_id) = &user_id {
+        println!("[Mouse Collector] Events are stamped user_id={}", user_id);
+    }
     
-    // Start listening to mouse events
-    if let Err(error) = listen(move |event| {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_micros();
-        
-        let mouse_event = match event.event_type {
-            EventType::MouseMove { x, y } => {
-                Some(MouseEvent {
-                    event_type: "mouse".to_string(),
-                    ts: timestamp,
-                    x: Some(x),
-                    y: Some(y),
-                    event: "move".to_string(),
-                    button: None,
-                    scroll_delta: None,
-                })
+    // Replay whatever a previous run logged but never got acknowledged
+    let mut wal = EventWal::open(config.wal_path("mouse"), config.wal_max_bytes)
+        .expect("Failed to open WAL");
+    if wal.pending() > 0 {
+        println!(
+            "[Mouse Collector] Replaying {} undelivered events from {}",
+            wal.pending(),
+            wal.path().display()
+        );
+        drain_wal(&mut wal, bus.as_mut(), &channel, compress, LABEL);
+    }
+    let outbox = Arc::new(Mutex::new((wal, bus)));
+    
+    // Pause/resume commands arrive on their own connection; the flag gates publishing
+    let paused = Arc::new(AtomicBool::new(false));
+    let control_flag = paused.clone();
+    let control_client = redis_client.clone();
+    let control_channel = config.control_channel();
+    thread::spawn(move || loop {
+        let result = watch_control_blocking(&control_client, &control_channel, |message| {
+            match message {
+                Ok(command) => {
+                    let Some(pause) = command.pauses() else {
+                        return;
+                    };
+                    if control_flag.swap(pause, Ordering::SeqCst) != pause {
+                        println!("[Mouse Collector] {}", if pause { "Paused" } else { "Resumed" });
+                    }
+                }
+                Err(e) => eprintln!("[Mouse Collector] Ignoring malformed control message: {}", e),
             }
-            EventType::ButtonPress(button) => {
-                Some(MouseEvent {
-                    event_type: "mouse".to_string(),
-                    ts: timestamp,
-                    x: None,
-                    y: None,
-                    event: "press".to_string(),
-                    button: Some(format!("{:?}", button)),
-                    scroll_delta: None,
-                })
+        });
+        if let Err(e) = result {
+            eprintln!("[Mouse Collector] Control channel unavailable, retrying: {:?}", e);
+        }
+        thread::sleep(Duration::from_secs(1));
+    });
+    
+    // Stop publishing and exit cleanly on Ctrl+C / SIGTERM
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let handler_flag = shutdown.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .expect("Failed to install signal handler");
+    
+    println!("[Mouse Collector] Listening for mouse events (Ctrl+C to stop)");
+    
+    // Start listening to mouse events; rdev::listen never returns, so it gets
+    // its own thread while main waits for shutdown
+    let listener_flag = shutdown.clone();
+    let listener_paused = paused.clone();
+    let listener_outbox = outbox.clone();
+    let listener_channel = channel.clone();
+    thread::spawn(move || {
+        let callback_flag = listener_flag.clone();
+        // rdev only reports coordinates on moves, so clicks reuse the last one seen
+        let mut last_position: Option<(f64, f64)> = None;
+        if let Err(error) = listen(move |event| {
+            if callback_flag.load(Ordering::Relaxed) || listener_paused.load(Ordering::Relaxed) {
+                return;
             }
-            EventType::ButtonRelease(button) => {
-                Some(MouseEvent {
-                    event_type: "mouse".to_string(),
-                    ts: timestamp,
-                    x: None,
-                    y: None,
-                    event: "release".to_string(),
-                    button: Some(format!("{:?}", button)),
-                    scroll_delta: None,
-                })
+            
+            let timestamp = clock.now_micros();
+            
+            let mouse_event = match event.event_type {
+                EventType::MouseMove { x, y } => {
+                    last_position = Some((x, y));
+                    Some(MouseEvent {
+                        event_type: "mouse".to_string(),
+                        ts: timestamp,
+                        dev_mode,
+                        user_id: user_id.clone(),
+                        x: Some(x),
+                        y: Some(y),
+                        event: "move".to_string(),
+                        button: None,
+                        scroll_delta: None,
+                    })
+                }
+                EventType::ButtonPress(button) => {
+                    Some(MouseEvent {
+                        event_type: "mouse".to_string(),
+                        ts: timestamp,
+                        dev_mode,
+                        user_id: user_id.clone(),
+                        x: last_position.map(|(x, _)| x),
+                        y: last_position.map(|(_, y)| y),
+                        event: "press".to_string(),
+                        button: Some(format!("{:?}", button)),
+                        scroll_delta: None,
+                    })
+                }
+                EventType::ButtonRelease(button) => {
+                    Some(MouseEvent {
+                        event_type: "mouse".to_string(),
+                        ts: timestamp,
+                        dev_mode,
+                        user_id: user_id.clone(),
+                        x: last_position.map(|(x, _)| x),
+                        y: last_position.map(|(_, y)| y),
+                        event: "release".to_string(),
+                        button: Some(format!("{:?}", button)),
+                        scroll_delta: None,
+                    })
+                }
+                EventType::Wheel { delta_x: _, delta_y } => {
+                    Some(MouseEvent {
+                        event_type: "mouse".to_string(),
+                        ts: timestamp,
+                        dev_mode,
+                        user_id: user_id.clone(),
+                        x: None,
+                        y: None,
+                        event: "scroll".to_string(),
+                        button: None,
+                        scroll_delta: Some(delta_y as f64),
+                    })
+                }
+                _ => None,
+            };
+            
+            if let Some(event) = mouse_event {
+                // Serialize to JSON
+                let json = match &signer {
+                    Some(signer) => signer.sign(&event),
+                    None => serde_json::to_string(&event),
+                }
+                .expect("Failed to serialize event");
+            
+                // Log first, then publish whatever Redis will take
+                let mut outbox = listener_outbox.lock().expect("WAL lock poisoned");
+                let (wal, bus) = &mut *outbox;
+                record_event(wal, &json, LABEL);
+                drain_wal(wal, bus.as_mut(), &listener_channel, compress, LABEL);
             }
-            EventType::Wheel { delta_x: _, delta_y } => {
-                Some(MouseEvent {
-                    event_type: "mouse".to_string(),
-                    ts: timestamp,
-                    x: None,
-                    y: None,
-                    event: "scroll".to_string(),
-                    button: None,
-                    scroll_delta: Some(delta_y),
-                })
+        }) {
+            eprintln!("[Mouse Collector] Error: {:?}", error);
+            listener_flag.store(true, Ordering::SeqCst);
+        }
+    });
+    
+    // The event bus is shared with the listener thread, so heartbeats get their own connection
+    let mut heartbeat_link = RedisLink::new(redis_client);
+    let heartbeat_every = Duration::from_secs(HEARTBEAT_INTERVAL_SECS);
+    let mut next_heartbeat = Instant::now();
+    
+    while !shutdown.load(Ordering::SeqCst) {
+        if Instant::now() >= next_heartbeat {
+            let is_paused = paused.load(Ordering::SeqCst);
+            if let Some(con) = heartbeat_link.connection() {
+                if let Err(e) = send_heartbeat(con, &heartbeat_channel, "mouse", is_paused) {
+                    eprintln!("[Mouse Collector] Failed to send heartbeat: {:?}", e);
+                    heartbeat_link.reset();
+                }
             }
-            _ => None,
-        };
+            next_heartbeat = Instant::now() + heartbeat_every;
+        }
         
-        if let Some(event) = mouse_event {
-            // Serialize to JSON
-            let json = serde_json::to_string(&event)
-                .expect("Failed to serialize event");
-            
-            // Publish to Redis channel
-            let _: () = con.publish("seclyzer:events", json)
-                .expect("Failed to publish to Redis");
+        // Keep draining a backlog left by an outage even when no new events arrive
+        {
+            let mut outbox = outbox.lock().expect("WAL lock poisoned");
+            let (wal, bus) = &mut *outbox;
+            drain_wal(wal, bus.as_mut(), &channel, compress, LABEL);
         }
-    }) {
-        eprintln!("[Mouse Collector] Error: {:?}", error);
+        thread::sleep(Duration::from_millis(100));
     }
+    println!("[Mouse Collector] Shutting down");
 }
@@ -1,40 +1,211 @@
 use app_tracker::AppTracker;
-use common::{init_logging, AppContext};
+use common::{
+    init_logging, shutdown_signal, tag_user, user_path, AppContext, AppTransition,
+    BusEventSource, Config, ControlCommand, EventSource, HeartbeatMonitor, PublishSkipped,
+    RawEvent, UserSessions,
+};
+use serde_json::json;
 use tokio::time::{interval, Duration};
-use tracing::{info, error};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use tracing::{info, warn, error};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     init_logging();
     info!("App Tracker starting");
-    
-    let ctx = AppContext::new().await?;
-    let config = ctx.config.clone();
-    
-    let tracker = Arc::new(Mutex::new(AppTracker::new()));
-    
+
+    let config = Config::load()?;
+
+    // Recorded events are replayed into fresh trackers and each user's final state printed
+    if let Some(mut source) = config.event_source.open_local().await? {
+        let tracker_config = config.clone();
+        let mut trackers = UserSessions::new(config.user_id.clone(), move |_| {
+            AppTracker::new()
+                .with_categories(tracker_config.app_categories.clone())
+                .with_filter(tracker_config.app_filter())
+        });
+        let mut last_ts = None;
+        while let Some(event) = source.next_event().await {
+            last_ts = Some(event.ts as f64 / 1_000_000.0);
+            let user = trackers.user_of(&event);
+            handle_event(trackers.session(user.as_deref()), event);
+        }
+        for (user, tracker) in trackers.iter_mut() {
+            if let Some(ts) = last_ts {
+                tracker.close_session(ts);
+            }
+            tracker.prune_old(config.app_max_apps, config.app_max_sessions_per_app);
+            let mut state = tracker.get_state();
+            tag_user(&mut state, user);
+            println!("{}", state);
+        }
+        return Ok(());
+    }
+
+    let ctx = AppContext::from_config(config).await?;
+    let state_path = PathBuf::from(&ctx.config.app_state_path);
+
+    // One app profile per user, each saved next to the configured one
+    let tracker_config = ctx.config.clone();
+    let tracker_state_path = state_path.clone();
+    let mut trackers = UserSessions::new(ctx.config.user_id.clone(), move |user| {
+        load_tracker(&user_path(&tracker_state_path, user))
+            .with_categories(tracker_config.app_categories.clone())
+            .with_filter(tracker_config.app_filter())
+    });
+    let default_user = ctx.config.user_id.clone();
+    trackers.session(default_user.as_deref());
+
+    let mut events =
+        BusEventSource::subscribe(ctx.bus.as_ref(), &ctx.config.event_channel(), "app_tracker")
+            .await?
+            .with_queue(ctx.config.event_queue_size, ctx.config.event_overflow)
+            .with_dedup(ctx.config.event_dedup_window)
+            .with_verifier(ctx.config.event_verifier());
+    let mut heartbeats = ctx.redis.subscribe(&ctx.config.heartbeat_channel()).await?;
+    let mut control = ctx.redis.subscribe(&ctx.config.control_channel()).await?;
+    let mut paused = false;
+    let mut collector_monitor = HeartbeatMonitor::new(ctx.config.heartbeat_timeout as f64);
+    let mut source_alive = false;
+    ctx.spawn_health_server();
+    let feature_channel = ctx.config.feature_channel("app");
     let mut update_interval = interval(Duration::from_secs(60));
-    
+    let mut checkpoint_interval = interval(Duration::from_secs(300));
+    let mut flush_interval = interval(Duration::from_secs(ctx.config.flush_interval));
+
     info!("App Tracker initialized and ready");
-    
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
     loop {
         tokio::select! {
+            _ = &mut shutdown => break,
+            Some(event) = events.next_event() => if !paused {
+                let user = trackers.user_of(&event);
+                let transition = handle_event(trackers.session(user.as_deref()), event);
+                // Skipped, like features, when dev mode suppresses output
+                if let (Some(transition), Some(influx), Some(_)) =
+                    (transition, &ctx.influx, &feature_channel)
+                {
+                    if let Err(e) = influx.write_app_transition(&transition).await {
+                        error!("Failed to write app transition to InfluxDB: {}", e);
+                    }
+                }
+            },
+            Some(payload) = control.recv() => match ControlCommand::from_json(&payload) {
+                Ok(command) if command.pauses().is_some_and(|pause| pause != paused) => {
+                    paused = !paused;
+                    info!("{} by control channel", if paused { "Paused" } else { "Resumed" });
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping malformed control message: {}", e),
+            },
+            Some(payload) = heartbeats.recv() => {
+                if let Err(e) = collector_monitor.record_json(&payload) {
+                    warn!("Skipping malformed heartbeat: {}", e);
+                }
+            }
             _ = update_interval.tick() => {
-                let tracker_locked = tracker.lock().await;
-                let state = tracker_locked.get_state();
-                
+                ctx.metrics.set_dropped_events(events.dropped_events());
+                let alive = collector_monitor.is_alive("app", unix_now());
+                if alive != source_alive {
+                    if alive {
+                        info!("App monitor heartbeat received");
+                    } else {
+                        warn!("App monitor missed its heartbeat; app state may be stale");
+                    }
+                    source_alive = alive;
+                }
+
+                if paused {
+                    continue;
+                }
+                for (user, tracker) in trackers.iter_mut() {
+                    tracker.check_idle(unix_now());
+                    let mut state = tracker.get_state();
+                    tag_user(&mut state, user);
+                    state["source_alive"] = json!(if source_alive { 1.0 } else { 0.0 });
+
+                    // Publish state to the configured sink
+                    if let Some(channel) = &feature_channel {
+                        match ctx.sink.publish(channel, &state).await {
+                            Ok(()) => ctx.metrics.record_publish(),
+                            Err(e) if e.is::<PublishSkipped>() => {}
+                            Err(e) => error!("Failed to publish app state: {}", e),
+                        }
+                    }
+                }
                 info!("Updated app patterns");
-                
-                // Publish state to Redis
-                if let Err(e) = ctx.redis.publish_features(
-                    "seclyzer:features:app",
-                    &state,
-                ).await {
-                    error!("Failed to publish app state: {}", e);
+            }
+            _ = flush_interval.tick() => {
+                if let Some(influx) = &ctx.influx {
+                    if let Err(e) = influx.flush().await {
+                        error!("Failed to flush InfluxDB batch: {}", e);
+                    }
                 }
             }
+            _ = checkpoint_interval.tick() => {
+                for (user, tracker) in trackers.iter_mut() {
+                    let dropped = tracker
+                        .prune_old(ctx.config.app_max_apps, ctx.config.app_max_sessions_per_app);
+                    if dropped > 0 {
+                        info!("Pruned {} rarely used apps from the profile", dropped);
+                    }
+                    if let Err(e) = tracker.save(&user_path(&state_path, user)) {
+                        error!("Failed to save app profile: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(influx) = &ctx.influx {
+        if let Err(e) = influx.flush().await {
+            error!("Failed to flush InfluxDB batch on shutdown: {}", e);
+        }
+    }
+    for (user, tracker) in trackers.iter_mut() {
+        tracker.close_session(unix_now());
+        if let Err(e) = tracker.save(&user_path(&state_path, user)) {
+            error!("Failed to save app profile on shutdown: {:#}", e);
+        }
+    }
+    info!("App Tracker stopped cleanly");
+    Ok(())
+}
+
+/// The app profile saved at `path`, or an empty one if there's none or it's unreadable
+fn load_tracker(path: &Path) -> AppTracker {
+    if !path.exists() {
+        return AppTracker::new();
+    }
+    match AppTracker::load(path) {
+        Ok(tracker) => {
+            info!("Restored app profile from {}", path.display());
+            tracker
+        }
+        Err(e) => {
+            warn!("Ignoring unreadable app profile: {:#}", e);
+            AppTracker::new()
         }
     }
 }
+
+/// Feed an app focus event to the tracker, ignoring other modalities; returns the
+/// transition it caused, if any
+fn handle_event(tracker: &mut AppTracker, event: RawEvent) -> Option<AppTransition> {
+    if event.event_type != "app" {
+        return None;
+    }
+    let app_name = event.app_name?;
+    tracker.handle_app_switch(app_name, event.window_class, event.ts as f64 / 1_000_000.0)
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
@@ -1,10 +1,33 @@
-use std::collections::{HashMap, VecDeque};
-use chrono::{Utc, Timelike};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc, Timelike};
+use common::{AppFilter, AppTransition};
+use serde::{Deserialize, Serialize};
+
+/// Seconds without an app event before the current session is closed as idle
+pub const IDLE_TIMEOUT_SECONDS: f64 = 300.0;
+
+/// Pseudo-app that idle periods are recorded under
+pub const IDLE_APP: &str = "idle";
+
+/// Category reported for apps the category map doesn't mention
+pub const UNCATEGORIZED: &str = "uncategorized";
 
 #[derive(Debug, Clone)]
 pub struct AppEvent {
     pub timestamp: f64,
     pub app_name: String,
+    pub window_class: Option<String>,
+}
+
+/// Learned profile persisted across restarts
+#[derive(Debug, Serialize, Deserialize)]
+struct TrackerSnapshot {
+    transitions: Vec<(String, String, u32)>,
+    app_durations: HashMap<String, Vec<f64>>,
+    time_patterns: HashMap<String, HashMap<u32, u32>>,
 }
 
 pub struct AppTracker {
@@ -14,6 +37,14 @@ pub struct AppTracker {
     app_durations: HashMap<String, Vec<f64>>,
     time_patterns: HashMap<String, HashMap<u32, u32>>,
     recent_events: VecDeque<AppEvent>,
+    last_event_time: Option<f64>,
+    idle_since: Option<f64>,
+    /// Lowercased app name or window class -> category
+    categories: HashMap<String, String>,
+    /// Last window class seen for each app, for category lookup
+    app_classes: HashMap<String, String>,
+    /// Apps left out of tracking altogether
+    filter: AppFilter,
 }
 
 impl AppTracker {
@@ -25,15 +56,151 @@ impl AppTracker {
             app_durations: HashMap::new(),
             time_patterns: HashMap::new(),
             recent_events: VecDeque::with_capacity(1000),
+            last_event_time: None,
+            idle_since: None,
+            categories: HashMap::new(),
+            app_classes: HashMap::new(),
+            filter: AppFilter::default(),
+        }
+    }
+    
+    /// Group apps into categories (e.g. `firefox -> browser`) keyed by app name or window class
+    pub fn with_categories(mut self, categories: HashMap<String, String>) -> Self {
+        self.categories = categories
+            .into_iter()
+            .map(|(app, category)| (app.to_lowercase(), category))
+            .collect();
+        self
+    }
+    
+    /// Ignore focus events for apps the filter doesn't allow, as if they never had focus
+    pub fn with_filter(mut self, filter: AppFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+    
+    /// Category for an app, matched on its name first and then its window class
+    pub fn category_of(&self, app_name: &str) -> &str {
+        let by_class = || {
+            self.app_classes
+                .get(app_name)
+                .and_then(|class| self.categories.get(&class.to_lowercase()))
+        };
+        self.categories
+            .get(&app_name.to_lowercase())
+            .or_else(by_class)
+            .map_or(UNCATEGORIZED, String::as_str)
+    }
+    
+    /// Save the learned transitions, durations and time patterns to disk
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let snapshot = TrackerSnapshot {
+            transitions: self
+                .transitions
+                .iter()
+                .map(|((from_app, to_app), count)| (from_app.clone(), to_app.clone(), *count))
+                .collect(),
+            app_durations: self.app_durations.clone(),
+            time_patterns: self.time_patterns.clone(),
+        };
+        let json = serde_json::to_string(&snapshot)?;
+        
+        // Write then rename so a crash mid-save can't corrupt the previous state
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+    
+    /// Restore a tracker from state written by `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let snapshot: TrackerSnapshot = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        
+        let mut tracker = AppTracker::new();
+        tracker.transitions = snapshot
+            .transitions
+            .into_iter()
+            .map(|(from_app, to_app, count)| ((from_app, to_app), count))
+            .collect();
+        tracker.app_durations = snapshot.app_durations;
+        tracker.time_patterns = snapshot.time_patterns;
+        Ok(tracker)
+    }
+    
+    /// Close the current session as idle if no app event arrived within the timeout
+    pub fn check_idle(&mut self, now: f64) {
+        if self.idle_since.is_some() {
+            return;
         }
+        let Some(last_event_time) = self.last_event_time else {
+            return;
+        };
+        if now - last_event_time <= IDLE_TIMEOUT_SECONDS {
+            return;
+        }
+        
+        // The session is credited up to the timeout, idle time starts after it
+        let idle_start = last_event_time + IDLE_TIMEOUT_SECONDS;
+        if let (Some(app), Some(start_time)) = (self.current_app.take(), self.current_app_start.take()) {
+            self.app_durations
+                .entry(app)
+                .or_insert_with(Vec::new)
+                .push(idle_start - start_time);
+        }
+        self.idle_since = Some(idle_start);
     }
     
-    /// Handle app switch event
-    pub fn handle_app_switch(&mut self, app_name: String, timestamp: f64) {
+    /// End the current session (e.g. on shutdown), recording its duration
+    pub fn close_session(&mut self, timestamp: f64) {
+        self.check_idle(timestamp);
+        if let Some(idle_start) = self.idle_since.take() {
+            self.app_durations
+                .entry(IDLE_APP.to_string())
+                .or_insert_with(Vec::new)
+                .push(timestamp - idle_start);
+        }
+        if let (Some(app), Some(start_time)) = (self.current_app.take(), self.current_app_start.take()) {
+            self.app_durations
+                .entry(app)
+                .or_insert_with(Vec::new)
+                .push(timestamp - start_time);
+        }
+        self.last_event_time = None;
+    }
+    
+    /// Handle app switch event, returning the transition away from the previous app
+    /// if focus moved to a different one
+    pub fn handle_app_switch(
+        &mut self,
+        app_name: String,
+        window_class: Option<String>,
+        timestamp: f64,
+    ) -> Option<AppTransition> {
+        if !self.filter.allows(&app_name, window_class.as_deref()) {
+            return None;
+        }
         let now = Utc::now();
         let hour = now.hour() as u32;
         
-        // Record transition if switching from a previous app
+        // Close out any idle period that ended with this event
+        self.check_idle(timestamp);
+        if let Some(idle_start) = self.idle_since.take() {
+            self.app_durations
+                .entry(IDLE_APP.to_string())
+                .or_insert_with(Vec::new)
+                .push(timestamp - idle_start);
+        }
+        self.last_event_time = Some(timestamp);
+        
+        // Record transition if switching from a previous app; refocusing the same app
+        // (e.g. back from a filtered one) carries on its session
+        let same_app = self.current_app.as_deref() == Some(app_name.as_str());
+        let mut transition = None;
         if let Some(prev_app) = self.current_app.take() {
             if prev_app != app_name {
                 if let Some(start_time) = self.current_app_start {
@@ -48,13 +215,28 @@ impl AppTracker {
                         .entry(prev_app.clone())
                         .or_insert_with(Vec::new)
                         .push(duration);
+                    
+                    let switched_at = DateTime::from_timestamp_micros((timestamp * 1e6) as i64)
+                        .unwrap_or_default();
+                    transition = Some(AppTransition {
+                        from_app: prev_app.clone(),
+                        to_app: app_name.clone(),
+                        duration_ms: (duration * 1000.0).round() as i64,
+                        timestamp: switched_at.to_rfc3339(),
+                    });
                 }
             }
         }
         
+        if let Some(class) = &window_class {
+            self.app_classes.insert(app_name.clone(), class.clone());
+        }
+        
         // Update current app
         self.current_app = Some(app_name.clone());
-        self.current_app_start = Some(timestamp);
+        if !same_app {
+            self.current_app_start = Some(timestamp);
+        }
         
         // Record time pattern
         self.time_patterns
@@ -71,7 +253,51 @@ impl AppTracker {
         self.recent_events.push_back(AppEvent {
             timestamp,
             app_name,
+            window_class,
         });
+        
+        transition
+    }
+    
+    /// Bound the learned profile: keep the `max_apps` apps with the most recorded
+    /// time (plus the current app and idle) and each app's last
+    /// `max_durations_per_app` sessions; returns how many apps were dropped
+    pub fn prune_old(&mut self, max_apps: usize, max_durations_per_app: usize) -> usize {
+        let mut apps: HashSet<&str> = self.app_durations.keys().map(String::as_str).collect();
+        apps.extend(self.time_patterns.keys().map(String::as_str));
+        apps.extend(self.transitions.keys().flat_map(|(from, to)| [from.as_str(), to.as_str()]));
+        
+        // Most-used first: total recorded time, then how often the app took focus
+        let mut ranked: Vec<(&str, f64, u32)> = apps
+            .iter()
+            .filter(|&&app| app != IDLE_APP && Some(app) != self.current_app.as_deref())
+            .map(|&app| {
+                let time = self.app_durations.get(app).map_or(0.0, |d| d.iter().sum());
+                let focus_count = self.time_patterns.get(app).map_or(0, |h| h.values().sum());
+                (app, time, focus_count)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(b.0)));
+        
+        let mut keep: HashSet<String> = ranked
+            .iter()
+            .take(max_apps.saturating_sub(self.current_app.is_some() as usize))
+            .map(|(app, _, _)| app.to_string())
+            .collect();
+        keep.insert(IDLE_APP.to_string());
+        keep.extend(self.current_app.clone());
+        let dropped = apps.iter().filter(|&&app| !keep.contains(app)).count();
+        
+        self.transitions
+            .retain(|(from, to), _| keep.contains(from) && keep.contains(to));
+        self.time_patterns.retain(|app, _| keep.contains(app));
+        self.app_classes.retain(|app, _| keep.contains(app));
+        self.app_durations.retain(|app, _| keep.contains(app));
+        for durations in self.app_durations.values_mut() {
+            let excess = durations.len().saturating_sub(max_durations_per_app);
+            durations.drain(..excess);
+        }
+        dropped
     }
     
     /// Calculate transition probabilities
@@ -92,6 +318,34 @@ impl AppTracker {
         probs
     }
     
+    /// Average Shannon entropy (bits) of outgoing transitions, weighted by visit count
+    pub fn calculate_transition_entropy(&self) -> f64 {
+        let mut outgoing: HashMap<&str, Vec<u32>> = HashMap::new();
+        for ((from_app, _to_app), count) in &self.transitions {
+            outgoing.entry(from_app.as_str()).or_default().push(*count);
+        }
+        
+        let total_visits: u32 = outgoing.values().flatten().sum();
+        if total_visits == 0 {
+            return 0.0;
+        }
+        
+        let mut weighted_entropy = 0.0;
+        for counts in outgoing.values() {
+            let visits: u32 = counts.iter().sum();
+            let entropy: f64 = counts
+                .iter()
+                .map(|&c| {
+                    let p = c as f64 / visits as f64;
+                    -p * p.log2()
+                })
+                .sum();
+            weighted_entropy += entropy * visits as f64;
+        }
+        
+        weighted_entropy / total_visits as f64
+    }
+    
     /// Calculate time-of-day preferences
     pub fn calculate_time_preferences(&self) -> HashMap<String, HashMap<u32, f64>> {
         let mut prefs = HashMap::new();
@@ -132,6 +386,45 @@ impl AppTracker {
         stats
     }
     
+    /// Total and per-session time for each app category; idle time is left out
+    pub fn calculate_category_stats(&self) -> serde_json::Value {
+        let mut totals: HashMap<&str, (f64, usize)> = HashMap::new();
+        for (app, durations) in &self.app_durations {
+            if app == IDLE_APP || durations.is_empty() {
+                continue;
+            }
+            let entry = totals.entry(self.category_of(app)).or_default();
+            entry.0 += durations.iter().sum::<f64>();
+            entry.1 += durations.len();
+        }
+        
+        let mut stats = serde_json::json!({});
+        for (category, (total_time, session_count)) in totals {
+            stats[category] = serde_json::json!({
+                "total_time_seconds": total_time,
+                "avg_session_seconds": total_time / session_count as f64,
+                "session_count": session_count,
+            });
+        }
+        stats
+    }
+    
+    /// Fraction of recorded session time spent idle
+    pub fn calculate_idle_fraction(&self) -> f64 {
+        let total_time: f64 = self.app_durations.values().flatten().sum();
+        let idle_time: f64 = self
+            .app_durations
+            .get(IDLE_APP)
+            .map(|durations| durations.iter().sum())
+            .unwrap_or(0.0);
+        
+        if total_time > 0.0 {
+            idle_time / total_time
+        } else {
+            0.0
+        }
+    }
+    
     /// Get current state as JSON
     pub fn get_state(&self) -> serde_json::Value {
         serde_json::json!({
@@ -139,7 +432,10 @@ impl AppTracker {
             "transition_matrix": self.calculate_transition_matrix(),
             "time_preferences": self.calculate_time_preferences(),
             "usage_stats": self.calculate_usage_stats(),
+            "category_stats": self.calculate_category_stats(),
             "transition_count": self.transitions.len(),
+            "idle_fraction": self.calculate_idle_fraction(),
+            "transition_entropy": self.calculate_transition_entropy(),
         })
     }
 }
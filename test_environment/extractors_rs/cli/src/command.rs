@@ -0,0 +1,93 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: seclyzer <command>\n\
+    \n\
+    commands:\n  \
+      collect keyboard|mouse|app     run one collector\n  \
+      collect synthetic              publish generated office-profile events instead\n  \
+      extract keystroke|mouse|app    run one extractor\n  \
+      run-all                        run every component, restarting any that crash\n  \
+      diff A.json B.json [BASELINE]  rank features by how far they moved from A to B\n  \
+      help                           show this message";
+
+/// One pipeline process the CLI can launch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Component {
+    /// Name used in logs
+    pub name: &'static str,
+    /// Executable launched for it
+    pub binary: &'static str,
+}
+
+pub const KEYBOARD_COLLECTOR: Component = Component { name: "keyboard", binary: "keyboard_collector" };
+pub const MOUSE_COLLECTOR: Component = Component { name: "mouse", binary: "mouse_collector" };
+pub const APP_MONITOR: Component = Component { name: "app", binary: "app_monitor" };
+pub const SYNTHETIC_COLLECTOR: Component = Component { name: "synthetic", binary: "synthetic" };
+pub const KEYSTROKE_EXTRACTOR: Component = Component { name: "keystroke", binary: "keystroke_extractor" };
+pub const MOUSE_EXTRACTOR: Component = Component { name: "mouse-extractor", binary: "mouse_extractor" };
+pub const APP_TRACKER: Component = Component { name: "app-tracker", binary: "app_tracker" };
+
+/// Everything `run-all` supervises, extractors first so no early events are missed
+pub const ALL_COMPONENTS: &[Component] = &[
+    KEYSTROKE_EXTRACTOR,
+    MOUSE_EXTRACTOR,
+    APP_TRACKER,
+    KEYBOARD_COLLECTOR,
+    MOUSE_COLLECTOR,
+    APP_MONITOR,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Run(Component),
+    RunAll,
+    /// Compare two feature windows, optionally against a saved baseline
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+        baseline: Option<PathBuf>,
+    },
+    Help,
+}
+
+/// Parse the arguments after the program name
+pub fn parse<I, S>(args: I) -> Result<Command>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let args: Vec<&str> = args.iter().map(|a| a.as_ref()).collect();
+    match args.as_slice() {
+        ["collect", target] => match *target {
+            "keyboard" => Ok(Command::Run(KEYBOARD_COLLECTOR)),
+            "mouse" => Ok(Command::Run(MOUSE_COLLECTOR)),
+            "app" => Ok(Command::Run(APP_MONITOR)),
+            "synthetic" => Ok(Command::Run(SYNTHETIC_COLLECTOR)),
+            other => bail!(
+                "unknown collector {:?} (expected keyboard, mouse, app or synthetic)",
+                other
+            ),
+        },
+        ["extract", target] => match *target {
+            "keystroke" => Ok(Command::Run(KEYSTROKE_EXTRACTOR)),
+            "mouse" => Ok(Command::Run(MOUSE_EXTRACTOR)),
+            "app" => Ok(Command::Run(APP_TRACKER)),
+            other => bail!("unknown extractor {:?} (expected keystroke, mouse or app)", other),
+        },
+        ["run-all"] => Ok(Command::RunAll),
+        ["diff", a, b] => Ok(Command::Diff {
+            a: a.into(),
+            b: b.into(),
+            baseline: None,
+        }),
+        ["diff", a, b, baseline] => Ok(Command::Diff {
+            a: a.into(),
+            b: b.into(),
+            baseline: Some(baseline.into()),
+        }),
+        [] | ["help"] | ["-h"] | ["--help"] => Ok(Command::Help),
+        _ => bail!("unrecognized command: {}\n\n{}", args.join(" "), USAGE),
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use common::{feature_diff, BaselineProfile};
+use std::fs;
+use std::path::Path;
+
+/// Print the features that changed from window `a` to `b`, largest contribution first
+///
+/// Both files hold one emitted feature window as JSON; `baseline` is a saved
+/// `<modality>_baseline.json`, without which contributions are raw changes.
+pub fn run(a: &Path, b: &Path, baseline: Option<&Path>) -> Result<()> {
+    let a_features = read_window(a)?;
+    let b_features = read_window(b)?;
+    let baseline = match baseline {
+        Some(path) => BaselineProfile::load(path)?,
+        None => BaselineProfile::new(),
+    };
+
+    let diff = feature_diff(&a_features, &b_features, &baseline);
+    if diff.is_empty() {
+        println!("No feature changed");
+        return Ok(());
+    }
+    let width = diff.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    println!(
+        "{:<width$}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "feature", "a", "b", "change", "contribution"
+    );
+    for (name, contribution) in diff {
+        // Both sides are known numeric, since feature_diff only compares those
+        let before = a_features[&name].as_f64().unwrap_or_default();
+        let after = b_features[&name].as_f64().unwrap_or_default();
+        println!(
+            "{:<width$}  {:>12.4}  {:>12.4}  {:>12.4}  {:>12.4}",
+            name,
+            before,
+            after,
+            after - before,
+            contribution
+        );
+    }
+    Ok(())
+}
+
+fn read_window(path: &Path) -> Result<serde_json::Value> {
+    let json =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+}
@@ -0,0 +1,57 @@
+mod command;
+mod diff;
+mod supervisor;
+
+use command::{Command, ALL_COMPONENTS, USAGE};
+use common::{init_logging, shutdown_signal, Config};
+use tokio::sync::watch;
+use tracing::info;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let command = command::parse(std::env::args().skip(1))?;
+    match &command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        // Offline and read-only, so no config or logging needed
+        Command::Diff { a, b, baseline } => return diff::run(a, b, baseline.as_deref()),
+        _ => {}
+    }
+
+    init_logging();
+    // Fail fast on a bad config before any child process inherits it
+    Config::load()?;
+
+    let bin_dir = supervisor::bin_dir();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
+
+    match command {
+        Command::Run(component) => {
+            let status = supervisor::run_once(component, bin_dir.as_deref(), shutdown_rx).await?;
+            if let Some(code) = status.and_then(|s| s.code()).filter(|code| *code != 0) {
+                std::process::exit(code);
+            }
+        }
+        Command::RunAll => {
+            info!("Starting {} components", ALL_COMPONENTS.len());
+            let tasks: Vec<_> = ALL_COMPONENTS
+                .iter()
+                .map(|&component| {
+                    tokio::spawn(supervisor::supervise(component, bin_dir.clone(), shutdown_rx.clone()))
+                })
+                .collect();
+            for task in tasks {
+                let _ = task.await;
+            }
+            info!("All components stopped");
+        }
+        Command::Help | Command::Diff { .. } => unreachable!(),
+    }
+    Ok(())
+}
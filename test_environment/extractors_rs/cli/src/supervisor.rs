@@ -0,0 +1,138 @@
+use crate::command::Component;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{error, info, warn};
+
+/// First restart delay after a crash; doubles on each consecutive crash
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A component that stays up this long is considered healthy again and resets the backoff
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// How long a child gets to exit after SIGTERM before it's killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(10);
+
+/// Directory component binaries are launched from
+///
+/// `SECLYZER_BIN_DIR` wins; otherwise the directory holding this executable, falling
+/// back to `PATH` lookup when a binary isn't there.
+pub fn bin_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("SECLYZER_BIN_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+fn binary_path(component: Component, bin_dir: Option<&Path>) -> PathBuf {
+    match bin_dir.map(|dir| dir.join(component.binary)) {
+        Some(path) if path.exists() => path,
+        _ => PathBuf::from(component.binary),
+    }
+}
+
+/// Launch a component with its output forwarded into our log
+fn spawn(component: Component, bin_dir: Option<&Path>) -> Result<(Child, Vec<JoinHandle<()>>)> {
+    let path = binary_path(component, bin_dir);
+    let mut child = Command::new(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to start {} ({})", component.name, path.display()))?;
+    info!("Started {} (pid {:?})", component.name, child.id());
+
+    let mut forwarders = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        forwarders.push(tokio::spawn(forward_lines(component.name, stdout)));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forwarders.push(tokio::spawn(forward_lines(component.name, stderr)));
+    }
+    Ok((child, forwarders))
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(name: &'static str, reader: R) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        info!("[{}] {}", name, line);
+    }
+}
+
+/// Ask a child to stop with SIGTERM, killing it if it doesn't exit in time
+async fn terminate(component: Component, child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: kill(2) with a pid we own; failure just means it already exited
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+    match timeout(TERMINATE_GRACE, child.wait()).await {
+        Ok(_) => info!("{} stopped", component.name),
+        Err(_) => {
+            warn!("{} ignored SIGTERM, killing it", component.name);
+            let _ = child.kill().await;
+        }
+    }
+}
+
+/// Run a component once in the foreground until it exits or shutdown is requested
+pub async fn run_once(
+    component: Component,
+    bin_dir: Option<&Path>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<Option<ExitStatus>> {
+    let (mut child, forwarders) = spawn(component, bin_dir)?;
+    let status = tokio::select! {
+        status = child.wait() => Some(status?),
+        _ = shutdown.changed() => {
+            terminate(component, &mut child).await;
+            None
+        }
+    };
+    // Let the last lines of output reach the log before reporting the exit
+    for forwarder in forwarders {
+        let _ = forwarder.await;
+    }
+    Ok(status)
+}
+
+/// Keep a component running, restarting it with exponential backoff when it fails
+pub async fn supervise(component: Component, bin_dir: Option<PathBuf>, mut shutdown: watch::Receiver<bool>) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        if *shutdown.borrow() {
+            return;
+        }
+        let started = Instant::now();
+        match run_once(component, bin_dir.as_deref(), shutdown.clone()).await {
+            Ok(None) => return,
+            Ok(Some(status)) if status.success() => {
+                info!("{} exited cleanly; not restarting", component.name);
+                return;
+            }
+            Ok(Some(status)) => warn!("{} exited unexpectedly ({})", component.name, status),
+            Err(e) => error!("{:#}", e),
+        }
+
+        if started.elapsed() >= STABLE_RUN {
+            backoff = INITIAL_BACKOFF;
+        }
+        warn!("Restarting {} in {:?}", component.name, backoff);
+        tokio::select! {
+            _ = sleep(backoff) => {}
+            _ = shutdown.changed() => return,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
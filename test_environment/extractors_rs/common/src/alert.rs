@@ -0,0 +1,164 @@
+// Webhook alerts on sustained anomalies. A single odd window is normal (a new
+// game, someone else borrowing the keyboard for a moment), so an alert only goes
+// out once the anomaly score has stayed above the threshold for several windows
+// in a row, and then only once for that whole run: the next alert needs the score
+// to drop back below the threshold first, and the cooldown to have passed.
+
+use crate::user::USER_ID_FIELD;
+use anyhow::{Context, Result};
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Features with the largest |z-score| listed in an alert
+pub const ALERT_TOP_FEATURES: usize = 5;
+
+/// How long a webhook may take to accept an alert
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One feature's share in an anomaly
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureContribution {
+    pub feature: String,
+    pub z_score: f64,
+}
+
+/// Body POSTed to the webhook
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub modality: String,
+    pub anomaly_score: f64,
+    pub threshold: f64,
+    /// Windows in a row above the threshold when the alert fired
+    pub consecutive_windows: u32,
+    /// End of the window that triggered the alert (Unix seconds)
+    pub timestamp: f64,
+    /// Largest |z-score| first
+    pub top_features: Vec<FeatureContribution>,
+    /// User whose windows were anomalous, on hosts with several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Decides which windows start a sustained anomaly
+#[derive(Debug, Clone)]
+pub struct AlertDebouncer {
+    threshold: f64,
+    consecutive_windows: u32,
+    cooldown_seconds: f64,
+    streak: u32,
+    /// Whether the current streak has already been alerted on
+    alerted: bool,
+    last_alert: Option<f64>,
+}
+
+impl AlertDebouncer {
+    pub fn new(threshold: f64, consecutive_windows: u32, cooldown_seconds: f64) -> Self {
+        AlertDebouncer {
+            threshold,
+            consecutive_windows: consecutive_windows.max(1),
+            cooldown_seconds,
+            streak: 0,
+            alerted: false,
+            last_alert: None,
+        }
+    }
+
+    /// Windows in a row above the threshold, up to the latest one observed
+    pub fn streak(&self) -> u32 {
+        self.streak
+    }
+
+    /// Record a window's score; true if it should be alerted on
+    pub fn observe(&mut self, score: f64, timestamp: f64) -> bool {
+        if score.is_nan() || score <= self.threshold {
+            self.streak = 0;
+            self.alerted = false;
+            return false;
+        }
+        self.streak += 1;
+        let cooled_down = self
+            .last_alert
+            .is_none_or(|last| timestamp - last >= self.cooldown_seconds);
+        if self.alerted || self.streak < self.consecutive_windows || !cooled_down {
+            return false;
+        }
+        self.alerted = true;
+        self.last_alert = Some(timestamp);
+        true
+    }
+}
+
+/// POSTs an `Alert` to a webhook when a modality's anomaly score stays high
+pub struct AlertSink {
+    client: HttpClient,
+    url: String,
+    modality: String,
+    debouncer: AlertDebouncer,
+}
+
+impl AlertSink {
+    pub fn new(url: &str, modality: &str, debouncer: AlertDebouncer) -> Self {
+        AlertSink {
+            client: HttpClient::new(),
+            url: url.to_string(),
+            modality: modality.to_string(),
+            debouncer,
+        }
+    }
+
+    /// Check one window's `BaselineProfile::score` output, sending an alert if it
+    /// completes a run of anomalous windows; returns the alert sent, if any
+    pub async fn observe(
+        &mut self,
+        scores: &serde_json::Value,
+        timestamp: f64,
+    ) -> Result<Option<Alert>> {
+        let anomaly_score = scores["anomaly_score"].as_f64().unwrap_or(0.0);
+        if !self.debouncer.observe(anomaly_score, timestamp) {
+            return Ok(None);
+        }
+
+        let alert = Alert {
+            modality: self.modality.clone(),
+            anomaly_score,
+            threshold: self.debouncer.threshold,
+            consecutive_windows: self.debouncer.streak(),
+            timestamp,
+            top_features: top_features(scores, ALERT_TOP_FEATURES),
+            user_id: scores[USER_ID_FIELD].as_str().map(str::to_string),
+        };
+        self.client
+            .post(&self.url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&alert)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to POST alert to {}", self.url))?;
+        Ok(Some(alert))
+    }
+}
+
+/// The `count` z-scores of largest magnitude, largest first
+fn top_features(scores: &serde_json::Value, count: usize) -> Vec<FeatureContribution> {
+    let mut features: Vec<FeatureContribution> = scores["z_scores"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(feature, z)| {
+            Some(FeatureContribution {
+                feature: feature.clone(),
+                z_score: z.as_f64()?,
+            })
+        })
+        .collect();
+    features.sort_by(|a, b| {
+        b.z_score
+            .abs()
+            .total_cmp(&a.z_score.abs())
+            .then_with(|| a.feature.cmp(&b.feature))
+    });
+    features.truncate(count);
+    features
+}
@@ -0,0 +1,88 @@
+// Which apps may be tracked at all. Users keep password managers and banking apps
+// out with a denylist, or name the only apps worth tracking with an allowlist.
+// app_monitor never reports a filtered app, and app_tracker drops any that still
+// arrive (an older collector, a replayed recording), so a filtered app leaves no
+// trace: no session, no transition in or out of it, and no idle time in its place.
+
+/// Case-insensitive app patterns: with `*` or `?` a glob over the whole name,
+/// otherwise a substring
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl AppFilter {
+    /// A filter passing apps that match an `allow` pattern (any app, if there are
+    /// none) and no `deny` pattern
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        let patterns = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .map(|pattern| pattern.trim().to_lowercase())
+                .filter(|pattern| !pattern.is_empty())
+                .collect()
+        };
+        AppFilter {
+            allow: patterns(allow),
+            deny: patterns(deny),
+        }
+    }
+
+    /// Whether every app passes
+    pub fn is_open(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether an app may be tracked, judged on its name and window class; the
+    /// denylist wins over the allowlist
+    pub fn allows(&self, app_name: &str, window_class: Option<&str>) -> bool {
+        let names: Vec<String> = std::iter::once(app_name)
+            .chain(window_class)
+            .map(str::to_lowercase)
+            .collect();
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| names.iter().any(|name| pattern_matches(pattern, name)))
+        };
+        !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+    }
+}
+
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    if pattern.contains(['*', '?']) {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        glob_matches(&pattern, &name)
+    } else {
+        name.contains(pattern)
+    }
+}
+
+/// `*` matches any run of characters, `?` any one; backtracks to the latest `*`
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Pattern position after the latest `*`, and where in the name it started matching
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after_star, start)) => {
+                    p = after_star;
+                    n = start + 1;
+                    star = Some((after_star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
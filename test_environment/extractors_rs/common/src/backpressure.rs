@@ -0,0 +1,87 @@
+// Bounded hand-off between a subscription and the code consuming it. A relay task
+// drains the subscription into a queue of fixed size and feeds the consumer through
+// a tokio mpsc channel, so a burst of events that outpaces extraction costs at most
+// `capacity` events of memory; the `OverflowPolicy` decides what gives when it's full.
+
+use crate::config::OverflowPolicy;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Relay `upstream` through a queue of at most `capacity` items, applying `policy`
+/// when it's full
+///
+/// Returns the consumer's end and a count of the items dropped so far. A warning
+/// is logged, under `label`, when drops start and again once the queue has drained.
+/// The relay ends when `upstream` closes and the queue is empty, or when the
+/// consumer goes away.
+pub fn bounded_relay<T: Send + 'static>(
+    mut upstream: mpsc::Receiver<T>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    label: String,
+) -> (mpsc::Receiver<T>, Arc<AtomicU64>) {
+    let capacity = capacity.max(1);
+    let dropped = Arc::new(AtomicU64::new(0));
+    // The queue lives in the relay; the channel only hands one item over at a time
+    let (tx, rx) = mpsc::channel(1);
+
+    let counter = dropped.clone();
+    tokio::spawn(async move {
+        let mut queue: VecDeque<T> = VecDeque::with_capacity(capacity);
+        let mut upstream_open = true;
+        let mut burst_drops = 0u64;
+
+        loop {
+            let full = queue.len() >= capacity;
+            let accepting = upstream_open && !(full && policy == OverflowPolicy::Block);
+            tokio::select! {
+                biased;
+                permit = tx.reserve(), if !queue.is_empty() => {
+                    let Ok(permit) = permit else {
+                        break;
+                    };
+                    if let Some(item) = queue.pop_front() {
+                        permit.send(item);
+                    }
+                    if queue.is_empty() && burst_drops > 0 {
+                        tracing::warn!(
+                            "{} queue drained after dropping {} events",
+                            label,
+                            burst_drops
+                        );
+                        burst_drops = 0;
+                    }
+                }
+                item = upstream.recv(), if accepting => {
+                    let Some(item) = item else {
+                        upstream_open = false;
+                        continue;
+                    };
+                    if !full {
+                        queue.push_back(item);
+                        continue;
+                    }
+                    if burst_drops == 0 {
+                        tracing::warn!(
+                            "{} queue full ({} events); applying {:?}",
+                            label,
+                            capacity,
+                            policy
+                        );
+                    }
+                    if policy == OverflowPolicy::DropOldest {
+                        queue.pop_front();
+                        queue.push_back(item);
+                    }
+                    burst_drops += 1;
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+                else => break,
+            }
+        }
+    });
+
+    (rx, dropped)
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Samples a feature needs before its z-score is trusted
+const MIN_SAMPLES: u64 = 2;
+
+/// Numeric fields that label an emission rather than describe behaviour
+pub const NON_FEATURE_FIELDS: &[&str] = &[
+    "window_start",
+    "window_end",
+    "observed_event_rate",
+    "window_coverage",
+    "schema_version",
+    "fingerprint",
+];
+
+/// Running mean/variance for one feature (Welford's algorithm)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    pub fn update(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample variance, 0.0 until there are two samples
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Standard deviation, once there are enough samples and they vary
+    pub fn usable_std_dev(&self) -> Option<f64> {
+        let std_dev = self.std_dev();
+        (self.count >= MIN_SAMPLES && std_dev > 0.0).then_some(std_dev)
+    }
+
+    /// Standard score of `value`, 0.0 while the baseline is too thin or constant
+    pub fn z_score(&self, value: f64) -> f64 {
+        match self.usable_std_dev() {
+            Some(std_dev) => (value - self.mean) / std_dev,
+            None => 0.0,
+        }
+    }
+}
+
+/// Per-feature model of "normal" behaviour learned from extracted feature maps
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BaselineProfile {
+    stats: HashMap<String, RunningStats>,
+    frozen: bool,
+}
+
+impl BaselineProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop learning; later samples are scored but no longer update the baseline
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Stats for a single feature, if it has been seen
+    pub fn stats(&self, feature: &str) -> Option<&RunningStats> {
+        self.stats.get(feature)
+    }
+
+    /// Fold every numeric field of a feature map into the baseline (no-op when frozen)
+    pub fn update(&mut self, features: &serde_json::Value) {
+        if self.frozen {
+            return;
+        }
+        for (name, value) in numeric_fields(features) {
+            self.stats.entry(name.to_string()).or_default().update(value);
+        }
+    }
+
+    /// Z-score of every numeric field against the baseline
+    pub fn z_scores(&self, features: &serde_json::Value) -> HashMap<String, f64> {
+        numeric_fields(features)
+            .map(|(name, value)| {
+                let z = self.stats.get(name).map(|s| s.z_score(value)).unwrap_or(0.0);
+                (name.to_string(), z)
+            })
+            .collect()
+    }
+
+    /// Mean absolute z-score across features
+    pub fn anomaly_score(z_scores: &HashMap<String, f64>) -> f64 {
+        if z_scores.is_empty() {
+            0.0
+        } else {
+            z_scores.values().map(|z| z.abs()).sum::<f64>() / z_scores.len() as f64
+        }
+    }
+
+    /// Per-feature z-scores plus the aggregate anomaly score, without learning
+    pub fn score(&self, features: &serde_json::Value) -> serde_json::Value {
+        let z_scores = self.z_scores(features);
+        json!({
+            "anomaly_score": Self::anomaly_score(&z_scores),
+            "z_scores": z_scores,
+            "baseline_frozen": self.frozen,
+        })
+    }
+
+    /// Score a feature map against the baseline, then learn from it
+    ///
+    /// Scoring happens first so a sample can't pull the baseline toward itself.
+    pub fn score_and_update(&mut self, features: &serde_json::Value) -> serde_json::Value {
+        let scores = self.score(features);
+        self.update(features);
+        scores
+    }
+
+    /// Persist the profile as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+
+        // Write then rename so a crash mid-save can't corrupt the previous profile
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Restore a profile written by `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Load the profile at `path`, starting fresh if it's missing or unreadable
+    pub fn load_or_new(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::new();
+        }
+        match Self::load(path) {
+            Ok(profile) => {
+                tracing::info!("Restored baseline from {}", path.display());
+                profile
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable baseline: {:#}", e);
+                Self::new()
+            }
+        }
+    }
+}
+
+/// Every numeric field of a feature map, except those in `NON_FEATURE_FIELDS`
+pub(crate) fn numeric_fields(
+    features: &serde_json::Value,
+) -> impl Iterator<Item = (&str, f64)> {
+    features
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| !NON_FEATURE_FIELDS.contains(&name.as_str()))
+        .filter_map(|(name, value)| value.as_f64().map(|v| (name.as_str(), v)))
+}
@@ -0,0 +1,249 @@
+// Circuit breakers for backends that go down. Without one, every tick of an outage
+// costs a failed request (with its retries) and an error line. After a run of
+// failures the breaker opens: calls are skipped without touching the backend, and
+// once a backoff has passed a single probe call is let through. A failed probe
+// reopens the breaker for twice as long (up to a cap); a successful one closes it.
+// Opening and closing are logged once each, whatever happens in between.
+
+use crate::health::Metrics;
+use crate::sink::FeatureSink;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Whether calls go through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Healthy (or not failing often enough to open): every call goes through
+    Closed,
+    /// Failing: calls are skipped until the backoff has passed
+    Open,
+    /// Backoff over: one probe call is in flight, and its result decides
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+/// Opens after `failure_threshold` failures in a row and probes with backoff
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    state: State,
+    /// Times opened since the breaker was last closed, to grow the backoff
+    trips: u32,
+    /// Calls skipped since the breaker opened
+    skipped: u64,
+}
+
+impl CircuitBreaker {
+    /// A closed breaker for the backend `name` (used in logs); a `failure_threshold`
+    /// of 0 never opens
+    pub fn new(
+        name: &str,
+        failure_threshold: u32,
+        base_backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        CircuitBreaker {
+            name: name.to_string(),
+            failure_threshold,
+            base_backoff,
+            max_backoff: max_backoff.max(base_backoff),
+            state: State::Closed { failures: 0 },
+            trips: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match self.state {
+            State::Closed { .. } => BreakerState::Closed,
+            State::Open { .. } => BreakerState::Open,
+            State::HalfOpen => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may go through now; see `allow_at`
+    pub fn allow(&mut self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    /// Whether a call may go through at `now`. Once the backoff has passed the
+    /// breaker half-opens and lets this one call through as a probe; the caller
+    /// must report its result with `record_success` or `record_failure`.
+    pub fn allow_at(&mut self, now: Instant) -> bool {
+        match self.state {
+            State::Closed { .. } => true,
+            State::Open { until } if now >= until => {
+                self.state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => {
+                self.skipped += 1;
+                false
+            }
+        }
+    }
+
+    /// The allowed call succeeded
+    pub fn record_success(&mut self) {
+        if self.trips > 0 {
+            tracing::info!(
+                "{} recovered; resuming after skipping {} calls",
+                self.name,
+                self.skipped
+            );
+        }
+        self.state = State::Closed { failures: 0 };
+        self.trips = 0;
+        self.skipped = 0;
+    }
+
+    /// The allowed call failed; see `record_failure_at`
+    pub fn record_failure(&mut self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    /// The allowed call failed at `now`: a probe reopens the breaker with a longer
+    /// backoff, and a closed breaker opens once failures reach the threshold
+    pub fn record_failure_at(&mut self, now: Instant) {
+        match self.state {
+            State::Closed { failures } => {
+                let failures = failures + 1;
+                if self.failure_threshold > 0 && failures >= self.failure_threshold {
+                    self.open(now);
+                    tracing::warn!(
+                        "{} failed {} times in a row; skipping it and probing again in {:?}",
+                        self.name,
+                        failures,
+                        self.backoff()
+                    );
+                } else {
+                    self.state = State::Closed { failures };
+                }
+            }
+            State::HalfOpen => {
+                self.open(now);
+                tracing::debug!("{} probe failed; next in {:?}", self.name, self.backoff());
+            }
+            State::Open { .. } => {}
+        }
+    }
+
+    /// How long the breaker stays open before its next probe: the base backoff,
+    /// doubled for every failed probe, up to the cap
+    pub fn backoff(&self) -> Duration {
+        let doublings = self.trips.saturating_sub(1).min(31);
+        self.base_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff)
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.trips += 1;
+        self.state = State::Open {
+            until: now + self.backoff(),
+        };
+    }
+}
+
+/// A message a `BreakerSink` dropped instead of delivering, because its breaker was
+/// open or the probe it went out as failed; callers needn't log it again
+#[derive(Debug, thiserror::Error)]
+#[error("feature sink is skipped while its circuit breaker is open")]
+pub struct PublishSkipped;
+
+/// A `FeatureSink` behind a circuit breaker
+///
+/// While the breaker is open, messages are dropped without trying the sink and
+/// flushes are skipped. Dropped messages, failed probes included, come back as
+/// `PublishSkipped` and are counted in `Metrics::skipped_publishes`; other errors
+/// are returned while the breaker is closed.
+pub struct BreakerSink {
+    inner: Box<dyn FeatureSink>,
+    breaker: Mutex<CircuitBreaker>,
+    metrics: Arc<Metrics>,
+}
+
+impl BreakerSink {
+    pub fn new(
+        inner: Box<dyn FeatureSink>,
+        breaker: CircuitBreaker,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        BreakerSink {
+            inner,
+            breaker: Mutex::new(breaker),
+            metrics,
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.breaker().state()
+    }
+
+    fn breaker(&self) -> std::sync::MutexGuard<'_, CircuitBreaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether a call may go through, and whether it's the probe
+    fn admit(&self) -> Option<bool> {
+        let mut breaker = self.breaker();
+        breaker
+            .allow()
+            .then(|| breaker.state() == BreakerState::HalfOpen)
+    }
+
+    /// Report an allowed call's result to the breaker; a failed probe comes back as
+    /// `None`, since the breaker has already logged it
+    fn settle(&self, probing: bool, result: Result<()>) -> Option<Result<()>> {
+        match result {
+            Ok(()) => {
+                self.breaker().record_success();
+                Some(Ok(()))
+            }
+            Err(e) => {
+                self.breaker().record_failure();
+                (!probing).then_some(Err(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FeatureSink for BreakerSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        let delivered = match self.admit() {
+            Some(probing) => {
+                let result = self.inner.publish(channel, value).await;
+                self.settle(probing, result)
+            }
+            None => None,
+        };
+        delivered.unwrap_or_else(|| {
+            self.metrics.record_skipped_publish();
+            Err(PublishSkipped.into())
+        })
+    }
+
+    fn declare_columns(&self, channel: &str, columns: &[&str]) {
+        self.inner.declare_columns(channel, columns);
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let Some(probing) = self.admit() else {
+            return Ok(());
+        };
+        let result = self.inner.flush().await;
+        self.settle(probing, result).unwrap_or(Ok(()))
+    }
+}
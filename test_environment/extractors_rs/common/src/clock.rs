@@ -0,0 +1,47 @@
+// Event timestamps for collectors. The wall clock is read once, at startup, and
+// every timestamp after that is that instant plus the monotonic time elapsed since,
+// so an NTP step can't make an event look older than the one before it (which
+// would turn into negative dwell and flight times). The timestamps stay close to
+// wall-clock time, so they still line up with other logs, but drift from it by
+// however much the system clock is corrected while the collector runs.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Monotonic source of wall-clock-anchored event timestamps
+#[derive(Debug, Clone, Copy)]
+pub struct EventClock {
+    /// Microseconds since the Unix epoch when the clock was anchored
+    epoch_us: u128,
+    start: Instant,
+}
+
+impl EventClock {
+    /// Anchor to the current wall-clock time
+    pub fn new() -> Self {
+        Self::anchored(SystemTime::now(), Instant::now())
+    }
+
+    /// Anchor `start` to the wall-clock time `wall`; a `wall` before the Unix
+    /// epoch counts as the epoch
+    pub fn anchored(wall: SystemTime, start: Instant) -> Self {
+        let epoch_us = wall.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros();
+        EventClock { epoch_us, start }
+    }
+
+    /// Microseconds since the Unix epoch, never less than a previous reading
+    pub fn now_micros(&self) -> u128 {
+        self.micros_at(Instant::now())
+    }
+
+    /// Timestamp of `instant`, in microseconds since the Unix epoch; instants
+    /// before the anchor map to the anchor itself
+    pub fn micros_at(&self, instant: Instant) -> u128 {
+        self.epoch_us + instant.saturating_duration_since(self.start).as_micros()
+    }
+}
+
+impl Default for EventClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
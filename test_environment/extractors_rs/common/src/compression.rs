@@ -0,0 +1,32 @@
+use anyhow::{Context, Result};
+
+/// First byte of a compressed payload; 0xFF never occurs in UTF-8, so plaintext JSON can't start with it
+pub const COMPRESSED_MAGIC: u8 = 0xFF;
+
+/// Payloads shorter than this are sent as-is; LZ4 framing would cost more than it saves
+pub const COMPRESS_MIN_BYTES: usize = 256;
+
+/// Wire bytes for a JSON payload: LZ4 behind `COMPRESSED_MAGIC` when `compress` is set
+/// and the payload is large enough, otherwise the plain UTF-8 text
+pub fn encode_payload(json: &str, compress: bool) -> Vec<u8> {
+    if !compress || json.len() < COMPRESS_MIN_BYTES {
+        return json.as_bytes().to_vec();
+    }
+    let compressed = lz4_flex::compress_prepend_size(json.as_bytes());
+    let mut payload = Vec::with_capacity(compressed.len() + 1);
+    payload.push(COMPRESSED_MAGIC);
+    payload.extend_from_slice(&compressed);
+    payload
+}
+
+/// Recover the JSON text from wire bytes, compressed or not
+pub fn decode_payload(payload: &[u8]) -> Result<String> {
+    match payload.split_first() {
+        Some((&COMPRESSED_MAGIC, compressed)) => {
+            let bytes = lz4_flex::decompress_size_prepended(compressed)
+                .context("Failed to decompress payload")?;
+            String::from_utf8(bytes).context("Decompressed payload is not UTF-8")
+        }
+        _ => String::from_utf8(payload.to_vec()).context("Payload is not UTF-8"),
+    }
+}
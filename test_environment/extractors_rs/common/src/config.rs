@@ -1,53 +1,1139 @@
-use anyhow::Result;
+use crate::alert::{AlertDebouncer, AlertSink};
+use crate::app_filter::AppFilter;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::event_source::EventSourceKind;
+use crate::signing::EventSigner;
+use crate::sink::SinkKind;
+use crate::stats;
+use crate::user::{is_valid_user_id, user_path};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
+/// Config file used by `Config::load` when `SECLYZER_CONFIG` isn't set
+pub const DEFAULT_CONFIG_FILE: &str = "seclyzer.toml";
+
+/// How raw events travel from collectors to extractors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTransport {
+    /// Fire-and-forget pub/sub; events published while an extractor is down are lost
+    PubSub,
+    /// Redis Streams with consumer groups for at-least-once delivery
+    Stream,
+}
+
+impl FromStr for EventTransport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pubsub" => Ok(EventTransport::PubSub),
+            "stream" => Ok(EventTransport::Stream),
+            other => anyhow::bail!("unknown event transport {:?} (expected pubsub or stream)", other),
+        }
+    }
+}
+
+/// Message broker carrying raw events from collectors and features from extractors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventBusKind {
+    /// Redis, over pub/sub or streams per `event_transport`
+    Redis,
+    /// Kafka at `kafka_brokers`; needs a build with the `kafka` feature
+    Kafka,
+    /// Unix domain sockets in `unix_socket_dir`, for a single host; raw events only,
+    /// with Redis taking them while no subscriber is listening and carrying features
+    Unix,
+}
+
+impl FromStr for EventBusKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Ok(EventBusKind::Redis),
+            "kafka" => Ok(EventBusKind::Kafka),
+            "unix" => Ok(EventBusKind::Unix),
+            other => anyhow::bail!("unknown event bus {:?} (expected redis, kafka or unix)", other),
+        }
+    }
+}
+
+/// What extractors do with their output while dev mode is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DevModeRouting {
+    /// Publish to `<channel>:dev` so it stays out of the real baseline
+    Channel,
+    /// Don't publish or store features at all
+    Skip,
+}
+
+impl FromStr for DevModeRouting {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "channel" => Ok(DevModeRouting::Channel),
+            "skip" => Ok(DevModeRouting::Skip),
+            other => anyhow::bail!("unknown dev mode routing {:?} (expected channel or skip)", other),
+        }
+    }
+}
+
+/// What identifies a key in keystroke features
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyIdentity {
+    /// The key name the collector reported, which follows the keyboard layout
+    Logical,
+    /// The physical position (`code`), so features survive a layout switch
+    Position,
+}
+
+impl FromStr for KeyIdentity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "logical" => Ok(KeyIdentity::Logical),
+            "position" => Ok(KeyIdentity::Position),
+            other => anyhow::bail!("unknown key identity {:?} (expected logical or position)", other),
+        }
+    }
+}
+
+/// Outlier trimming applied to keystroke timing intervals before they are summarized
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierTrim {
+    /// Keep every interval that passed the hard caps
+    None,
+    /// Drop intervals outside Tukey's 1.5 IQR fences
+    Iqr,
+    /// Drop intervals more than three scaled MADs from the median
+    Mad,
+}
+
+impl OutlierTrim {
+    /// The intervals that survive trimming
+    pub fn apply(self, values: Vec<f64>) -> Vec<f64> {
+        match self {
+            OutlierTrim::None => values,
+            OutlierTrim::Iqr => stats::iqr_filter(&values),
+            OutlierTrim::Mad => stats::mad_filter(&values),
+        }
+    }
+}
+
+impl FromStr for OutlierTrim {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(OutlierTrim::None),
+            "iqr" => Ok(OutlierTrim::Iqr),
+            "mad" => Ok(OutlierTrim::Mad),
+            other => anyhow::bail!("unknown outlier trim {:?} (expected none, iqr or mad)", other),
+        }
+    }
+}
+
+/// Which mouse features a window publishes, trading fidelity for bandwidth
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseFeatureProfile {
+    /// Every model feature
+    Full,
+    /// Speed, curvature, click and scroll summaries (30 features)
+    Compact,
+    /// A handful of summaries for the most constrained links (8 features)
+    Minimal,
+}
+
+impl FromStr for MouseFeatureProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(MouseFeatureProfile::Full),
+            "compact" => Ok(MouseFeatureProfile::Compact),
+            "minimal" => Ok(MouseFeatureProfile::Minimal),
+            other => anyhow::bail!("unknown mouse feature profile {:?} (expected full, compact or minimal)", other),
+        }
+    }
+}
+
+/// How windows that end before the extractor has seen a full window of events are published
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowWarmup {
+    /// Publish them like any other window
+    Off,
+    /// Publish them with `partial_window: 1.0` (and later windows with `0.0`)
+    Flag,
+    /// Don't publish them at all
+    Suppress,
+}
+
+impl WindowWarmup {
+    /// A window's features as they should be published, given whether the window
+    /// reaches back before the first event the extractor saw
+    pub fn apply(
+        self,
+        mut features: serde_json::Value,
+        partial: bool,
+    ) -> Option<serde_json::Value> {
+        match self {
+            WindowWarmup::Off => {}
+            WindowWarmup::Flag => {
+                features["partial_window"] = serde_json::json!(if partial { 1.0 } else { 0.0 });
+            }
+            WindowWarmup::Suppress if partial => return None,
+            WindowWarmup::Suppress => {}
+        }
+        Some(features)
+    }
+}
+
+impl FromStr for WindowWarmup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(WindowWarmup::Off),
+            "flag" => Ok(WindowWarmup::Flag),
+            "suppress" => Ok(WindowWarmup::Suppress),
+            other => anyhow::bail!("unknown window warmup {:?} (expected off, flag or suppress)", other),
+        }
+    }
+}
+
+/// What happens to a live event that arrives while the extractor's event queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Make room by discarding the longest-waiting event, keeping the freshest data
+    DropOldest,
+    /// Discard the arriving event, keeping what is already queued
+    DropNewest,
+    /// Stop reading until there is room, leaving the backlog to Redis or Kafka
+    Block,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop_newest" => Ok(OverflowPolicy::DropNewest),
+            "block" => Ok(OverflowPolicy::Block),
+            other => anyhow::bail!(
+                "unknown overflow policy {:?} (expected drop_oldest, drop_newest or block)",
+                other
+            ),
+        }
+    }
+}
+
+/// Sub-channel names appended to `topic_prefix`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ChannelNames {
+    /// Raw events from the collectors
+    pub events: String,
+    /// Per-modality features, as `<features>:<modality>`
+    pub features: String,
+    /// Collector heartbeats
+    pub heartbeat: String,
+    /// Scorer output
+    pub score: String,
+    /// Cross-modality records from the fusion service
+    pub fused: String,
+    /// Pause/resume commands for collectors and extractors
+    pub control: String,
+}
+
+impl Default for ChannelNames {
+    fn default() -> Self {
+        ChannelNames {
+            events: "events".to_string(),
+            features: "features".to_string(),
+            heartbeat: "heartbeat".to_string(),
+            score: "score".to_string(),
+            fused: "fused".to_string(),
+            control: "control".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub redis_host: String,
     pub redis_port: u16,
+    /// Redis 6 ACL user; sent only together with `redis_password`
+    pub redis_username: Option<String>,
     pub redis_password: Option<String>,
-    
+    /// Connect over TLS (`rediss://`), as managed Redis services require
+    pub redis_tls: bool,
+    pub event_transport: EventTransport,
+    pub event_bus: EventBusKind,
+    pub kafka_brokers: String,
+    /// Directory holding the subscriber sockets when `event_bus` is unix;
+    /// `$XDG_RUNTIME_DIR/seclyzer` when unset
+    pub unix_socket_dir: Option<String>,
+    pub compress_events: bool,
+    pub wal_dir: String,
+    pub wal_max_bytes: u64,
+    pub topic_prefix: String,
+    pub channels: ChannelNames,
+    pub event_source: EventSourceKind,
+    /// Live events waiting for the extractor, at most
+    pub event_queue_size: usize,
+    pub event_overflow: OverflowPolicy,
+    /// Recent distinct events remembered to drop redelivered copies; 0 disables
+    pub event_dedup_window: usize,
+    /// Shared secret collectors sign events with (HMAC-SHA256), if any
+    pub event_signing_key: Option<String>,
+    /// Drop events whose signature is missing or wrong instead of extracting them
+    pub require_signed_events: bool,
+    pub sink: SinkKind,
+    pub parquet_flush_rows: usize,
+    pub parquet_flush_seconds: u64,
+    /// Rows per CSV file before the CSV sink starts a new one
+    pub csv_rotate_rows: usize,
+
     pub influx_url: String,
     pub influx_token: String,
     pub influx_org: String,
     pub influx_bucket: String,
-    
+    pub influx_batch_size: usize,
+    pub flush_interval: u64,
+    pub influx_max_retries: u32,
+    pub influx_retry_base_ms: u64,
+    /// Most points written to InfluxDB per second, on average; 0 for no limit
+    pub influx_max_points_per_sec: f64,
+    /// Points that may be written at once before the rate limit applies
+    pub influx_burst_points: u64,
+    /// Longest wait for a TCP connection to InfluxDB
+    pub influx_connect_timeout_ms: u64,
+    /// Longest wait for any InfluxDB request, from sending to the whole response
+    pub influx_request_timeout_ms: u64,
+    /// Fail startup when InfluxDB's readiness check fails, rather than warn and carry on
+    pub influx_fail_fast: bool,
+    /// Failures in a row before writes to a sink or InfluxDB are paused; 0 never pauses
+    pub circuit_failure_threshold: u32,
+    /// First pause after the failure threshold, doubled after every failed probe
+    pub circuit_backoff_ms: u64,
+    /// Longest pause between probes of a failing sink or InfluxDB
+    pub circuit_max_backoff_ms: u64,
+
     pub window_seconds: u64,
     pub update_interval: u64,
-    
+    pub hop_seconds: Option<u64>,
+    pub emit_idle_records: bool,
+    /// Whether windows reaching back before the first event are published, flagged or dropped
+    pub window_warmup: WindowWarmup,
+    pub target_digraphs: Option<Vec<String>>,
+    pub session_gap_seconds: f64,
+    pub keystroke_buffer_size: usize,
+    pub key_identity: KeyIdentity,
+    pub outlier_trim: OutlierTrim,
+    pub privacy_mode: bool,
+    /// Compute keystroke timing features with OS auto-repeat presses folded into one
+    pub collapse_autorepeat: bool,
+    /// Keep keystroke dwell/flight statistics up to date per event instead of per window
+    pub incremental_stats: bool,
+    pub mouse_buffer_size: usize,
+    pub mouse_min_events: usize,
+    pub mouse_degraded_mode: bool,
+    /// Mouse features published per window: `full`, or the smaller `compact`/`minimal` subsets
+    pub mouse_feature_profile: MouseFeatureProfile,
+    /// Cursor moves shorter than this many pixels from the last kept position are dropped as jitter
+    pub min_move_distance_px: f64,
+    pub screen_width: Option<u32>,
+    pub screen_height: Option<u32>,
+
     pub dev_mode: bool,
+    pub dev_mode_routing: DevModeRouting,
+    /// User this host's collectors stamp on events, and whose session unstamped events join
+    pub user_id: Option<String>,
+    /// Seconds after their last event before another user's session is dropped from memory
+    pub user_idle_ttl_seconds: u64,
+
+    pub app_state_path: String,
+    pub app_categories: HashMap<String, String>,
+    /// Apps never tracked, as substrings or globs of the app name or window class
+    pub app_denylist: Vec<String>,
+    /// If set, the only apps tracked; the denylist still applies
+    pub app_allowlist: Vec<String>,
+    pub app_max_apps: usize,
+    pub app_max_sessions_per_app: usize,
+    pub baseline_dir: String,
+    pub baseline_frozen: bool,
+    /// URL sustained anomalies are POSTed to as JSON alerts; no alerting when unset
+    pub alert_webhook_url: Option<String>,
+    /// Anomaly score a window must exceed to count towards an alert
+    pub alert_threshold: f64,
+    /// Windows in a row above the threshold before an alert fires
+    pub alert_consecutive_windows: u32,
+    /// Least time between two alerts of one modality
+    pub alert_cooldown_seconds: u64,
+    pub scorer_model_path: String,
+    pub health_port: u16,
+    /// Address the health server listens on; loopback unless scraped from elsewhere
+    pub health_bind: IpAddr,
+    pub heartbeat_timeout: u64,
+    pub fusion_staleness_seconds: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            redis_host: "localhost".to_string(),
+            redis_port: 6379,
+            redis_username: None,
+            redis_password: None,
+            redis_tls: false,
+            event_transport: EventTransport::PubSub,
+            event_bus: EventBusKind::Redis,
+            kafka_brokers: "localhost:9092".to_string(),
+            unix_socket_dir: None,
+            compress_events: false,
+            wal_dir: ".".to_string(),
+            wal_max_bytes: 64 * 1024 * 1024,
+            topic_prefix: "seclyzer".to_string(),
+            channels: ChannelNames::default(),
+            event_source: EventSourceKind::Redis,
+            event_queue_size: 10_000,
+            event_overflow: OverflowPolicy::DropOldest,
+            event_dedup_window: 10_000,
+            event_signing_key: None,
+            require_signed_events: false,
+            sink: SinkKind::Redis,
+            parquet_flush_rows: 10_000,
+            parquet_flush_seconds: 300,
+            csv_rotate_rows: 100_000,
+
+            influx_url: "http://localhost:8086".to_string(),
+            influx_token: "token".to_string(),
+            influx_org: "seclyzer".to_string(),
+            influx_bucket: "behavioral_data".to_string(),
+            influx_batch_size: 500,
+            flush_interval: 10,
+            influx_max_retries: 3,
+            influx_retry_base_ms: 200,
+            influx_max_points_per_sec: 0.0,
+            influx_burst_points: 5000,
+            influx_connect_timeout_ms: 2000,
+            influx_request_timeout_ms: 10_000,
+            influx_fail_fast: true,
+            circuit_failure_threshold: 5,
+            circuit_backoff_ms: 1000,
+            circuit_max_backoff_ms: 60_000,
+
+            window_seconds: 30,
+            update_interval: 5,
+            hop_seconds: None,
+            emit_idle_records: false,
+            window_warmup: WindowWarmup::Off,
+            target_digraphs: None,
+            session_gap_seconds: 2.0,
+            keystroke_buffer_size: 10_000,
+            key_identity: KeyIdentity::Logical,
+            outlier_trim: OutlierTrim::None,
+            privacy_mode: false,
+            collapse_autorepeat: false,
+            incremental_stats: false,
+            mouse_buffer_size: 50_000,
+            mouse_min_events: 50,
+            mouse_degraded_mode: false,
+            mouse_feature_profile: MouseFeatureProfile::Full,
+            min_move_distance_px: 0.0,
+            screen_width: None,
+            screen_height: None,
+
+            dev_mode: false,
+            dev_mode_routing: DevModeRouting::Channel,
+            user_id: None,
+            user_idle_ttl_seconds: 1800,
+
+            app_state_path: "app_tracker_state.json".to_string(),
+            app_categories: HashMap::new(),
+            app_denylist: Vec::new(),
+            app_allowlist: Vec::new(),
+            app_max_apps: 200,
+            app_max_sessions_per_app: 1000,
+            baseline_dir: ".".to_string(),
+            baseline_frozen: false,
+            alert_webhook_url: None,
+            alert_threshold: 3.0,
+            alert_consecutive_windows: 3,
+            alert_cooldown_seconds: 300,
+            scorer_model_path: "scorer_model.json".to_string(),
+            health_port: 0,
+            health_bind: IpAddr::from([127, 0, 0, 1]),
+            heartbeat_timeout: 15,
+            fusion_staleness_seconds: 10.0,
+        }
+    }
 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
         dotenv::dotenv().ok();
-        
-        Ok(Config {
-            redis_host: env::var("REDIS_HOST").unwrap_or_else(|_| "localhost".to_string()),
-            redis_port: env::var("REDIS_PORT")
-                .unwrap_or_else(|_| "6379".to_string())
-                .parse()?,
-            redis_password: env::var("REDIS_PASSWORD").ok(),
-            
-            influx_url: env::var("INFLUX_URL")
-                .unwrap_or_else(|_| "http://localhost:8086".to_string()),
-            influx_token: env::var("INFLUX_TOKEN")
-                .unwrap_or_else(|_| "token".to_string()),
-            influx_org: env::var("INFLUX_ORG")
-                .unwrap_or_else(|_| "seclyzer".to_string()),
-            influx_bucket: env::var("INFLUX_BUCKET")
-                .unwrap_or_else(|_| "behavioral_data".to_string()),
-            
-            window_seconds: env::var("WINDOW_SECONDS")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()?,
-            update_interval: env::var("UPDATE_INTERVAL")
-                .unwrap_or_else(|_| "5".to_string())
-                .parse()?,
-            
-            dev_mode: env::var("SECLYZER_DEV_MODE")
-                .unwrap_or_else(|_| "false".to_string())
-                .parse()?,
+
+        let mut config = Config::default();
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse a `seclyzer.toml` file; fields missing from the file keep their defaults
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let config = Self::parse_file(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load the config file (if present) and apply environment overrides on top
+    pub fn load() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        let mut config = match env::var("SECLYZER_CONFIG") {
+            Ok(path) => Self::parse_file(&PathBuf::from(path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::parse_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Connection URL for `redis::Client::open`, including TLS and credentials if set
+    pub fn redis_url(&self) -> String {
+        self.format_redis_url(self.redis_password.as_deref().map(encode_userinfo))
+    }
+
+    /// `redis_url` with the password masked, for logging
+    pub fn redis_url_redacted(&self) -> String {
+        self.format_redis_url(self.redis_password.as_ref().map(|_| "***".to_string()))
+    }
+
+    fn format_redis_url(&self, password: Option<String>) -> String {
+        let scheme = if self.redis_tls { "rediss" } else { "redis" };
+        let username = self.redis_username.as_deref().map(encode_userinfo);
+        let credentials = match (username, password) {
+            (None, None) => String::new(),
+            (Some(username), None) => format!("{}@", username),
+            (username, Some(password)) => format!("{}:{}@", username.unwrap_or_default(), password),
+        };
+        format!("{}://{}{}:{}", scheme, credentials, self.redis_host, self.redis_port)
+    }
+
+    /// `<topic_prefix>:<name>`
+    pub fn topic(&self, name: &str) -> String {
+        format!("{}:{}", self.topic_prefix, name)
+    }
+
+    /// Channel/stream collectors publish raw events to
+    pub fn event_channel(&self) -> String {
+        self.topic(&self.channels.events)
+    }
+
+    /// Channel collectors publish heartbeats to
+    pub fn heartbeat_channel(&self) -> String {
+        self.topic(&self.channels.heartbeat)
+    }
+
+    /// Channel the scorer publishes scores to
+    pub fn score_channel(&self) -> String {
+        self.topic(&self.channels.score)
+    }
+
+    /// Channel the fusion service publishes joined records to
+    pub fn fused_channel(&self) -> String {
+        self.topic(&self.channels.fused)
+    }
+
+    /// Signer for collectors to sign events with, if a signing key is set
+    pub fn event_signer(&self) -> Option<EventSigner> {
+        self.event_signing_key.as_deref().map(EventSigner::new)
+    }
+
+    /// Signer for subscribers to check events against, if signatures are required
+    pub fn event_verifier(&self) -> Option<EventSigner> {
+        self.event_signer().filter(|_| self.require_signed_events)
+    }
+
+    /// Webhook alerting for `modality`'s anomaly scores, if a webhook URL is set
+    pub fn alert_sink(&self, modality: &str) -> Option<AlertSink> {
+        let url = self.alert_webhook_url.as_deref()?;
+        let debouncer = AlertDebouncer::new(
+            self.alert_threshold,
+            self.alert_consecutive_windows,
+            self.alert_cooldown_seconds as f64,
+        );
+        Some(AlertSink::new(url, modality, debouncer))
+    }
+
+    /// Circuit breaker for the backend `name`, with the configured threshold and backoff
+    pub fn circuit_breaker(&self, name: &str) -> CircuitBreaker {
+        CircuitBreaker::new(
+            name,
+            self.circuit_failure_threshold,
+            Duration::from_millis(self.circuit_backoff_ms),
+            Duration::from_millis(self.circuit_max_backoff_ms),
+        )
+    }
+
+    /// Which apps may be tracked, from the allow and deny lists
+    pub fn app_filter(&self) -> AppFilter {
+        AppFilter::new(&self.app_allowlist, &self.app_denylist)
+    }
+
+    /// Channel carrying pause/resume commands
+    pub fn control_channel(&self) -> String {
+        self.topic(&self.channels.control)
+    }
+
+    /// Channel extractors publish their buffered events to on a `dump` command
+    pub fn dump_channel(&self) -> String {
+        format!("{}:dump", self.control_channel())
+    }
+
+    /// Prefix shared by every modality's feature channel, including the trailing `:`
+    pub fn feature_channel_prefix(&self) -> String {
+        format!("{}:", self.topic(&self.channels.features))
+    }
+
+    /// Channel a modality's features go to, or `None` if dev mode suppresses them
+    pub fn feature_channel(&self, modality: &str) -> Option<String> {
+        let channel = format!("{}{}", self.feature_channel_prefix(), modality);
+        match (self.dev_mode, self.dev_mode_routing) {
+            (false, _) => Some(channel),
+            (true, DevModeRouting::Channel) => Some(format!("{}:dev", channel)),
+            (true, DevModeRouting::Skip) => None,
+        }
+    }
+
+    /// Where a modality's baseline profile is persisted
+    pub fn baseline_path(&self, modality: &str) -> PathBuf {
+        Path::new(&self.baseline_dir).join(format!("{}_baseline.json", modality))
+    }
+
+    /// Where `user`'s baseline profile for a modality is persisted (`baseline_path`
+    /// itself for the unattributed session)
+    pub fn user_baseline_path(&self, modality: &str, user: Option<&str>) -> PathBuf {
+        user_path(&self.baseline_path(modality), user)
+    }
+
+    /// Where a collector keeps its write-ahead log of undelivered events
+    pub fn wal_path(&self, source: &str) -> PathBuf {
+        Path::new(&self.wal_dir).join(format!("{}.wal", source))
+    }
+
+    /// Directory of the subscriber sockets when `event_bus` is unix: `unix_socket_dir`,
+    /// or `seclyzer` in the user's runtime directory, which no one else can get into
+    pub fn socket_dir(&self) -> Result<PathBuf> {
+        if let Some(dir) = &self.unix_socket_dir {
+            return Ok(PathBuf::from(dir));
+        }
+        match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+            Some(runtime_dir) => Ok(Path::new(&runtime_dir).join("seclyzer")),
+            None => anyhow::bail!("event_bus = unix needs unix_socket_dir or XDG_RUNTIME_DIR set"),
+        }
+    }
+
+    /// Seconds between the ends of consecutive feature windows (defaults to `update_interval`)
+    pub fn hop_seconds(&self) -> u64 {
+        self.hop_seconds.unwrap_or(self.update_interval)
+    }
+
+    /// Reject values the extractors can't run with
+    pub fn validate(&self) -> Result<()> {
+        anyhow::ensure!(!self.topic_prefix.is_empty(), "topic_prefix must not be empty");
+        for (name, channel) in [
+            ("events", &self.channels.events),
+            ("features", &self.channels.features),
+            ("heartbeat", &self.channels.heartbeat),
+            ("score", &self.channels.score),
+            ("fused", &self.channels.fused),
+            ("control", &self.channels.control),
+        ] {
+            anyhow::ensure!(!channel.is_empty(), "channels.{} must not be empty", name);
+        }
+        anyhow::ensure!(
+            self.event_bus != EventBusKind::Kafka || !self.kafka_brokers.is_empty(),
+            "kafka_brokers must not be empty when event_bus is kafka"
+        );
+        anyhow::ensure!(
+            self.event_bus != EventBusKind::Unix || self.unix_socket_dir.as_deref() != Some(""),
+            "unix_socket_dir must not be empty when event_bus is unix"
+        );
+        anyhow::ensure!(
+            self.event_queue_size > 0,
+            "event_queue_size must be greater than 0"
+        );
+        if let Some(user_id) = &self.user_id {
+            anyhow::ensure!(
+                is_valid_user_id(user_id),
+                "user_id must be 1-64 letters, digits, '.', '_', '-' or '@', not starting with '.' (got {:?})",
+                user_id
+            );
+        }
+        anyhow::ensure!(
+            self.user_idle_ttl_seconds > 0,
+            "user_idle_ttl_seconds must be greater than 0"
+        );
+        anyhow::ensure!(
+            !self.require_signed_events || self.event_signing_key.is_some(),
+            "require_signed_events needs an event_signing_key to verify with"
+        );
+        anyhow::ensure!(
+            self.wal_max_bytes > 0,
+            "wal_max_bytes must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.window_seconds > 0,
+            "window_seconds must be greater than 0 (got {})",
+            self.window_seconds
+        );
+        anyhow::ensure!(
+            self.update_interval > 0,
+            "update_interval must be greater than 0 (got {})",
+            self.update_interval
+        );
+        anyhow::ensure!(
+            self.influx_batch_size > 0,
+            "influx_batch_size must be greater than 0 (got {})",
+            self.influx_batch_size
+        );
+        anyhow::ensure!(
+            self.influx_max_points_per_sec.is_finite() && self.influx_max_points_per_sec >= 0.0,
+            "influx_max_points_per_sec must be 0 (no limit) or positive (got {})",
+            self.influx_max_points_per_sec
+        );
+        anyhow::ensure!(
+            self.influx_burst_points > 0,
+            "influx_burst_points must be greater than 0 (got {})",
+            self.influx_burst_points
+        );
+        anyhow::ensure!(
+            self.influx_connect_timeout_ms > 0 && self.influx_request_timeout_ms > 0,
+            "influx_connect_timeout_ms and influx_request_timeout_ms must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.circuit_backoff_ms > 0 && self.circuit_max_backoff_ms >= self.circuit_backoff_ms,
+            "circuit_backoff_ms must be greater than 0 and at most circuit_max_backoff_ms (got {} and {})",
+            self.circuit_backoff_ms,
+            self.circuit_max_backoff_ms
+        );
+        anyhow::ensure!(
+            self.flush_interval > 0,
+            "flush_interval must be greater than 0 (got {})",
+            self.flush_interval
+        );
+        anyhow::ensure!(
+            self.parquet_flush_rows > 0 && self.parquet_flush_seconds > 0,
+            "parquet_flush_rows and parquet_flush_seconds must be greater than 0"
+        );
+        anyhow::ensure!(self.csv_rotate_rows > 0, "csv_rotate_rows must be greater than 0");
+        anyhow::ensure!(
+            self.hop_seconds != Some(0),
+            "hop_seconds must be greater than 0"
+        );
+        if let Some(digraphs) = &self.target_digraphs {
+            for digraph in digraphs {
+                anyhow::ensure!(
+                    digraph.chars().count() == 2 && digraph.chars().all(|c| c.is_ascii_alphanumeric()),
+                    "target digraphs must be two letters or digits (got {:?})",
+                    digraph
+                );
+            }
+        }
+        anyhow::ensure!(
+            self.keystroke_buffer_size > 0 && self.mouse_buffer_size > 0,
+            "keystroke_buffer_size and mouse_buffer_size must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.min_move_distance_px.is_finite() && self.min_move_distance_px >= 0.0,
+            "min_move_distance_px must not be negative (got {})",
+            self.min_move_distance_px
+        );
+        anyhow::ensure!(
+            self.app_max_apps > 0 && self.app_max_sessions_per_app > 0,
+            "app_max_apps and app_max_sessions_per_app must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.alert_threshold.is_finite() && self.alert_threshold >= 0.0,
+            "alert_threshold must not be negative (got {})",
+            self.alert_threshold
+        );
+        anyhow::ensure!(
+            self.alert_consecutive_windows > 0,
+            "alert_consecutive_windows must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.heartbeat_timeout > 0,
+            "heartbeat_timeout must be greater than 0 (got {})",
+            self.heartbeat_timeout
+        );
+        anyhow::ensure!(
+            self.session_gap_seconds.is_finite() && self.session_gap_seconds > 0.0,
+            "session_gap_seconds must be greater than 0 (got {})",
+            self.session_gap_seconds
+        );
+        anyhow::ensure!(
+            self.fusion_staleness_seconds.is_finite() && self.fusion_staleness_seconds >= 0.0,
+            "fusion_staleness_seconds must not be negative (got {})",
+            self.fusion_staleness_seconds
+        );
+        anyhow::ensure!(
+            !self.incremental_stats
+                || (self.outlier_trim == OutlierTrim::None && !self.collapse_autorepeat),
+            "incremental_stats can't be combined with outlier_trim or collapse_autorepeat"
+        );
+        anyhow::ensure!(
+            self.screen_width.is_some() == self.screen_height.is_some(),
+            "screen_width and screen_height must be set together"
+        );
+        anyhow::ensure!(
+            self.screen_width != Some(0) && self.screen_height != Some(0),
+            "screen dimensions must be greater than 0"
+        );
+        Ok(())
+    }
+
+    fn parse_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Override fields with any environment variables that are set
+    fn apply_env(&mut self) -> Result<()> {
+        if let Ok(host) = env::var("REDIS_HOST") {
+            self.redis_host = host;
+        }
+        if let Some(port) = env_parse("REDIS_PORT")? {
+            self.redis_port = port;
+        }
+        if let Ok(username) = env::var("REDIS_USERNAME") {
+            self.redis_username = Some(username).filter(|u| !u.is_empty());
+        }
+        if let Ok(password) = env::var("REDIS_PASSWORD") {
+            self.redis_password = Some(password).filter(|p| !p.is_empty());
+        }
+        if let Some(tls) = env_parse("REDIS_TLS")? {
+            self.redis_tls = tls;
+        }
+        if let Ok(transport) = env::var("EVENT_TRANSPORT") {
+            self.event_transport = transport.parse()?;
+        }
+        if let Ok(bus) = env::var("EVENT_BUS") {
+            self.event_bus = bus.parse()?;
+        }
+        if let Ok(brokers) = env::var("KAFKA_BROKERS") {
+            self.kafka_brokers = brokers;
+        }
+        if let Ok(dir) = env::var("UNIX_SOCKET_DIR") {
+            self.unix_socket_dir = Some(dir).filter(|dir| !dir.is_empty());
+        }
+        if let Some(compress) = env_parse("COMPRESS_EVENTS")? {
+            self.compress_events = compress;
+        }
+        if let Ok(dir) = env::var("WAL_DIR") {
+            self.wal_dir = dir;
+        }
+        if let Some(max_bytes) = env_parse("WAL_MAX_BYTES")? {
+            self.wal_max_bytes = max_bytes;
+        }
+        if let Ok(prefix) = env::var("TOPIC_PREFIX") {
+            self.topic_prefix = prefix;
+        }
+        if let Ok(channel) = env::var("EVENT_CHANNEL") {
+            self.channels.events = channel;
+        }
+        if let Ok(source) = env::var("EVENT_SOURCE") {
+            self.event_source = source.parse()?;
+        }
+        if let Some(size) = env_parse("EVENT_QUEUE_SIZE")? {
+            self.event_queue_size = size;
+        }
+        if let Ok(policy) = env::var("EVENT_OVERFLOW") {
+            self.event_overflow = policy.parse()?;
+        }
+        if let Some(window) = env_parse("EVENT_DEDUP_WINDOW")? {
+            self.event_dedup_window = window;
+        }
+        if let Ok(key) = env::var("EVENT_SIGNING_KEY") {
+            self.event_signing_key = Some(key).filter(|k| !k.is_empty());
+        }
+        if let Some(require) = env_parse("REQUIRE_SIGNED_EVENTS")? {
+            self.require_signed_events = require;
+        }
+        if let Ok(sink) = env::var("SINK") {
+            self.sink = sink.parse()?;
+        }
+        if let Some(rows) = env_parse("PARQUET_FLUSH_ROWS")? {
+            self.parquet_flush_rows = rows;
+        }
+        if let Some(seconds) = env_parse("PARQUET_FLUSH_SECONDS")? {
+            self.parquet_flush_seconds = seconds;
+        }
+        if let Some(rows) = env_parse("CSV_ROTATE_ROWS")? {
+            self.csv_rotate_rows = rows;
+        }
+
+        if let Ok(url) = env::var("INFLUX_URL") {
+            self.influx_url = url;
+        }
+        if let Ok(token) = env::var("INFLUX_TOKEN") {
+            self.influx_token = token;
+        }
+        if let Ok(org) = env::var("INFLUX_ORG") {
+            self.influx_org = org;
+        }
+        if let Ok(bucket) = env::var("INFLUX_BUCKET") {
+            self.influx_bucket = bucket;
+        }
+        if let Some(batch_size) = env_parse("INFLUX_BATCH_SIZE")? {
+            self.influx_batch_size = batch_size;
+        }
+        if let Some(flush_interval) = env_parse("FLUSH_INTERVAL")? {
+            self.flush_interval = flush_interval;
+        }
+        if let Some(retries) = env_parse("INFLUX_MAX_RETRIES")? {
+            self.influx_max_retries = retries;
+        }
+        if let Some(base_ms) = env_parse("INFLUX_RETRY_BASE_MS")? {
+            self.influx_retry_base_ms = base_ms;
+        }
+        if let Some(rate) = env_parse("INFLUX_MAX_POINTS_PER_SEC")? {
+            self.influx_max_points_per_sec = rate;
+        }
+        if let Some(burst) = env_parse("INFLUX_BURST_POINTS")? {
+            self.influx_burst_points = burst;
+        }
+        if let Some(timeout) = env_parse("INFLUX_CONNECT_TIMEOUT_MS")? {
+            self.influx_connect_timeout_ms = timeout;
+        }
+        if let Some(timeout) = env_parse("INFLUX_REQUEST_TIMEOUT_MS")? {
+            self.influx_request_timeout_ms = timeout;
+        }
+        if let Some(fail_fast) = env_parse("INFLUX_FAIL_FAST")? {
+            self.influx_fail_fast = fail_fast;
+        }
+        if let Some(threshold) = env_parse("CIRCUIT_FAILURE_THRESHOLD")? {
+            self.circuit_failure_threshold = threshold;
+        }
+        if let Some(backoff) = env_parse("CIRCUIT_BACKOFF_MS")? {
+            self.circuit_backoff_ms = backoff;
+        }
+        if let Some(backoff) = env_parse("CIRCUIT_MAX_BACKOFF_MS")? {
+            self.circuit_max_backoff_ms = backoff;
+        }
+
+        if let Some(window_seconds) = env_parse("WINDOW_SECONDS")? {
+            self.window_seconds = window_seconds;
+        }
+        if let Some(update_interval) = env_parse("UPDATE_INTERVAL")? {
+            self.update_interval = update_interval;
+        }
+        if let Some(hop) = env_parse("HOP_SECONDS")? {
+            self.hop_seconds = Some(hop);
+        }
+        if let Some(emit) = env_parse("EMIT_IDLE_RECORDS")? {
+            self.emit_idle_records = emit;
+        }
+        if let Ok(warmup) = env::var("WINDOW_WARMUP") {
+            self.window_warmup = warmup.parse()?;
+        }
+        if let Ok(digraphs) = env::var("TARGET_DIGRAPHS") {
+            self.target_digraphs = Some(
+                digraphs
+                    .split(',')
+                    .map(|d| d.trim().to_string())
+                    .filter(|d| !d.is_empty())
+                    .collect(),
+            );
+        }
+        if let Some(gap) = env_parse("SESSION_GAP_SECONDS")? {
+            self.session_gap_seconds = gap;
+        }
+        if let Some(size) = env_parse("KEYSTROKE_BUFFER_SIZE")? {
+            self.keystroke_buffer_size = size;
+        }
+        if let Ok(identity) = env::var("KEY_IDENTITY") {
+            self.key_identity = identity.parse()?;
+        }
+        if let Ok(trim) = env::var("OUTLIER_TRIM") {
+            self.outlier_trim = trim.parse()?;
+        }
+        if let Some(privacy_mode) = env_parse("PRIVACY_MODE")? {
+            self.privacy_mode = privacy_mode;
+        }
+        if let Some(collapse) = env_parse("COLLAPSE_AUTOREPEAT")? {
+            self.collapse_autorepeat = collapse;
+        }
+        if let Some(incremental) = env_parse("INCREMENTAL_STATS")? {
+            self.incremental_stats = incremental;
+        }
+        if let Some(size) = env_parse("MOUSE_BUFFER_SIZE")? {
+            self.mouse_buffer_size = size;
+        }
+        if let Some(min_events) = env_parse("MOUSE_MIN_EVENTS")? {
+            self.mouse_min_events = min_events;
+        }
+        if let Some(degraded_mode) = env_parse("MOUSE_DEGRADED_MODE")? {
+            self.mouse_degraded_mode = degraded_mode;
+        }
+        if let Ok(profile) = env::var("MOUSE_FEATURE_PROFILE") {
+            self.mouse_feature_profile = profile.parse()?;
+        }
+        if let Some(distance) = env_parse("MIN_MOVE_DISTANCE_PX")? {
+            self.min_move_distance_px = distance;
+        }
+        if let Some(width) = env_parse("SCREEN_WIDTH")? {
+            self.screen_width = Some(width);
+        }
+        if let Some(height) = env_parse("SCREEN_HEIGHT")? {
+            self.screen_height = Some(height);
+        }
+
+        if let Some(dev_mode) = env_parse("SECLYZER_DEV_MODE")? {
+            self.dev_mode = dev_mode;
+        }
+        if let Ok(routing) = env::var("DEV_MODE_ROUTING") {
+            self.dev_mode_routing = routing.parse()?;
+        }
+        if let Ok(user_id) = env::var("USER_ID") {
+            self.user_id = Some(user_id).filter(|id| !id.is_empty());
+        }
+        if let Some(seconds) = env_parse("USER_IDLE_TTL_SECONDS")? {
+            self.user_idle_ttl_seconds = seconds;
+        }
+
+        if let Ok(path) = env::var("APP_STATE_PATH") {
+            self.app_state_path = path;
+        }
+        if let Ok(categories) = env::var("APP_CATEGORIES") {
+            self.app_categories = parse_app_categories(&categories)?;
+        }
+        if let Ok(denylist) = env::var("APP_DENYLIST") {
+            self.app_denylist = parse_app_patterns(&denylist);
+        }
+        if let Ok(allowlist) = env::var("APP_ALLOWLIST") {
+            self.app_allowlist = parse_app_patterns(&allowlist);
+        }
+        if let Some(max_apps) = env_parse("APP_MAX_APPS")? {
+            self.app_max_apps = max_apps;
+        }
+        if let Some(max_sessions) = env_parse("APP_MAX_SESSIONS_PER_APP")? {
+            self.app_max_sessions_per_app = max_sessions;
+        }
+        if let Ok(dir) = env::var("BASELINE_DIR") {
+            self.baseline_dir = dir;
+        }
+        if let Some(frozen) = env_parse("BASELINE_FROZEN")? {
+            self.baseline_frozen = frozen;
+        }
+        if let Ok(url) = env::var("ALERT_WEBHOOK_URL") {
+            self.alert_webhook_url = Some(url).filter(|u| !u.is_empty());
+        }
+        if let Some(threshold) = env_parse("ALERT_THRESHOLD")? {
+            self.alert_threshold = threshold;
+        }
+        if let Some(windows) = env_parse("ALERT_CONSECUTIVE_WINDOWS")? {
+            self.alert_consecutive_windows = windows;
+        }
+        if let Some(seconds) = env_parse("ALERT_COOLDOWN_SECONDS")? {
+            self.alert_cooldown_seconds = seconds;
+        }
+        if let Ok(path) = env::var("SCORER_MODEL_PATH") {
+            self.scorer_model_path = path;
+        }
+        if let Some(port) = env_parse("HEALTH_PORT")? {
+            self.health_port = port;
+        }
+        if let Some(address) = env_parse("HEALTH_BIND")? {
+            self.health_bind = address;
+        }
+        if let Some(timeout) = env_parse("HEARTBEAT_TIMEOUT")? {
+            self.heartbeat_timeout = timeout;
+        }
+        if let Some(staleness) = env_parse("FUSION_STALENESS_SECONDS")? {
+            self.fusion_staleness_seconds = staleness;
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated app patterns, e.g. `keepassxc,*bank*`
+fn parse_app_patterns(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty())
+        .collect()
+}
+
+/// Parse `app=category` pairs separated by commas, e.g. `firefox=browser,code=editor`
+fn parse_app_categories(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((app, category)) if !app.trim().is_empty() && !category.trim().is_empty() => {
+                Ok((app.trim().to_string(), category.trim().to_string()))
+            }
+            _ => anyhow::bail!(
+                "APP_CATEGORIES entries must look like app=category (got {:?})",
+                pair
+            ),
+        })
+        .collect()
+}
+
+/// Percent-encode a URL username or password so `:`, `@`, `/` etc. survive parsing
+fn encode_userinfo(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
         })
+        .collect()
+}
+
+/// Parse an environment variable if it's set
+fn env_parse<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .with_context(|| format!("Invalid value for {}: {:?}", name, value)),
+        Err(_) => Ok(None),
     }
 }
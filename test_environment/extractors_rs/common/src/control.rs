@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// Message on the control channel, e.g. `{"cmd": "pause"}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum ControlCommand {
+    /// Stop collecting and publishing until resumed (password prompts, meetings, ...)
+    Pause,
+    Resume,
+    /// Publish the extractors' buffered events to the dump channel, for debugging
+    /// odd feature values; collectors ignore it
+    Dump,
+}
+
+impl ControlCommand {
+    pub fn from_json(payload: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(payload)
+    }
+
+    /// Whether monitoring is paused once this command has been applied, or `None`
+    /// if the command doesn't pause or resume
+    pub fn pauses(self) -> Option<bool> {
+        match self {
+            ControlCommand::Pause => Some(true),
+            ControlCommand::Resume => Some(false),
+            ControlCommand::Dump => None,
+        }
+    }
+}
+
+/// Block on the control channel, handing each message to `on_message` until the
+/// connection fails; for the collectors, which have no async runtime
+pub fn watch_control_blocking<F>(
+    client: &redis::Client,
+    channel: &str,
+    mut on_message: F,
+) -> redis::RedisResult<()>
+where
+    F: FnMut(serde_json::Result<ControlCommand>),
+{
+    let mut con = client.get_connection()?;
+    let mut pubsub = con.as_pubsub();
+    pubsub.subscribe(channel)?;
+    loop {
+        let payload: String = pubsub.get_message()?.get_payload()?;
+        on_message(ControlCommand::from_json(&payload));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::sink::FeatureSink;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Columns describing the emission itself, filled in by the sink
+const EMISSION_COLUMNS: &[&str] = &["timestamp", "modality"];
+
+/// Columns taken from every record ahead of the declared feature columns
+const WINDOW_COLUMNS: &[&str] = &["window_start", "window_end"];
+
+/// The open file for one channel
+struct Table {
+    /// Record columns, after the emission columns
+    columns: Vec<String>,
+    out: Option<BufWriter<File>>,
+    /// Rows in the current file
+    rows: usize,
+    /// Files opened so far, numbering the next so quick rotations never collide
+    files: usize,
+}
+
+#[derive(Default)]
+struct Tables {
+    declared: HashMap<String, Table>,
+    /// Undeclared channels already warned about
+    ignored: HashSet<String>,
+}
+
+/// Appends feature records to per-channel CSV files, for training without Parquet tooling
+///
+/// Each file opens with a header row: the emission `timestamp` (RFC 3339), the
+/// `modality` (last segment of the channel), the window bounds and then the declared
+/// feature columns. A feature missing from a record is an empty cell. Rows are
+/// flushed as they're written, so the file can be tailed. After `rotate_rows` rows
+/// the file is closed and the next record starts a new one, as
+/// `<dir>/<channel>-<timestamp>-<n>.csv`.
+pub struct CsvSink {
+    dir: PathBuf,
+    rotate_rows: usize,
+    tables: Mutex<Tables>,
+}
+
+impl CsvSink {
+    pub fn new(dir: PathBuf, rotate_rows: usize) -> Self {
+        CsvSink {
+            dir,
+            rotate_rows,
+            tables: Mutex::new(Tables::default()),
+        }
+    }
+
+    /// Start a new file for a channel and write its header
+    fn open_file(&self, channel: &str, table: &mut Table) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let name = format!(
+            "{}-{}-{}.csv",
+            channel.replace(':', "_"),
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            table.files
+        );
+        let path = self.dir.join(name);
+        let file =
+            File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        let header = EMISSION_COLUMNS.iter().copied();
+        write_row(&mut out, header.chain(table.columns.iter().map(String::as_str)))?;
+
+        tracing::info!("Writing {} feature records to {}", channel, path.display());
+        table.out = Some(out);
+        table.rows = 0;
+        table.files += 1;
+        Ok(())
+    }
+}
+
+/// Write one CSV line, quoting any field that needs it
+fn write_row<'a>(out: &mut impl Write, fields: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let line = fields.into_iter().map(escape_field).collect::<Vec<_>>().join(",");
+    writeln!(out, "{}", line)?;
+    Ok(())
+}
+
+/// RFC 4180 quoting: fields with a comma, quote or line break are wrapped in quotes,
+/// with inner quotes doubled
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[async_trait]
+impl FeatureSink for CsvSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(table) = tables.declared.get_mut(channel) else {
+            if tables.ignored.insert(channel.to_string()) {
+                tracing::warn!("No CSV columns declared for {}; not writing it", channel);
+            }
+            return Ok(());
+        };
+
+        if table.out.is_none() {
+            self.open_file(channel, table)?;
+        }
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let modality = channel.rsplit(':').next().unwrap_or(channel);
+        let values: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| {
+                value
+                    .get(column)
+                    .and_then(serde_json::Value::as_f64)
+                    .map_or_else(String::new, |v| v.to_string())
+            })
+            .collect();
+        let Some(out) = table.out.as_mut() else {
+            return Ok(());
+        };
+        write_row(
+            out,
+            [timestamp.as_str(), modality]
+                .into_iter()
+                .chain(values.iter().map(String::as_str)),
+        )?;
+        out.flush()?;
+
+        table.rows += 1;
+        if table.rows >= self.rotate_rows {
+            table.out = None;
+        }
+        Ok(())
+    }
+
+    fn declare_columns(&self, channel: &str, columns: &[&str]) {
+        let columns = WINDOW_COLUMNS
+            .iter()
+            .chain(columns)
+            .map(|column| column.to_string())
+            .collect();
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        tables.declared.insert(
+            channel.to_string(),
+            Table {
+                columns,
+                out: None,
+                rows: 0,
+                files: 0,
+            },
+        );
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        for table in tables.declared.values_mut() {
+            if let Some(out) = table.out.as_mut() {
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+// Duplicate suppression for at-least-once transports. A stream consumer that
+// crashes before acking gets its pending entries again, and a collector retrying a
+// publish whose ack was lost sends the event twice; a keystroke counted twice
+// inflates key counts and puts a zero flight time into every timing feature. Events
+// carry no ID of their own, so one is derived from what the event says happened.
+
+use crate::RawEvent;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Identity of an event: its source, timestamp and everything it records, but not
+/// delivery details like the dev-mode flag
+pub fn event_id(event: &RawEvent) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (
+        &event.event_type,
+        event.ts,
+        &event.event,
+        &event.key,
+        event.code,
+        &event.button,
+        &event.app_name,
+        &event.window_class,
+        &event.user_id,
+    )
+        .hash(&mut hasher);
+    [event.x, event.y, event.scroll_delta]
+        .map(|value| value.map(f64::to_bits))
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Remembers the IDs of the last `capacity` distinct events and flags repeats of them
+#[derive(Debug, Clone)]
+pub struct EventDeduplicator {
+    capacity: usize,
+    seen: HashSet<u64>,
+    /// `seen` in arrival order, oldest first, for eviction
+    order: VecDeque<u64>,
+    duplicates: u64,
+}
+
+impl EventDeduplicator {
+    pub fn new(capacity: usize) -> Self {
+        EventDeduplicator {
+            capacity: capacity.max(1),
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            duplicates: 0,
+        }
+    }
+
+    /// Whether `event` repeats one seen recently; a new event is remembered
+    pub fn is_duplicate(&mut self, event: &RawEvent) -> bool {
+        let id = event_id(event);
+        if !self.seen.insert(id) {
+            self.duplicates += 1;
+            return true;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        false
+    }
+
+    /// Duplicates flagged so far
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
@@ -0,0 +1,674 @@
+// The broker between collectors, extractors and their consumers. Raw events and
+// features go over an `EventBus` (Redis or, with the `kafka` feature, Kafka);
+// heartbeats, control commands and health checks stay on Redis either way. On a
+// single host, raw events can skip the broker and go straight from collector to
+// extractor over Unix domain sockets, with Redis as the fallback.
+
+use crate::config::{Config, EventBusKind, EventTransport};
+use crate::redis_client::{RedisClient, STREAM_FIELD, STREAM_MAXLEN};
+use crate::redis_link::RedisLink;
+use anyhow::Result;
+use async_trait::async_trait;
+use redis::streams::StreamMaxlen;
+use redis::Commands;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Publishes and subscribes to named topics on the configured broker
+#[async_trait]
+pub trait EventBus: Send + Sync {
+    /// Send `payload` on `topic`
+    async fn publish(&self, topic: &str, payload: &serde_json::Value) -> Result<()>;
+
+    /// Receive every (decompressed) payload published on `topic`; subscribers with
+    /// different `group`s each see every message
+    async fn subscribe(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<String>>;
+
+    /// Like `subscribe`, but each payload is acknowledged to the broker only once its
+    /// `Delivery` is dropped, so anything still unhandled when the subscriber dies is
+    /// delivered again. Brokers without acknowledgements hand payloads over as-is.
+    async fn subscribe_acked(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<Delivery>> {
+        Ok(unacked(self.subscribe(topic, group).await?))
+    }
+}
+
+/// A payload from `EventBus::subscribe_acked`; hold on to it until the event it
+/// carries has been handled, as dropping it acknowledges the payload
+#[derive(Debug)]
+pub struct Delivery {
+    payload: String,
+    /// Where to send `id` once handled, for brokers that track acknowledgements
+    ack: Option<(String, mpsc::UnboundedSender<String>)>,
+}
+
+impl Delivery {
+    /// A payload with nothing to acknowledge
+    pub fn unacked(payload: String) -> Self {
+        Delivery { payload, ack: None }
+    }
+
+    /// A payload whose `id` is sent to `acks` when it's dropped
+    pub fn acked(payload: String, id: String, acks: mpsc::UnboundedSender<String>) -> Self {
+        Delivery { payload, ack: Some((id, acks)) }
+    }
+
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    /// Take the payload, acknowledging it right away
+    pub fn into_payload(mut self) -> String {
+        std::mem::take(&mut self.payload)
+    }
+}
+
+impl Drop for Delivery {
+    fn drop(&mut self) {
+        if let Some((id, acks)) = self.ack.take() {
+            // The subscription is gone if this fails, and the entry is redelivered
+            let _ = acks.send(id);
+        }
+    }
+}
+
+/// Wrap payloads that have nothing to acknowledge as `Delivery`s
+pub(crate) fn unacked(mut payloads: mpsc::Receiver<String>) -> mpsc::Receiver<Delivery> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(payload) = payloads.recv().await {
+            if tx.send(Delivery::unacked(payload)).await.is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Blocking counterpart of `EventBus` for collectors, which run outside a runtime
+pub trait BlockingEventBus: Send {
+    /// Send an already encoded payload on `topic`, failing if the broker didn't take it
+    fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<()>;
+
+    /// Whether a publish is worth attempting now, e.g. false between reconnect attempts
+    fn ready(&mut self) -> bool {
+        true
+    }
+}
+
+/// Connect to the bus `config.event_bus` names; Redis reuses the given client
+pub async fn connect(config: &Config, redis: Arc<RedisClient>) -> Result<Arc<dyn EventBus>> {
+    match config.event_bus {
+        EventBusKind::Redis => Ok(Arc::new(RedisEventBus::new(redis))),
+        #[cfg(feature = "kafka")]
+        EventBusKind::Kafka => Ok(Arc::new(kafka::KafkaEventBus::new(
+            &config.kafka_brokers,
+            config.compress_events,
+        )?)),
+        #[cfg(not(feature = "kafka"))]
+        EventBusKind::Kafka => anyhow::bail!("event_bus = kafka needs the kafka feature"),
+        #[cfg(unix)]
+        EventBusKind::Unix => Ok(Arc::new(unix::UnixEventBus::new(
+            config.socket_dir()?,
+            config.compress_events,
+            Some(Arc::new(RedisEventBus::new(redis))),
+        ))),
+        #[cfg(not(unix))]
+        EventBusKind::Unix => anyhow::bail!("event_bus = unix needs a Unix platform"),
+    }
+}
+
+/// Blocking `connect` for collectors; Redis publishes through its own `RedisLink`
+pub fn connect_blocking(
+    config: &Config,
+    redis: redis::Client,
+) -> Result<Box<dyn BlockingEventBus>> {
+    match config.event_bus {
+        EventBusKind::Redis => Ok(Box::new(RedisEventLink::new(
+            RedisLink::new(redis),
+            config.event_transport,
+        ))),
+        #[cfg(feature = "kafka")]
+        EventBusKind::Kafka => Ok(Box::new(kafka::KafkaEventLink::new(&config.kafka_brokers)?)),
+        #[cfg(not(feature = "kafka"))]
+        EventBusKind::Kafka => anyhow::bail!("event_bus = kafka needs the kafka feature"),
+        #[cfg(unix)]
+        EventBusKind::Unix => Ok(Box::new(unix::UnixEventLink::new(
+            config.socket_dir()?,
+            Some(Box::new(RedisEventLink::new(
+                RedisLink::new(redis),
+                config.event_transport,
+            ))),
+        ))),
+        #[cfg(not(unix))]
+        EventBusKind::Unix => anyhow::bail!("event_bus = unix needs a Unix platform"),
+    }
+}
+
+/// Kafka topic for a channel name; Kafka only allows `[a-zA-Z0-9._-]`, so the
+/// `:` separators become `.`
+pub fn kafka_topic(channel: &str) -> String {
+    channel.replace(':', ".")
+}
+
+/// Redis pub/sub or streams, per `EVENT_TRANSPORT`
+pub struct RedisEventBus {
+    redis: Arc<RedisClient>,
+}
+
+impl RedisEventBus {
+    pub fn new(redis: Arc<RedisClient>) -> Self {
+        RedisEventBus { redis }
+    }
+}
+
+#[async_trait]
+impl EventBus for RedisEventBus {
+    async fn publish(&self, topic: &str, payload: &serde_json::Value) -> Result<()> {
+        self.redis.publish_event(topic, payload).await
+    }
+
+    async fn subscribe(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<String>> {
+        // Stream entries are acknowledged as they're handed over
+        let mut deliveries = self.redis.subscribe_events(topic, group).await?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            while let Some(delivery) = deliveries.recv().await {
+                if tx.send(delivery.into_payload()).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    async fn subscribe_acked(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<Delivery>> {
+        self.redis.subscribe_events(topic, group).await
+    }
+}
+
+/// Blocking Redis publisher that drops its connection after a failed send, so the
+/// next publish reconnects
+pub struct RedisEventLink {
+    link: RedisLink,
+    transport: EventTransport,
+}
+
+impl RedisEventLink {
+    pub fn new(link: RedisLink, transport: EventTransport) -> Self {
+        RedisEventLink { link, transport }
+    }
+}
+
+impl BlockingEventBus for RedisEventLink {
+    fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<()> {
+        let con = self
+            .link
+            .connection()
+            .ok_or_else(|| anyhow::anyhow!("Redis unavailable"))?;
+        let sent: redis::RedisResult<()> = match self.transport {
+            EventTransport::Stream => con
+                .xadd_maxlen(
+                    topic,
+                    StreamMaxlen::Approx(STREAM_MAXLEN),
+                    "*",
+                    &[(STREAM_FIELD, payload)],
+                )
+                .map(|_: String| ()),
+            EventTransport::PubSub => con.publish(topic, payload),
+        };
+        if sent.is_err() {
+            self.link.reset();
+        }
+        Ok(sent?)
+    }
+
+    fn ready(&mut self) -> bool {
+        self.link.connection().is_some()
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaEventBus, KafkaEventLink};
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::{kafka_topic, BlockingEventBus, EventBus};
+    use crate::compression::{decode_payload, encode_payload};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::producer::{BaseProducer, BaseRecord, FutureProducer, FutureRecord, Producer};
+    use rdkafka::Message;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// How long a publish waits for the broker to acknowledge the message
+    const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Capacity of the channel between a consumer task and its subscriber
+    const SUBSCRIBE_BUFFER: usize = 10000;
+
+    /// Kafka producer plus the settings subscriptions need for their consumers
+    pub struct KafkaEventBus {
+        brokers: String,
+        producer: FutureProducer,
+        compress: bool,
+    }
+
+    impl KafkaEventBus {
+        /// Producer for `brokers` (comma-separated `host:port`), compressing payloads
+        /// if `compress` is set
+        pub fn new(brokers: &str, compress: bool) -> Result<Self> {
+            tracing::info!("Using Kafka at {}", brokers);
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .set("message.timeout.ms", SEND_TIMEOUT.as_millis().to_string())
+                .create()?;
+            Ok(KafkaEventBus {
+                brokers: brokers.to_string(),
+                producer,
+                compress,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EventBus for KafkaEventBus {
+        async fn publish(&self, topic: &str, payload: &serde_json::Value) -> Result<()> {
+            let payload = encode_payload(&serde_json::to_string(payload)?, self.compress);
+            let topic = kafka_topic(topic);
+            self.producer
+                .send(FutureRecord::<(), _>::to(&topic).payload(&payload), SEND_TIMEOUT)
+                .await
+                .map_err(|(e, _)| e)?;
+            Ok(())
+        }
+
+        /// Joins consumer group `group`, starting from the newest offset the first
+        /// time the group is seen
+        async fn subscribe(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<String>> {
+            let consumer: StreamConsumer = ClientConfig::new()
+                .set("bootstrap.servers", &self.brokers)
+                .set("group.id", group)
+                .set("auto.offset.reset", "latest")
+                .create()?;
+            let topic = kafka_topic(topic);
+            consumer.subscribe(&[&topic])?;
+            tracing::info!("Consuming Kafka topic {} as group {}", topic, group);
+
+            let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+            tokio::spawn(async move {
+                loop {
+                    let payload = match consumer.recv().await {
+                        Ok(message) => message.payload().map(decode_payload),
+                        Err(e) => {
+                            tracing::warn!("Failed to read Kafka topic {}: {}", topic, e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    };
+                    match payload {
+                        Some(Ok(payload)) => {
+                            if tx.send(payload).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Err(e)) => tracing::warn!("Unreadable message on {}: {:#}", topic, e),
+                        None => {}
+                    }
+                }
+                tracing::warn!("Subscription to {} ended", topic);
+            });
+
+            Ok(rx)
+        }
+    }
+
+    /// Blocking Kafka producer for collectors; each publish flushes, so a broker
+    /// outage fails the publish and the event stays in the WAL
+    pub struct KafkaEventLink {
+        producer: BaseProducer,
+    }
+
+    impl KafkaEventLink {
+        pub fn new(brokers: &str) -> Result<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .set("message.timeout.ms", SEND_TIMEOUT.as_millis().to_string())
+                .create()?;
+            Ok(KafkaEventLink { producer })
+        }
+    }
+
+    impl BlockingEventBus for KafkaEventLink {
+        fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<()> {
+            let topic = kafka_topic(topic);
+            self.producer
+                .send(BaseRecord::<(), _>::to(&topic).payload(payload))
+                .map_err(|(e, _)| e)?;
+            self.producer.flush(SEND_TIMEOUT)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{UnixEventBus, UnixEventLink};
+
+/// Unix domain sockets in one directory: every subscription listens on
+/// `<topic>@<group>.sock` there, and a publish writes the payload to each socket of
+/// its topic as a frame of a 4-byte big-endian length and the (maybe compressed)
+/// JSON. With no subscriber listening, the publish goes to the fallback bus. The
+/// directory must belong to this user and be closed to everyone else, since whoever
+/// can get into it could read every raw event or inject their own.
+#[cfg(unix)]
+mod unix {
+    use super::{BlockingEventBus, EventBus};
+    use crate::compression::{decode_payload, encode_payload};
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::{mpsc, Mutex};
+
+    /// Capacity of the channel between a socket listener and its subscriber
+    const SUBSCRIBE_BUFFER: usize = 10000;
+
+    /// Largest frame a subscriber accepts; anything bigger means a corrupt stream
+    const MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+    /// Longest a publish waits on one subscriber that stopped reading before giving up
+    /// on its connection, so it can't stall the collector
+    const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Socket a `group` subscribed to `topic` listens on
+    fn socket_path(dir: &Path, topic: &str, group: &str) -> PathBuf {
+        dir.join(format!("{}@{}.sock", topic.replace('/', "_"), group.replace('/', "_")))
+    }
+
+    /// Create `dir` with mode 0700 if it's missing, then check it's private
+    fn create_private_dir(dir: &Path) -> Result<()> {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        check_private_dir(dir)
+    }
+
+    /// Refuse a socket directory that isn't ours alone: a symlink, one another user
+    /// owns (they may have made it first), or one others can read or write
+    fn check_private_dir(dir: &Path) -> Result<()> {
+        let metadata = fs::symlink_metadata(dir)
+            .with_context(|| format!("Failed to inspect {}", dir.display()))?;
+        anyhow::ensure!(metadata.is_dir(), "{} is not a directory", dir.display());
+        // SAFETY: geteuid has no preconditions and can't fail
+        let uid = unsafe { libc::geteuid() };
+        anyhow::ensure!(
+            metadata.uid() == uid,
+            "{} belongs to uid {}, not this user ({}); refusing to use its sockets",
+            dir.display(),
+            metadata.uid(),
+            uid
+        );
+        anyhow::ensure!(
+            metadata.mode() & 0o077 == 0,
+            "{} is open to other users (mode {:o}); chmod 700 it",
+            dir.display(),
+            metadata.mode() & 0o777
+        );
+        Ok(())
+    }
+
+    /// Sockets of every subscription to `topic`; none while `dir` doesn't exist, and
+    /// an error if it isn't private
+    fn subscriber_sockets(dir: &Path, topic: &str) -> Result<Vec<PathBuf>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        check_private_dir(dir)?;
+        let prefix = format!("{}@", topic.replace('/', "_"));
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to list {}", dir.display()))?;
+        Ok(entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".sock"))
+            })
+            .collect())
+    }
+
+    /// Write a whole frame, or give up after `WRITE_TIMEOUT`; a frame cut short leaves
+    /// the stream unusable, so either way a failed write drops the connection
+    async fn write_frame(stream: &mut UnixStream, frame: &[u8]) -> bool {
+        tokio::time::timeout(WRITE_TIMEOUT, stream.write_all(frame))
+            .await
+            .is_ok_and(|written| written.is_ok())
+    }
+
+    fn frame(payload: &[u8]) -> Result<Vec<u8>> {
+        anyhow::ensure!(
+            payload.len() <= MAX_FRAME_BYTES,
+            "Payload of {} bytes is too big for a socket frame",
+            payload.len()
+        );
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        Ok(frame)
+    }
+
+    /// Direct delivery to subscribers on this host, through `fallback` (Redis, from
+    /// `connect`) when none is listening; subscriptions take from both
+    pub struct UnixEventBus {
+        dir: PathBuf,
+        compress: bool,
+        fallback: Option<std::sync::Arc<dyn EventBus>>,
+        /// Open connections to subscriber sockets, reused across publishes
+        connections: Mutex<HashMap<PathBuf, UnixStream>>,
+    }
+
+    impl UnixEventBus {
+        pub fn new(
+            dir: impl AsRef<Path>,
+            compress: bool,
+            fallback: Option<std::sync::Arc<dyn EventBus>>,
+        ) -> Self {
+            UnixEventBus {
+                dir: dir.as_ref().to_path_buf(),
+                compress,
+                fallback,
+                connections: Mutex::new(HashMap::new()),
+            }
+        }
+
+        /// Write `frame` to every subscriber of `topic`; returns how many took it
+        async fn send_to_subscribers(&self, topic: &str, frame: &[u8]) -> Result<usize> {
+            let mut connections = self.connections.lock().await;
+            let mut delivered = 0;
+            for path in subscriber_sockets(&self.dir, topic)? {
+                let cached = match connections.get_mut(&path) {
+                    Some(stream) => write_frame(stream, frame).await,
+                    None => false,
+                };
+                // The subscriber may have restarted since; a fresh connection finds it
+                let sent = cached
+                    || match UnixStream::connect(&path).await {
+                        Ok(mut stream) => {
+                            let sent = write_frame(&mut stream, frame).await;
+                            connections.insert(path.clone(), stream);
+                            sent
+                        }
+                        Err(_) => false,
+                    };
+                if sent {
+                    delivered += 1;
+                } else {
+                    connections.remove(&path);
+                }
+            }
+            Ok(delivered)
+        }
+    }
+
+    #[async_trait]
+    impl EventBus for UnixEventBus {
+        async fn publish(&self, topic: &str, payload: &serde_json::Value) -> Result<()> {
+            let encoded = encode_payload(&serde_json::to_string(payload)?, self.compress);
+            if self.send_to_subscribers(topic, &frame(&encoded)?).await? > 0 {
+                return Ok(());
+            }
+            match &self.fallback {
+                Some(fallback) => fallback.publish(topic, payload).await,
+                None => anyhow::bail!(
+                    "No subscriber to {} is listening in {}",
+                    topic,
+                    self.dir.display()
+                ),
+            }
+        }
+
+        /// Listens on this subscription's socket, replacing one a previous run
+        /// left behind, and also takes whatever arrives on the fallback
+        async fn subscribe(&self, topic: &str, group: &str) -> Result<mpsc::Receiver<String>> {
+            create_private_dir(&self.dir)?;
+            let path = socket_path(&self.dir, topic, group);
+            let _ = fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)
+                .with_context(|| format!("Failed to listen on {}", path.display()))?;
+            tracing::info!("Listening for {} on {}", topic, path.display());
+
+            let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+            if let Some(fallback) = &self.fallback {
+                let mut fallback_rx = fallback.subscribe(topic, group).await?;
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    while let Some(payload) = fallback_rx.recv().await {
+                        if tx.send(payload).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            let topic = topic.to_string();
+            tokio::spawn(async move {
+                while !tx.is_closed() {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(read_frames(stream, tx.clone(), topic.clone()));
+                        }
+                        Err(e) => {
+                            tracing::warn!("Failed to accept a publisher for {}: {}", topic, e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    }
+                }
+            });
+
+            Ok(rx)
+        }
+    }
+
+    /// Forward one publisher's frames until it disconnects or sends garbage
+    async fn read_frames(mut stream: UnixStream, tx: mpsc::Sender<String>, topic: String) {
+        let mut payload = Vec::new();
+        while let Ok(len) = stream.read_u32().await {
+            let len = len as usize;
+            if len > MAX_FRAME_BYTES {
+                tracing::warn!("Dropping a publisher to {} that sent a {}-byte frame", topic, len);
+                return;
+            }
+            payload.resize(len, 0);
+            if stream.read_exact(&mut payload).await.is_err() {
+                return;
+            }
+            match decode_payload(&payload) {
+                Ok(payload) => {
+                    if tx.send(payload).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => tracing::warn!("Unreadable message on {}: {:#}", topic, e),
+            }
+        }
+    }
+
+    /// Blocking `UnixEventBus` publisher for collectors; with no subscriber
+    /// listening, events go to `fallback`, or fail and stay in the WAL
+    pub struct UnixEventLink {
+        dir: PathBuf,
+        fallback: Option<Box<dyn BlockingEventBus>>,
+        connections: HashMap<PathBuf, std::os::unix::net::UnixStream>,
+    }
+
+    impl UnixEventLink {
+        pub fn new(dir: impl AsRef<Path>, fallback: Option<Box<dyn BlockingEventBus>>) -> Self {
+            UnixEventLink {
+                dir: dir.as_ref().to_path_buf(),
+                fallback,
+                connections: HashMap::new(),
+            }
+        }
+    }
+
+    impl BlockingEventBus for UnixEventLink {
+        fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<()> {
+            let frame = frame(payload)?;
+            let mut delivered = 0;
+            for path in subscriber_sockets(&self.dir, topic)? {
+                let cached = self
+                    .connections
+                    .get_mut(&path)
+                    .is_some_and(|stream| stream.write_all(&frame).is_ok());
+                let sent = cached
+                    || match std::os::unix::net::UnixStream::connect(&path) {
+                        Ok(mut stream) => {
+                            // A subscriber that stopped reading mustn't stall the collector
+                            let sent = stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+                                && stream.write_all(&frame).is_ok();
+                            self.connections.insert(path.clone(), stream);
+                            sent
+                        }
+                        Err(_) => false,
+                    };
+                if sent {
+                    delivered += 1;
+                } else {
+                    self.connections.remove(&path);
+                }
+            }
+            if delivered > 0 {
+                return Ok(());
+            }
+            match &mut self.fallback {
+                Some(fallback) => fallback.publish(topic, payload),
+                None => anyhow::bail!(
+                    "No subscriber to {} is listening in {}",
+                    topic,
+                    self.dir.display()
+                ),
+            }
+        }
+
+        fn ready(&mut self) -> bool {
+            let listening = check_private_dir(&self.dir).is_ok()
+                && fs::read_dir(&self.dir).is_ok_and(|entries| {
+                    entries
+                        .flatten()
+                        .any(|entry| entry.path().extension().is_some_and(|ext| ext == "sock"))
+                });
+            listening || self.fallback.as_mut().is_some_and(|fallback| fallback.ready())
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::backpressure::bounded_relay;
+use crate::config::OverflowPolicy;
+use crate::{Delivery, EventBus, EventDeduplicator, EventSigner, RawEvent};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+/// Where an extractor reads raw events from
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum EventSourceKind {
+    /// Live events from the collectors over the event bus (Redis unless `EVENT_BUS` says otherwise)
+    Redis,
+    /// A recorded JSON-lines event file
+    File(PathBuf),
+    /// JSON-lines events piped to stdin
+    Stdin,
+}
+
+impl FromStr for EventSourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("file:") {
+            anyhow::ensure!(!path.is_empty(), "event source file: needs a path");
+            return Ok(EventSourceKind::File(PathBuf::from(path)));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Ok(EventSourceKind::Redis),
+            "stdin" => Ok(EventSourceKind::Stdin),
+            other => anyhow::bail!("unknown event source {:?} (expected redis, file:PATH or stdin)", other),
+        }
+    }
+}
+
+impl TryFrom<String> for EventSourceKind {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl EventSourceKind {
+    /// Open a file or stdin source; `None` for Redis, which needs a live `EventBus`
+    pub async fn open_local(&self) -> Result<Option<Box<dyn EventSource>>> {
+        match self {
+            EventSourceKind::Redis => Ok(None),
+            EventSourceKind::File(path) => {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .with_context(|| format!("Failed to open event file {}", path.display()))?;
+                Ok(Some(Box::new(ReaderEventSource::new(BufReader::new(file)))))
+            }
+            EventSourceKind::Stdin => {
+                Ok(Some(Box::new(ReaderEventSource::new(BufReader::new(tokio::io::stdin())))))
+            }
+        }
+    }
+}
+
+/// A stream of validated raw events
+#[async_trait]
+pub trait EventSource: Send {
+    /// The next event, or `None` once the source is exhausted or closed
+    async fn next_event(&mut self) -> Option<RawEvent>;
+}
+
+/// Events delivered over an `EventBus` (Redis pub/sub or streams, or Kafka)
+pub struct BusEventSource {
+    channel: String,
+    payloads: mpsc::Receiver<Delivery>,
+    /// The event last returned, acknowledged once the next one is asked for
+    handling: Option<Delivery>,
+    dropped: Arc<AtomicU64>,
+    /// Checks each payload's signature before it's parsed, when set
+    verifier: Option<EventSigner>,
+    /// Drops redelivered events, when set
+    dedup: Option<EventDeduplicator>,
+}
+
+impl BusEventSource {
+    /// Subscribe to `channel`; `group` names the consumer group for streams and Kafka
+    pub async fn subscribe(bus: &dyn EventBus, channel: &str, group: &str) -> Result<Self> {
+        let payloads = bus.subscribe_acked(channel, group).await?;
+        Ok(BusEventSource {
+            channel: channel.to_string(),
+            payloads,
+            handling: None,
+            dropped: Arc::new(AtomicU64::new(0)),
+            verifier: None,
+            dedup: None,
+        })
+    }
+
+    /// Drop events that don't carry a valid signature from `verifier`, if given
+    pub fn with_verifier(self, verifier: Option<EventSigner>) -> Self {
+        BusEventSource { verifier, ..self }
+    }
+
+    /// Drop events identical to one of the last `window` distinct events, as an
+    /// at-least-once transport may deliver them again; 0 keeps every event
+    pub fn with_dedup(self, window: usize) -> Self {
+        let dedup = (window > 0).then(|| EventDeduplicator::new(window));
+        BusEventSource { dedup, ..self }
+    }
+
+    /// Hold at most `capacity` undelivered events, applying `policy` to any beyond that
+    pub fn with_queue(self, capacity: usize, policy: OverflowPolicy) -> Self {
+        let (payloads, dropped) =
+            bounded_relay(self.payloads, capacity, policy, self.channel.clone());
+        BusEventSource { payloads, dropped, ..self }
+    }
+
+    /// Events dropped so far because the queue was full
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Redelivered events dropped so far
+    pub fn duplicate_events(&self) -> u64 {
+        self.dedup.as_ref().map_or(0, EventDeduplicator::duplicates)
+    }
+}
+
+#[async_trait]
+impl EventSource for BusEventSource {
+    async fn next_event(&mut self) -> Option<RawEvent> {
+        // Asking for the next event means the last one has been handled
+        self.handling = None;
+        loop {
+            // Rejected and dropped payloads are acknowledged too; they won't get better
+            let delivery = self.payloads.recv().await?;
+            let payload = delivery.payload();
+            if let Some(verifier) = &self.verifier {
+                if let Err(e) = verifier.verify(payload) {
+                    tracing::warn!("Dropping event that failed signature check: {}", e);
+                    continue;
+                }
+            }
+            match RawEvent::from_json(payload) {
+                Ok(event) => {
+                    if self.dedup.as_mut().is_some_and(|dedup| dedup.is_duplicate(&event)) {
+                        tracing::debug!(
+                            "Dropping duplicate {} event at {}",
+                            event.event_type,
+                            event.ts
+                        );
+                        continue;
+                    }
+                    self.handling = Some(delivery);
+                    return Some(event);
+                }
+                Err(e) => tracing::warn!("Skipping malformed event: {}", e),
+            }
+        }
+    }
+}
+
+/// JSON-lines events read from a file, stdin or any other buffered reader
+pub struct ReaderEventSource<R> {
+    lines: tokio::io::Lines<R>,
+}
+
+impl<R: AsyncBufRead + Unpin + Send> ReaderEventSource<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderEventSource { lines: reader.lines() }
+    }
+}
+
+#[async_trait]
+impl<R: AsyncBufRead + Unpin + Send> EventSource for ReaderEventSource<R> {
+    async fn next_event(&mut self) -> Option<RawEvent> {
+        loop {
+            let line = match self.lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return None,
+                Err(e) => {
+                    tracing::error!("Failed to read events: {}", e);
+                    return None;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match RawEvent::from_json(&line) {
+                Ok(event) => return Some(event),
+                Err(e) => tracing::warn!("Skipping malformed event: {}", e),
+            }
+        }
+    }
+}
+
+/// Events held in memory, mostly for tests
+#[derive(Debug, Default)]
+pub struct MemoryEventSource {
+    events: VecDeque<RawEvent>,
+}
+
+impl From<Vec<RawEvent>> for MemoryEventSource {
+    fn from(events: Vec<RawEvent>) -> Self {
+        MemoryEventSource { events: events.into() }
+    }
+}
+
+#[async_trait]
+impl EventSource for MemoryEventSource {
+    async fn next_event(&mut self) -> Option<RawEvent> {
+        self.events.pop_front()
+    }
+}
+
+/// An extractor that can be fed from any `EventSource`
+pub trait WindowedExtractor {
+    /// Buffer an event if it belongs to this extractor's modality
+    fn add_raw_event(&mut self, event: RawEvent);
+
+    /// Every window ending at `now` (Unix seconds): none when there's too little to
+    /// go on, and one per user when several share the extractor
+    fn extract_windows_at(&mut self, now: f64) -> Vec<serde_json::Value>;
+}
+
+/// Drive an extractor through a finite source on event time, writing one JSON line
+/// per emitted window to `out`; returns the number of windows written
+///
+/// Windows end every `hop_seconds` after the first event, with a final window
+/// covering the latest one.
+pub async fn extract_offline<E: WindowedExtractor>(
+    source: &mut dyn EventSource,
+    extractor: &mut E,
+    hop_seconds: u64,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let hop = hop_seconds as f64;
+    let mut written = 0;
+    let mut emit = |extractor: &mut E, window_end: f64, out: &mut dyn Write| -> Result<()> {
+        for features in extractor.extract_windows_at(window_end) {
+            writeln!(out, "{}", features)?;
+            written += 1;
+        }
+        Ok(())
+    };
+
+    let mut next_window_end: Option<f64> = None;
+    let mut latest_ts: Option<f64> = None;
+    while let Some(event) = source.next_event().await {
+        let ts = event.ts as f64 / 1_000_000.0;
+        let window_end = next_window_end.get_or_insert(ts + hop);
+        while ts > *window_end {
+            emit(extractor, *window_end, out)?;
+            *window_end += hop;
+        }
+        extractor.add_raw_event(event);
+        latest_ts = Some(latest_ts.map_or(ts, |latest| latest.max(ts)));
+    }
+
+    if let Some(ts) = latest_ts {
+        emit(extractor, ts, out)?;
+    }
+    out.flush()?;
+    Ok(written)
+}
@@ -0,0 +1,51 @@
+use std::future::pending;
+use tokio::task::{JoinError, JoinHandle};
+
+/// Feature computation for one window, running on tokio's blocking pool
+///
+/// A large window can take long enough to compute that doing it inline in the
+/// `select!` loop would stop events from being drained. The loop starts the
+/// computation here instead and picks up the result from `finished`, a branch that
+/// stays pending while nothing is running. `T` is what a computation produces: one
+/// window's features by default, or e.g. a window per user.
+pub struct PendingExtraction<T = Option<serde_json::Value>> {
+    handle: Option<JoinHandle<T>>,
+}
+
+impl<T> Default for PendingExtraction<T> {
+    fn default() -> Self {
+        PendingExtraction { handle: None }
+    }
+}
+
+impl<T: Send + 'static> PendingExtraction<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a window is still being computed
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Run `compute` on a blocking thread; ignored while another window is running
+    pub fn start<F>(&mut self, compute: F)
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if !self.is_running() {
+            self.handle = Some(tokio::task::spawn_blocking(compute));
+        }
+    }
+
+    /// Resolves with the running computation's result; never resolves while idle.
+    /// Cancel-safe, so it can sit in a `select!` branch.
+    pub async fn finished(&mut self) -> Result<T, JoinError> {
+        let Some(handle) = self.handle.as_mut() else {
+            return pending().await;
+        };
+        let result = handle.await;
+        self.handle = None;
+        result
+    }
+}
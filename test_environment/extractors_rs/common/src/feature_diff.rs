@@ -0,0 +1,37 @@
+// Which features moved between two windows, for explaining a score: a window that
+// scored anomalous is diffed against a normal one, and the features that changed
+// most relative to their usual spread are the likely cause.
+
+use crate::baseline::{numeric_fields, BaselineProfile};
+
+/// Features of `b` that differ from `a`, largest contribution first
+///
+/// A feature's contribution is its change `b - a` in standard deviations of the
+/// baseline, so swings in naturally noisy features don't drown out small but unusual
+/// ones. Features the baseline can't normalize (unseen, too few samples or constant)
+/// contribute their raw change. Only numeric features present in both windows are
+/// compared, and unchanged ones are left out.
+pub fn feature_diff(
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+    baseline: &BaselineProfile,
+) -> Vec<(String, f64)> {
+    let mut diff: Vec<(String, f64)> = numeric_fields(b)
+        .filter_map(|(name, after)| {
+            let before = a.get(name)?.as_f64()?;
+            let change = after - before;
+            if change == 0.0 {
+                return None;
+            }
+            let contribution = match baseline.stats(name).and_then(|s| s.usable_std_dev()) {
+                Some(std_dev) => change / std_dev,
+                None => change,
+            };
+            Some((name.to_string(), contribution))
+        })
+        .collect();
+    diff.sort_by(|(name_x, x), (name_y, y)| {
+        y.abs().total_cmp(&x.abs()).then_with(|| name_x.cmp(name_y))
+    });
+    diff
+}
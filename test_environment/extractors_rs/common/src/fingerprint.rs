@@ -0,0 +1,53 @@
+// Locality-sensitive fingerprints of feature windows. Each feature is quantized
+// onto a logarithmic grid and hashed together with its name; the fingerprint is
+// the SimHash of those tokens, so windows whose features mostly land in the same
+// cells share most of their 64 bits. It is meant for cheap approximate grouping
+// (compare Hamming distances) ahead of running a full model, not for identity.
+
+/// Grid cells per doubling of a feature's magnitude
+const CELLS_PER_OCTAVE: f64 = 2.0;
+
+/// 64-bit SimHash of named feature values
+///
+/// Values within the same half-octave (same sign) hash alike, so near-identical
+/// feature vectors differ in few bits and unrelated ones in about half of them.
+/// Non-finite values are skipped. The result is stable across runs and machines.
+pub fn feature_fingerprint<'a>(features: impl IntoIterator<Item = (&'a str, f64)>) -> u64 {
+    let mut votes = [0i32; 64];
+    for (name, value) in features {
+        if !value.is_finite() {
+            continue;
+        }
+        let cell = ((1.0 + value.abs()).log2() * CELLS_PER_OCTAVE).round() as i64;
+        let cell = if value < 0.0 { -cell } else { cell };
+        let token = mix(fnv1a(name.as_bytes()) ^ mix(cell as u64));
+        for (bit, vote) in votes.iter_mut().enumerate() {
+            *vote += if (token >> bit) & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    votes
+        .iter()
+        .enumerate()
+        .filter(|(_, &vote)| vote > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+/// Number of bits two fingerprints differ in
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is guaranteed not to change between releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// SplitMix64 finalizer, so neighbouring cells give unrelated tokens
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,74 @@
+// App focus as seen by the keystroke and mouse extractors. Focus events share the
+// event channel with input events; they don't feed any feature, but they give each
+// window its context: the app in front when it closed, and whether focus moved
+// during it. An Alt-Tab leaves a short input gap that reads like the user pausing,
+// so consumers want to know a window's quiet spell came with a switch.
+
+use crate::RawEvent;
+use std::collections::VecDeque;
+
+/// The focused app and recent switches between apps
+#[derive(Debug, Clone, Default)]
+pub struct FocusTracker {
+    active_app: Option<String>,
+    /// When focus moved from one app to another (Unix seconds), oldest first
+    switches: VecDeque<f64>,
+}
+
+impl FocusTracker {
+    pub fn new() -> Self {
+        FocusTracker::default()
+    }
+
+    /// Take in an `app` focus event; a switch is focus moving to a different app,
+    /// so the first focus event and repeats of the current app aren't switches.
+    /// Switches more than `keep_seconds` older than this one are forgotten.
+    pub fn observe(&mut self, event: &RawEvent, keep_seconds: f64) {
+        let Some(app) = &event.app_name else {
+            return;
+        };
+        if self.active_app.as_ref().is_some_and(|active| active != app) {
+            let ts = event.ts as f64 / 1_000_000.0;
+            self.switches.push_back(ts);
+            while self
+                .switches
+                .front()
+                .is_some_and(|&t| t < ts - keep_seconds)
+            {
+                self.switches.pop_front();
+            }
+        }
+        self.active_app = Some(app.clone());
+    }
+
+    /// App named by the latest focus event, if one has arrived
+    pub fn active_app(&self) -> Option<&str> {
+        self.active_app.as_deref()
+    }
+
+    /// Whether focus switched apps in `(window_start, window_end]`; `None` until a
+    /// focus event has arrived, as there's no telling before then
+    pub fn contained_switch(&self, window_start: f64, window_end: f64) -> Option<bool> {
+        self.active_app.as_ref()?;
+        Some(
+            self.switches
+                .iter()
+                .any(|&t| t > window_start && t <= window_end),
+        )
+    }
+
+    /// Tag a published window with its focus context: `active_app` and
+    /// `contained_app_switch` (1.0 or 0.0), once focus events have been seen
+    pub fn tag(
+        features: &mut serde_json::Value,
+        active_app: Option<String>,
+        contained_switch: Option<bool>,
+    ) {
+        if let Some(app) = active_app {
+            features["active_app"] = serde_json::json!(app);
+        }
+        if let Some(switched) = contained_switch {
+            features["contained_app_switch"] = serde_json::json!(if switched { 1.0 } else { 0.0 });
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{InfluxClient, InfluxMetrics, RedisClient, RedisMetrics};
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Counters an extractor updates as it runs, rendered at `/metrics`
+#[derive(Debug, Default)]
+pub struct Metrics {
+    buffered_events: AtomicU64,
+    dropped_events: AtomicU64,
+    features_published: AtomicU64,
+    last_publish_ms: AtomicU64,
+    skipped_publishes: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_buffered_events(&self, count: usize) {
+        self.buffered_events.store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Total live events dropped so far because the event queue was full
+    pub fn set_dropped_events(&self, count: u64) {
+        self.dropped_events.store(count, Ordering::Relaxed);
+    }
+
+    /// Count a feature publish the sink delivered and stamp the time
+    pub fn record_publish(&self) {
+        self.features_published.fetch_add(1, Ordering::Relaxed);
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.last_publish_ms.store(now_ms, Ordering::Relaxed);
+    }
+
+    /// Count a feature message dropped because the sink's circuit breaker was open or
+    /// its probe failed
+    pub fn record_skipped_publish(&self) {
+        self.skipped_publishes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn buffered_events(&self) -> u64 {
+        self.buffered_events.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+
+    pub fn features_published(&self) -> u64 {
+        self.features_published.load(Ordering::Relaxed)
+    }
+
+    pub fn skipped_publishes(&self) -> u64 {
+        self.skipped_publishes.load(Ordering::Relaxed)
+    }
+
+    /// Unix seconds of the last publish, 0.0 if nothing was published yet
+    pub fn last_publish_timestamp(&self) -> f64 {
+        self.last_publish_ms.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    /// Prometheus text exposition of these counters plus the clients' own
+    pub fn render(&self, redis: &RedisMetrics, influx: &InfluxMetrics) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        metric(
+            "seclyzer_buffered_events",
+            "gauge",
+            "Events currently buffered for feature extraction",
+            self.buffered_events().to_string(),
+        );
+        metric(
+            "seclyzer_dropped_events_total",
+            "counter",
+            "Live events dropped because the event queue was full",
+            self.dropped_events().to_string(),
+        );
+        metric(
+            "seclyzer_features_published_total",
+            "counter",
+            "Feature sets published to Redis",
+            self.features_published().to_string(),
+        );
+        metric(
+            "seclyzer_last_publish_timestamp_seconds",
+            "gauge",
+            "Unix time of the last feature publish",
+            self.last_publish_timestamp().to_string(),
+        );
+        metric(
+            "seclyzer_skipped_publishes_total",
+            "counter",
+            "Feature messages dropped while the sink's circuit breaker was open",
+            self.skipped_publishes().to_string(),
+        );
+        metric(
+            "seclyzer_redis_reconnects_total",
+            "counter",
+            "Redis commands that hit a dropped connection",
+            redis.reconnects().to_string(),
+        );
+        metric(
+            "seclyzer_redis_published_total",
+            "counter",
+            "Messages published to Redis",
+            redis.published().to_string(),
+        );
+        metric(
+            "seclyzer_redis_publish_errors_total",
+            "counter",
+            "Redis publishes that failed",
+            redis.publish_errors().to_string(),
+        );
+        metric(
+            "seclyzer_influx_points_written_total",
+            "counter",
+            "Points written to InfluxDB",
+            influx.points_written().to_string(),
+        );
+        metric(
+            "seclyzer_influx_bytes_written_total",
+            "counter",
+            "Line-protocol bytes written to InfluxDB",
+            influx.bytes_written().to_string(),
+        );
+        metric(
+            "seclyzer_influx_write_failures_total",
+            "counter",
+            "InfluxDB write requests that failed",
+            influx.failed_writes().to_string(),
+        );
+        out
+    }
+}
+
+/// What the health server needs to answer `/health` and `/metrics`
+pub struct HealthState {
+    pub redis: Arc<RedisClient>,
+    /// `None` when InfluxDB isn't in use, which counts as healthy
+    pub influx: Option<Arc<InfluxClient>>,
+    pub metrics: Arc<Metrics>,
+}
+
+/// Route a request: `/health` checks dependencies, `/metrics` renders counters
+pub async fn handle(req: Request<Body>, state: &HealthState) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/health") => {
+            let redis_ok = state.redis.ping().await.is_ok();
+            let influx_ok = match &state.influx {
+                Some(influx) => influx.is_ready().await,
+                None => true,
+            };
+            let status = if redis_ok && influx_ok {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let body = serde_json::json!({
+                "status": if status == StatusCode::OK { "ok" } else { "degraded" },
+                "redis": redis_ok,
+                "influxdb": influx_ok,
+            });
+            response(status, "application/json", body.to_string())
+        }
+        (&Method::GET, "/metrics") => {
+            let no_influx = InfluxMetrics::default();
+            let influx = state.influx.as_deref().map_or(&no_influx, |influx| influx.metrics());
+            response(
+                StatusCode::OK,
+                "text/plain; version=0.0.4",
+                state.metrics.render(state.redis.metrics(), influx),
+            )
+        }
+        _ => response(StatusCode::NOT_FOUND, "text/plain", "not found".to_string()),
+    }
+}
+
+/// Serve `/health` and `/metrics` until the task is dropped
+pub async fn serve(addr: SocketAddr, state: Arc<HealthState>) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(req, &state).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("Failed to bind health server to {}", addr))?;
+    tracing::info!("Health server listening on {}", addr);
+    server.serve(make_service).await?;
+    Ok(())
+}
+
+fn response(status: StatusCode, content_type: &str, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .body(Body::from(body))
+        .unwrap_or_default()
+}
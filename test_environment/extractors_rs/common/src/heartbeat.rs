@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often collectors publish a heartbeat
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 5;
+
+/// Periodic "still running" message from a collector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub source: String,
+    pub ts: u64,  // microseconds
+    /// Set while the collector is paused from the control channel
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Heartbeat {
+    /// Heartbeat for `source` stamped with the current time
+    pub fn now(source: &str) -> Self {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        Heartbeat {
+            event_type: "heartbeat".to_string(),
+            source: source.to_string(),
+            ts,
+            paused: false,
+        }
+    }
+
+    /// Report whether the collector is currently paused
+    pub fn with_paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+}
+
+/// Tracks when each collector was last heard from
+#[derive(Debug, Clone)]
+pub struct HeartbeatMonitor {
+    timeout_seconds: f64,
+    last_seen: HashMap<String, f64>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(timeout_seconds: f64) -> Self {
+        HeartbeatMonitor {
+            timeout_seconds,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Note a heartbeat from `source` at `timestamp` (seconds)
+    pub fn record(&mut self, source: &str, timestamp: f64) {
+        let last = self.last_seen.entry(source.to_string()).or_insert(timestamp);
+        *last = last.max(timestamp);
+    }
+
+    /// Record a heartbeat payload from the channel, returning its source
+    pub fn record_json(&mut self, payload: &str) -> serde_json::Result<String> {
+        let heartbeat: Heartbeat = serde_json::from_str(payload)?;
+        self.record(&heartbeat.source, heartbeat.ts as f64 / 1_000_000.0);
+        Ok(heartbeat.source)
+    }
+
+    /// Seconds timestamp of the latest heartbeat from `source`
+    pub fn last_seen(&self, source: &str) -> Option<f64> {
+        self.last_seen.get(source).copied()
+    }
+
+    /// Whether `source` has sent a heartbeat within the timeout
+    pub fn is_alive(&self, source: &str, now: f64) -> bool {
+        self.last_seen(source)
+            .is_some_and(|last| now - last <= self.timeout_seconds)
+    }
+
+    /// A source is stale once it's been silent longer than the timeout, or never reported
+    pub fn is_stale(&self, source: &str, now: f64) -> bool {
+        !self.is_alive(source, now)
+    }
+}
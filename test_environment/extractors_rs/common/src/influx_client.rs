@@ -1,8 +1,93 @@
+use crate::circuit_breaker::{BreakerState, CircuitBreaker};
 use crate::config::Config;
-use anyhow::Result;
-use reqwest::Client as HttpClient;
-use serde_json::json;
+use crate::models::AppTransition;
+use crate::rate_limit::RateLimiter;
+use crate::user::USER_ID_FIELD;
+use anyhow::{Context, Result};
+use rand::Rng;
+use reqwest::{Client as HttpClient, StatusCode};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Upper bound on queued lines kept while InfluxDB is unreachable
+const MAX_PENDING_LINES: usize = 100_000;
+
+/// Measurement app switches are written to, one point per switch
+pub const APP_TRANSITION_MEASUREMENT: &str = "app_transition";
+
+/// Longest wait between write retries, however many have failed
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Why a single write request failed
+#[derive(Debug, thiserror::Error)]
+enum WriteAttemptError {
+    #[error("InfluxDB returned {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("request to InfluxDB failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl WriteAttemptError {
+    /// Timeouts, unreachable servers, 429 and 5xx may clear up; other 4xx won't
+    fn is_retryable(&self) -> bool {
+        match self {
+            WriteAttemptError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            WriteAttemptError::Request(e) => e.is_timeout() || e.is_connect(),
+        }
+    }
+}
+
+/// `Ok` if InfluxDB's readiness endpoint answers with a success status
+async fn check_ready(client: &HttpClient, url: &str) -> Result<()> {
+    let response = client
+        .get(&format!("{}/api/v2/ready", url))
+        .send()
+        .await
+        .with_context(|| format!("InfluxDB at {} didn't answer its readiness check", url))?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "InfluxDB at {} isn't ready: {}",
+        url,
+        response.status()
+    );
+    Ok(())
+}
+
+/// Line protocol waiting to be written
+#[derive(Default)]
+struct PendingBatch {
+    lines: Vec<String>,
+    oldest: Option<Instant>,
+}
+
+/// Counters for what an `InfluxClient` has written
+#[derive(Debug, Default)]
+pub struct InfluxMetrics {
+    points_written: AtomicU64,
+    bytes_written: AtomicU64,
+    failed_writes: AtomicU64,
+}
+
+impl InfluxMetrics {
+    /// Points accepted by InfluxDB
+    pub fn points_written(&self) -> u64 {
+        self.points_written.load(Ordering::Relaxed)
+    }
+    
+    /// Line-protocol bytes accepted by InfluxDB
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+    
+    /// Write requests that errored or were rejected
+    pub fn failed_writes(&self) -> u64 {
+        self.failed_writes.load(Ordering::Relaxed)
+    }
+}
 
 pub struct InfluxClient {
     client: HttpClient,
@@ -10,22 +95,33 @@ pub struct InfluxClient {
     token: String,
     org: String,
     bucket: String,
+    batch: Mutex<PendingBatch>,
+    batch_size: usize,
+    max_batch_age: Duration,
+    max_retries: u32,
+    retry_base: Duration,
+    /// Paces points written, when `influx_max_points_per_sec` is set
+    rate_limit: Option<RateLimiter>,
+    /// Skips flushes while InfluxDB keeps failing, keeping the points queued
+    breaker: std::sync::Mutex<CircuitBreaker>,
+    metrics: InfluxMetrics,
 }
 
 impl InfluxClient {
+    /// A client for `config.influx_url`, after checking InfluxDB is ready
+    ///
+    /// A failed check is an error unless `influx_fail_fast` is off, in which case it's
+    /// logged and writes are left to retry.
     pub async fn new(config: &Config) -> Result<Self> {
-        let client = HttpClient::new();
-        
-        // Test connection
-        let response = client
-            .get(&format!("{}/api/v2/ready", config.influx_url))
-            .send()
-            .await?;
+        let client = HttpClient::builder()
+            .connect_timeout(Duration::from_millis(config.influx_connect_timeout_ms))
+            .timeout(Duration::from_millis(config.influx_request_timeout_ms))
+            .build()?;
         
-        if response.status().is_success() {
-            tracing::info!("InfluxDB connection successful");
-        } else {
-            tracing::warn!("InfluxDB status: {}", response.status());
+        match check_ready(&client, &config.influx_url).await {
+            Ok(()) => tracing::info!("InfluxDB connection successful"),
+            Err(e) if config.influx_fail_fast => return Err(e),
+            Err(e) => tracing::warn!("{:#}; continuing without it", e),
         }
         
         Ok(InfluxClient {
@@ -34,52 +130,371 @@ impl InfluxClient {
             token: config.influx_token.clone(),
             org: config.influx_org.clone(),
             bucket: config.influx_bucket.clone(),
+            batch: Mutex::new(PendingBatch::default()),
+            batch_size: config.influx_batch_size,
+            max_batch_age: Duration::from_secs(config.flush_interval),
+            max_retries: config.influx_max_retries,
+            retry_base: Duration::from_millis(config.influx_retry_base_ms),
+            rate_limit: (config.influx_max_points_per_sec > 0.0).then(|| {
+                RateLimiter::new(config.influx_max_points_per_sec, config.influx_burst_points)
+            }),
+            breaker: std::sync::Mutex::new(config.circuit_breaker("InfluxDB")),
+            metrics: InfluxMetrics::default(),
         })
     }
     
-    /// Write a point in line protocol format
+    /// Write counters for this client
+    pub fn metrics(&self) -> &InfluxMetrics {
+        &self.metrics
+    }
+    
+    /// Whether flushes are going through or being skipped after repeated failures
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker().state()
+    }
+    
+    fn breaker(&self) -> std::sync::MutexGuard<'_, CircuitBreaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Whether InfluxDB's readiness endpoint answers successfully
+    pub async fn is_ready(&self) -> bool {
+        check_ready(&self.client, &self.url).await.is_ok()
+    }
+    
+    /// Queue a line for the next batched write, flushing when the batch is full or old
+    pub async fn queue_line(&self, line: String) -> Result<()> {
+        let should_flush = {
+            let mut batch = self.batch.lock().await;
+            batch.lines.push(line);
+            let oldest = *batch.oldest.get_or_insert_with(Instant::now);
+            batch.lines.len() >= self.batch_size || oldest.elapsed() >= self.max_batch_age
+        };
+        
+        if should_flush {
+            self.flush().await
+        } else {
+            Ok(())
+        }
+    }
+    
+    /// Queue a feature map as a point, keeping only its numeric values as fields
+    pub async fn queue_features(&self, measurement: &str, features: &serde_json::Value) -> Result<()> {
+        let timestamp_ns = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        self.queue_features_at(measurement, features, timestamp_ns).await
+    }
+    
+    /// Queue a feature map as a point at `timestamp_ns` (nanoseconds since the epoch)
+    /// rather than now, e.g. its window's end when backfilling history. A `user_id`
+    /// becomes a tag, so each user's series can be queried on its own.
+    pub async fn queue_features_at(
+        &self,
+        measurement: &str,
+        features: &serde_json::Value,
+        timestamp_ns: i64,
+    ) -> Result<()> {
+        let fields: HashMap<String, f64> = features
+            .as_object()
+            .map(|map| {
+                map.iter()
+                    .filter_map(|(k, v)| v.as_f64().map(|v| (k.clone(), v)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if fields.is_empty() {
+            return Ok(());
+        }
+        
+        let tags: HashMap<String, String> = features[USER_ID_FIELD]
+            .as_str()
+            .map(|user| HashMap::from([(USER_ID_FIELD.to_string(), user.to_string())]))
+            .unwrap_or_default();
+        let line = Self::build_line_protocol(measurement, &tags, &fields, timestamp_ns);
+        self.queue_line(line).await
+    }
+    
+    /// Queue an app switch as a point tagged `from_app`/`to_app` with a `duration_ms` field
+    pub async fn write_app_transition(&self, transition: &AppTransition) -> Result<()> {
+        self.queue_line(Self::app_transition_line(transition)).await
+    }
+    
+    /// Line protocol for an app switch, timed by its RFC 3339 `timestamp` (or now, if
+    /// that doesn't parse)
+    pub fn app_transition_line(transition: &AppTransition) -> String {
+        let tags = HashMap::from([
+            ("from_app".to_string(), transition.from_app.clone()),
+            ("to_app".to_string(), transition.to_app.clone()),
+        ]);
+        let fields = HashMap::from([("duration_ms".to_string(), transition.duration_ms)]);
+        let timestamp_ns = chrono::DateTime::parse_from_rfc3339(&transition.timestamp)
+            .ok()
+            .and_then(|time| time.timestamp_nanos_opt())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        Self::build_line_protocol(APP_TRANSITION_MEASUREMENT, &tags, &fields, timestamp_ns)
+    }
+    
+    /// Write all queued lines in one request; on failure they're re-queued
+    ///
+    /// While the circuit breaker is open the write is skipped and the lines stay
+    /// queued (up to the backlog limit). A probe write gets no retries, and its
+    /// failure isn't returned: the breaker already reported the outage.
+    pub async fn flush(&self) -> Result<()> {
+        let mut batch = self.batch.lock().await;
+        if batch.lines.is_empty() {
+            return Ok(());
+        }
+        let probing = {
+            let mut breaker = self.breaker();
+            if !breaker.allow() {
+                let dropped = Self::trim_backlog(&mut batch.lines);
+                if dropped > 0 {
+                    tracing::debug!("InfluxDB backlog full, dropped {} oldest points", dropped);
+                }
+                return Ok(());
+            }
+            breaker.state() == BreakerState::HalfOpen
+        };
+        let pending = std::mem::take(&mut *batch);
+        drop(batch);
+        
+        let body = Self::join_batch(&pending.lines);
+        let max_retries = if probing { 0 } else { self.max_retries };
+        match self.write_batch(body, max_retries).await {
+            Ok(()) => {
+                self.breaker().record_success();
+                tracing::debug!("Flushed {} points to InfluxDB", pending.lines.len());
+                Ok(())
+            }
+            Err(e) => {
+                self.breaker().record_failure();
+                let mut batch = self.batch.lock().await;
+                let mut lines = pending.lines;
+                lines.append(&mut batch.lines);
+                let dropped = Self::trim_backlog(&mut lines);
+                if dropped > 0 {
+                    tracing::warn!("InfluxDB backlog full, dropped {} oldest points", dropped);
+                }
+                batch.lines = lines;
+                batch.oldest = pending.oldest.or(batch.oldest);
+                if probing {
+                    Ok(())
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+    
+    /// Drop the oldest lines beyond `MAX_PENDING_LINES`; returns how many were dropped
+    fn trim_backlog(lines: &mut Vec<String>) -> usize {
+        let dropped = lines.len().saturating_sub(MAX_PENDING_LINES);
+        lines.drain(..dropped);
+        dropped
+    }
+    
+    /// Join queued lines into a single line-protocol request body
+    pub fn join_batch(lines: &[String]) -> String {
+        lines.join("\n")
+    }
+    
+    /// Write a point in line protocol format, retrying transient failures with backoff
+    ///
+    /// With a rate limit set, this first waits until the limit allows the points.
     pub async fn write_line_protocol(&self, line_protocol: String) -> Result<()> {
+        self.write_batch(line_protocol, self.max_retries).await
+    }
+    
+    /// `write_line_protocol` with at most `max_retries` retries
+    async fn write_batch(&self, line_protocol: String, max_retries: u32) -> Result<()> {
+        let points = line_protocol.lines().filter(|l| !l.is_empty()).count() as u64;
+        let bytes = line_protocol.len() as u64;
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire(points).await;
+        }
+        let result = self.send_with_retries(&line_protocol, max_retries).await;
+        match &result {
+            Ok(()) => {
+                self.metrics.points_written.fetch_add(points, Ordering::Relaxed);
+                self.metrics.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.metrics.failed_writes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+    
+    async fn send_with_retries(&self, line_protocol: &str, max_retries: u32) -> Result<()> {
+        let mut retries = 0;
+        loop {
+            match self.send_line_protocol(line_protocol).await {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_retryable() && retries < max_retries => {
+                    let delay = self.retry_delay(retries);
+                    tracing::warn!(
+                        "InfluxDB write failed ({}), retry {}/{} in {:?}",
+                        e,
+                        retries + 1,
+                        max_retries,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                Err(e) => {
+                    return Err(anyhow::Error::new(e)
+                        .context(format!("InfluxDB write failed after {} retries", retries)))
+                }
+            }
+        }
+    }
+    
+    /// Exponential backoff from `retry_base`, with jitter over the upper half of each step
+    fn retry_delay(&self, retries: u32) -> Duration {
+        let ceiling = self
+            .retry_base
+            .saturating_mul(2u32.saturating_pow(retries))
+            .min(MAX_RETRY_DELAY);
+        ceiling / 2 + ceiling.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+    }
+    
+    async fn send_line_protocol(&self, line_protocol: &str) -> Result<(), WriteAttemptError> {
         let response = self
             .client
             .post(&format!(
-                "{}/api/v2/write?org={}&bucket={}",
+                "{}/api/v2/write?org={}&bucket={}&precision=ns",
                 self.url, self.org, self.bucket
             ))
             .header("Authorization", format!("Token {}", self.token))
             .header("Content-Type", "text/plain")
-            .body(line_protocol)
+            .body(line_protocol.to_string())
             .send()
             .await?;
         
-        if response.status().is_success() {
+        let status = response.status();
+        if status.is_success() {
             Ok(())
         } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(WriteAttemptError::Status { status, body })
+        }
+    }
+    
+    /// Run a Flux query and return each result row as a JSON object
+    pub async fn query_flux(&self, flux: &str) -> Result<Vec<serde_json::Value>> {
+        let response = self
+            .client
+            .post(format!("{}/api/v2/query?org={}", self.url, self.org))
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Accept", "application/csv")
+            .json(&serde_json::json!({
+                "query": flux,
+                "type": "flux",
+                "dialect": {
+                    "header": true,
+                    "annotations": ["datatype", "group", "default"],
+                },
+            }))
+            .send()
+            .await?;
+        
+        if !response.status().is_success() {
+            let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("InfluxDB write failed: {}", text)
+            anyhow::bail!("InfluxDB query failed ({}): {}", status, text)
+        }
+        
+        let body = response.text().await?;
+        Ok(Self::parse_annotated_csv(&body))
+    }
+    
+    /// Parse an annotated CSV query response into JSON rows, typed by `#datatype`
+    pub fn parse_annotated_csv(body: &str) -> Vec<serde_json::Value> {
+        let mut rows = Vec::new();
+        let mut datatypes: Vec<String> = Vec::new();
+        let mut defaults: Vec<String> = Vec::new();
+        let mut header: Option<Vec<String>> = None;
+        
+        for line in body.lines() {
+            let line = line.trim_end_matches('\r');
+            
+            // A blank line ends the current table; the next one has its own header
+            if line.trim().is_empty() {
+                datatypes.clear();
+                defaults.clear();
+                header = None;
+                continue;
+            }
+            
+            let cells = split_csv_line(line);
+            if let Some(annotation) = cells.first().filter(|c| c.starts_with('#')) {
+                match annotation.as_str() {
+                    "#datatype" => datatypes = cells,
+                    "#default" => defaults = cells,
+                    _ => {}
+                }
+                continue;
+            }
+            
+            let Some(columns) = &header else {
+                header = Some(cells);
+                continue;
+            };
+            
+            let mut row = serde_json::Map::new();
+            for (i, (column, value)) in columns.iter().zip(cells).enumerate() {
+                if column.is_empty() {
+                    continue;
+                }
+                let value = match defaults.get(i) {
+                    Some(default) if value.is_empty() => default.clone(),
+                    _ => value,
+                };
+                let datatype = datatypes.get(i).map(String::as_str).unwrap_or("string");
+                row.insert(column.clone(), typed_csv_value(datatype, value));
+            }
+            rows.push(serde_json::Value::Object(row));
         }
+        
+        rows
     }
     
-    /// Convert tags and fields to line protocol
-    pub fn build_line_protocol(
+    /// Convert tags and fields to line protocol, escaping per the InfluxDB spec
+    pub fn build_line_protocol<V>(
         measurement: &str,
         tags: &HashMap<String, String>,
-        fields: &HashMap<String, f64>,
+        fields: &HashMap<String, V>,
         timestamp_ns: i64,
-    ) -> String {
-        let mut line = measurement.to_string();
+    ) -> String
+    where
+        V: Clone + Into<FieldValue>,
+    {
+        let mut line = escape_measurement(measurement);
         
-        // Add tags
+        // Add tags (sorted, as InfluxDB recommends; empty values aren't allowed)
+        let mut tags: Vec<(&String, &String)> = tags.iter().filter(|(_, v)| !v.is_empty()).collect();
+        tags.sort();
         for (k, v) in tags {
             line.push(',');
-            line.push_str(&format!("{}={}", k, v));
+            line.push_str(&format!("{}={}", escape_key(k), escape_key(v)));
         }
         
-        // Add fields
+        // Add fields (NaN/inf can't be represented and are skipped)
         line.push(' ');
-        let field_strs: Vec<String> = fields
+        let mut field_strs: Vec<String> = fields
             .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
+            .filter_map(|(k, v)| {
+                let value = match v.clone().into() {
+                    FieldValue::Float(f) if !f.is_finite() => return None,
+                    FieldValue::Float(f) => f.to_string(),
+                    FieldValue::Integer(i) => format!("{}i", i),
+                    FieldValue::Boolean(b) => b.to_string(),
+                    FieldValue::String(s) => escape_string_field(&s),
+                };
+                Some(format!("{}={}", escape_key(k), value))
+            })
             .collect();
+        field_strs.sort();
         line.push_str(&field_strs.join(","));
         
         // Add timestamp
@@ -89,3 +504,116 @@ impl InfluxClient {
         line
     }
 }
+
+/// A line-protocol field value
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Integer(value)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Boolean(value)
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::String(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::String(value.to_string())
+    }
+}
+
+/// Escape commas and spaces in a measurement name
+fn escape_measurement(measurement: &str) -> String {
+    let mut escaped = String::with_capacity(measurement.len());
+    for c in measurement.chars() {
+        if matches!(c, ',' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape commas, equals signs and spaces in tag keys, tag values and field keys
+fn escape_key(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quote a string field value, escaping backslashes and double quotes
+fn escape_string_field(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Split one CSV line, honouring double-quoted cells with `""` escapes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// Convert a CSV cell to JSON according to its Flux datatype annotation
+fn typed_csv_value(datatype: &str, value: String) -> serde_json::Value {
+    if value.is_empty() {
+        return serde_json::Value::Null;
+    }
+    match datatype {
+        "long" => value.parse::<i64>().map(Into::into).unwrap_or(value.into()),
+        "unsignedLong" => value.parse::<u64>().map(Into::into).unwrap_or(value.into()),
+        "double" => value.parse::<f64>().map(Into::into).unwrap_or(value.into()),
+        "boolean" => (value == "true").into(),
+        _ => value.into(),
+    }
+}
@@ -0,0 +1,306 @@
+// Key names, categories and physical positions. Collectors attach the X11
+// keycode (evdev scancode + 8) of each key as `code`; extractors keying features
+// by position translate it back to the name the key has at that position on a
+// US QWERTY board, so an AZERTY "A" and a QWERTY "Q" land in the same feature
+// bucket. In privacy mode collectors publish only each key's category (or which
+// modifier it is).
+
+/// `rdev::Key` names paired with the X11 keycode of their US QWERTY position
+const KEY_POSITIONS: &[(&str, u32)] = &[
+    ("Escape", 9),
+    ("Num1", 10),
+    ("Num2", 11),
+    ("Num3", 12),
+    ("Num4", 13),
+    ("Num5", 14),
+    ("Num6", 15),
+    ("Num7", 16),
+    ("Num8", 17),
+    ("Num9", 18),
+    ("Num0", 19),
+    ("Minus", 20),
+    ("Equal", 21),
+    ("Backspace", 22),
+    ("Tab", 23),
+    ("KeyQ", 24),
+    ("KeyW", 25),
+    ("KeyE", 26),
+    ("KeyR", 27),
+    ("KeyT", 28),
+    ("KeyY", 29),
+    ("KeyU", 30),
+    ("KeyI", 31),
+    ("KeyO", 32),
+    ("KeyP", 33),
+    ("LeftBracket", 34),
+    ("RightBracket", 35),
+    ("Return", 36),
+    ("ControlLeft", 37),
+    ("KeyA", 38),
+    ("KeyS", 39),
+    ("KeyD", 40),
+    ("KeyF", 41),
+    ("KeyG", 42),
+    ("KeyH", 43),
+    ("KeyJ", 44),
+    ("KeyK", 45),
+    ("KeyL", 46),
+    ("SemiColon", 47),
+    ("Quote", 48),
+    ("BackQuote", 49),
+    ("ShiftLeft", 50),
+    ("BackSlash", 51),
+    ("KeyZ", 52),
+    ("KeyX", 53),
+    ("KeyC", 54),
+    ("KeyV", 55),
+    ("KeyB", 56),
+    ("KeyN", 57),
+    ("KeyM", 58),
+    ("Comma", 59),
+    ("Dot", 60),
+    ("Slash", 61),
+    ("ShiftRight", 62),
+    ("KpMultiply", 63),
+    ("Alt", 64),
+    ("Space", 65),
+    ("CapsLock", 66),
+    ("F1", 67),
+    ("F2", 68),
+    ("F3", 69),
+    ("F4", 70),
+    ("F5", 71),
+    ("F6", 72),
+    ("F7", 73),
+    ("F8", 74),
+    ("F9", 75),
+    ("F10", 76),
+    ("NumLock", 77),
+    ("ScrollLock", 78),
+    ("Kp7", 79),
+    ("Kp8", 80),
+    ("Kp9", 81),
+    ("KpMinus", 82),
+    ("Kp4", 83),
+    ("Kp5", 84),
+    ("Kp6", 85),
+    ("KpPlus", 86),
+    ("Kp1", 87),
+    ("Kp2", 88),
+    ("Kp3", 89),
+    ("Kp0", 90),
+    ("KpDelete", 91),
+    ("IntlBackslash", 94),
+    ("F11", 95),
+    ("F12", 96),
+    ("KpReturn", 104),
+    ("ControlRight", 105),
+    ("KpDivide", 106),
+    ("PrintScreen", 107),
+    ("AltGr", 108),
+    ("Home", 110),
+    ("UpArrow", 111),
+    ("PageUp", 112),
+    ("LeftArrow", 113),
+    ("RightArrow", 114),
+    ("End", 115),
+    ("DownArrow", 116),
+    ("PageDown", 117),
+    ("Insert", 118),
+    ("Delete", 119),
+    ("Pause", 127),
+    ("MetaLeft", 133),
+    ("MetaRight", 134),
+];
+
+/// X11 keycode of the position a key name sits at on a US QWERTY board
+pub fn code_for_key(key: &str) -> Option<u32> {
+    KEY_POSITIONS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|&(_, code)| code)
+}
+
+/// Name of the key at a physical position on a US QWERTY board
+pub fn key_at_code(code: u32) -> Option<&'static str> {
+    KEY_POSITIONS
+        .iter()
+        .find(|&&(_, c)| c == code)
+        .map(|&(name, _)| name)
+}
+
+/// Lowercase a key name and strip rdev's `Key`/`Num` prefixes (`KeyA` -> `a`, `Num1` -> `1`)
+pub fn normalize_key(key: &str) -> String {
+    let stripped = match (key.strip_prefix("Key"), key.strip_prefix("Num")) {
+        (Some(rest), _) if rest.chars().count() == 1 => rest,
+        (_, Some(rest)) if rest.len() == 1 && rest.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => key,
+    };
+    stripped.to_lowercase()
+}
+
+/// Broad kind of key, for the `frac_*` distribution features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCategory {
+    Alpha,
+    Digit,
+    Symbol,
+    /// Arrows, paging, editing (backspace/delete/insert), escape and function keys
+    Navigation,
+    Whitespace,
+}
+
+impl KeyCategory {
+    /// Key name a collector in privacy mode publishes in place of any key of this category
+    pub fn token(self) -> &'static str {
+        match self {
+            KeyCategory::Alpha => "Alpha",
+            KeyCategory::Digit => "Digit",
+            KeyCategory::Symbol => "Symbol",
+            KeyCategory::Navigation => "Nav",
+            KeyCategory::Whitespace => "Space",
+        }
+    }
+
+    fn from_token(key: &str) -> Option<Self> {
+        [
+            KeyCategory::Alpha,
+            KeyCategory::Digit,
+            KeyCategory::Symbol,
+            KeyCategory::Navigation,
+            KeyCategory::Whitespace,
+        ]
+        .into_iter()
+        .find(|category| category.token() == key)
+    }
+}
+
+/// Normalized names of keys that produce whitespace
+const WHITESPACE_KEYS: &[&str] = &["space", "tab", "return", "enter", "kpreturn"];
+
+/// Normalized names of navigation and editing keys (function keys are matched separately)
+const NAVIGATION_KEYS: &[&str] = &[
+    "leftarrow", "rightarrow", "uparrow", "downarrow",
+    "home", "end", "pageup", "pagedown",
+    "backspace", "delete", "kpdelete", "insert", "escape",
+];
+
+/// Normalized names of punctuation keys, as rdev reports them
+const SYMBOL_KEYS: &[&str] = &[
+    "minus", "equal", "leftbracket", "rightbracket", "backslash", "intlbackslash",
+    "semicolon", "quote", "backquote", "comma", "dot", "slash",
+    "kpminus", "kpplus", "kpmultiply", "kpdivide",
+];
+
+/// Category a key (or a privacy-mode token) belongs to, or `None` for modifiers
+/// (shift, ctrl, alt, ...) and unknown keys
+pub fn key_category(key: &str) -> Option<KeyCategory> {
+    if let Some(category) = KeyCategory::from_token(key) {
+        return Some(category);
+    }
+    let key = normalize_key(key);
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(match c {
+            c if c.is_alphabetic() => KeyCategory::Alpha,
+            c if c.is_ascii_digit() => KeyCategory::Digit,
+            c if c.is_whitespace() => KeyCategory::Whitespace,
+            _ => KeyCategory::Symbol,
+        });
+    }
+
+    let key = key.as_str();
+    if WHITESPACE_KEYS.contains(&key) {
+        Some(KeyCategory::Whitespace)
+    } else if NAVIGATION_KEYS.contains(&key) || is_function_key(key) {
+        Some(KeyCategory::Navigation)
+    } else if SYMBOL_KEYS.contains(&key) {
+        Some(KeyCategory::Symbol)
+    } else if key
+        .strip_prefix("kp")
+        .is_some_and(|d| d.len() == 1 && d.chars().all(|c| c.is_ascii_digit()))
+    {
+        Some(KeyCategory::Digit)
+    } else {
+        None
+    }
+}
+
+/// Modifier key, with left and right variants folded together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Alt,
+    /// Cmd on macOS, the Windows/Super key elsewhere
+    Meta,
+}
+
+impl Modifier {
+    /// Whether holding it turns a key press into a shortcut (Ctrl/Alt/Cmd); Shift
+    /// only changes what gets typed
+    pub fn is_shortcut(self) -> bool {
+        self != Modifier::Shift
+    }
+
+    /// Key name a collector in privacy mode publishes in place of either variant
+    pub fn token(self) -> &'static str {
+        match self {
+            Modifier::Shift => "Shift",
+            Modifier::Control => "Ctrl",
+            Modifier::Alt => "Alt",
+            Modifier::Meta => "Meta",
+        }
+    }
+}
+
+/// Normalized names of modifier keys, as rdev reports them, and their privacy-mode tokens
+const MODIFIER_KEYS: &[(&str, Modifier)] = &[
+    ("shift", Modifier::Shift),
+    ("ctrl", Modifier::Control),
+    ("meta", Modifier::Meta),
+    ("shiftleft", Modifier::Shift),
+    ("shiftright", Modifier::Shift),
+    ("controlleft", Modifier::Control),
+    ("controlright", Modifier::Control),
+    ("alt", Modifier::Alt),
+    ("altgr", Modifier::Alt),
+    ("metaleft", Modifier::Meta),
+    ("metaright", Modifier::Meta),
+];
+
+/// Modifier a key (or a privacy-mode token) is, or `None` for every other key
+pub fn modifier_key(key: &str) -> Option<Modifier> {
+    let key = normalize_key(key);
+    MODIFIER_KEYS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|&(_, modifier)| modifier)
+}
+
+/// `f1` through `f24`
+fn is_function_key(key: &str) -> bool {
+    key.strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=24).contains(&n))
+}
+
+/// Stand-in for unknown keys in privacy mode
+pub const PRIVACY_OTHER_KEY: &str = "Other";
+
+/// What a collector in privacy mode publishes instead of `key`: its category token,
+/// which keeps dwell and flight timing but not what was typed
+///
+/// Backspace and Delete pass through, as they reveal no text and the correction
+/// features count them. Modifiers become their `Modifier` token, which keeps the
+/// chord features without saying which side of the keyboard was used.
+pub fn privacy_key(key: &str) -> &'static str {
+    match key {
+        "Backspace" => "Backspace",
+        "Delete" => "Delete",
+        _ => match modifier_key(key) {
+            Some(modifier) => modifier.token(),
+            None => key_category(key).map_or(PRIVACY_OTHER_KEY, KeyCategory::token),
+        },
+    }
+}
@@ -3,44 +3,186 @@ pub mod influx_client;
 pub mod models;
 pub mod config;
 pub mod logger;
+pub mod shutdown;
+pub mod baseline;
+pub mod health;
+pub mod heartbeat;
+pub mod event_source;
+pub mod event_bus;
+pub mod sink;
+#[cfg(feature = "parquet")]
+pub mod parquet_sink;
+pub mod csv_sink;
+pub mod compression;
+pub mod wal;
+pub mod redis_link;
+pub mod stats;
+pub mod control;
+pub mod extraction;
+pub mod keymap;
+pub mod record;
+pub mod fingerprint;
+pub mod backpressure;
+pub mod clock;
+pub mod rate_limit;
+pub mod signing;
+pub mod sliding_stats;
+pub mod alert;
+pub mod wm_class;
+pub mod app_filter;
+pub mod dedup;
+pub mod focus;
+pub mod feature_diff;
+pub mod user;
+pub mod circuit_breaker;
+pub mod live;
 
-pub use redis_client::RedisClient;
-pub use influx_client::InfluxClient;
-pub use config::Config;
+pub use redis_client::{RedisClient, RedisMetrics};
+pub use influx_client::{FieldValue, InfluxClient, InfluxMetrics};
+pub use config::{
+    ChannelNames, Config, DevModeRouting, EventBusKind, EventTransport, KeyIdentity,
+    MouseFeatureProfile, OutlierTrim, OverflowPolicy, WindowWarmup,
+};
+pub use logger::{init_logging, init_logging_with, LogFormat};
 pub use models::*;
+pub use shutdown::shutdown_signal;
+pub use baseline::{BaselineProfile, RunningStats};
+pub use health::{HealthState, Metrics};
+pub use event_source::{
+    extract_offline, BusEventSource, EventSource, EventSourceKind, MemoryEventSource,
+    ReaderEventSource, WindowedExtractor,
+};
+pub use event_bus::{BlockingEventBus, Delivery, EventBus, RedisEventBus, RedisEventLink};
+pub use heartbeat::{Heartbeat, HeartbeatMonitor, HEARTBEAT_INTERVAL_SECS};
+pub use sink::{BusSink, FeatureSink, RedisSink, SinkKind, StdoutSink};
+#[cfg(feature = "parquet")]
+pub use parquet_sink::ParquetSink;
+pub use csv_sink::CsvSink;
+pub use compression::{decode_payload, encode_payload};
+pub use wal::{drain_wal, record_event, EventWal};
+pub use redis_link::RedisLink;
+pub use control::{watch_control_blocking, ControlCommand};
+pub use extraction::PendingExtraction;
+pub use fingerprint::{feature_fingerprint, hamming_distance};
+pub use backpressure::bounded_relay;
+pub use clock::EventClock;
+pub use rate_limit::RateLimiter;
+pub use signing::{EventSigner, SignatureError};
+pub use sliding_stats::{QuantileSketch, SlidingStats, Summary};
+pub use alert::{Alert, AlertDebouncer, AlertSink, FeatureContribution};
+pub use wm_class::parse_wm_class;
+pub use app_filter::AppFilter;
+pub use dedup::{event_id, EventDeduplicator};
+pub use focus::FocusTracker;
+pub use feature_diff::feature_diff;
+pub use user::{is_valid_user_id, tag_user, user_path, UserSessions, USER_ID_FIELD};
+pub use circuit_breaker::{BreakerSink, BreakerState, CircuitBreaker, PublishSkipped};
+pub use live::{run_live, LiveExtractor, LiveModality};
 
 use anyhow::Result;
 use std::sync::Arc;
 
-/// Initialize logging system
-pub fn init_logging() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
-}
-
 /// Application context holding shared resources
 pub struct AppContext {
     pub redis: Arc<RedisClient>,
-    pub influx: Arc<InfluxClient>,
+    /// Raw events in, features out; Redis itself unless `EVENT_BUS` says otherwise
+    pub bus: Arc<dyn EventBus>,
+    /// `None` when the stdout sink is in use
+    pub influx: Option<Arc<InfluxClient>>,
+    pub sink: Box<dyn FeatureSink>,
     pub config: Arc<Config>,
+    pub metrics: Arc<Metrics>,
 }
 
 impl AppContext {
     pub async fn new() -> Result<Self> {
-        let config = Arc::new(Config::from_env()?);
+        let config = Config::load()?;
         tracing::info!("Loaded configuration");
+        Self::from_config(config).await
+    }
+    
+    /// Connect to Redis and, unless output goes to stdout, InfluxDB
+    pub async fn from_config(config: Config) -> Result<Self> {
+        let config = Arc::new(config);
         
         let redis = Arc::new(RedisClient::new(config.as_ref()).await?);
         tracing::info!("Connected to Redis");
+        let bus = event_bus::connect(config.as_ref(), redis.clone()).await?;
         
-        let influx = Arc::new(InfluxClient::new(config.as_ref()).await?);
-        tracing::info!("Connected to InfluxDB");
+        let (influx, sink): (_, Box<dyn FeatureSink>) = match &config.sink {
+            SinkKind::Redis => {
+                let influx = Arc::new(InfluxClient::new(config.as_ref()).await?);
+                tracing::info!("Connected to InfluxDB");
+                let sink: Box<dyn FeatureSink> = match config.event_bus {
+                    // Feature consumers subscribe on Redis, so the socket bus is for raw events only
+                    EventBusKind::Redis | EventBusKind::Unix => {
+                        Box::new(RedisSink::new(redis.clone()))
+                    }
+                    EventBusKind::Kafka => Box::new(BusSink::new(bus.clone())),
+                };
+                (Some(influx), sink)
+            }
+            SinkKind::Stdout => {
+                tracing::info!("Printing features to stdout; InfluxDB writes are disabled");
+                (None, Box::new(StdoutSink::new()))
+            }
+            #[cfg(feature = "parquet")]
+            SinkKind::Parquet(dir) => {
+                tracing::info!(
+                    "Writing features to Parquet files in {}; InfluxDB writes are disabled",
+                    dir.display()
+                );
+                let sink = ParquetSink::new(
+                    dir.clone(),
+                    config.parquet_flush_rows,
+                    std::time::Duration::from_secs(config.parquet_flush_seconds),
+                );
+                (None, Box::new(sink))
+            }
+            #[cfg(not(feature = "parquet"))]
+            SinkKind::Parquet(_) => anyhow::bail!("sink = parquet:DIR needs the parquet feature"),
+            SinkKind::Csv(dir) => {
+                tracing::info!(
+                    "Writing features to CSV files in {}; InfluxDB writes are disabled",
+                    dir.display()
+                );
+                let sink = CsvSink::new(dir.clone(), config.csv_rotate_rows);
+                (None, Box::new(sink))
+            }
+        };
+        // A sink that keeps failing is skipped for a while instead of tried every message
+        let metrics = Arc::new(Metrics::new());
+        let sink = Box::new(BreakerSink::new(
+            sink,
+            config.circuit_breaker("Feature sink"),
+            metrics.clone(),
+        ));
         
         Ok(AppContext {
             redis,
+            bus,
             influx,
+            sink,
             config,
+            metrics,
         })
     }
+    
+    /// Start the `/health` + `/metrics` server in the background if `health_port` is set
+    pub fn spawn_health_server(&self) {
+        if self.config.health_port == 0 {
+            return;
+        }
+        let addr = std::net::SocketAddr::new(self.config.health_bind, self.config.health_port);
+        let state = Arc::new(HealthState {
+            redis: self.redis.clone(),
+            influx: self.influx.clone(),
+            metrics: self.metrics.clone(),
+        });
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(addr, state).await {
+                tracing::error!("Health server stopped: {:#}", e);
+            }
+        });
+    }
 }
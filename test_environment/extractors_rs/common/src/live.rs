@@ -0,0 +1,346 @@
+// The live loop shared by the keystroke and mouse extractors: raw events in from
+// the bus, a window computed every hop on a blocking thread, and its features
+// published, scored against the user's baseline and alerted on. Control commands,
+// collector heartbeats, InfluxDB flushes and the periodic cleanup are handled here
+// too, so an extractor only says how to buffer events and compute a window.
+
+use crate::{
+    tag_user, AlertSink, AppContext, BaselineProfile, BusEventSource, Config, ControlCommand,
+    EventSource, HeartbeatMonitor, IdleRecord, PendingExtraction, PublishSkipped, UserSessions,
+    WindowedExtractor,
+};
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::{interval, Duration};
+use tracing::{error, info, warn};
+
+/// A windowed extractor that can run live under `run_live`
+pub trait LiveExtractor: WindowedExtractor + Send + 'static {
+    /// Everything one window needs, detached from the extractor so it can be
+    /// computed off the async runtime
+    type Window: Send + 'static;
+
+    fn from_config(config: &Config) -> Self;
+
+    /// Columns of the emitted feature records, for sinks that need a fixed schema
+    fn feature_names() -> &'static [&'static str];
+
+    fn buffered_events(&self) -> usize;
+
+    /// The buffered events as raw events, for the `dump` control command
+    fn dump_buffer(&self) -> serde_json::Value;
+
+    /// Drop events too old for any future window
+    fn cleanup_old_events(&mut self);
+
+    /// Advance the window schedule to `now` and snapshot the window ending there
+    fn begin_window(&mut self, now: f64) -> Self::Window;
+
+    /// Features for a window, or `None` when there's too little to go on
+    fn compute_window(window: Self::Window) -> Option<serde_json::Value>;
+
+    /// Add anything that depends on earlier windows to freshly computed features
+    fn finish_window(&mut self, features: Option<serde_json::Value>) -> Option<serde_json::Value> {
+        features
+    }
+}
+
+/// Names one live extractor goes by
+#[derive(Clone, Copy, Debug)]
+pub struct LiveModality {
+    /// Feature channel, baseline, alert and InfluxDB measurement prefix, e.g. "keystroke"
+    pub name: &'static str,
+    /// Heartbeat source of the collector feeding it, e.g. "keyboard"
+    pub collector: &'static str,
+    /// How log lines refer to it, e.g. "Keystroke"
+    pub title: &'static str,
+}
+
+/// One user's live state
+struct Session<E> {
+    extractor: E,
+    /// When the user's last event arrived, for evicting sessions that went idle
+    last_event: Instant,
+    baseline: BaselineProfile,
+    baseline_path: PathBuf,
+    alerts: Option<AlertSink>,
+}
+
+impl<E: LiveExtractor> Session<E> {
+    fn new(config: &Config, modality: &LiveModality, user: Option<&str>) -> Self {
+        let baseline_path = config.user_baseline_path(modality.name, user);
+        let mut baseline = BaselineProfile::load_or_new(&baseline_path);
+        if config.baseline_frozen {
+            baseline.freeze();
+        }
+        Session {
+            extractor: E::from_config(config),
+            last_event: Instant::now(),
+            baseline,
+            baseline_path,
+            alerts: config.alert_sink(modality.name),
+        }
+    }
+
+    fn save_baseline(&self) {
+        if let Err(e) = self.baseline.save(&self.baseline_path) {
+            error!("Failed to save baseline {}: {:#}", self.baseline_path.display(), e);
+        }
+    }
+}
+
+/// Extract features from live events until shutdown, one extractor and baseline
+/// per user so users sharing the host stay apart
+pub async fn run_live<E: LiveExtractor>(ctx: AppContext, modality: LiveModality) -> Result<()> {
+    let config = ctx.config.clone();
+    let title = modality.title;
+
+    let feature_channel = config.feature_channel(modality.name);
+    if config.dev_mode {
+        info!("Dev mode enabled; features go to {}", feature_channel.as_deref().unwrap_or("nowhere"));
+    }
+    if let Some(channel) = &feature_channel {
+        ctx.sink.declare_columns(channel, E::feature_names());
+    }
+
+    let session_config = config.clone();
+    let mut sessions = UserSessions::new(config.user_id.clone(), move |user| {
+        Session::<E>::new(&session_config, &modality, user)
+    });
+    let default_user = config.user_id.clone();
+    sessions.session(default_user.as_deref());
+
+    ctx.spawn_health_server();
+
+    let group = format!("{}_extractor", modality.name);
+    let mut events = BusEventSource::subscribe(ctx.bus.as_ref(), &config.event_channel(), &group)
+        .await?
+        .with_queue(config.event_queue_size, config.event_overflow)
+        .with_dedup(config.event_dedup_window)
+        .with_verifier(config.event_verifier());
+    let mut heartbeats = ctx.redis.subscribe(&config.heartbeat_channel()).await?;
+    let mut control = ctx.redis.subscribe(&config.control_channel()).await?;
+    let mut paused = false;
+    let mut extraction = PendingExtraction::new();
+    // Users in the window being computed, whose sessions its results go back to
+    let mut extracting: HashSet<Option<String>> = HashSet::new();
+    let idle_ttl = Duration::from_secs(config.user_idle_ttl_seconds);
+    let mut collector_monitor = HeartbeatMonitor::new(config.heartbeat_timeout as f64);
+    let mut source_alive = false;
+    let mut update_interval = interval(Duration::from_secs(config.hop_seconds()));
+    let mut cleanup_interval = interval(Duration::from_secs(60));
+    let mut flush_interval = interval(Duration::from_secs(config.flush_interval));
+
+    info!("{} Extractor initialized and ready", title);
+
+    let shutdown = crate::shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            // Events arriving while paused are dropped, not buffered for later
+            Some(event) = events.next_event() => if !paused {
+                let user = sessions.user_of(&event);
+                let session = sessions.session(user.as_deref());
+                session.last_event = Instant::now();
+                session.extractor.add_raw_event(event);
+            },
+            Some(payload) = control.recv() => match ControlCommand::from_json(&payload) {
+                Ok(ControlCommand::Dump) => {
+                    for (user, session) in sessions.iter_mut() {
+                        let mut dump = json!({
+                            "extractor": modality.name,
+                            "ts": unix_now(),
+                            "events": session.extractor.dump_buffer(),
+                        });
+                        tag_user(&mut dump, user);
+                        match ctx.redis.publish_features(&config.dump_channel(), &dump).await {
+                            Ok(()) => info!(
+                                "Dumped {} buffered events",
+                                session.extractor.buffered_events()
+                            ),
+                            Err(e) => error!("Failed to publish buffer dump: {}", e),
+                        }
+                    }
+                }
+                Ok(command) if command.pauses().is_some_and(|pause| pause != paused) => {
+                    paused = !paused;
+                    info!("{} by control channel", if paused { "Paused" } else { "Resumed" });
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping malformed control message: {}", e),
+            },
+            Some(payload) = heartbeats.recv() => {
+                if let Err(e) = collector_monitor.record_json(&payload) {
+                    warn!("Skipping malformed heartbeat: {}", e);
+                }
+            }
+            _ = update_interval.tick() => {
+                ctx.metrics.set_buffered_events(
+                    sessions.iter_mut().map(|(_, session)| session.extractor.buffered_events()).sum(),
+                );
+                ctx.metrics.set_dropped_events(events.dropped_events());
+                let alive = collector_monitor.is_alive(modality.collector, unix_now());
+                if alive != source_alive {
+                    if alive {
+                        info!("Heartbeat received from the {} collector", modality.collector);
+                    } else {
+                        warn!(
+                            "The {} collector missed its heartbeat; features may be stale",
+                            modality.collector
+                        );
+                    }
+                    source_alive = alive;
+                }
+                if paused {
+                    continue;
+                }
+                if extraction.is_running() {
+                    warn!("Previous {} window is still being computed; skipping this one", modality.name);
+                    continue;
+                }
+                let now = unix_now();
+                let windows: Vec<_> = sessions
+                    .iter_mut()
+                    .map(|(user, session)| {
+                        (user.map(str::to_string), session.extractor.begin_window(now))
+                    })
+                    .collect();
+                extracting = windows.iter().map(|(user, _)| user.clone()).collect();
+                extraction.start(move || {
+                    windows
+                        .into_iter()
+                        .map(|(user, window)| (user, E::compute_window(window)))
+                        .collect::<Vec<_>>()
+                });
+            }
+            // Computed on a blocking thread so events keep draining meanwhile
+            result = extraction.finished() => {
+                extracting.clear();
+                let windows = match result {
+                    Ok(windows) => windows,
+                    Err(e) => {
+                        error!("{} feature computation failed: {}", title, e);
+                        Vec::new()
+                    }
+                };
+                for (user, features) in windows {
+                    let session = sessions.session(user.as_deref());
+                    let Some(mut features) = session.extractor.finish_window(features) else {
+                        continue;
+                    };
+                    tag_user(&mut features, user.as_deref());
+                    features["source_alive"] = json!(if source_alive { 1.0 } else { 0.0 });
+                    let idle = IdleRecord::is_idle(&features);
+                    if idle {
+                        info!("No {} activity in window", modality.name);
+                    } else {
+                        info!("Extracted {} features", modality.name);
+                    }
+
+                    // Dev-mode output goes to a separate channel or nowhere
+                    if let Some(channel) = &feature_channel {
+                        match ctx.sink.publish(channel, &features).await {
+                            Ok(()) => ctx.metrics.record_publish(),
+                            Err(e) if e.is::<PublishSkipped>() => {}
+                            Err(e) => error!("Failed to publish features: {}", e),
+                        }
+
+                        if let Some(influx) = &ctx.influx {
+                            let measurement = if idle {
+                                format!("{}_activity", modality.name)
+                            } else {
+                                format!("{}_features", modality.name)
+                            };
+                            if let Err(e) = influx.queue_features(&measurement, &features).await {
+                                error!("Failed to write features to InfluxDB: {}", e);
+                            }
+                        }
+
+                        // Idle records carry no behaviour to score
+                        if idle {
+                            continue;
+                        }
+
+                        // Dev-mode samples are scored but never learned
+                        let mut scores = if config.dev_mode {
+                            session.baseline.score(&features)
+                        } else {
+                            session.baseline.score_and_update(&features)
+                        };
+                        tag_user(&mut scores, user.as_deref());
+                        let scores_channel = format!("{}:scores", channel);
+                        match ctx.sink.publish(&scores_channel, &scores).await {
+                            Ok(()) => {}
+                            Err(e) if e.is::<PublishSkipped>() => {}
+                            Err(e) => error!("Failed to publish anomaly scores: {}", e),
+                        }
+                        if let Some(influx) = &ctx.influx {
+                            let measurement = format!("{}_anomaly", modality.name);
+                            if let Err(e) = influx.queue_features(&measurement, &scores).await {
+                                error!("Failed to write anomaly scores to InfluxDB: {}", e);
+                            }
+                        }
+                        if let Some(alerts) = &mut session.alerts {
+                            let window_end = features["window_end"].as_f64().unwrap_or_else(unix_now);
+                            match alerts.observe(&scores, window_end).await {
+                                Ok(Some(alert)) => warn!(
+                                    "Sustained {} anomaly (score {:.2} for {} windows); alert sent",
+                                    modality.name,
+                                    alert.anomaly_score,
+                                    alert.consecutive_windows
+                                ),
+                                Ok(None) => {}
+                                Err(e) => error!("Failed to send anomaly alert: {:#}", e),
+                            }
+                        }
+                    }
+                }
+            }
+            _ = flush_interval.tick() => {
+                if let Some(influx) = &ctx.influx {
+                    if let Err(e) = influx.flush().await {
+                        error!("Failed to flush InfluxDB batch: {}", e);
+                    }
+                }
+            }
+            _ = cleanup_interval.tick() => {
+                for (_, session) in sessions.iter_mut() {
+                    session.extractor.cleanup_old_events();
+                    session.save_baseline();
+                }
+                // Users who went quiet leave memory, but not while their window is computing
+                sessions.retain(|user, session| {
+                    session.last_event.elapsed() < idle_ttl
+                        || extracting.contains(&user.map(str::to_string))
+                });
+                info!("Cleaned up old events");
+            }
+        }
+    }
+
+    if let Some(influx) = &ctx.influx {
+        if let Err(e) = influx.flush().await {
+            error!("Failed to flush InfluxDB batch on shutdown: {}", e);
+        }
+    }
+    if let Err(e) = ctx.sink.flush().await {
+        error!("Failed to flush feature sink on shutdown: {:#}", e);
+    }
+    for (_, session) in sessions.iter_mut() {
+        session.save_baseline();
+    }
+    info!("{} Extractor stopped cleanly", title);
+    Ok(())
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
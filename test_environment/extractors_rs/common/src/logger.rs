@@ -1,10 +1,60 @@
+use std::env;
+use std::str::FromStr;
 use tracing::Level;
 
+/// Output format for log lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines for a terminal
+    Text,
+    /// One JSON object per line for log shippers
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => anyhow::bail!("unknown log format {:?} (expected text or json)", other),
+        }
+    }
+}
+
+/// Initialize logging from `SECLYZER_LOG_FORMAT` and `SECLYZER_LOG_LEVEL`/`RUST_LOG`
+///
+/// Falls back to text output at INFO when unset or unparseable.
 pub fn init_logging() {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
+    let format = env::var("SECLYZER_LOG_FORMAT")
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(LogFormat::Text);
+    init_logging_with(format, level_from_env());
+}
+
+/// Initialize logging with an explicit format and maximum level
+pub fn init_logging_with(format: LogFormat, level: Level) {
+    // Logs go to stderr so stdout stays clean for offline feature output
+    let builder = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
         .with_file(true)
         .with_line_number(true)
-        .with_thread_ids(true)
-        .init();
+        .with_thread_ids(true);
+
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+/// `SECLYZER_LOG_LEVEL` wins over `RUST_LOG`; only plain level names are understood
+fn level_from_env() -> Level {
+    ["SECLYZER_LOG_LEVEL", "RUST_LOG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find_map(|value| value.trim().parse().ok())
+        .unwrap_or(Level::INFO)
 }
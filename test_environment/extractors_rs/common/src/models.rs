@@ -1,14 +1,19 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::user::is_valid_user_id;
 use std::collections::HashMap;
 
 /// Raw event from Redis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawEvent {
+    #[serde(rename = "type")]
     pub event_type: String,  // "keystroke", "mouse", "app"
     pub ts: u64,             // microseconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    /// X11 keycode of the key's physical position, when the collector knows it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub event: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -21,6 +26,90 @@ pub struct RawEvent {
     pub scroll_delta: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub app_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_class: Option<String>,
+    #[serde(default)]
+    pub dev_mode: bool,
+    /// The user the event belongs to, on hosts several people use at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Earliest `ts` accepted as real (2000-01-01, in microseconds)
+const MIN_PLAUSIBLE_TS: u64 = 946_684_800_000_000;
+
+/// How far ahead of the local clock an event may be stamped (one day, in microseconds)
+const MAX_CLOCK_SKEW_US: u64 = 86_400_000_000;
+
+/// Why a payload couldn't be turned into a `RawEvent`
+#[derive(Debug, thiserror::Error)]
+pub enum EventParseError {
+    #[error("invalid event JSON: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("unknown event type {0:?} (expected keystroke, mouse or app)")]
+    UnknownType(String),
+    #[error("{event_type} event is missing {field}")]
+    MissingField {
+        event_type: String,
+        field: &'static str,
+    },
+    #[error("implausible timestamp {0} (expected microseconds since the Unix epoch)")]
+    ImplausibleTimestamp(u64),
+    #[error("invalid user id {0:?}")]
+    InvalidUserId(String),
+}
+
+impl RawEvent {
+    /// Parse and validate a payload from the event channel
+    pub fn from_json(payload: &str) -> Result<RawEvent, EventParseError> {
+        let event: RawEvent = serde_json::from_str(payload)?;
+        event.validate()?;
+        Ok(event)
+    }
+
+    fn validate(&self) -> Result<(), EventParseError> {
+        let missing = |field| EventParseError::MissingField {
+            event_type: self.event_type.clone(),
+            field,
+        };
+        match self.event_type.as_str() {
+            "keystroke" => {
+                if self.key.is_none() {
+                    return Err(missing("key"));
+                }
+                if self.event.is_none() {
+                    return Err(missing("event"));
+                }
+            }
+            "mouse" => {
+                if self.event.is_none() {
+                    return Err(missing("event"));
+                }
+                if self.event.as_deref() == Some("move") && (self.x.is_none() || self.y.is_none()) {
+                    return Err(missing("x/y coordinates"));
+                }
+            }
+            "app" => {
+                if self.app_name.is_none() {
+                    return Err(missing("app_name"));
+                }
+            }
+            other => return Err(EventParseError::UnknownType(other.to_string())),
+        }
+
+        let now_us = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        if self.ts < MIN_PLAUSIBLE_TS || self.ts > now_us + MAX_CLOCK_SKEW_US {
+            return Err(EventParseError::ImplausibleTimestamp(self.ts));
+        }
+        // User ids end up in file names, so only safe ones get that far
+        if let Some(user_id) = self.user_id.as_deref().filter(|id| !is_valid_user_id(id)) {
+            return Err(EventParseError::InvalidUserId(user_id.to_string()));
+        }
+        Ok(())
+    }
 }
 
 /// Keystroke event
@@ -52,7 +141,7 @@ pub struct AppEvent {
     pub dev_mode: bool,
 }
 
-/// Keystroke features (140 total)
+/// Keystroke features (`keystroke_extractor::record::FEATURE_COUNT` of them, plus target digraphs)
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct KeystrokeFeatures {
     pub timestamp: String,
@@ -61,7 +150,7 @@ pub struct KeystrokeFeatures {
     pub features: HashMap<String, f64>,
 }
 
-/// Mouse features (38 total)
+/// Mouse features (`mouse_extractor::record::FEATURE_COUNT` of them)
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MouseFeatures {
     pub timestamp: String,
@@ -79,6 +168,32 @@ pub struct AppTransition {
     pub timestamp: String,
 }
 
+/// Published in place of features when a window has too few events, so an idle
+/// user shows up as a record instead of a gap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleRecord {
+    pub activity: String,  // always "none"
+    pub event_count: usize,
+    pub window_start: f64,
+    pub window_end: f64,
+}
+
+impl IdleRecord {
+    pub fn new(event_count: usize, window_start: f64, window_end: f64) -> Self {
+        IdleRecord {
+            activity: "none".to_string(),
+            event_count,
+            window_start,
+            window_end,
+        }
+    }
+    
+    /// Whether a published record is an idle marker rather than a feature set
+    pub fn is_idle(record: &serde_json::Value) -> bool {
+        record.get("activity").and_then(|a| a.as_str()) == Some("none")
+    }
+}
+
 /// InfluxDB write request
 #[derive(Debug, Clone, Serialize)]
 pub struct InfluxPoint {
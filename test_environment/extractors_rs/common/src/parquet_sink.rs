@@ -0,0 +1,156 @@
+use crate::sink::FeatureSink;
+use anyhow::{Context, Result};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Columns every file starts with, ahead of the declared feature columns
+const WINDOW_COLUMNS: &[&str] = &["window_start", "window_end"];
+
+/// Records buffered for one channel
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Option<f64>>>,
+    started: Instant,
+    /// Files written so far, numbering the next so quick flushes never collide
+    files: usize,
+}
+
+#[derive(Default)]
+struct Tables {
+    declared: HashMap<String, Table>,
+    /// Undeclared channels already warned about
+    ignored: HashSet<String>,
+}
+
+/// Buffers feature records per channel and writes each batch to its own Parquet file
+///
+/// Every declared column is a nullable `Float64`, so a whole session loads with one
+/// `pandas.read_parquet(dir)`; a feature missing from a record is null. A batch is
+/// written once it holds `flush_rows` records or its first record is
+/// `flush_interval` old, as `<dir>/<channel>-<timestamp>-<n>.parquet`.
+pub struct ParquetSink {
+    dir: PathBuf,
+    flush_rows: usize,
+    flush_interval: Duration,
+    tables: Mutex<Tables>,
+}
+
+impl ParquetSink {
+    pub fn new(dir: PathBuf, flush_rows: usize, flush_interval: Duration) -> Self {
+        ParquetSink {
+            dir,
+            flush_rows,
+            flush_interval,
+            tables: Mutex::new(Tables::default()),
+        }
+    }
+
+    /// Write a channel's buffered records to a new file and start a fresh batch
+    fn write_table(&self, channel: &str, table: &mut Table) -> Result<()> {
+        if table.rows.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<Field> = table
+            .columns
+            .iter()
+            .map(|name| Field::new(name, DataType::Float64, true))
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let arrays: Vec<ArrayRef> = (0..table.columns.len())
+            .map(|i| {
+                let values: Vec<Option<f64>> = table.rows.iter().map(|row| row[i]).collect();
+                Arc::new(Float64Array::from(values)) as ArrayRef
+            })
+            .collect();
+        let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let name = format!(
+            "{}-{}-{}.parquet",
+            channel.replace(':', "_"),
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            table.files
+        );
+        let path = self.dir.join(name);
+        // Written under another name first so readers never pick up a half-written file
+        let partial = path.with_extension("parquet.partial");
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let file = File::create(&partial)
+            .with_context(|| format!("Failed to create {}", partial.display()))?;
+        let mut writer = ArrowWriter::try_new(file, schema, Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        fs::rename(&partial, &path)?;
+
+        tracing::info!("Wrote {} feature records to {}", table.rows.len(), path.display());
+        table.rows.clear();
+        table.files += 1;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FeatureSink for ParquetSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(table) = tables.declared.get_mut(channel) else {
+            if tables.ignored.insert(channel.to_string()) {
+                tracing::warn!("No Parquet columns declared for {}; not writing it", channel);
+            }
+            return Ok(());
+        };
+
+        if table.rows.is_empty() {
+            table.started = Instant::now();
+        }
+        let row = table
+            .columns
+            .iter()
+            .map(|column| value.get(column).and_then(serde_json::Value::as_f64))
+            .collect();
+        table.rows.push(row);
+
+        if table.rows.len() >= self.flush_rows || table.started.elapsed() >= self.flush_interval {
+            self.write_table(channel, table)?;
+        }
+        Ok(())
+    }
+
+    fn declare_columns(&self, channel: &str, columns: &[&str]) {
+        let columns = WINDOW_COLUMNS
+            .iter()
+            .chain(columns)
+            .map(|column| column.to_string())
+            .collect();
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        tables.declared.insert(
+            channel.to_string(),
+            Table {
+                columns,
+                rows: Vec::new(),
+                started: Instant::now(),
+                files: 0,
+            },
+        );
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        for (channel, table) in tables.declared.iter_mut() {
+            self.write_table(channel, table)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+struct Bucket {
+    /// Tokens available; negative while a write larger than the burst is being paid off
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Token bucket pacing writes to a steady rate
+///
+/// The bucket starts full, so up to `burst` tokens go out at once; after that they
+/// refill at `rate` per second. A caller asking for more than is available waits
+/// for the shortfall, holding the bucket so later callers queue behind it in order.
+/// Asking for more than `burst` at once is allowed and leaves the bucket in debt,
+/// so a large batch goes through whole and the writes after it wait longer.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// `rate` tokens per second, at most `burst` at once; `rate` must be positive
+    pub fn new(rate: f64, burst: u64) -> Self {
+        RateLimiter {
+            rate,
+            burst: burst as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `tokens`, waiting until the rate allows them
+    pub async fn acquire(&self, tokens: u64) {
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled = now;
+
+        bucket.tokens -= tokens as f64;
+        if bucket.tokens < 0.0 {
+            let wait = Duration::from_secs_f64(-bucket.tokens / self.rate);
+            tracing::debug!(
+                "Rate limit reached, waiting {:?} for {} tokens",
+                wait,
+                tokens
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
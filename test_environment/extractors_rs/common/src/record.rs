@@ -0,0 +1,131 @@
+// Typed feature records. Each extractor declares its record once with
+// `feature_record!`, which keeps the struct fields, the canonical model column
+// order and the flattening helpers from drifting apart: a feature missing from the
+// calculator is a missing-field compile error instead of a silently absent key.
+// Every published record carries its `schema_version`, so a consumer trained on one
+// feature set can refuse windows from another instead of scoring them, and its
+// `padded_features`, so a model can tell a computed 0.0 from one that only stands
+// in for a feature the window had no data for.
+
+/// Key the record's schema version is published under
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// Key the record's `feature_fingerprint` is published under
+pub const FINGERPRINT_FIELD: &str = "fingerprint";
+
+/// Declare a feature record struct
+///
+/// `features` lists the model inputs in canonical column order, `metadata` the
+/// per-window `f64` values that aren't model inputs; both become `pub f64` fields.
+/// A `padded_features` list follows them, for calculators to fill with
+/// `mark_padded`. Any further fields (e.g. a `#[serde(flatten)]` map of runtime-named features)
+/// are declared as usual after them. `schema_version` must be bumped whenever
+/// `features` gains, loses, renames or reorders a feature. Users need `serde` and
+/// `serde_json`.
+#[macro_export]
+macro_rules! feature_record {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            schema_version $version:literal;
+            features { $($feature:ident),* $(,)? }
+            metadata { $($metadata:ident),* $(,)? }
+            $($(#[$extra_meta:meta])* pub $extra:ident: $extra_ty:ty,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+        pub struct $name {
+            $(pub $feature: f64,)*
+            $(pub $metadata: f64,)*
+            /// Model features left at their 0.0 pad because the window had nothing
+            /// to compute them from, in `FEATURE_NAMES` order
+            #[serde(default)]
+            pub padded_features: Vec<String>,
+            $($(#[$extra_meta])* pub $extra: $extra_ty,)*
+        }
+
+        impl $name {
+            /// Version of the feature set, published as `schema_version`
+            pub const SCHEMA_VERSION: u32 = $version;
+
+            /// Model feature names, in canonical column order
+            pub const FEATURE_NAMES: &'static [&'static str] = &[$(stringify!($feature)),*];
+
+            /// Number of model features, for consumers to check their input width against
+            pub const fn feature_count() -> usize {
+                Self::FEATURE_NAMES.len()
+            }
+
+            /// Per-window metadata names, in declaration order
+            pub const METADATA_NAMES: &'static [&'static str] = &[$(stringify!($metadata)),*];
+
+            /// Number of fixed keys `to_json` publishes: every feature and metadata
+            /// value plus `padded_features`, `schema_version` and `fingerprint`
+            pub const fn field_count() -> usize {
+                Self::FEATURE_NAMES.len() + Self::METADATA_NAMES.len() + 3
+            }
+
+            /// Mark every model feature whose name `padded` accepts as padded
+            pub fn mark_padded(&mut self, padded: impl Fn(&str) -> bool) {
+                let already: std::collections::HashSet<String> =
+                    std::mem::take(&mut self.padded_features).into_iter().collect();
+                self.padded_features = Self::FEATURE_NAMES
+                    .iter()
+                    .filter(|name| already.contains(**name) || padded(name))
+                    .map(|name| name.to_string())
+                    .collect();
+            }
+
+            /// Whether `feature` was padded rather than computed
+            pub fn is_padded(&self, feature: &str) -> bool {
+                self.padded_features.iter().any(|name| name == feature)
+            }
+
+            /// Model features in `FEATURE_NAMES` order
+            pub fn to_vector(&self) -> Vec<f64> {
+                vec![$(self.$feature),*]
+            }
+
+            /// Every feature and metadata value
+            pub fn values_mut(&mut self) -> impl Iterator<Item = &mut f64> {
+                [$(&mut self.$feature,)* $(&mut self.$metadata,)*].into_iter()
+            }
+
+            /// Every numeric field by its serialized name, for code still working on maps
+            pub fn to_hashmap(&self) -> std::collections::HashMap<String, f64> {
+                match serde_json::to_value(self) {
+                    Ok(serde_json::Value::Object(fields)) => fields
+                        .into_iter()
+                        .filter_map(|(name, value)| Some((name, value.as_f64()?)))
+                        .collect(),
+                    _ => std::collections::HashMap::new(),
+                }
+            }
+
+            /// SimHash of the model features, for approximate grouping of windows
+            pub fn fingerprint(&self) -> u64 {
+                $crate::feature_fingerprint(
+                    Self::FEATURE_NAMES.iter().copied().zip(self.to_vector()),
+                )
+            }
+
+            /// The record as the JSON object extractors publish, stamped with
+            /// `schema_version` and `fingerprint`
+            pub fn to_json(&self) -> serde_json::Value {
+                let mut json = serde_json::to_value(self).unwrap_or_default();
+                if let Some(fields) = json.as_object_mut() {
+                    fields.insert(
+                        $crate::record::SCHEMA_VERSION_FIELD.to_string(),
+                        Self::SCHEMA_VERSION.into(),
+                    );
+                    fields.insert(
+                        $crate::record::FINGERPRINT_FIELD.to_string(),
+                        self.fingerprint().into(),
+                    );
+                }
+                json
+            }
+        }
+    };
+}
@@ -1,47 +1,370 @@
-use crate::config::Config;
+use crate::compression::{decode_payload, encode_payload};
+use crate::config::{Config, EventTransport};
+use crate::event_bus::{unacked, Delivery};
 use anyhow::Result;
+use futures_util::StreamExt;
 use redis::aio::ConnectionManager;
-use redis::{AsyncCommands, Client};
-use serde_json::json;
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, Client, RedisResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+
+/// Capacity of the channel between a subscription task and its consumer
+const SUBSCRIBE_BUFFER: usize = 10000;
+
+/// Approximate cap on stream length so an unconsumed stream can't grow forever
+pub(crate) const STREAM_MAXLEN: usize = 100_000;
+
+/// Stream entry field holding the JSON event payload
+pub(crate) const STREAM_FIELD: &str = "data";
+
+/// How long an XREADGROUP call blocks waiting for new entries
+const STREAM_BLOCK_MS: usize = 5000;
+
+/// Entries fetched per XREADGROUP call
+const STREAM_READ_COUNT: usize = 500;
+
+/// Counters for what a `RedisClient` has sent
+#[derive(Debug, Default)]
+pub struct RedisMetrics {
+    published: AtomicU64,
+    publish_errors: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl RedisMetrics {
+    /// Messages successfully published or appended to a stream
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+    
+    /// Publishes or stream appends that failed
+    pub fn publish_errors(&self) -> u64 {
+        self.publish_errors.load(Ordering::Relaxed)
+    }
+    
+    /// Commands that hit a dropped connection
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+    
+    /// Tally the outcome of a publish
+    fn record_publish<T>(&self, result: &RedisResult<T>) {
+        self.record_publishes(result, 1);
+    }
+    
+    /// Tally the outcome of `count` messages sent together
+    fn record_publishes<T>(&self, result: &RedisResult<T>, count: usize) {
+        let counter = if result.is_ok() { &self.published } else { &self.publish_errors };
+        counter.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
 
 pub struct RedisClient {
+    client: Client,
     manager: ConnectionManager,
+    transport: EventTransport,
+    compress: bool,
+    metrics: RedisMetrics,
 }
 
 impl RedisClient {
     pub async fn new(config: &Config) -> Result<Self> {
-        let client_url = if let Some(password) = &config.redis_password {
-            format!(
-                "redis://:{password}@{}:{}",
-                config.redis_host, config.redis_port
-            )
-        } else {
-            format!("redis://{}:{}", config.redis_host, config.redis_port)
-        };
-        
-        let client = Client::open(client_url)?;
-        let manager = ConnectionManager::new(client).await?;
+        tracing::info!("Connecting to Redis at {}", config.redis_url_redacted());
+        let client = Client::open(config.redis_url())?;
+        let manager = ConnectionManager::new(client.clone()).await?;
         
         // Test connection
         let mut conn = manager.clone();
         let pong: String = redis::cmd("PING").query_async(&mut conn).await?;
         tracing::info!("Redis connection test: {}", pong);
         
-        Ok(RedisClient { manager })
+        Ok(RedisClient {
+            client,
+            manager,
+            transport: config.event_transport,
+            compress: config.compress_events,
+            metrics: RedisMetrics::default(),
+        })
+    }
+    
+    /// Check the server still answers
+    pub async fn ping(&self) -> Result<()> {
+        let mut conn = self.manager.clone();
+        self.track(redis::cmd("PING").query_async::<_, String>(&mut conn).await)?;
+        Ok(())
+    }
+    
+    /// Times a command hit a dropped connection and the manager had to reconnect
+    pub fn reconnect_count(&self) -> u64 {
+        self.metrics.reconnects()
+    }
+    
+    /// Publish and connection counters for this client
+    pub fn metrics(&self) -> &RedisMetrics {
+        &self.metrics
+    }
+    
+    /// Count connection-level failures; `ConnectionManager` reconnects after each one
+    fn track<T>(&self, result: RedisResult<T>) -> RedisResult<T> {
+        if let Err(e) = &result {
+            if e.is_connection_dropped() || e.is_io_error() {
+                self.metrics.reconnects.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
     }
     
-    /// Publish features to Redis channel
+    /// Publish features to Redis channel, compressed if `COMPRESS_EVENTS` is set
     pub async fn publish_features(
         &self,
         channel: &str,
         features: &serde_json::Value,
     ) -> Result<()> {
         let mut conn = self.manager.clone();
-        let json_str = serde_json::to_string(features)?;
-        conn.publish::<_, _, ()>(channel, json_str).await?;
+        let payload = encode_payload(&serde_json::to_string(features)?, self.compress);
+        let result = self.track(conn.publish::<_, _, ()>(channel, payload).await);
+        self.metrics.record_publish(&result);
+        result?;
+        Ok(())
+    }
+    
+    /// Publish several `(channel, message)` pairs in one pipelined round trip,
+    /// compressed like `publish_features`; they either all go out or the call fails
+    pub async fn publish_batch(&self, messages: &[(String, serde_json::Value)]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (channel, message) in messages {
+            let payload = encode_payload(&serde_json::to_string(message)?, self.compress);
+            pipe.publish(channel, payload).ignore();
+        }
+        
+        let mut conn = self.manager.clone();
+        let result = self.track(pipe.query_async::<_, ()>(&mut conn).await);
+        self.metrics.record_publishes(&result, messages.len());
+        result?;
         Ok(())
     }
     
+    /// Send a raw event over the configured transport (pub/sub or stream)
+    pub async fn publish_event(&self, channel: &str, event: &serde_json::Value) -> Result<()> {
+        match self.transport {
+            EventTransport::PubSub => self.publish_features(channel, event).await,
+            EventTransport::Stream => self.xadd(channel, event).await.map(|_| ()),
+        }
+    }
+    
+    /// Subscribe to a pub/sub channel, forwarding each (decompressed) payload to the receiver
+    pub async fn subscribe(&self, channel: &str) -> Result<mpsc::Receiver<String>> {
+        let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(channel).await?;
+        tracing::info!("Subscribed to {}", channel);
+        
+        let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+        let channel = channel.to_string();
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message();
+            while let Some(msg) = messages.next().await {
+                match decode_payload(msg.get_payload_bytes()) {
+                    Ok(payload) => {
+                        if tx.send(payload).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => tracing::warn!("Unreadable message on {}: {:#}", channel, e),
+                }
+            }
+            tracing::warn!("Subscription to {} ended", channel);
+        });
+        
+        Ok(rx)
+    }
+    
+    /// Subscribe to every channel matching a glob pattern, forwarding `(channel, payload)` pairs
+    pub async fn psubscribe(&self, pattern: &str) -> Result<mpsc::Receiver<(String, String)>> {
+        let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+        pubsub.psubscribe(pattern).await?;
+        tracing::info!("Subscribed to pattern {}", pattern);
+        
+        let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+        let pattern = pattern.to_string();
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message();
+            while let Some(msg) = messages.next().await {
+                let channel = msg.get_channel_name().to_string();
+                match decode_payload(msg.get_payload_bytes()) {
+                    Ok(payload) => {
+                        if tx.send((channel, payload)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => tracing::warn!("Unreadable message on {}: {:#}", channel, e),
+                }
+            }
+            tracing::warn!("Subscription to {} ended", pattern);
+        });
+        
+        Ok(rx)
+    }
+    
+    /// Append an event to a stream, returning the entry id
+    pub async fn xadd(&self, stream: &str, event: &serde_json::Value) -> Result<String> {
+        let mut conn = self.manager.clone();
+        let payload = encode_payload(&serde_json::to_string(event)?, self.compress);
+        let result: RedisResult<String> = self.track(
+            conn.xadd_maxlen(
+                stream,
+                StreamMaxlen::Approx(STREAM_MAXLEN),
+                "*",
+                &[(STREAM_FIELD, payload)],
+            )
+            .await,
+        );
+        self.metrics.record_publish(&result);
+        Ok(result?)
+    }
+    
+    /// Read entries for a consumer group, returning `(id, payload)` pairs.
+    ///
+    /// `start_id` is `">"` for new entries or `"0"` for this consumer's pending ones.
+    pub async fn xread_group(
+        &self,
+        stream: &str,
+        group: &str,
+        consumer: &str,
+        start_id: &str,
+    ) -> Result<Vec<(String, String)>> {
+        // XREADGROUP blocks, so it gets its own connection instead of the shared manager
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        Self::read_group(&mut conn, stream, group, consumer, start_id).await
+    }
+    
+    /// Acknowledge processed stream entries so they aren't redelivered
+    pub async fn xack(&self, stream: &str, group: &str, ids: &[String]) -> Result<()> {
+        let mut conn = self.manager.clone();
+        self.track(conn.xack::<_, _, _, ()>(stream, group, ids).await)?;
+        Ok(())
+    }
+    
+    /// Subscribe to raw events over the configured transport.
+    ///
+    /// With streams, `group` names the consumer group; each extractor uses its own so
+    /// every extractor sees every event. Stream entries are acknowledged once their
+    /// `Delivery` is dropped; pub/sub has nothing to acknowledge.
+    pub async fn subscribe_events(&self, channel: &str, group: &str) -> Result<mpsc::Receiver<Delivery>> {
+        match self.transport {
+            EventTransport::PubSub => Ok(unacked(self.subscribe(channel).await?)),
+            EventTransport::Stream => self.subscribe_stream(channel, group).await,
+        }
+    }
+    
+    /// Consume a stream through a consumer group, acknowledging each entry when its
+    /// `Delivery` is dropped
+    ///
+    /// A new group starts at the beginning of the stream, so events appended before
+    /// the first subscriber came up are read too. Entries that were delivered but
+    /// never acknowledged, e.g. because the extractor died while handling them, are
+    /// read again on the next start.
+    pub async fn subscribe_stream(&self, stream: &str, group: &str) -> Result<mpsc::Receiver<Delivery>> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        
+        // Create the group (and stream) if needed; BUSYGROUP means it already exists
+        let created: redis::RedisResult<()> = conn.xgroup_create_mkstream(stream, group, "0").await;
+        if let Err(e) = created {
+            if !e.to_string().contains("BUSYGROUP") {
+                return Err(e.into());
+            }
+        }
+        tracing::info!("Reading stream {} as group {}", stream, group);
+        
+        let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+        let (ack_tx, mut ack_rx) = mpsc::unbounded_channel::<String>();
+        let stream = stream.to_string();
+        let group = group.to_string();
+        tokio::spawn(async move {
+            // Entries delivered before a restart but never acked come first, read
+            // from after the last one seen until the backlog is empty
+            let mut start_id = "0".to_string();
+            loop {
+                let mut handled = Vec::new();
+                while let Ok(id) = ack_rx.try_recv() {
+                    handled.push(id);
+                }
+                if !handled.is_empty() {
+                    if let Err(e) = conn.xack::<_, _, _, ()>(&stream, &group, &handled).await {
+                        tracing::warn!("Failed to ack {} entries on {}: {}", handled.len(), stream, e);
+                    }
+                }
+                
+                let entries = match Self::read_group(&mut conn, &stream, &group, &group, &start_id).await {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        tracing::warn!("Failed to read stream {}: {}", stream, e);
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                if start_id != ">" {
+                    match entries.last() {
+                        Some((id, _)) => start_id = id.clone(),
+                        None => start_id = ">".to_string(),
+                    }
+                }
+                
+                for (id, payload) in entries {
+                    if tx.send(Delivery::acked(payload, id, ack_tx.clone())).await.is_err() {
+                        tracing::warn!("Stream consumer for {} closed", stream);
+                        return;
+                    }
+                }
+            }
+        });
+        
+        Ok(rx)
+    }
+    
+    async fn read_group<C>(
+        conn: &mut C,
+        stream: &str,
+        group: &str,
+        consumer: &str,
+        start_id: &str,
+    ) -> Result<Vec<(String, String)>>
+    where
+        C: redis::aio::ConnectionLike + Send,
+    {
+        let options = StreamReadOptions::default()
+            .group(group, consumer)
+            .count(STREAM_READ_COUNT)
+            .block(STREAM_BLOCK_MS);
+        let reply: Option<StreamReadReply> = conn
+            .xread_options(&[stream], &[start_id], &options)
+            .await?;
+        
+        Ok(reply
+            .map(|reply| {
+                reply
+                    .keys
+                    .into_iter()
+                    .flat_map(|key| key.ids)
+                    .filter_map(|entry| {
+                        let payload: Vec<u8> = entry.get(STREAM_FIELD)?;
+                        match decode_payload(&payload) {
+                            Ok(payload) => Some((entry.id, payload)),
+                            Err(e) => {
+                                tracing::warn!("Unreadable entry {} on {}: {:#}", entry.id, stream, e);
+                                None
+                            }
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+    
     /// Get Redis connection manager
     pub fn connection_manager(&self) -> ConnectionManager {
         self.manager.clone()
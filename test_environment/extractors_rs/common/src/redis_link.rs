@@ -0,0 +1,44 @@
+// Blocking Redis connection for the collectors, which run outside an async runtime
+
+use std::time::{Duration, Instant};
+
+/// Minimum wait between reconnect attempts while Redis is unreachable
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Blocking Redis connection for collectors that is re-established after an outage
+///
+/// `redis::Connection` never recovers once the server goes away, so a failed
+/// connection is dropped with `reset` and reopened on a later `connection` call.
+pub struct RedisLink {
+    client: redis::Client,
+    con: Option<redis::Connection>,
+    retry_at: Instant,
+}
+
+impl RedisLink {
+    pub fn new(client: redis::Client) -> Self {
+        RedisLink {
+            client,
+            con: None,
+            retry_at: Instant::now(),
+        }
+    }
+
+    /// The open connection, reconnecting first if the last attempt was long enough ago
+    pub fn connection(&mut self) -> Option<&mut redis::Connection> {
+        if self.con.is_none() && Instant::now() >= self.retry_at {
+            self.retry_at = Instant::now() + RECONNECT_INTERVAL;
+            self.con = self.client.get_connection().ok();
+        }
+        self.con.as_mut()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.con.is_some()
+    }
+
+    /// Forget a connection that just failed
+    pub fn reset(&mut self) {
+        self.con = None;
+    }
+}
@@ -0,0 +1,90 @@
+// HMAC-SHA256 signatures on raw events, so subscribers can reject events that
+// didn't come from a collector holding the shared secret. The MAC covers the
+// event's canonical JSON: the object without its `sig` field, serialized compactly
+// with keys in sorted order, which is what serde_json produces for a `Value`. That
+// way it doesn't depend on the field order or spacing the collector wrote, and a
+// signature survives a round trip through the WAL or a Kafka topic unchanged.
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+/// Field holding an event's signature, as lowercase hex
+pub const SIGNATURE_FIELD: &str = "sig";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why an event was rejected by `EventSigner::verify`
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    #[error("invalid event JSON: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("event is not a JSON object")]
+    NotAnObject,
+    #[error("event is unsigned")]
+    Unsigned,
+    #[error("event signature does not match its contents")]
+    Mismatch,
+}
+
+/// Signs and verifies raw events with a shared secret
+#[derive(Clone)]
+pub struct EventSigner {
+    key: Vec<u8>,
+}
+
+impl EventSigner {
+    pub fn new(secret: &str) -> Self {
+        EventSigner {
+            key: secret.as_bytes().to_vec(),
+        }
+    }
+
+    /// Serialize `event` with a `sig` field added
+    ///
+    /// `event` must serialize to a JSON object; anything else is returned unsigned.
+    pub fn sign<T: Serialize>(&self, event: &T) -> serde_json::Result<String> {
+        let mut value = serde_json::to_value(event)?;
+        if let Some(object) = value.as_object_mut() {
+            object.remove(SIGNATURE_FIELD);
+            let mac = self.mac(&serde_json::to_string(object)?);
+            let signature: String = mac
+                .finalize()
+                .into_bytes()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            object.insert(SIGNATURE_FIELD.to_string(), signature.into());
+        }
+        serde_json::to_string(&value)
+    }
+
+    /// Check a payload's `sig` against its contents
+    pub fn verify(&self, payload: &str) -> Result<(), SignatureError> {
+        let mut value: serde_json::Value = serde_json::from_str(payload)?;
+        let object = value.as_object_mut().ok_or(SignatureError::NotAnObject)?;
+        let signature = match object.remove(SIGNATURE_FIELD) {
+            Some(serde_json::Value::String(signature)) => signature,
+            Some(_) => return Err(SignatureError::Mismatch),
+            None => return Err(SignatureError::Unsigned),
+        };
+        let signature = decode_hex(&signature).ok_or(SignatureError::Mismatch)?;
+        self.mac(&serde_json::to_string(object)?)
+            .verify_slice(&signature)
+            .map_err(|_| SignatureError::Mismatch)
+    }
+
+    fn mac(&self, canonical: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(canonical.as_bytes());
+        mac
+    }
+}
+
+/// Bytes of a hex string, or `None` if it isn't one (an odd length fails on the last pair)
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,144 @@
+use crate::{EventBus, RedisClient};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Where extractors send their feature output
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SinkKind {
+    /// Publish to Redis (or Kafka, with `EVENT_BUS=kafka`) and write points to InfluxDB
+    Redis,
+    /// Pretty-print to stdout for debugging; InfluxDB is not contacted
+    Stdout,
+    /// Batch feature records into Parquet files in a directory, for model training;
+    /// InfluxDB is not contacted. Needs the `parquet` feature.
+    Parquet(PathBuf),
+    /// Append feature records to CSV files in a directory, for training without
+    /// Parquet tooling; InfluxDB is not contacted
+    Csv(PathBuf),
+}
+
+impl FromStr for SinkKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(dir) = s.strip_prefix("parquet:") {
+            anyhow::ensure!(!dir.is_empty(), "parquet sink needs a directory");
+            #[cfg(not(feature = "parquet"))]
+            anyhow::bail!("parquet sink needs the parquet feature");
+            #[cfg(feature = "parquet")]
+            return Ok(SinkKind::Parquet(PathBuf::from(dir)));
+        }
+        if let Some(dir) = s.strip_prefix("csv:") {
+            anyhow::ensure!(!dir.is_empty(), "csv sink needs a directory");
+            return Ok(SinkKind::Csv(PathBuf::from(dir)));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Ok(SinkKind::Redis),
+            "stdout" => Ok(SinkKind::Stdout),
+            other => anyhow::bail!(
+                "unknown sink {:?} (expected redis, stdout, parquet:DIR or csv:DIR)",
+                other
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for SinkKind {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Destination for feature, score and state messages
+#[async_trait]
+pub trait FeatureSink: Send + Sync {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()>;
+
+    /// Fix the columns of records on `channel`; sinks with a schema drop messages on
+    /// channels nothing was declared for
+    fn declare_columns(&self, _channel: &str, _columns: &[&str]) {}
+
+    /// Write out anything still buffered, e.g. on shutdown
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Publishes each message on its Redis pub/sub channel
+pub struct RedisSink {
+    redis: Arc<RedisClient>,
+}
+
+impl RedisSink {
+    pub fn new(redis: Arc<RedisClient>) -> Self {
+        RedisSink { redis }
+    }
+}
+
+#[async_trait]
+impl FeatureSink for RedisSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        self.redis.publish_features(channel, value).await
+    }
+}
+
+/// Publishes each message on an `EventBus` topic named after its channel
+pub struct BusSink {
+    bus: Arc<dyn EventBus>,
+}
+
+impl BusSink {
+    pub fn new(bus: Arc<dyn EventBus>) -> Self {
+        BusSink { bus }
+    }
+}
+
+#[async_trait]
+impl FeatureSink for BusSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        self.bus.publish(channel, value).await
+    }
+}
+
+/// Writes each message as a pretty-printed `{"channel": ..., "value": ...}` document
+pub struct StdoutSink {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl StdoutSink {
+    pub fn new() -> Self {
+        Self::with_writer(std::io::stdout())
+    }
+
+    /// Write to `out` instead of stdout
+    pub fn with_writer<W: Write + Send + 'static>(out: W) -> Self {
+        StdoutSink {
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+}
+
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FeatureSink for StdoutSink {
+    async fn publish(&self, channel: &str, value: &serde_json::Value) -> Result<()> {
+        let message = serde_json::json!({ "channel": channel, "value": value });
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(out, "{}", serde_json::to_string_pretty(&message)?)?;
+        out.flush()?;
+        Ok(())
+    }
+}
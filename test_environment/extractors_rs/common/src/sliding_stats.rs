@@ -0,0 +1,247 @@
+// Summary statistics over a sliding time window, kept up to date as samples are
+// added and evicted instead of recomputed from the whole window each time. Count,
+// mean, standard deviation, min and max are exact; quantiles come from a
+// log-bucketed sketch whose estimates are within `SKETCH_RELATIVE_ERROR` of a
+// value of the right rank. Unlike a t-digest, the sketch supports removing
+// samples, which the window needs as old ones fall out of it.
+
+use crate::stats;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Relative error of quantile estimates
+pub const SKETCH_RELATIVE_ERROR: f64 = 0.01;
+
+/// Quantile sketch with bucket boundaries at powers of `gamma`
+///
+/// A value `v > 0` falls in bucket `ceil(log_gamma(v))`; every value in a bucket
+/// is estimated by the one point within the relative error of all of them.
+/// Non-positive values are counted as zero.
+#[derive(Debug, Clone)]
+pub struct QuantileSketch {
+    gamma_ln: f64,
+    buckets: BTreeMap<i32, usize>,
+    zeros: usize,
+    count: usize,
+}
+
+impl QuantileSketch {
+    pub fn new(relative_error: f64) -> Self {
+        let gamma = (1.0 + relative_error) / (1.0 - relative_error);
+        QuantileSketch {
+            gamma_ln: gamma.ln(),
+            buckets: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        match self.bucket(value) {
+            Some(index) => *self.buckets.entry(index).or_insert(0) += 1,
+            None => self.zeros += 1,
+        }
+        self.count += 1;
+    }
+
+    /// Take out one earlier `insert` of `value`
+    pub fn remove(&mut self, value: f64) {
+        match self.bucket(value) {
+            Some(index) => {
+                let Some(count) = self.buckets.get_mut(&index) else {
+                    return;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    self.buckets.remove(&index);
+                }
+            }
+            None if self.zeros > 0 => self.zeros -= 1,
+            None => return,
+        }
+        self.count -= 1;
+    }
+
+    /// Estimate of the value at 0-based `rank` in sorted order; 0.0 when empty
+    pub fn value_at_rank(&self, rank: usize) -> f64 {
+        if rank < self.zeros {
+            return 0.0;
+        }
+        let mut seen = self.zeros;
+        for (&index, &count) in &self.buckets {
+            seen += count;
+            if seen > rank {
+                return self.estimate(index);
+            }
+        }
+        self.buckets
+            .keys()
+            .next_back()
+            .map_or(0.0, |&index| self.estimate(index))
+    }
+
+    fn bucket(&self, value: f64) -> Option<i32> {
+        (value > 0.0).then(|| (value.ln() / self.gamma_ln).ceil() as i32)
+    }
+
+    /// The point within the relative error of every value in bucket `index`
+    fn estimate(&self, index: i32) -> f64 {
+        let upper = (index as f64 * self.gamma_ln).exp();
+        2.0 * upper / (1.0 + self.gamma_ln.exp())
+    }
+}
+
+/// Statistics of the samples timestamped after a moving cutoff
+///
+/// Samples may arrive a little out of timestamp order (a key's dwell time is only
+/// known at its release, but belongs to its press); each is slotted in by
+/// timestamp, so `evict_through` always removes exactly the older ones.
+#[derive(Debug, Clone)]
+pub struct SlidingStats {
+    /// `(timestamp, value)`, oldest first
+    samples: VecDeque<(f64, f64)>,
+    sum: f64,
+    sum_squares: f64,
+    /// Sample count per value, keyed to sort like the values, for exact min and max
+    values: BTreeMap<i64, usize>,
+    sketch: QuantileSketch,
+}
+
+impl Default for SlidingStats {
+    fn default() -> Self {
+        SlidingStats {
+            samples: VecDeque::new(),
+            sum: 0.0,
+            sum_squares: 0.0,
+            values: BTreeMap::new(),
+            sketch: QuantileSketch::new(SKETCH_RELATIVE_ERROR),
+        }
+    }
+}
+
+impl SlidingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, timestamp: f64, value: f64) {
+        let at = self
+            .samples
+            .iter()
+            .rposition(|&(ts, _)| ts <= timestamp)
+            .map_or(0, |i| i + 1);
+        self.samples.insert(at, (timestamp, value));
+        self.sum += value;
+        self.sum_squares += value * value;
+        *self.values.entry(order_key(value)).or_insert(0) += 1;
+        self.sketch.insert(value);
+    }
+
+    /// Drop samples timestamped at or before `cutoff`
+    pub fn evict_through(&mut self, cutoff: f64) {
+        while let Some(&(timestamp, value)) = self.samples.front() {
+            if timestamp > cutoff {
+                break;
+            }
+            self.samples.pop_front();
+            self.sum -= value;
+            self.sum_squares -= value * value;
+            let key = order_key(value);
+            if let Some(count) = self.values.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.values.remove(&key);
+                }
+            }
+            self.sketch.remove(value);
+        }
+        if self.samples.is_empty() {
+            // Clear what rounding has left behind
+            self.sum = 0.0;
+            self.sum_squares = 0.0;
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Summary of the current samples, matching `Summary::of` on them up to
+    /// rounding (and the sketch's error for the quantiles)
+    pub fn summary(&self) -> Summary {
+        let count = self.count();
+        if count == 0 {
+            return Summary::default();
+        }
+        let n = count as f64;
+        let mean = self.sum / n;
+        let std_dev = if count < 2 {
+            0.0
+        } else {
+            (self.sum_squares / n - mean * mean).max(0.0).sqrt()
+        };
+        let value = |key: Option<&i64>| key.map_or(0.0, |&key| from_order_key(key));
+        let median = if count % 2 == 1 {
+            self.sketch.value_at_rank(count / 2)
+        } else {
+            (self.sketch.value_at_rank(count / 2 - 1) + self.sketch.value_at_rank(count / 2)) / 2.0
+        };
+        Summary {
+            count,
+            mean,
+            std_dev,
+            min: value(self.values.keys().next()),
+            max: value(self.values.keys().next_back()),
+            median,
+            q25: self.sketch.value_at_rank(percentile_rank(count, 25.0)),
+            q75: self.sketch.value_at_rank(percentile_rank(count, 75.0)),
+        }
+    }
+}
+
+/// The usual statistics of a set of values
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub q25: f64,
+    pub q75: f64,
+}
+
+impl Summary {
+    /// Exact summary of `values`, computed in one batch
+    pub fn of(values: &[f64]) -> Self {
+        Summary {
+            count: values.len(),
+            mean: stats::mean(values),
+            std_dev: stats::std_dev(values),
+            min: stats::min(values),
+            max: stats::max(values),
+            median: stats::median(values),
+            q25: stats::percentile(values, 25.0),
+            q75: stats::percentile(values, 75.0),
+        }
+    }
+}
+
+/// Rank `stats::percentile` reads for `p` out of `count` values
+fn percentile_rank(count: usize, p: f64) -> usize {
+    ((count as f64 * p / 100.0) as usize).min(count - 1)
+}
+
+/// Integer that sorts the way `f64::total_cmp` sorts the value
+fn order_key(value: f64) -> i64 {
+    let bits = value.to_bits() as i64;
+    bits ^ (((bits >> 63) as u64) >> 1) as i64
+}
+
+fn from_order_key(key: i64) -> f64 {
+    f64::from_bits((key ^ (((key >> 63) as u64) >> 1) as i64) as u64)
+}
@@ -0,0 +1,122 @@
+// Summary statistics shared by the feature calculators. Every function returns
+// 0.0 for an empty slice, so a window without enough events yields a zero
+// feature rather than NaN or infinity.
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Population variance; 0.0 for fewer than two values
+pub fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+}
+
+/// Population standard deviation; 0.0 for fewer than two values
+pub fn std_dev(values: &[f64]) -> f64 {
+    variance(values).sqrt()
+}
+
+pub fn min(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().copied().fold(f64::INFINITY, f64::min)
+}
+
+pub fn max(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// Middle value, or the mean of the two middle values for an even count
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let sorted = sorted(values);
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    }
+}
+
+/// Value at rank `len * p / 100` of the sorted values (`p` in 0..=100, clamped)
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let sorted = sorted(values);
+    let rank = (sorted.len() as f64 * p.clamp(0.0, 100.0) / 100.0) as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Values inside Tukey's fences, `[q25 - 1.5 IQR, q75 + 1.5 IQR]`; fewer than
+/// four values are returned as-is, too few to call any of them an outlier
+pub fn iqr_filter(values: &[f64]) -> Vec<f64> {
+    if values.len() < 4 {
+        return values.to_vec();
+    }
+    let (q25, q75) = (percentile(values, 25.0), percentile(values, 75.0));
+    let fence = 1.5 * (q75 - q25);
+    values
+        .iter()
+        .copied()
+        .filter(|v| *v >= q25 - fence && *v <= q75 + fence)
+        .collect()
+}
+
+/// Values within three scaled median absolute deviations of the median; fewer
+/// than four values, or a zero MAD, are returned as-is
+pub fn mad_filter(values: &[f64]) -> Vec<f64> {
+    if values.len() < 4 {
+        return values.to_vec();
+    }
+    let center = median(values);
+    let deviations: Vec<f64> = values.iter().map(|v| (v - center).abs()).collect();
+    // 1.4826 scales the MAD to a standard deviation for normally distributed data
+    let mad = 1.4826 * median(&deviations);
+    if mad == 0.0 {
+        return values.to_vec();
+    }
+    values
+        .iter()
+        .copied()
+        .filter(|v| (v - center).abs() <= 3.0 * mad)
+        .collect()
+}
+
+fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted
+}
+
+/// Events per second over the span the timestamps actually cover, rather than
+/// the nominal window; 0.0 when they span no time
+pub fn event_rate(timestamps: &[f64]) -> f64 {
+    let span = max(timestamps) - min(timestamps);
+    if span > 0.0 {
+        (timestamps.len() - 1) as f64 / span
+    } else {
+        0.0
+    }
+}
+
+/// Fraction of a `window_seconds` window between the first and last timestamp
+pub fn coverage(timestamps: &[f64], window_seconds: f64) -> f64 {
+    if window_seconds <= 0.0 {
+        return 0.0;
+    }
+    ((max(timestamps) - min(timestamps)) / window_seconds).clamp(0.0, 1.0)
+}
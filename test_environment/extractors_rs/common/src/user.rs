@@ -0,0 +1,140 @@
+// Per-user state for hosts several people use at once. Collectors stamp each event
+// with their `USER_ID`, and extractors keep a separate session (event buffer,
+// baseline, app profile) for every user id they see, so one user's behaviour never
+// leaks into another's profile. Without user ids there's a single session, as before.
+
+use crate::event_source::WindowedExtractor;
+use crate::models::RawEvent;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Field events and feature records name their user in
+pub const USER_ID_FIELD: &str = "user_id";
+
+/// Longest user id accepted
+const MAX_USER_ID_LEN: usize = 64;
+
+/// Whether `user_id` is safe to key state and name files by: 1 to 64 ASCII letters,
+/// digits, `.`, `_`, `-` or `@`, not starting with `.`
+pub fn is_valid_user_id(user_id: &str) -> bool {
+    (1..=MAX_USER_ID_LEN).contains(&user_id.len())
+        && !user_id.starts_with('.')
+        && user_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@'))
+}
+
+/// `path` for one user: `keystroke_baseline.json` becomes `keystroke_baseline.alice.json`;
+/// unchanged without a user, so single-user state stays where it always was
+pub fn user_path(path: &Path, user: Option<&str>) -> PathBuf {
+    let Some(user) = user else {
+        return path.to_path_buf();
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, user, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, user),
+    };
+    path.with_file_name(name)
+}
+
+/// Record `user` as the user a feature record describes; records of unattributed
+/// sessions are left as they are
+pub fn tag_user(features: &mut serde_json::Value, user: Option<&str>) {
+    if let (Some(user), Some(fields)) = (user, features.as_object_mut()) {
+        fields.insert(USER_ID_FIELD.to_string(), user.into());
+    }
+}
+
+/// Builds the session for the user it's given
+type SessionFactory<S> = Box<dyn FnMut(Option<&str>) -> S + Send>;
+
+/// One session per user id, created the first time the user is seen
+///
+/// Events are attributed to their own `user_id`, or to this host's user when they
+/// don't carry one; `None` is the session of events nobody claimed.
+pub struct UserSessions<S> {
+    default_user: Option<String>,
+    sessions: BTreeMap<Option<String>, S>,
+    new_session: SessionFactory<S>,
+}
+
+impl<S> UserSessions<S> {
+    /// Sessions built by `new_session` for the user they're for, with `default_user`
+    /// (normally `Config::user_id`) owning unattributed events
+    pub fn new(
+        default_user: Option<String>,
+        new_session: impl FnMut(Option<&str>) -> S + Send + 'static,
+    ) -> Self {
+        UserSessions {
+            default_user,
+            sessions: BTreeMap::new(),
+            new_session: Box::new(new_session),
+        }
+    }
+
+    /// The user unattributed events belong to
+    pub fn default_user(&self) -> Option<&str> {
+        self.default_user.as_deref()
+    }
+
+    /// The user an event belongs to
+    pub fn user_of(&self, event: &RawEvent) -> Option<String> {
+        event.user_id.clone().or_else(|| self.default_user.clone())
+    }
+
+    /// `user`'s session, created if this is the first time they're seen
+    pub fn session(&mut self, user: Option<&str>) -> &mut S {
+        let key = user.map(str::to_string);
+        if !self.sessions.contains_key(&key) {
+            let session = (self.new_session)(user);
+            self.sessions.insert(key.clone(), session);
+        }
+        self.sessions
+            .get_mut(&key)
+            .expect("session was just inserted")
+    }
+
+    /// Every session with its user, in user id order (the unattributed one first)
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Option<&str>, &mut S)> {
+        self.sessions
+            .iter_mut()
+            .map(|(user, session)| (user.as_deref(), session))
+    }
+
+    /// Number of users with a session
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Drop the sessions `keep` rejects, except the default user's
+    pub fn retain(&mut self, mut keep: impl FnMut(Option<&str>, &mut S) -> bool) {
+        let default_user = self.default_user.clone();
+        self.sessions
+            .retain(|user, session| *user == default_user || keep(user.as_deref(), session));
+    }
+}
+
+impl<E: WindowedExtractor> WindowedExtractor for UserSessions<E> {
+    fn add_raw_event(&mut self, event: RawEvent) {
+        let user = self.user_of(&event);
+        self.session(user.as_deref()).add_raw_event(event);
+    }
+
+    /// Every user's windows, each tagged with its `user_id`
+    fn extract_windows_at(&mut self, now: f64) -> Vec<serde_json::Value> {
+        self.iter_mut()
+            .flat_map(|(user, extractor)| {
+                let mut windows = extractor.extract_windows_at(now);
+                for window in &mut windows {
+                    tag_user(window, user);
+                }
+                windows
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,243 @@
+use crate::compression::encode_payload;
+use crate::event_bus::BlockingEventBus;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// How often a drain persists its progress; a crash replays at most this many events
+const CURSOR_SYNC_EVERY: usize = 100;
+
+/// Once full, the log is compacted down to this fraction of its limit so the next
+/// few appends don't each trigger another rewrite
+const COMPACT_TARGET: f64 = 0.75;
+
+/// Append-only on-disk log of collector events awaiting delivery
+///
+/// Each event is one JSON line. A sidecar `<path>.cursor` file records the byte
+/// offset of the first entry not yet acknowledged, so entries left over from a
+/// crash or a Redis outage are sent again on the next start. Delivery is
+/// at-least-once: a crash mid-drain can resend up to `CURSOR_SYNC_EVERY` events.
+#[derive(Debug)]
+pub struct EventWal {
+    path: PathBuf,
+    cursor_path: PathBuf,
+    file: File,
+    len: u64,
+    cursor: u64,
+    pending: usize,
+    max_bytes: u64,
+}
+
+impl EventWal {
+    /// Open or create the log at `path`, holding at most `max_bytes` of events
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let cursor_path = PathBuf::from(format!("{}.cursor", path.display()));
+
+        let file = private_file().create(true).read(true).append(true).open(&path)?;
+        let len = Self::repair_torn_tail(&file)?;
+        let cursor = fs::read_to_string(&cursor_path)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .filter(|&c| c <= len)
+            .unwrap_or(0);
+
+        let mut wal = EventWal {
+            path,
+            cursor_path,
+            file,
+            len,
+            cursor,
+            pending: 0,
+            max_bytes,
+        };
+        wal.pending = wal.read_pending()?.len();
+        Ok(wal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries written but not yet acknowledged
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Record an event; returns how many of the oldest unsent events were dropped to make room
+    pub fn append(&mut self, event: &str) -> io::Result<usize> {
+        let entry_len = event.len() as u64 + 1;
+        let mut dropped = 0;
+        if self.len + entry_len > self.max_bytes {
+            dropped = self.compact(entry_len)?;
+        }
+
+        let mut line = Vec::with_capacity(event.len() + 1);
+        line.extend_from_slice(event.as_bytes());
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.len += entry_len;
+        self.pending += 1;
+        Ok(dropped)
+    }
+
+    /// Hand unsent entries to `send` in order, stopping at the first failure
+    ///
+    /// Acknowledged entries are skipped on the next drain or restart; once everything
+    /// has been sent the log is truncated. Returns how many entries were sent.
+    pub fn drain<E, F>(&mut self, mut send: F) -> Result<usize, E>
+    where
+        E: From<io::Error>,
+        F: FnMut(&str) -> Result<(), E>,
+    {
+        if self.pending == 0 {
+            return Ok(0);
+        }
+
+        let mut sent = 0;
+        let mut result = Ok(());
+        for entry in self.read_pending()? {
+            if let Err(e) = send(&entry) {
+                result = Err(e);
+                break;
+            }
+            self.cursor += entry.len() as u64 + 1;
+            self.pending -= 1;
+            sent += 1;
+            if sent % CURSOR_SYNC_EVERY == 0 {
+                self.save_cursor()?;
+            }
+        }
+
+        if self.pending == 0 {
+            self.truncate()?;
+        } else {
+            self.save_cursor()?;
+        }
+        result.map(|_| sent)
+    }
+
+    /// Unsent entries, oldest first
+    fn read_pending(&self) -> io::Result<Vec<String>> {
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(self.cursor))?;
+        reader.lines().collect()
+    }
+
+    /// Drop everything already sent and then the oldest unsent entries until there's
+    /// room for `incoming` bytes; returns how many unsent entries were lost
+    fn compact(&mut self, incoming: u64) -> io::Result<usize> {
+        let target = ((self.max_bytes as f64 * COMPACT_TARGET) as u64).saturating_sub(incoming);
+        let entries = self.read_pending()?;
+        let mut size: u64 = entries.iter().map(|e| e.len() as u64 + 1).sum();
+        let mut dropped = 0;
+        for entry in &entries {
+            if size <= target {
+                break;
+            }
+            size -= entry.len() as u64 + 1;
+            dropped += 1;
+        }
+
+        let tmp_path = PathBuf::from(format!("{}.tmp", self.path.display()));
+        {
+            // A temp file left by a crash keeps its old mode; start from a new one
+            let _ = fs::remove_file(&tmp_path);
+            let file = private_file().write(true).create_new(true).open(&tmp_path)?;
+            let mut tmp = io::BufWriter::new(file);
+            for entry in &entries[dropped..] {
+                writeln!(tmp, "{}", entry)?;
+            }
+            tmp.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        self.len = size;
+        self.cursor = 0;
+        self.pending = entries.len() - dropped;
+        self.save_cursor()?;
+        Ok(dropped)
+    }
+
+    /// Empty the log once every entry has been acknowledged
+    fn truncate(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        self.cursor = 0;
+        self.save_cursor()
+    }
+
+    fn save_cursor(&self) -> io::Result<()> {
+        private_file()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.cursor_path)?
+            .write_all(self.cursor.to_string().as_bytes())
+    }
+
+    /// Cut off a partial last line left by a crash mid-append; returns the repaired length
+    fn repair_torn_tail(file: &File) -> io::Result<u64> {
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(0))?;
+        let mut complete = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            complete += read as u64;
+        }
+        if complete < len {
+            file.set_len(complete)?;
+        }
+        Ok(complete)
+    }
+}
+
+/// Options for the log's own files, readable by this user alone on Unix since they
+/// hold raw keystrokes; the mode applies to files this creates
+fn private_file() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+}
+
+/// Log an event to the WAL before it's sent, so it survives a Redis outage or a
+/// crash; a full or unwritable log is reported under `label`, e.g. "Mouse Collector"
+pub fn record_event(wal: &mut EventWal, event: &str, label: &str) {
+    match wal.append(event) {
+        Ok(0) => {}
+        Ok(dropped) => eprintln!(
+            "[{}] WAL full; dropped {} oldest undelivered events",
+            label, dropped
+        ),
+        Err(e) => eprintln!("[{}] Failed to write WAL: {:?}", label, e),
+    }
+}
+
+/// Send everything the WAL holds to `channel`, stopping at the first failure so
+/// it's retried later
+pub fn drain_wal(
+    wal: &mut EventWal,
+    bus: &mut dyn BlockingEventBus,
+    channel: &str,
+    compress: bool,
+    label: &str,
+) {
+    if wal.pending() == 0 || !bus.ready() {
+        return;
+    }
+    let sent = wal.drain(|json| bus.publish(channel, &encode_payload(json, compress)));
+    if let Err(e) = sent {
+        eprintln!("[{}] Event bus unavailable, keeping events in the WAL: {:#}", label, e);
+    }
+}
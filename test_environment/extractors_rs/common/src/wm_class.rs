@@ -0,0 +1,29 @@
+// Parsing of the X11 `WM_CLASS` window property, which names the application a
+// window belongs to. ICCCM specifies two null-terminated strings, the instance
+// name and then the class name (e.g. `Navigator\0firefox\0`), but real clients
+// send one string, trailing padding nulls or extra parts, and although the
+// property is typed Latin-1, many modern toolkits write UTF-8 into it.
+
+/// `(app_name, window_class)` from a raw `WM_CLASS` value, or `None` if it names nothing
+///
+/// The app name is the instance part and the window class the class part; a
+/// single part is used for both, and parts after the second are ignored. Each
+/// part is decoded as UTF-8 when it's valid UTF-8, and as Latin-1 otherwise, so
+/// no byte is ever lost to a replacement character.
+pub fn parse_wm_class(value: &[u8]) -> Option<(String, String)> {
+    let mut parts = value
+        .split(|&b| b == 0)
+        .map(decode_part)
+        .filter(|part| !part.is_empty());
+    let app_name = parts.next()?;
+    let window_class = parts.next().unwrap_or_else(|| app_name.clone());
+    Some((app_name, window_class))
+}
+
+fn decode_part(bytes: &[u8]) -> String {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    };
+    text.trim().to_string()
+}
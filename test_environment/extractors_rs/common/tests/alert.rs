@@ -0,0 +1,119 @@
+// Feeds anomaly scores through an AlertSink pointed at a local webhook and checks
+// one alert arrives per sustained anomaly rather than one per anomalous window:
+//
+//     cargo test -p common --test alert
+
+use common::{AlertDebouncer, AlertSink};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Accepts every POST, keeping the JSON bodies; returns its URL
+async fn webhook(received: Arc<Mutex<Vec<serde_json::Value>>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let received = received.clone();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut content_length = 0;
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::from_slice(&body).unwrap());
+                    stream
+                        .get_mut()
+                        .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                        .await
+                        .unwrap();
+                }
+            });
+        }
+    });
+    url
+}
+
+fn scores(anomaly_score: f64) -> serde_json::Value {
+    json!({
+        "anomaly_score": anomaly_score,
+        "z_scores": {"dwell_mean": anomaly_score, "flight_mean": -2.0 * anomaly_score, "total_keys": 0.5},
+        "baseline_frozen": false,
+    })
+}
+
+#[tokio::test]
+async fn fires_once_per_sustained_anomaly() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let url = webhook(received.clone()).await;
+    let mut sink = AlertSink::new(&url, "keystroke", AlertDebouncer::new(3.0, 3, 0.0));
+
+    // A two-window blip, then two sustained anomalies split by one normal window
+    let windows = [
+        1.0, 5.0, 5.0, 1.0, 4.0, 5.0, 6.0, 7.0, 8.0, 1.0, 9.0, 9.0, 9.0, 9.0,
+    ];
+    let mut sent = Vec::new();
+    for (i, score) in windows.into_iter().enumerate() {
+        let timestamp = 1_700_000_000.0 + 5.0 * i as f64;
+        if let Some(alert) = sink.observe(&scores(score), timestamp).await.unwrap() {
+            sent.push(alert);
+        }
+    }
+
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].timestamp, 1_700_000_030.0);
+    assert_eq!(sent[0].anomaly_score, 6.0);
+    assert_eq!(sent[1].timestamp, 1_700_000_060.0);
+    assert_eq!(sent[1].consecutive_windows, 3);
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0]["modality"], "keystroke");
+    assert_eq!(received[0]["anomaly_score"], 6.0);
+    assert_eq!(received[0]["timestamp"], 1_700_000_030.0);
+    let top: Vec<&str> = received[0]["top_features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["feature"].as_str().unwrap())
+        .collect();
+    assert_eq!(top, ["flight_mean", "dwell_mean", "total_keys"]);
+    assert_eq!(received[0]["top_features"][0]["z_score"], -12.0);
+}
+
+#[test]
+fn cooldown_holds_back_a_quick_second_alert() {
+    let mut debouncer = AlertDebouncer::new(3.0, 2, 60.0);
+    let fired: Vec<bool> = [
+        (5.0, 0.0),
+        (5.0, 5.0),
+        (1.0, 10.0),
+        (5.0, 15.0),
+        (5.0, 20.0),
+        (5.0, 70.0),
+    ]
+    .into_iter()
+    .map(|(score, timestamp)| debouncer.observe(score, timestamp))
+    .collect();
+    // The second run is held back until the cooldown has passed, then alerted once
+    assert_eq!(fired, [false, true, false, false, false, true]);
+}
@@ -0,0 +1,46 @@
+// Checks the app allow/deny lists against app names and window classes as the
+// collectors report them:
+//
+//     cargo test -p common --test app_filter
+
+use common::AppFilter;
+
+fn patterns(list: &[&str]) -> Vec<String> {
+    list.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn denylist_matches_substrings_and_globs() {
+    let filter = AppFilter::new(&[], &patterns(&["KeePass", "*bank*", "1password?"]));
+    assert!(!filter.is_open());
+    // Substring, case-insensitive, on the name or the window class
+    assert!(!filter.allows("keepassxc", Some("KeePassXC")));
+    assert!(!filter.allows("Navigator", Some("keepass-web")));
+    // Globs match the whole name
+    assert!(!filter.allows("MyBankingApp", None));
+    assert!(!filter.allows("1password8", Some("1Password")));
+    assert!(filter.allows("1password", None));
+    assert!(filter.allows("firefox", Some("Navigator")));
+}
+
+#[test]
+fn allowlist_limits_tracking_but_denylist_wins() {
+    let filter = AppFilter::new(
+        &patterns(&["code", "firefox", "jetbrains-*"]),
+        &patterns(&["private"]),
+    );
+    assert!(filter.allows("code", Some("Code")));
+    assert!(filter.allows("jetbrains-idea", Some("jetbrains-idea")));
+    // Allowed through its window class
+    assert!(filter.allows("Navigator", Some("firefox")));
+    assert!(!filter.allows("slack", Some("Slack")));
+    assert!(!filter.allows("firefox-private", Some("firefox")));
+}
+
+#[test]
+fn empty_lists_allow_everything() {
+    let filter = AppFilter::new(&patterns(&[" ", ""]), &[]);
+    assert!(filter.is_open());
+    assert!(filter.allows("anything", None));
+    assert!(filter.allows("日本語エディタ", Some("Éditeur")));
+}
@@ -0,0 +1,54 @@
+// Floods a bounded relay whose consumer isn't reading and checks each overflow
+// policy keeps the events it promises to:
+//
+//     cargo test -p common --test backpressure
+
+use common::{bounded_relay, OverflowPolicy};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const CAPACITY: usize = 10;
+const FLOOD: u32 = 100;
+
+/// Push `FLOOD` numbered events through a relay of `CAPACITY`, only reading once
+/// they've all arrived; returns what the consumer got and the drop count
+async fn flood(policy: OverflowPolicy) -> (Vec<u32>, u64) {
+    let (tx, upstream) = mpsc::channel(FLOOD as usize);
+    let (mut rx, dropped) = bounded_relay(upstream, CAPACITY, policy, "test".to_string());
+    for i in 0..FLOOD {
+        tx.send(i).await.unwrap();
+    }
+    drop(tx);
+    // Let the relay take in everything it's going to before the consumer reads
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut received = Vec::new();
+    while let Some(i) = rx.recv().await {
+        received.push(i);
+    }
+    (received, dropped.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+#[tokio::test]
+async fn drop_oldest_keeps_the_latest_events() {
+    let (received, dropped) = flood(OverflowPolicy::DropOldest).await;
+    // Event 0 was already handed over before the consumer stalled
+    let expected: Vec<u32> = std::iter::once(0).chain(FLOOD - CAPACITY as u32..FLOOD).collect();
+    assert_eq!(received, expected);
+    assert_eq!(dropped, (FLOOD - 1) as u64 - CAPACITY as u64);
+}
+
+#[tokio::test]
+async fn drop_newest_keeps_the_earliest_events() {
+    let (received, dropped) = flood(OverflowPolicy::DropNewest).await;
+    let expected: Vec<u32> = (0..=CAPACITY as u32).collect();
+    assert_eq!(received, expected);
+    assert_eq!(dropped, (FLOOD - 1) as u64 - CAPACITY as u64);
+}
+
+#[tokio::test]
+async fn block_loses_nothing() {
+    let (received, dropped) = flood(OverflowPolicy::Block).await;
+    assert_eq!(received, (0..FLOOD).collect::<Vec<_>>());
+    assert_eq!(dropped, 0);
+}
@@ -0,0 +1,75 @@
+// Welford's running mean and variance against a two-pass batch computation, at
+// every prefix of a long, offset run of samples and on the small edge cases:
+//
+//     cargo test -p common --test baseline
+
+use common::RunningStats;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Mean and sample variance computed in two passes over all of `values`
+fn batch(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = if values.len() < 2 {
+        0.0
+    } else {
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+    };
+    (mean, variance)
+}
+
+fn assert_close(incremental: f64, batch: f64, what: &str) {
+    let tolerance = batch.abs() * 1e-9 + 1e-9;
+    assert!(
+        (incremental - batch).abs() <= tolerance,
+        "{what}: incremental {incremental} vs batch {batch}"
+    );
+}
+
+#[test]
+fn incremental_matches_batch_at_every_prefix() {
+    // A large offset with a small spread is where the naive sum-of-squares
+    // formula loses its precision
+    let mut rng = StdRng::seed_from_u64(11);
+    let values: Vec<f64> = (0..2_000)
+        .map(|_| 1e6 + rng.gen_range(40.0..400.0))
+        .collect();
+
+    let mut stats = RunningStats::default();
+    for (i, &value) in values.iter().enumerate() {
+        stats.update(value);
+        let (mean, variance) = batch(&values[..=i]);
+        assert_eq!(stats.count(), i as u64 + 1);
+        assert_close(stats.mean(), mean, &format!("mean after {}", i + 1));
+        assert_close(
+            stats.variance(),
+            variance,
+            &format!("variance after {}", i + 1),
+        );
+    }
+    assert_close(stats.std_dev(), batch(&values).1.sqrt(), "std_dev");
+}
+
+#[test]
+fn small_and_constant_inputs() {
+    let mut stats = RunningStats::default();
+    assert_eq!(
+        (stats.count(), stats.mean(), stats.variance()),
+        (0, 0.0, 0.0)
+    );
+
+    stats.update(120.0);
+    assert_eq!((stats.mean(), stats.variance()), (120.0, 0.0));
+    assert_eq!(stats.usable_std_dev(), None);
+
+    stats.update(80.0);
+    assert_eq!((stats.mean(), stats.variance()), (100.0, 800.0));
+
+    let mut constant = RunningStats::default();
+    for _ in 0..10 {
+        constant.update(42.0);
+    }
+    assert_eq!((constant.mean(), constant.variance()), (42.0, 0.0));
+    // A baseline that never varies can't score anything
+    assert_eq!(constant.z_score(1_000.0), 0.0);
+}
@@ -0,0 +1,229 @@
+// Circuit breakers opening on repeated failures and probing for recovery, on their
+// own and around a failing sink and an unreachable InfluxDB:
+//
+//     cargo test -p common --test circuit_breaker
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{
+    BreakerSink, BreakerState, CircuitBreaker, Config, FeatureSink, InfluxClient, Metrics,
+    PublishSkipped,
+};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const BACKOFF: Duration = Duration::from_secs(1);
+
+fn breaker(threshold: u32) -> CircuitBreaker {
+    CircuitBreaker::new("test backend", threshold, BACKOFF, Duration::from_secs(5))
+}
+
+/// A breaker that has just opened at `t0` after three failures
+fn opened(t0: Instant) -> CircuitBreaker {
+    let mut breaker = breaker(3);
+    for _ in 0..3 {
+        assert!(breaker.allow_at(t0));
+        breaker.record_failure_at(t0);
+    }
+    breaker
+}
+
+#[test]
+fn opens_after_the_failure_threshold() {
+    let t0 = Instant::now();
+    let mut breaker = breaker(3);
+    for _ in 0..2 {
+        assert!(breaker.allow_at(t0));
+        breaker.record_failure_at(t0);
+    }
+    assert_eq!(breaker.state(), BreakerState::Closed);
+
+    breaker.record_failure_at(t0);
+    assert_eq!(breaker.state(), BreakerState::Open);
+    assert!(!breaker.allow_at(t0 + BACKOFF / 2));
+}
+
+#[test]
+fn success_resets_the_failure_count() {
+    let t0 = Instant::now();
+    let mut breaker = breaker(3);
+    breaker.record_failure_at(t0);
+    breaker.record_failure_at(t0);
+    breaker.record_success();
+    breaker.record_failure_at(t0);
+    breaker.record_failure_at(t0);
+    assert_eq!(breaker.state(), BreakerState::Closed);
+}
+
+#[test]
+fn half_opens_for_one_probe_after_the_backoff() {
+    let t0 = Instant::now();
+    let mut breaker = opened(t0);
+    assert!(!breaker.allow_at(t0 + BACKOFF - Duration::from_millis(1)));
+
+    assert!(breaker.allow_at(t0 + BACKOFF));
+    assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    // Only the probe goes through until its result is in
+    assert!(!breaker.allow_at(t0 + BACKOFF));
+
+    breaker.record_success();
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    assert!(breaker.allow_at(t0 + BACKOFF));
+}
+
+#[test]
+fn failed_probes_back_off_further_up_to_the_cap() {
+    let t0 = Instant::now();
+    let mut breaker = opened(t0);
+    let mut now = t0;
+    let mut backoffs = Vec::new();
+    for _ in 0..5 {
+        now += breaker.backoff();
+        assert!(breaker.allow_at(now));
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        backoffs.push(breaker.backoff().as_secs());
+    }
+    assert_eq!(backoffs, [2, 4, 5, 5, 5]);
+    assert!(!breaker.allow_at(now + Duration::from_secs(4)));
+}
+
+#[test]
+fn zero_threshold_never_opens() {
+    let t0 = Instant::now();
+    let mut breaker = breaker(0);
+    for _ in 0..100 {
+        breaker.record_failure_at(t0);
+    }
+    assert!(breaker.allow_at(t0));
+}
+
+/// A sink that always fails, counting the calls that reach it
+struct DownSink {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl FeatureSink for DownSink {
+    async fn publish(&self, _channel: &str, _value: &serde_json::Value) -> Result<()> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        anyhow::bail!("connection refused")
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        anyhow::bail!("connection refused")
+    }
+}
+
+fn down_sink(calls: &Arc<AtomicUsize>, metrics: &Arc<Metrics>) -> BreakerSink {
+    down_sink_with_backoff(calls, metrics, Duration::from_secs(60))
+}
+
+fn down_sink_with_backoff(
+    calls: &Arc<AtomicUsize>,
+    metrics: &Arc<Metrics>,
+    backoff: Duration,
+) -> BreakerSink {
+    BreakerSink::new(
+        Box::new(DownSink {
+            calls: calls.clone(),
+        }),
+        CircuitBreaker::new("sink", 2, backoff, backoff),
+        metrics.clone(),
+    )
+}
+
+#[tokio::test]
+async fn open_sink_breaker_stops_calling_the_sink() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let metrics = Arc::new(Metrics::new());
+    let sink = down_sink(&calls, &metrics);
+
+    let mut errors = 0;
+    for _ in 0..10 {
+        match sink
+            .publish("seclyzer:features:keystroke", &json!({}))
+            .await
+        {
+            Ok(()) => metrics.record_publish(),
+            Err(e) if e.is::<PublishSkipped>() => {}
+            Err(_) => errors += 1,
+        }
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    assert_eq!(errors, 2);
+    // Nothing was delivered, so nothing counts as published
+    assert_eq!(metrics.features_published(), 0);
+    assert_eq!(metrics.last_publish_timestamp(), 0.0);
+    assert_eq!(sink.state(), BreakerState::Open);
+    assert_eq!(metrics.skipped_publishes(), 8);
+    assert!(metrics
+        .render(&Default::default(), &Default::default())
+        .contains("seclyzer_skipped_publishes_total 8"));
+}
+
+#[tokio::test]
+async fn failed_sink_probes_come_back_skipped() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let metrics = Arc::new(Metrics::new());
+    // No backoff, so every publish after the breaker opens goes out as a probe
+    let sink = down_sink_with_backoff(&calls, &metrics, Duration::ZERO);
+
+    for _ in 0..2 {
+        let error = sink
+            .publish("seclyzer:features:keystroke", &json!({}))
+            .await;
+        assert!(!error.unwrap_err().is::<PublishSkipped>());
+    }
+    let error = sink
+        .publish("seclyzer:features:keystroke", &json!({}))
+        .await;
+    assert!(error.unwrap_err().is::<PublishSkipped>());
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.skipped_publishes(), 1);
+}
+
+#[tokio::test]
+async fn sink_flushes_are_skipped_while_open() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let metrics = Arc::new(Metrics::new());
+    let sink = down_sink(&calls, &metrics);
+
+    assert!(sink.flush().await.is_err());
+    assert!(sink.flush().await.is_err());
+    assert_eq!(sink.state(), BreakerState::Open);
+    assert!(sink.flush().await.is_ok());
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    // Only dropped messages count as skipped publishes
+    assert_eq!(metrics.skipped_publishes(), 0);
+}
+
+#[tokio::test]
+async fn influx_flushes_are_skipped_while_open() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let config = Config {
+        influx_url: url,
+        influx_fail_fast: false,
+        influx_max_retries: 0,
+        circuit_failure_threshold: 2,
+        circuit_backoff_ms: 60_000,
+        ..Config::default()
+    };
+    let influx = InfluxClient::new(&config).await.unwrap();
+    influx
+        .queue_line("keystroke_features dwell_mean=100 1".to_string())
+        .await
+        .unwrap();
+
+    assert!(influx.flush().await.is_err());
+    assert!(influx.flush().await.is_err());
+    assert_eq!(influx.breaker_state(), BreakerState::Open);
+    // Skipped without a request, and the point stays queued for when it recovers
+    assert!(influx.flush().await.is_ok());
+    assert_eq!(influx.metrics().failed_writes(), 2);
+}
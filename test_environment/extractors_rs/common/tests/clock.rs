@@ -0,0 +1,63 @@
+// Steps the wall clock back in the middle of a run of key presses and checks the
+// event clock still gives them non-negative flight times:
+//
+//     cargo test -p common --test clock
+
+use common::EventClock;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Key presses 150 ms apart
+const PRESS_OFFSETS_MS: [u64; 6] = [0, 150, 300, 450, 600, 750];
+/// How far NTP steps the wall clock back, just before the fourth press
+const STEP_BACK: Duration = Duration::from_secs(2);
+
+/// Milliseconds between consecutive presses, as the keystroke extractor computes them
+fn flight_times(timestamps_us: &[u128]) -> Vec<f64> {
+    timestamps_us
+        .windows(2)
+        .map(|pair| (pair[1] as f64 - pair[0] as f64) / 1000.0)
+        .collect()
+}
+
+#[test]
+fn backward_clock_step_keeps_flight_times_non_negative() {
+    let start = Instant::now();
+    let wall = SystemTime::now();
+    let clock = EventClock::anchored(wall, start);
+
+    // What reading the wall clock at each press would have given
+    let wall_readings: Vec<u128> = PRESS_OFFSETS_MS
+        .iter()
+        .enumerate()
+        .map(|(i, &ms)| {
+            let mut at = wall + Duration::from_millis(ms);
+            if i >= 3 {
+                at -= STEP_BACK;
+            }
+            at.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_micros()
+        })
+        .collect();
+    assert!(flight_times(&wall_readings).iter().any(|&flight| flight < 0.0));
+
+    // The monotonic clock doesn't see the step
+    let readings: Vec<u128> = PRESS_OFFSETS_MS
+        .iter()
+        .map(|&ms| clock.micros_at(start + Duration::from_millis(ms)))
+        .collect();
+    assert_eq!(flight_times(&readings), vec![150.0; PRESS_OFFSETS_MS.len() - 1]);
+    assert_eq!(
+        readings[0],
+        wall.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_micros()
+    );
+}
+
+#[test]
+fn readings_never_decrease() {
+    let clock = EventClock::new();
+    let mut last = clock.now_micros();
+    for _ in 0..1000 {
+        let now = clock.now_micros();
+        assert!(now >= last);
+        last = now;
+    }
+}
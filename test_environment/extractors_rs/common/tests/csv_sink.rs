@@ -0,0 +1,117 @@
+// Publishes feature records through the CSV sink and reads the files back:
+//
+//     cargo test -p common --test csv_sink
+
+use common::{CsvSink, FeatureSink};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+const CHANNEL: &str = "seclyzer:features:keystroke";
+
+/// Split one CSV line into fields, undoing RFC 4180 quoting
+fn parse_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// The channel's CSV files in the order they were written
+fn csv_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+        .collect();
+    // Numbered last, so sort on that rather than the timestamp
+    files.sort_by_key(|path| {
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        stem.rsplit('-').next().unwrap().parse::<usize>().unwrap()
+    });
+    files
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("seclyzer-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[tokio::test]
+async fn rows_parse_back_under_one_header() {
+    let dir = scratch_dir("csv-sink");
+    let sink = CsvSink::new(dir.clone(), 100);
+    sink.declare_columns(CHANNEL, &["dwell_mean", "odd,\"name\""]);
+
+    for i in 0..5 {
+        let record = json!({
+            "window_start": 100.0 + i as f64,
+            "window_end": 130.0 + i as f64,
+            "dwell_mean": 80.5 + i as f64,
+            "active_app": "ignored",
+        });
+        sink.publish(CHANNEL, &record).await.unwrap();
+    }
+    // Scores aren't declared, so they're left out
+    sink.publish("seclyzer:features:keystroke:scores", &json!({"score": 1.0}))
+        .await
+        .unwrap();
+    sink.flush().await.unwrap();
+
+    let files = csv_files(&dir);
+    assert_eq!(files.len(), 1);
+    let text = std::fs::read_to_string(&files[0]).unwrap();
+    let rows: Vec<Vec<String>> = text.lines().map(parse_line).collect();
+
+    assert_eq!(
+        rows[0],
+        ["timestamp", "modality", "window_start", "window_end", "dwell_mean", "odd,\"name\""]
+    );
+    assert_eq!(rows.len(), 6);
+    for (i, row) in rows[1..].iter().enumerate() {
+        assert_eq!(row.len(), rows[0].len());
+        assert!(chrono::DateTime::parse_from_rfc3339(&row[0]).is_ok(), "{}", row[0]);
+        assert_eq!(row[1], "keystroke");
+        assert_eq!(row[4].parse::<f64>().unwrap(), 80.5 + i as f64);
+        // Missing from the record, so empty
+        assert_eq!(row[5], "");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rotation_repeats_the_header() {
+    let dir = scratch_dir("csv-rotate");
+    let sink = CsvSink::new(dir.clone(), 2);
+    sink.declare_columns(CHANNEL, &["dwell_mean"]);
+
+    for i in 0..5 {
+        let record = json!({"window_start": 0.0, "window_end": 30.0, "dwell_mean": i as f64});
+        sink.publish(CHANNEL, &record).await.unwrap();
+    }
+
+    let files = csv_files(&dir);
+    let row_counts: Vec<usize> = files
+        .iter()
+        .map(|path| {
+            let text = std::fs::read_to_string(path).unwrap();
+            assert!(text.starts_with("timestamp,modality,window_start,window_end,dwell_mean\n"));
+            text.lines().count() - 1
+        })
+        .collect();
+    assert_eq!(row_counts, [2, 2, 1]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,108 @@
+// Delivers a stream with redelivered copies mixed in, as an at-least-once transport
+// would, and checks the subscriber passes each event once:
+//
+//     cargo test -p common --test dedup
+
+use async_trait::async_trait;
+use common::{BusEventSource, EventBus, EventDeduplicator, EventSource, RawEvent};
+use serde_json::json;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// Hands its subscriber a fixed list of payloads
+struct CannedBus(Mutex<Vec<String>>);
+
+#[async_trait]
+impl EventBus for CannedBus {
+    async fn publish(&self, _topic: &str, _payload: &serde_json::Value) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        _topic: &str,
+        _group: &str,
+    ) -> anyhow::Result<mpsc::Receiver<String>> {
+        let payloads = std::mem::take(&mut *self.0.lock().unwrap());
+        let (tx, rx) = mpsc::channel(payloads.len().max(1));
+        for payload in payloads {
+            tx.send(payload).await.unwrap();
+        }
+        Ok(rx)
+    }
+}
+
+const BASE_TS: u64 = 1_700_000_000_000_000;
+
+fn keystroke(ts: u64, key: &str, event: &str) -> serde_json::Value {
+    json!({"type": "keystroke", "ts": BASE_TS + ts, "key": key, "event": event})
+}
+
+#[tokio::test]
+async fn replayed_duplicates_are_dropped() {
+    let events = [
+        keystroke(0, "KeyA", "press"),
+        keystroke(80_000, "KeyA", "release"),
+        // A press and release at the same instant are different events
+        keystroke(150_000, "KeyB", "press"),
+        keystroke(150_000, "KeyB", "release"),
+        json!({"type": "mouse", "ts": BASE_TS + 150_000, "x": 10.0, "y": 20.0, "event": "move"}),
+        json!({"type": "mouse", "ts": BASE_TS + 150_000, "x": 11.0, "y": 20.0, "event": "move"}),
+    ];
+    let mut payloads: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    // Redelivered after a consumer restart, with the fields in another order
+    payloads.insert(2, events[0].to_string());
+    payloads.push(
+        r#"{"event":"release","key":"KeyA","ts":1700000000080000,"type":"keystroke"}"#.to_string(),
+    );
+    payloads.push(events[4].to_string());
+
+    let bus = CannedBus(Mutex::new(payloads));
+    let mut source = BusEventSource::subscribe(&bus, "seclyzer:events", "test")
+        .await
+        .unwrap()
+        .with_dedup(100);
+    let mut received = Vec::new();
+    while let Some(event) = source.next_event().await {
+        received.push(event);
+    }
+    let expected: Vec<RawEvent> = events
+        .iter()
+        .map(|e| RawEvent::from_json(&e.to_string()).unwrap())
+        .collect();
+    assert_eq!(received.len(), expected.len());
+    for (got, want) in received.iter().zip(&expected) {
+        assert_eq!(common::event_id(got), common::event_id(want));
+    }
+    assert_eq!(source.duplicate_events(), 3);
+}
+
+#[test]
+fn only_the_window_is_remembered() {
+    let event = |ts: u64| RawEvent::from_json(&keystroke(ts, "KeyA", "press").to_string()).unwrap();
+    let mut dedup = EventDeduplicator::new(3);
+    assert!(!dedup.is_duplicate(&event(1)));
+    assert!(dedup.is_duplicate(&event(1)));
+    for ts in 2..=4 {
+        assert!(!dedup.is_duplicate(&event(ts)));
+    }
+    // Event 1 has been pushed out by three newer ones, so it passes again
+    assert!(!dedup.is_duplicate(&event(1)));
+    assert!(dedup.is_duplicate(&event(4)));
+    assert_eq!(dedup.duplicates(), 2);
+}
+
+#[tokio::test]
+async fn zero_window_keeps_everything() {
+    let payload = keystroke(0, "KeyA", "press").to_string();
+    let bus = CannedBus(Mutex::new(vec![payload.clone(), payload]));
+    let mut source = BusEventSource::subscribe(&bus, "seclyzer:events", "test")
+        .await
+        .unwrap()
+        .with_dedup(0);
+    let mut count = 0;
+    while source.next_event().await.is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 2);
+}
@@ -0,0 +1,63 @@
+// Ranks feature changes between two windows against a learned baseline:
+//
+//     cargo test -p common --test feature_diff
+
+use common::{feature_diff, BaselineProfile};
+use serde_json::json;
+
+/// A baseline where `dwell_mean` swings by tens of ms and `error_rate` barely moves
+fn baseline() -> BaselineProfile {
+    let mut baseline = BaselineProfile::new();
+    for i in 0..20 {
+        let wobble = if i % 2 == 0 { 1.0 } else { -1.0 };
+        baseline.update(&json!({
+            "dwell_mean": 100.0 + 40.0 * wobble,
+            "error_rate": 0.05 + 0.01 * wobble,
+            "typing_speed_wpm": 60.0 + 5.0 * wobble,
+        }));
+    }
+    baseline
+}
+
+#[test]
+fn largest_normalized_change_ranks_first() {
+    let a = json!({"dwell_mean": 100.0, "error_rate": 0.05, "typing_speed_wpm": 60.0});
+    // dwell_mean moves most in raw terms, but error_rate moves most for this user
+    let b = json!({"dwell_mean": 130.0, "error_rate": 0.09, "typing_speed_wpm": 55.0});
+
+    let diff = feature_diff(&a, &b, &baseline());
+    let names: Vec<&str> = diff.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["error_rate", "typing_speed_wpm", "dwell_mean"]);
+    let std_dev = baseline().stats("error_rate").unwrap().std_dev();
+    assert!((diff[0].1 - 0.04 / std_dev).abs() < 1e-9);
+    // Contributions keep their sign
+    assert!(diff[1].1 < 0.0);
+}
+
+#[test]
+fn without_a_baseline_raw_changes_rank() {
+    let a = json!({"dwell_mean": 100.0, "error_rate": 0.05});
+    let b = json!({"dwell_mean": 130.0, "error_rate": 0.09});
+
+    let diff = feature_diff(&a, &b, &BaselineProfile::new());
+    assert_eq!(diff[0].0, "dwell_mean");
+    assert!((diff[0].1 - 30.0).abs() < 1e-9);
+}
+
+#[test]
+fn unchanged_missing_and_metadata_fields_are_left_out() {
+    let a = json!({
+        "dwell_mean": 100.0,
+        "flight_mean": 120.0,
+        "window_end": 1_700_000_000.0,
+        "active_app": "code",
+    });
+    let b = json!({
+        "dwell_mean": 100.0,
+        "error_rate": 0.2,
+        "window_end": 1_700_000_005.0,
+        "active_app": "firefox",
+    });
+
+    assert!(feature_diff(&a, &b, &baseline()).is_empty());
+}
@@ -0,0 +1,118 @@
+// Flux queries against a local stand-in for InfluxDB's query API, checking the
+// request sent and how the annotated CSV response is turned into rows:
+//
+//     cargo test -p common --test influx_query
+
+use common::{Config, InfluxClient};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Two tables as InfluxDB returns them: annotations, a header, rows, and a blank
+/// line between tables. The second table's `host` column falls back to its default.
+const RESPONSE: &str = "\
+#datatype,string,long,dateTime:RFC3339,double,string,string\r
+#group,false,false,false,false,true,true\r
+#default,_result,,,,,\r
+,result,table,_time,_value,_field,app\r
+,,0,2024-01-01T00:00:00Z,120.5,dwell_mean,\"Google Chrome\"\r
+,,0,2024-01-01T00:00:05Z,98,dwell_mean,\"a=b,c\"\r
+\r
+#datatype,string,long,dateTime:RFC3339,boolean,string\r
+#group,false,false,false,false,true\r
+#default,_result,,,,laptop\r
+,result,table,_time,_value,host\r
+,,1,2024-01-01T00:00:00Z,true,\r
+,,1,2024-01-01T00:00:05Z,false,desktop\r
+";
+
+/// The request line and body of every request, in order
+type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Answer readiness checks with 200 and queries with `RESPONSE`, one request per
+/// connection
+async fn serve(listener: TcpListener, requests: Requests) {
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (request_line, body) = read_request(&mut stream).await;
+        let response = if request_line.starts_with("POST /api/v2/query") {
+            format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/csv\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                RESPONSE.len(),
+                RESPONSE
+            )
+        } else {
+            "HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+        };
+        requests.lock().unwrap().push((request_line, body));
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> (String, String) {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        data.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&data).to_string();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if n == 0 || data.len() >= end + 4 + length {
+                let request_line = text.lines().next().unwrap_or_default().to_string();
+                return (request_line, text[end + 4..].to_string());
+            }
+        } else if n == 0 {
+            return (text, String::new());
+        }
+    }
+}
+
+#[tokio::test]
+async fn query_rows_are_typed_by_their_annotations() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    tokio::spawn(serve(listener, requests.clone()));
+
+    let config = Config {
+        influx_url: url,
+        influx_org: "seclyzer".to_string(),
+        ..Config::default()
+    };
+    let influx = InfluxClient::new(&config).await.unwrap();
+    let flux = r#"from(bucket: "behavioral_data") |> range(start: -1h)"#;
+    let rows = influx.query_flux(flux).await.unwrap();
+
+    assert_eq!(
+        rows,
+        [
+            json!({"result": "_result", "table": 0, "_time": "2024-01-01T00:00:00Z",
+                   "_value": 120.5, "_field": "dwell_mean", "app": "Google Chrome"}),
+            json!({"result": "_result", "table": 0, "_time": "2024-01-01T00:00:05Z",
+                   "_value": 98.0, "_field": "dwell_mean", "app": "a=b,c"}),
+            json!({"result": "_result", "table": 1, "_time": "2024-01-01T00:00:00Z",
+                   "_value": true, "host": "laptop"}),
+            json!({"result": "_result", "table": 1, "_time": "2024-01-01T00:00:05Z",
+                   "_value": false, "host": "desktop"}),
+        ]
+    );
+
+    let requests = requests.lock().unwrap();
+    let (request_line, body) = requests.last().unwrap();
+    assert!(
+        request_line.starts_with("POST /api/v2/query?org=seclyzer "),
+        "{request_line}"
+    );
+    let body: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["query"], flux);
+    assert_eq!(body["type"], "flux");
+}
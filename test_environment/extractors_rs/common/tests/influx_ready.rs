@@ -0,0 +1,80 @@
+// Startup readiness checks against local stand-ins for an InfluxDB that is down,
+// hung or not ready yet; none needs a real server:
+//
+//     cargo test -p common --test influx_ready
+
+use common::{Config, InfluxClient};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn config(url: String) -> Config {
+    Config {
+        influx_url: url,
+        influx_connect_timeout_ms: 300,
+        influx_request_timeout_ms: 500,
+        ..Config::default()
+    }
+}
+
+/// A local URL nothing listens on
+fn closed_url() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+#[tokio::test]
+async fn nothing_listening_is_an_error() {
+    let error = InfluxClient::new(&config(closed_url()))
+        .await
+        .err()
+        .expect("startup should fail");
+    assert!(format!("{error:#}").contains("didn't answer its readiness check"));
+}
+
+#[tokio::test]
+async fn silent_server_times_out() {
+    // Connections complete in the backlog, but nothing is ever read or answered
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(Duration::from_secs(5), InfluxClient::new(&config(url)))
+        .await
+        .expect("readiness check hung past its timeout");
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+    drop(listener);
+}
+
+#[tokio::test]
+async fn not_ready_status_is_an_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request).await;
+        let _ = stream
+            .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n")
+            .await;
+    });
+
+    let error = InfluxClient::new(&config(url))
+        .await
+        .err()
+        .expect("startup should fail");
+    assert!(format!("{error:#}").contains("isn't ready: 503"));
+}
+
+#[tokio::test]
+async fn without_fail_fast_startup_continues() {
+    let config = Config {
+        influx_fail_fast: false,
+        ..config(closed_url())
+    };
+    let influx = InfluxClient::new(&config)
+        .await
+        .expect("startup should carry on");
+    assert!(!influx.is_ready().await);
+}
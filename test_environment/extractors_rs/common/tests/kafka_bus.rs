@@ -0,0 +1,40 @@
+// Round-trips an event through a local Kafka broker. Needs the `kafka` feature and a
+// broker with topic auto-creation at KAFKA_BROKERS (default localhost:9092):
+//
+//     cargo test -p common --features kafka --test kafka_bus
+#![cfg(feature = "kafka")]
+
+use common::event_bus::{EventBus, KafkaEventBus};
+use std::time::Duration;
+
+#[tokio::test]
+async fn event_round_trips_through_kafka() {
+    let brokers = std::env::var("KAFKA_BROKERS").unwrap_or_else(|_| "localhost:9092".to_string());
+    let bus = KafkaEventBus::new(&brokers, false).expect("Failed to create Kafka producer");
+
+    let id = uuid::Uuid::new_v4();
+    let topic = format!("seclyzer-test:events-{}", id);
+    let mut received = bus
+        .subscribe(&topic, &format!("seclyzer-test-{}", id))
+        .await
+        .expect("Failed to subscribe");
+    let event = serde_json::json!({"type": "keystroke", "ts": 1, "key": "KeyA", "event": "press"});
+
+    // The new consumer starts at the latest offset once its partitions are assigned,
+    // so keep publishing until one copy makes it through
+    let payload = tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            bus.publish(&topic, &event).await.expect("Failed to publish");
+            if let Ok(Some(payload)) =
+                tokio::time::timeout(Duration::from_secs(1), received.recv()).await
+            {
+                return payload;
+            }
+        }
+    })
+    .await
+    .expect("No event came back from Kafka");
+
+    let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+    assert_eq!(payload, event);
+}
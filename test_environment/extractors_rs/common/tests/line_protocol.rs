@@ -0,0 +1,97 @@
+// Line protocol escaping for measurements, tags and every kind of field value:
+//
+//     cargo test -p common --test line_protocol
+
+use common::{FieldValue, InfluxClient};
+use std::collections::HashMap;
+
+fn tags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn fields(pairs: &[(&str, FieldValue)]) -> HashMap<String, FieldValue> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect()
+}
+
+#[test]
+fn spaces_commas_and_equals_are_escaped_in_tags() {
+    let line = InfluxClient::build_line_protocol(
+        "app_usage",
+        &tags(&[("app", "Google Chrome"), ("window", "a=b,c")]),
+        &fields(&[("duration_ms", FieldValue::Integer(1500))]),
+        1,
+    );
+    assert_eq!(
+        line,
+        r"app_usage,app=Google\ Chrome,window=a\=b\,c duration_ms=1500i 1"
+    );
+}
+
+#[test]
+fn measurements_escape_commas_and_spaces_but_not_equals() {
+    let line = InfluxClient::build_line_protocol(
+        "key stats,v=2",
+        &tags(&[]),
+        &fields(&[("count", FieldValue::Integer(3))]),
+        1,
+    );
+    assert_eq!(line, r"key\ stats\,v=2 count=3i 1");
+}
+
+#[test]
+fn field_keys_are_escaped_and_string_values_quoted() {
+    let line = InfluxClient::build_line_protocol(
+        "apps",
+        &tags(&[]),
+        &fields(&[
+            ("app name", FieldValue::from("Google Chrome")),
+            ("title", FieldValue::from(r#"say "hi", a=b,c \ done"#)),
+        ]),
+        1,
+    );
+    assert_eq!(
+        line,
+        r#"apps app\ name="Google Chrome",title="say \"hi\", a=b,c \\ done" 1"#
+    );
+}
+
+#[test]
+fn field_values_are_written_by_type() {
+    let line = InfluxClient::build_line_protocol(
+        "m",
+        &tags(&[]),
+        &fields(&[
+            ("float", FieldValue::from(0.25)),
+            ("whole_float", FieldValue::from(2.0)),
+            ("integer", FieldValue::from(-7i64)),
+            ("flag", FieldValue::from(true)),
+            ("text", FieldValue::from("x".to_string())),
+        ]),
+        1_700_000_000_000_000_000,
+    );
+    assert_eq!(
+        line,
+        r#"m flag=true,float=0.25,integer=-7i,text="x",whole_float=2 1700000000000000000"#
+    );
+}
+
+#[test]
+fn non_finite_fields_and_empty_tags_are_left_out() {
+    let line = InfluxClient::build_line_protocol(
+        "m",
+        &tags(&[("app", ""), ("host", "laptop")]),
+        &fields(&[
+            ("nan", FieldValue::Float(f64::NAN)),
+            ("inf", FieldValue::Float(f64::INFINITY)),
+            ("ok", FieldValue::Float(1.5)),
+        ]),
+        1,
+    );
+    assert_eq!(line, "m,host=laptop ok=1.5 1");
+}
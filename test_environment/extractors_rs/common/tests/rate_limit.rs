@@ -0,0 +1,47 @@
+// Takes a burst of tokens from the rate limiter and checks how they're paced:
+//
+//     cargo test -p common --test rate_limit
+
+use common::RateLimiter;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn burst_then_paced_to_rate() {
+    // 100 points/sec, 10 at once
+    let limiter = RateLimiter::new(100.0, 10);
+    let start = Instant::now();
+
+    for _ in 0..10 {
+        limiter.acquire(1).await;
+    }
+    assert!(
+        start.elapsed() < Duration::from_millis(50),
+        "{:?}",
+        start.elapsed()
+    );
+
+    // The other 20 come 10 ms apart
+    for _ in 0..20 {
+        limiter.acquire(1).await;
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(190), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(400), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn batch_larger_than_burst_delays_the_next() {
+    let limiter = RateLimiter::new(100.0, 10);
+    let start = Instant::now();
+
+    // Sent whole once the 20 points over the burst have been waited for
+    limiter.acquire(30).await;
+    let first = start.elapsed();
+    assert!(first >= Duration::from_millis(190), "{:?}", first);
+
+    // The bucket is empty again, so the next point waits its own 10 ms
+    limiter.acquire(1).await;
+    let elapsed = start.elapsed() - first;
+    assert!(elapsed >= Duration::from_millis(8), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(100), "{:?}", elapsed);
+}
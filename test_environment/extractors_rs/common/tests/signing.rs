@@ -0,0 +1,100 @@
+// Signs events as a collector would and checks a subscriber requiring signatures
+// passes them through while dropping tampered, unsigned and mis-keyed ones:
+//
+//     cargo test -p common --test signing
+
+use async_trait::async_trait;
+use common::{BusEventSource, EventBus, EventSigner, EventSource, RawEvent, SignatureError};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+const SECRET: &str = "collector-secret";
+
+/// Hands its subscriber a fixed list of payloads
+struct CannedBus(Mutex<Vec<String>>);
+
+#[async_trait]
+impl EventBus for CannedBus {
+    async fn publish(&self, _topic: &str, _payload: &serde_json::Value) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        _topic: &str,
+        _group: &str,
+    ) -> anyhow::Result<mpsc::Receiver<String>> {
+        let payloads = std::mem::take(&mut *self.0.lock().unwrap());
+        let (tx, rx) = mpsc::channel(payloads.len().max(1));
+        for payload in payloads {
+            tx.send(payload).await.unwrap();
+        }
+        Ok(rx)
+    }
+}
+
+fn keystroke(ts: u64, key: &str) -> serde_json::Value {
+    serde_json::json!({"type": "keystroke", "ts": ts, "key": key, "event": "press"})
+}
+
+#[test]
+fn tampered_payload_fails_verification() {
+    let signer = EventSigner::new(SECRET);
+    let signed = signer.sign(&keystroke(1_700_000_000_000_000, "a")).unwrap();
+    assert!(signer.verify(&signed).is_ok());
+
+    // Key order and spacing don't matter, only the contents
+    let value: serde_json::Value = serde_json::from_str(&signed).unwrap();
+    assert!(signer
+        .verify(&serde_json::to_string_pretty(&value).unwrap())
+        .is_ok());
+
+    let tampered = signed.replace("\"key\":\"a\"", "\"key\":\"b\"");
+    assert_ne!(tampered, signed);
+    assert!(matches!(
+        signer.verify(&tampered),
+        Err(SignatureError::Mismatch)
+    ));
+
+    let unsigned = serde_json::to_string(&keystroke(1_700_000_000_000_000, "a")).unwrap();
+    assert!(matches!(
+        signer.verify(&unsigned),
+        Err(SignatureError::Unsigned)
+    ));
+
+    let other_key = EventSigner::new("someone-else");
+    assert!(matches!(
+        other_key.verify(&signed),
+        Err(SignatureError::Mismatch)
+    ));
+}
+
+#[tokio::test]
+async fn subscriber_drops_invalid_events() {
+    let signer = EventSigner::new(SECRET);
+    let base_ts = 1_700_000_000_000_000;
+    let payloads = vec![
+        signer.sign(&keystroke(base_ts, "a")).unwrap(),
+        // Re-timed after signing
+        signer
+            .sign(&keystroke(base_ts + 1, "b"))
+            .unwrap()
+            .replace(&(base_ts + 1).to_string(), &(base_ts + 5).to_string()),
+        serde_json::to_string(&keystroke(base_ts + 2, "c")).unwrap(),
+        EventSigner::new("injected")
+            .sign(&keystroke(base_ts + 3, "d"))
+            .unwrap(),
+        signer.sign(&keystroke(base_ts + 4, "e")).unwrap(),
+    ];
+    let bus = CannedBus(Mutex::new(payloads));
+
+    let mut source = BusEventSource::subscribe(&bus, "seclyzer:events", "test")
+        .await
+        .unwrap()
+        .with_verifier(Some(signer));
+    let mut keys = Vec::new();
+    while let Some(RawEvent { key, .. }) = source.next_event().await {
+        keys.push(key.unwrap());
+    }
+    assert_eq!(keys, ["a", "e"]);
+}
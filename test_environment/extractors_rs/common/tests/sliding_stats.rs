@@ -0,0 +1,90 @@
+// Slides a window over a long run of timing samples, comparing the incremental
+// statistics with a batch recomputation at every step and timing both:
+//
+//     cargo test -p common --test sliding_stats -- --nocapture
+
+use common::{SlidingStats, Summary};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Samples in the window at once
+const WINDOW: usize = 5_000;
+/// Window positions compared
+const STEPS: usize = 200;
+
+/// Dwell-like times in ms, a few arriving slightly out of timestamp order
+fn samples(count: usize) -> Vec<(f64, f64)> {
+    let mut rng = StdRng::seed_from_u64(7);
+    (0..count)
+        .map(|i| {
+            let jitter = if i % 10 == 0 { -1.5 } else { 0.0 };
+            (i as f64 + jitter, rng.gen_range(40.0..400.0))
+        })
+        .collect()
+}
+
+fn assert_close(incremental: f64, batch: f64, relative: f64, what: &str) {
+    let tolerance = batch.abs() * relative + 1e-9;
+    assert!(
+        (incremental - batch).abs() <= tolerance,
+        "{}: incremental {} vs batch {}",
+        what,
+        incremental,
+        batch
+    );
+}
+
+#[test]
+fn matches_batch_and_is_faster() {
+    let samples = samples(WINDOW + STEPS);
+    let mut incremental = SlidingStats::new();
+    let mut window: VecDeque<(f64, f64)> = VecDeque::new();
+    let (mut incremental_time, mut batch_time) = (Duration::ZERO, Duration::ZERO);
+
+    for (i, &(timestamp, value)) in samples.iter().enumerate() {
+        incremental.push(timestamp, value);
+        window.push_back((timestamp, value));
+        if i < WINDOW {
+            continue;
+        }
+        let cutoff = (i - WINDOW) as f64;
+        window.retain(|&(ts, _)| ts > cutoff);
+
+        let started = Instant::now();
+        incremental.evict_through(cutoff);
+        let fast = incremental.summary();
+        incremental_time += started.elapsed();
+
+        let started = Instant::now();
+        let values: Vec<f64> = window.iter().map(|&(_, value)| value).collect();
+        let exact = Summary::of(&values);
+        batch_time += started.elapsed();
+
+        assert_eq!(fast.count, exact.count);
+        assert_eq!(fast.min, exact.min);
+        assert_eq!(fast.max, exact.max);
+        assert_close(fast.mean, exact.mean, 1e-9, "mean");
+        assert_close(fast.std_dev, exact.std_dev, 1e-6, "std_dev");
+        // Quantiles are estimates; neighbouring ranks are ~0.02 ms apart here
+        assert_close(fast.median, exact.median, 0.011, "median");
+        assert_close(fast.q25, exact.q25, 0.011, "q25");
+        assert_close(fast.q75, exact.q75, 0.011, "q75");
+    }
+
+    println!(
+        "{} windows of {} samples: incremental {:?}, batch {:?}",
+        STEPS, WINDOW, incremental_time, batch_time
+    );
+    assert!(incremental_time * 5 < batch_time);
+}
+
+#[test]
+fn empty_after_everything_is_evicted() {
+    let mut stats = SlidingStats::new();
+    for (timestamp, value) in samples(100) {
+        stats.push(timestamp, value);
+    }
+    stats.evict_through(1000.0);
+    assert_eq!(stats.summary(), Summary::default());
+}
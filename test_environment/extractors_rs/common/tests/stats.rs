@@ -0,0 +1,74 @@
+// The summary statistics behind every feature, on empty, single-value, odd and
+// even inputs, given out of order so nothing relies on pre-sorted data:
+//
+//     cargo test -p common --test stats
+
+use common::stats::{max, mean, median, min, percentile, std_dev, variance};
+
+const ODD: [f64; 5] = [30.0, 10.0, 50.0, 20.0, 40.0];
+const EVEN: [f64; 4] = [40.0, 10.0, 30.0, 20.0];
+
+#[test]
+fn empty_input_is_zero_everywhere() {
+    let empty: [f64; 0] = [];
+    assert_eq!(mean(&empty), 0.0);
+    assert_eq!(variance(&empty), 0.0);
+    assert_eq!(std_dev(&empty), 0.0);
+    assert_eq!(median(&empty), 0.0);
+    assert_eq!(percentile(&empty, 50.0), 0.0);
+    assert_eq!(min(&empty), 0.0);
+    assert_eq!(max(&empty), 0.0);
+}
+
+#[test]
+fn single_value_is_its_own_summary() {
+    let one = [42.0];
+    assert_eq!(mean(&one), 42.0);
+    // Too few values to vary
+    assert_eq!(variance(&one), 0.0);
+    assert_eq!(std_dev(&one), 0.0);
+    assert_eq!(median(&one), 42.0);
+    for p in [0.0, 50.0, 100.0] {
+        assert_eq!(percentile(&one, p), 42.0);
+    }
+    assert_eq!(min(&one), 42.0);
+    assert_eq!(max(&one), 42.0);
+}
+
+#[test]
+fn odd_count() {
+    assert_eq!(mean(&ODD), 30.0);
+    // Population variance: (400 + 100 + 0 + 100 + 400) / 5
+    assert_eq!(variance(&ODD), 200.0);
+    assert_eq!(std_dev(&ODD), 200f64.sqrt());
+    assert_eq!(median(&ODD), 30.0);
+    assert_eq!(min(&ODD), 10.0);
+    assert_eq!(max(&ODD), 50.0);
+}
+
+#[test]
+fn even_count() {
+    assert_eq!(mean(&EVEN), 25.0);
+    // (225 + 25 + 25 + 225) / 4
+    assert_eq!(variance(&EVEN), 125.0);
+    // The mean of the two middle values
+    assert_eq!(median(&EVEN), 25.0);
+    assert_eq!(min(&EVEN), 10.0);
+    assert_eq!(max(&EVEN), 40.0);
+}
+
+#[test]
+fn percentile_takes_the_value_at_rank_len_times_p() {
+    // Ranks 0, 1 (5 * 0.25 = 1.25), 2, 4 (5 * 0.9 = 4.5) and the last value
+    assert_eq!(percentile(&ODD, 0.0), 10.0);
+    assert_eq!(percentile(&ODD, 25.0), 20.0);
+    assert_eq!(percentile(&ODD, 50.0), 30.0);
+    assert_eq!(percentile(&ODD, 90.0), 50.0);
+    assert_eq!(percentile(&ODD, 100.0), 50.0);
+    // On an even count the 50th percentile is the upper middle value, not the median
+    assert_eq!(percentile(&EVEN, 50.0), 30.0);
+    assert_eq!(percentile(&EVEN, 75.0), 40.0);
+    // Out-of-range `p` is clamped
+    assert_eq!(percentile(&ODD, -10.0), 10.0);
+    assert_eq!(percentile(&ODD, 250.0), 50.0);
+}
@@ -0,0 +1,156 @@
+// Round-trips events over Unix domain sockets in a temporary directory, from both
+// the async bus extractors use and the blocking link collectors use:
+//
+//     cargo test -p common --test unix_bus
+#![cfg(unix)]
+
+use common::encode_payload;
+use common::event_bus::{BlockingEventBus, EventBus, UnixEventBus, UnixEventLink};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn socket_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("seclyzer-test-{}", uuid::Uuid::new_v4().simple()))
+}
+
+async fn next(received: &mut mpsc::Receiver<String>) -> serde_json::Value {
+    let payload = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .expect("No event came over the socket")
+        .expect("Subscription ended");
+    serde_json::from_str(&payload).unwrap()
+}
+
+#[tokio::test]
+async fn events_round_trip_to_every_group() {
+    let dir = socket_dir();
+    let bus = UnixEventBus::new(&dir, true, None);
+    let mut keystroke = bus
+        .subscribe("seclyzer:events", "keystroke_extractor")
+        .await
+        .unwrap();
+    let mut mouse = bus
+        .subscribe("seclyzer:events", "mouse_extractor")
+        .await
+        .unwrap();
+    let mut other = bus
+        .subscribe("seclyzer:other", "keystroke_extractor")
+        .await
+        .unwrap();
+
+    let events: Vec<serde_json::Value> = (0..3)
+        .map(|i| json!({"type": "keystroke", "ts": 1_700_000_000_000_000u64 + i, "key": "KeyA", "event": "press"}))
+        .collect();
+    for event in &events {
+        bus.publish("seclyzer:events", event).await.unwrap();
+    }
+    // A large payload goes compressed and comes back whole
+    let large = json!({"type": "app", "ts": 1, "app_name": "x".repeat(10_000), "event": "focus"});
+    bus.publish("seclyzer:events", &large).await.unwrap();
+
+    for received in [&mut keystroke, &mut mouse] {
+        for event in &events {
+            assert_eq!(&next(received).await, event);
+        }
+        assert_eq!(next(received).await, large);
+    }
+    assert!(other.try_recv().is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn collector_link_reaches_the_extractor() {
+    let dir = socket_dir();
+    let mut link = UnixEventLink::new(&dir, None);
+    assert!(!link.ready());
+    // Nobody listening and no fallback: the publish fails, so the event stays in the WAL
+    assert!(link.publish("seclyzer:events", b"{}").is_err());
+
+    let bus = UnixEventBus::new(&dir, false, None);
+    let mut received = bus
+        .subscribe("seclyzer:events", "app_tracker")
+        .await
+        .unwrap();
+    assert!(link.ready());
+    let event = json!({"type": "app", "ts": 7, "app_name": "code", "event": "focus"});
+    let payload = encode_payload(&event.to_string(), false);
+    tokio::task::spawn_blocking(move || {
+        link.publish("seclyzer:events", &payload).unwrap();
+        link.publish("seclyzer:events", &payload).unwrap();
+    })
+    .await
+    .unwrap();
+    assert_eq!(next(&mut received).await, event);
+    assert_eq!(next(&mut received).await, event);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn socket_dir_is_created_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = socket_dir();
+    let bus = UnixEventBus::new(&dir, false, None);
+    let _received = bus
+        .subscribe("seclyzer:events", "app_tracker")
+        .await
+        .unwrap();
+    let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn socket_dir_open_to_others_is_refused() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = socket_dir();
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let bus = UnixEventBus::new(&dir, false, None);
+    let error = bus
+        .subscribe("seclyzer:events", "app_tracker")
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{error:#}").contains("open to other users"),
+        "{error:#}"
+    );
+    // Nor does a collector write into it, even with a socket planted there
+    let _planted =
+        std::os::unix::net::UnixListener::bind(dir.join("seclyzer:events@planted.sock")).unwrap();
+    let mut link = UnixEventLink::new(&dir, None);
+    assert!(!link.ready());
+    let error = link.publish("seclyzer:events", b"{}").unwrap_err();
+    assert!(
+        format!("{error:#}").contains("open to other users"),
+        "{error:#}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stalled_subscriber_does_not_block_the_collector() {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let dir = socket_dir();
+    std::fs::DirBuilder::new().mode(0o700).create(&dir).unwrap();
+    // Listening, but never accepting or reading
+    let _stalled =
+        std::os::unix::net::UnixListener::bind(dir.join("seclyzer:events@stalled.sock")).unwrap();
+
+    let mut link = UnixEventLink::new(&dir, None);
+    let started = std::time::Instant::now();
+    let payload = vec![b'x'; 8 * 1024 * 1024];
+    assert!(link.publish("seclyzer:events", &payload).is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
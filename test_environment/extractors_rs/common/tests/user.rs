@@ -0,0 +1,67 @@
+// Per-user sessions on shared hosts: routing, state paths and user id checks:
+//
+//     cargo test -p common --test user
+
+use common::{is_valid_user_id, user_path, RawEvent, UserSessions};
+use std::path::Path;
+
+fn event(user_id: Option<&str>) -> RawEvent {
+    serde_json::from_value(serde_json::json!({
+        "type": "keystroke",
+        "ts": 1_700_000_000_000_000u64,
+        "key": "KeyA",
+        "event": "press",
+        "user_id": user_id,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn events_route_to_their_users_session() {
+    let mut sessions = UserSessions::new(Some("alice".to_string()), |_| 0usize);
+    for user_id in [Some("bob"), None, Some("alice"), Some("bob")] {
+        let user = sessions.user_of(&event(user_id));
+        *sessions.session(user.as_deref()) += 1;
+    }
+
+    let counts: Vec<(Option<&str>, usize)> = sessions
+        .iter_mut()
+        .map(|(user, count)| (user, *count))
+        .collect();
+    // Unattributed events belong to the default user
+    assert_eq!(counts, [(Some("alice"), 2), (Some("bob"), 2)]);
+}
+
+#[test]
+fn retain_keeps_the_default_user() {
+    let mut sessions = UserSessions::new(Some("alice".to_string()), |_| ());
+    sessions.session(Some("alice"));
+    sessions.session(Some("bob"));
+    sessions.retain(|_, _| false);
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions.iter_mut().next().unwrap().0, Some("alice"));
+}
+
+#[test]
+fn user_state_is_saved_beside_the_shared_path() {
+    let path = Path::new("/var/lib/seclyzer/keystroke_baseline.json");
+    assert_eq!(user_path(path, None), path);
+    assert_eq!(
+        user_path(path, Some("alice")),
+        Path::new("/var/lib/seclyzer/keystroke_baseline.alice.json")
+    );
+    assert_eq!(
+        user_path(Path::new("state"), Some("bob")),
+        Path::new("state.bob")
+    );
+}
+
+#[test]
+fn user_ids_must_be_safe_file_name_parts() {
+    for valid in ["alice", "bob.smith", "u-42", "carol@example.com"] {
+        assert!(is_valid_user_id(valid), "{valid}");
+    }
+    for invalid in ["", ".hidden", "../etc", "a b", "ünïcode", &"x".repeat(65)] {
+        assert!(!is_valid_user_id(invalid), "{invalid}");
+    }
+}
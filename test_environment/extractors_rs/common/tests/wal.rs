@@ -0,0 +1,160 @@
+// The collectors' write-ahead log: partial drains, replay after the log is
+// dropped and reopened, and repair of a line torn by a crash mid-append:
+//
+//     cargo test -p common --test wal
+
+use common::EventWal;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A fresh log path in its own temp directory, removed by `cleanup`
+fn wal_path() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("seclyzer-wal-{}", uuid::Uuid::new_v4().simple()))
+        .join("events.wal")
+}
+
+fn cleanup(path: &Path) {
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+fn events(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!(r#"{{"seq":{i}}}"#)).collect()
+}
+
+/// Drain everything, collecting what was sent
+fn drain_all(wal: &mut EventWal) -> Vec<String> {
+    let mut sent = Vec::new();
+    wal.drain::<std::io::Error, _>(|entry| {
+        sent.push(entry.to_string());
+        Ok(())
+    })
+    .unwrap();
+    sent
+}
+
+#[test]
+fn partial_drain_resumes_where_it_stopped() {
+    let path = wal_path();
+    let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+    for event in events(5) {
+        wal.append(&event).unwrap();
+    }
+
+    // The bus goes away after two publishes
+    let mut sent = Vec::new();
+    let result = wal.drain(|entry| {
+        if sent.len() == 2 {
+            return Err(std::io::Error::other("connection refused"));
+        }
+        sent.push(entry.to_string());
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(sent, events(5)[..2]);
+    assert_eq!(wal.pending(), 3);
+
+    assert_eq!(drain_all(&mut wal), events(5)[2..]);
+    assert_eq!(wal.pending(), 0);
+    // Once everything is acknowledged the log is emptied
+    assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    cleanup(&path);
+}
+
+#[test]
+fn unsent_events_are_replayed_after_reopening() {
+    let path = wal_path();
+    {
+        let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+        for event in events(4) {
+            wal.append(&event).unwrap();
+        }
+        let mut sent = 0;
+        let _ = wal.drain(|_| {
+            sent += 1;
+            if sent > 1 {
+                Err(std::io::Error::other("connection refused"))
+            } else {
+                Ok(())
+            }
+        });
+        // Dropped without another drain, as when the collector is killed
+    }
+
+    let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+    assert_eq!(wal.pending(), 3);
+    assert_eq!(drain_all(&mut wal), events(4)[1..]);
+
+    // Nothing is sent twice once a replay has been acknowledged
+    drop(wal);
+    let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+    assert_eq!(wal.pending(), 0);
+    assert!(drain_all(&mut wal).is_empty());
+    cleanup(&path);
+}
+
+#[test]
+fn torn_last_line_is_cut_off_on_open() {
+    let path = wal_path();
+    {
+        let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+        for event in events(2) {
+            wal.append(&event).unwrap();
+        }
+    }
+    // A crash halfway through writing the third event
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(br#"{"seq":"#).unwrap();
+    drop(file);
+
+    let mut wal = EventWal::open(&path, 1 << 20).unwrap();
+    assert_eq!(wal.pending(), 2);
+    // Later appends start on a line of their own
+    wal.append(r#"{"seq":2}"#).unwrap();
+    assert_eq!(drain_all(&mut wal), events(3));
+    cleanup(&path);
+}
+
+#[test]
+fn full_log_drops_the_oldest_unsent_events() {
+    let path = wal_path();
+    // Room for ten 10-byte entries
+    let mut wal = EventWal::open(&path, 100).unwrap();
+    let mut dropped = 0;
+    for event in events(10) {
+        dropped += wal.append(&event).unwrap();
+    }
+    assert_eq!(dropped, 0);
+
+    dropped += wal.append(r#"{"seq":10}"#).unwrap();
+    assert!(dropped > 0);
+    let sent = drain_all(&mut wal);
+    assert_eq!(sent.len() + dropped, 11);
+    assert_eq!(sent.last().unwrap(), r#"{"seq":10}"#);
+    assert_eq!(sent[0], events(10)[dropped]);
+    cleanup(&path);
+}
+
+#[cfg(unix)]
+#[test]
+fn log_files_are_private_to_this_user() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = wal_path();
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let mut wal = EventWal::open(&path, 100).unwrap();
+    assert_eq!(mode(&path), 0o600);
+
+    // Filling the log compacts it into a new file and saves the cursor
+    for event in events(11) {
+        wal.append(&event).unwrap();
+    }
+    drain_all(&mut wal);
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(
+        mode(Path::new(&format!("{}.cursor", path.display()))),
+        0o600
+    );
+    cleanup(&path);
+}
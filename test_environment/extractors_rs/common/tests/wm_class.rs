@@ -0,0 +1,66 @@
+// Parses WM_CLASS values as real X11 clients set them:
+//
+//     cargo test -p common --test wm_class
+
+use common::parse_wm_class;
+
+fn parsed(value: &[u8]) -> Option<(String, String)> {
+    parse_wm_class(value)
+}
+
+fn pair(app_name: &str, window_class: &str) -> Option<(String, String)> {
+    Some((app_name.to_string(), window_class.to_string()))
+}
+
+#[test]
+fn instance_and_class() {
+    assert_eq!(
+        parsed(b"Navigator\0firefox\0"),
+        pair("Navigator", "firefox")
+    );
+    assert_eq!(
+        parsed(b"gnome-terminal-server\0Gnome-terminal\0"),
+        pair("gnome-terminal-server", "Gnome-terminal")
+    );
+    // Without the final terminator
+    assert_eq!(parsed(b"code\0Code"), pair("code", "Code"));
+}
+
+#[test]
+fn single_part_names_both() {
+    assert_eq!(parsed(b"xterm\0"), pair("xterm", "xterm"));
+    assert_eq!(
+        parsed(b"steam_app_1091500"),
+        pair("steam_app_1091500", "steam_app_1091500")
+    );
+}
+
+#[test]
+fn padding_and_extra_parts() {
+    assert_eq!(
+        parsed(b"jetbrains-idea\0jetbrains-idea\0\0\0\0"),
+        pair("jetbrains-idea", "jetbrains-idea")
+    );
+    assert_eq!(parsed(b"\0Slack\0"), pair("Slack", "Slack"));
+    assert_eq!(
+        parsed(b"Alacritty\0Alacritty\0extra\0"),
+        pair("Alacritty", "Alacritty")
+    );
+}
+
+#[test]
+fn utf8_and_latin1() {
+    assert_eq!(
+        parsed("日本語エディタ\0Éditeur\0".as_bytes()),
+        pair("日本語エディタ", "Éditeur")
+    );
+    // Latin-1 as ICCCM specifies: 0xE9 is é, not an invalid UTF-8 byte
+    assert_eq!(parsed(b"caf\xe9\0Caf\xe9\0"), pair("café", "Café"));
+}
+
+#[test]
+fn empty_values_name_nothing() {
+    assert_eq!(parsed(b""), None);
+    assert_eq!(parsed(b"\0\0"), None);
+    assert_eq!(parsed(b" \0"), None);
+}
@@ -0,0 +1,100 @@
+use common::baseline::NON_FEATURE_FIELDS;
+use common::IdleRecord;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Modalities every fused record has a slot for, in output order
+pub const MODALITIES: &[&str] = &["keystroke", "mouse", "app"];
+
+/// Recent windows kept per modality to pick the nearest one from
+const HISTORY_LEN: usize = 8;
+
+/// One modality's numeric features, stamped with the time they describe
+#[derive(Debug, Clone)]
+struct Window {
+    ts: f64,
+    fields: BTreeMap<String, f64>,
+}
+
+/// Aligns the latest keystroke, mouse and app windows into one record
+///
+/// Each join picks, per modality, the buffered window nearest the join timestamp.
+/// Windows further away than the staleness limit count as missing: their fields
+/// are zero-filled and `<modality>_present` is 0.
+#[derive(Debug)]
+pub struct FeatureJoin {
+    staleness_seconds: f64,
+    history: HashMap<String, VecDeque<Window>>,
+    known_fields: HashMap<String, BTreeSet<String>>,
+}
+
+impl FeatureJoin {
+    pub fn new(staleness_seconds: f64) -> Self {
+        FeatureJoin {
+            staleness_seconds,
+            history: HashMap::new(),
+            known_fields: HashMap::new(),
+        }
+    }
+
+    /// Buffer a feature message; it's timed by its `window_end`, or `received_at` if it has none
+    pub fn update(&mut self, modality: &str, features: &Value, received_at: f64) {
+        if !MODALITIES.contains(&modality) || IdleRecord::is_idle(features) {
+            return;
+        }
+        let Some(map) = features.as_object() else {
+            return;
+        };
+
+        let ts = map.get("window_end").and_then(|v| v.as_f64()).unwrap_or(received_at);
+        let fields: BTreeMap<String, f64> = map
+            .iter()
+            .filter(|(name, _)| !NON_FEATURE_FIELDS.contains(&name.as_str()))
+            .filter_map(|(name, value)| value.as_f64().map(|v| (name.clone(), v)))
+            .collect();
+
+        self.known_fields
+            .entry(modality.to_string())
+            .or_default()
+            .extend(fields.keys().cloned());
+        let history = self.history.entry(modality.to_string()).or_default();
+        if history.len() >= HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(Window { ts, fields });
+    }
+
+    /// Fused record for timestamp `ts`, with fields named `<modality>_<feature>` and
+    /// `<modality>_offset` giving how far the chosen window's end is from `ts`
+    pub fn join(&self, ts: f64) -> Value {
+        let mut fused = Map::new();
+        fused.insert("ts".to_string(), Value::from(ts));
+
+        for &modality in MODALITIES {
+            let nearest = self.nearest(modality, ts);
+            fused.insert(
+                format!("{}_present", modality),
+                Value::from(if nearest.is_some() { 1.0 } else { 0.0 }),
+            );
+            fused.insert(
+                format!("{}_offset", modality),
+                Value::from(nearest.map_or(0.0, |w| w.ts - ts)),
+            );
+
+            for name in self.known_fields.get(modality).into_iter().flatten() {
+                let value = nearest.and_then(|w| w.fields.get(name)).copied().unwrap_or(0.0);
+                fused.insert(format!("{}_{}", modality, name), Value::from(value));
+            }
+        }
+        Value::Object(fused)
+    }
+
+    /// Buffered window closest to `ts`, if any is within the staleness limit
+    fn nearest(&self, modality: &str, ts: f64) -> Option<&Window> {
+        self.history
+            .get(modality)?
+            .iter()
+            .filter(|w| (w.ts - ts).abs() <= self.staleness_seconds)
+            .min_by(|a, b| (a.ts - ts).abs().total_cmp(&(b.ts - ts).abs()))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod join;
+
+pub use join::{FeatureJoin, MODALITIES};
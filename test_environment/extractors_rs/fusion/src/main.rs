@@ -0,0 +1,77 @@
+use common::{
+    init_logging, shutdown_signal, tag_user, Config, RedisClient, UserSessions, USER_ID_FIELD,
+};
+use fusion::{FeatureJoin, MODALITIES};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{interval, Duration};
+use tracing::{error, info, warn};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_logging();
+    info!("Fusion starting");
+
+    // Fusion only needs Redis, so skip AppContext and its InfluxDB check
+    let config = Config::load()?;
+    let redis = RedisClient::new(&config).await?;
+
+    let feature_prefix = config.feature_channel_prefix();
+    let fused_channel = config.fused_channel();
+    let mut features = redis.psubscribe(&format!("{}*", feature_prefix)).await?;
+    // Windows are only joined with the same user's other modalities
+    let staleness_seconds = config.fusion_staleness_seconds;
+    let mut joins = UserSessions::new(None, move |_| FeatureJoin::new(staleness_seconds));
+    let mut tick = interval(Duration::from_secs(config.hop_seconds()));
+
+    info!("Fusion initialized and ready");
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some((channel, payload)) = features.recv() => {
+                // Only plain modality channels; `:dev` and `:scores` sub-channels are skipped
+                let Some(modality) = channel.strip_prefix(feature_prefix.as_str()) else {
+                    continue;
+                };
+                if modality.contains(':') {
+                    continue;
+                }
+                match serde_json::from_str::<serde_json::Value>(&payload) {
+                    Ok(features) => joins
+                        .session(features[USER_ID_FIELD].as_str())
+                        .update(modality, &features, unix_now()),
+                    Err(e) => warn!("Skipping malformed features on {}: {}", channel, e),
+                }
+            }
+            _ = tick.tick() => {
+                for (user, join) in joins.iter_mut() {
+                    let mut fused = join.join(unix_now());
+                    // Nothing to correlate until at least one modality has reported
+                    let any_present = MODALITIES
+                        .iter()
+                        .any(|m| fused[format!("{}_present", m)].as_f64() == Some(1.0));
+                    if !any_present {
+                        continue;
+                    }
+                    tag_user(&mut fused, user);
+                    if let Err(e) = redis.publish_features(&fused_channel, &fused).await {
+                        error!("Failed to publish fused record: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    info!("Fusion stopped cleanly");
+    Ok(())
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
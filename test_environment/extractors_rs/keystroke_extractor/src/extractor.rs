@@ -1,32 +1,187 @@
-use crate::features::{KeystrokeEvent, KeystrokeFeatureCalculator};
-use std::collections::VecDeque;
+use crate::features::{
+    KeystrokeEvent, KeystrokeFeatureCalculator, TimingSummaries, MAX_DWELL_MS, MAX_FLIGHT_MS,
+};
+use common::{
+    keymap, Config, FocusTracker, IdleRecord, KeyIdentity, LiveExtractor, OutlierTrim, RawEvent,
+    SlidingStats, WindowWarmup, WindowedExtractor,
+};
+use std::collections::{HashMap, VecDeque};
 use tokio::time::{interval, Duration};
 use redis::aio::ConnectionManager;
 use chrono::Utc;
+use tracing::warn;
+
+/// Weight of the newest window in the `wpm_ewma` running average
+pub const WPM_EWMA_ALPHA: f64 = 0.3;
+
+/// Events buffered unless `with_buffer_size` says otherwise
+pub const DEFAULT_BUFFER_SIZE: usize = 10_000;
 
 pub struct KeystrokeExtractor {
     events: VecDeque<KeystrokeEvent>,
     feature_calculator: KeystrokeFeatureCalculator,
     window_seconds: u64,
-    update_interval: u64,
+    hop_seconds: u64,
+    last_window_end: Option<f64>,
+    idle_records: bool,
+    wpm_ewma: Option<f64>,
+    buffer_size: usize,
+    key_identity: KeyIdentity,
+    privacy_mode: bool,
+    /// Whether the last insert evicted an event still inside the window
+    saturated: bool,
+    /// Focused app and recent app switches, from focus events
+    focus: FocusTracker,
+    /// What happens to windows that start before `first_event`
+    warmup: WindowWarmup,
+    /// Timestamp of the first event added, from which windows are complete
+    first_event: Option<f64>,
+    /// Dwell and flight statistics kept up as events arrive, if enabled
+    timings: Option<IncrementalTimings>,
 }
 
 impl KeystrokeExtractor {
+    /// `update_interval` is also the hop between windows unless `with_hop_seconds` overrides it
     pub fn new(window_seconds: u64, update_interval: u64) -> Self {
         KeystrokeExtractor {
-            events: VecDeque::with_capacity(10000),
+            events: VecDeque::with_capacity(DEFAULT_BUFFER_SIZE),
             feature_calculator: KeystrokeFeatureCalculator::new(window_seconds),
             window_seconds,
-            update_interval,
+            hop_seconds: update_interval,
+            last_window_end: None,
+            idle_records: false,
+            wpm_ewma: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            key_identity: KeyIdentity::Logical,
+            privacy_mode: false,
+            saturated: false,
+            focus: FocusTracker::new(),
+            warmup: WindowWarmup::Off,
+            first_event: None,
+            timings: None,
         }
     }
     
-    /// Add a keystroke event to the buffer
+    /// An extractor with every setting taken from `config`
+    pub fn from_config(config: &Config) -> Self {
+        let extractor = KeystrokeExtractor::new(config.window_seconds, config.update_interval)
+            .with_dev_mode(config.dev_mode)
+            .with_session_gap(config.session_gap_seconds)
+            .with_hop_seconds(config.hop_seconds())
+            .with_idle_records(config.emit_idle_records)
+            .with_window_warmup(config.window_warmup)
+            .with_buffer_size(config.keystroke_buffer_size)
+            .with_key_identity(config.key_identity)
+            .with_outlier_trim(config.outlier_trim)
+            .with_privacy_mode(config.privacy_mode)
+            .with_collapse_autorepeat(config.collapse_autorepeat)
+            .with_incremental_stats(config.incremental_stats);
+        match &config.target_digraphs {
+            Some(digraphs) => extractor.with_target_digraphs(digraphs.clone()),
+            None => extractor,
+        }
+    }
+    
+    /// Mark emitted features as dev-mode self-testing data
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.feature_calculator = self.feature_calculator.with_dev_mode(dev_mode);
+        self
+    }
+    
+    /// Letter pairs to emit a dedicated `digraph_<pair>_mean` latency for
+    pub fn with_target_digraphs(mut self, digraphs: Vec<String>) -> Self {
+        self.feature_calculator = self.feature_calculator.with_target_digraphs(digraphs);
+        self
+    }
+    
+    /// Idle gap (seconds without a keypress) that splits typing sessions
+    pub fn with_session_gap(mut self, gap_seconds: f64) -> Self {
+        self.feature_calculator = self.feature_calculator.with_session_gap(gap_seconds);
+        self
+    }
+    
+    /// Trim outlying dwell, flight and rhythm intervals after the hard caps
+    pub fn with_outlier_trim(mut self, outlier_trim: OutlierTrim) -> Self {
+        self.feature_calculator = self.feature_calculator.with_outlier_trim(outlier_trim);
+        self
+    }
+    
+    /// Seconds between the ends of consecutive feature windows
+    pub fn with_hop_seconds(mut self, hop_seconds: u64) -> Self {
+        self.hop_seconds = hop_seconds;
+        self
+    }
+    
+    /// Return an `IdleRecord` instead of `None` when a window is too sparse for features
+    pub fn with_idle_records(mut self, idle_records: bool) -> Self {
+        self.idle_records = idle_records;
+        self
+    }
+    
+    /// Flag or suppress windows that reach back before the first event, since they
+    /// cover less than `window_seconds` of activity
+    pub fn with_window_warmup(mut self, warmup: WindowWarmup) -> Self {
+        self.warmup = warmup;
+        self
+    }
+    
+    /// Most events kept at once; the oldest are dropped beyond it
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self.events = VecDeque::with_capacity(buffer_size);
+        self
+    }
+    
+    /// Identify keys by physical position when events carry a `code`
+    pub fn with_key_identity(mut self, key_identity: KeyIdentity) -> Self {
+        self.key_identity = key_identity;
+        self
+    }
+    
+    /// Fold held-key auto-repeat into one press before computing timing features
+    pub fn with_collapse_autorepeat(mut self, collapse_autorepeat: bool) -> Self {
+        self.feature_calculator =
+            self.feature_calculator.with_collapse_autorepeat(collapse_autorepeat);
+        self
+    }
+    
+    /// Reduce incoming keys to their category, as a collector in privacy mode does
+    pub fn with_privacy_mode(mut self, privacy_mode: bool) -> Self {
+        self.privacy_mode = privacy_mode;
+        self
+    }
+    
+    /// Keep dwell and flight statistics up to date as events arrive rather than
+    /// recomputing them each window; their quantiles become estimates (within 1%)
+    ///
+    /// Only valid without outlier trimming or auto-repeat collapsing, which need
+    /// the whole window at once.
+    pub fn with_incremental_stats(mut self, incremental: bool) -> Self {
+        self.timings = incremental.then(IncrementalTimings::default);
+        self
+    }
+    
+    /// Add a keystroke event to the buffer, evicting events too old to matter
     pub fn add_event(&mut self, timestamp: f64, key: String, event_type: String) {
-        // Keep buffer bounded
-        if self.events.len() >= 10000 {
-            self.events.pop_front();
+        self.first_event.get_or_insert(timestamp);
+        // Time-based eviction keeps the buffer sized to the window; the count cap
+        // is only a backstop against a flood of events
+        let retention_cutoff = timestamp - self.retention_seconds();
+        self.evict_before(retention_cutoff);
+        if let Some(timings) = &mut self.timings {
+            timings.evict_through(retention_cutoff);
+            timings.record(timestamp, &key, &event_type);
+        }
+        let window_start = timestamp - self.window_seconds as f64;
+        let evicted_in_window = self.events.len() >= self.buffer_size
+            && self.events.pop_front().is_some_and(|e| e.timestamp > window_start);
+        if evicted_in_window && !self.saturated {
+            warn!(
+                "Keystroke buffer full ({} events); dropping events still inside the window",
+                self.buffer_size
+            );
         }
+        self.saturated = evicted_in_window;
         
         self.events.push_back(KeystrokeEvent {
             timestamp,
@@ -35,15 +190,98 @@ impl KeystrokeExtractor {
         });
     }
     
-    /// Extract features from current buffer
-    pub fn extract_features(&self) -> Option<serde_json::Value> {
-        let events: Vec<KeystrokeEvent> = self.events.iter().cloned().collect();
+    /// Number of events currently buffered
+    pub fn buffered_events(&self) -> usize {
+        self.events.len()
+    }
+    
+    /// The buffered events, oldest first, as raw keystroke events that can be fed
+    /// back in with `EVENT_SOURCE=stdin`
+    ///
+    /// Keys are as buffered, so already mapped by `KEY_IDENTITY` or `PRIVACY_MODE`.
+    pub fn dump_buffer(&self) -> serde_json::Value {
+        self.events
+            .iter()
+            .map(|event| {
+                serde_json::json!({
+                    "type": "keystroke",
+                    "ts": (event.timestamp * 1_000_000.0).round() as u64,
+                    "key": event.key,
+                    "event": event.event_type,
+                })
+            })
+            .collect()
+    }
+    
+    /// Extract features for the next sliding window ending around now
+    pub fn extract_features(&mut self) -> Option<serde_json::Value> {
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs_f64();
         
-        self.feature_calculator.extract_features(&events, current_time)
+        self.extract_features_at(current_time)
+    }
+    
+    /// Extract features for the window one hop after the previous one
+    ///
+    /// Consecutive windows overlap by exactly `window_seconds - hop_seconds`; if `now`
+    /// has drifted more than a hop from that schedule (e.g. after a stall) the
+    /// windows re-anchor at `now`.
+    pub fn extract_features_at(&mut self, now: f64) -> Option<serde_json::Value> {
+        let features = self.begin_window(now).compute();
+        self.finish_window(features)
+    }
+    
+    /// Advance the window schedule to `now` and snapshot what the window needs
+    ///
+    /// Together with `KeystrokeWindow::compute` and `finish_window` this splits
+    /// `extract_features_at` so the computation can run off the async runtime.
+    pub fn begin_window(&mut self, now: f64) -> KeystrokeWindow {
+        let hop = self.hop_seconds as f64;
+        let window_end = match self.last_window_end {
+            Some(last) if (now - (last + hop)).abs() < hop => last + hop,
+            _ => now,
+        };
+        self.last_window_end = Some(window_end);
+        let window_start = window_end - self.window_seconds as f64;
+        
+        KeystrokeWindow {
+            timings: self.timings.as_mut().map(|timings| timings.summaries(window_start)),
+            events: self.events.iter().cloned().collect(),
+            window_end,
+            window_seconds: self.window_seconds,
+            idle_records: self.idle_records,
+            feature_calculator: self.feature_calculator.clone(),
+            active_app: self.focus.active_app().map(str::to_string),
+            contained_app_switch: self.focus.contained_switch(window_start, window_end),
+            warmup: self.warmup,
+            partial_window: !self.first_event.is_some_and(|first| window_start >= first),
+        }
+    }
+    
+    /// Add the WPM trend, which depends on earlier windows, to computed features
+    pub fn finish_window(
+        &mut self,
+        features: Option<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        let mut features = features?;
+        self.update_wpm_trend(&mut features);
+        Some(features)
+    }
+    
+    /// Fold this window's `typing_speed_wpm` into the EWMA and add `wpm_ewma` and
+    /// `wpm_trend` (the EWMA's change since the last window; positive while rising)
+    fn update_wpm_trend(&mut self, features: &mut serde_json::Value) {
+        let Some(wpm) = features.get("typing_speed_wpm").and_then(|v| v.as_f64()) else {
+            return;
+        };
+        let previous = self.wpm_ewma;
+        let ewma = previous.map_or(wpm, |prev| prev + WPM_EWMA_ALPHA * (wpm - prev));
+        self.wpm_ewma = Some(ewma);
+        
+        features["wpm_ewma"] = serde_json::json!(ewma);
+        features["wpm_trend"] = serde_json::json!(previous.map_or(0.0, |prev| ewma - prev));
     }
     
     /// Clear old events outside the window
@@ -53,8 +291,15 @@ impl KeystrokeExtractor {
             .unwrap_or_default()
             .as_secs_f64();
         
-        let cutoff_time = current_time - (self.window_seconds as f64 * 2.0);
-        
+        self.evict_before(current_time - self.retention_seconds());
+    }
+    
+    /// How far back events are kept: two windows, so late events still pair up
+    fn retention_seconds(&self) -> f64 {
+        self.window_seconds as f64 * 2.0
+    }
+    
+    fn evict_before(&mut self, cutoff_time: f64) {
         while let Some(front) = self.events.front() {
             if front.timestamp < cutoff_time {
                 self.events.pop_front();
@@ -64,3 +309,166 @@ impl KeystrokeExtractor {
         }
     }
 }
+
+/// Dwell and flight times kept in sliding windows as events arrive
+///
+/// Each time is stamped with the press it starts from, the same press the batch
+/// calculation needs inside the window to count it, so evicting through the window
+/// start leaves exactly the times the batch calculation would find.
+#[derive(Default)]
+struct IncrementalTimings {
+    /// Press time of each key currently down
+    held: HashMap<String, f64>,
+    last_press: Option<f64>,
+    dwell: SlidingStats,
+    flight: SlidingStats,
+}
+
+impl IncrementalTimings {
+    fn record(&mut self, timestamp: f64, key: &str, event_type: &str) {
+        match event_type {
+            "press" => {
+                if let Some(previous) = self.last_press {
+                    let flight = (timestamp - previous) * 1000.0;
+                    if flight > 0.0 && flight < MAX_FLIGHT_MS {
+                        self.flight.push(previous, flight);
+                    }
+                }
+                self.last_press = Some(timestamp);
+                self.held.insert(key.to_string(), timestamp);
+            }
+            "release" => {
+                if let Some(press_time) = self.held.remove(key) {
+                    let dwell = (timestamp - press_time) * 1000.0;
+                    if dwell > 0.0 && dwell < MAX_DWELL_MS {
+                        self.dwell.push(press_time, dwell);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    fn evict_through(&mut self, cutoff: f64) {
+        self.dwell.evict_through(cutoff);
+        self.flight.evict_through(cutoff);
+    }
+    
+    /// Summaries of the times whose press came after `window_start`
+    fn summaries(&mut self, window_start: f64) -> TimingSummaries {
+        self.evict_through(window_start);
+        TimingSummaries {
+            dwell: self.dwell.summary(),
+            flight: self.flight.summary(),
+        }
+    }
+}
+
+/// One window's events and settings, detached from the extractor
+pub struct KeystrokeWindow {
+    events: Vec<KeystrokeEvent>,
+    /// Dwell and flight statistics, when kept incrementally
+    timings: Option<TimingSummaries>,
+    window_end: f64,
+    window_seconds: u64,
+    idle_records: bool,
+    feature_calculator: KeystrokeFeatureCalculator,
+    active_app: Option<String>,
+    contained_app_switch: Option<bool>,
+    warmup: WindowWarmup,
+    /// Whether the window starts before the first event the extractor saw
+    partial_window: bool,
+}
+
+impl KeystrokeWindow {
+    /// Features for the window, or an `IdleRecord` when it's too sparse and idle records are on,
+    /// tagged with the `active_app` focused when the window closed and whether focus
+    /// switched apps during it; a window started before the first event is then
+    /// flagged or dropped as `WindowWarmup` says
+    pub fn compute(self) -> Option<serde_json::Value> {
+        let features = self.feature_calculator.extract_features_with(
+            &self.events,
+            self.window_end,
+            self.timings,
+        );
+        let mut features = match features {
+            Some(features) => features,
+            None if self.idle_records => {
+                let window_start = self.window_end - self.window_seconds as f64;
+                let event_count = self
+                    .events
+                    .iter()
+                    .filter(|e| e.timestamp > window_start && e.timestamp <= self.window_end)
+                    .count();
+                serde_json::to_value(IdleRecord::new(event_count, window_start, self.window_end))
+                    .ok()?
+            }
+            None => return None,
+        };
+        
+        FocusTracker::tag(&mut features, self.active_app, self.contained_app_switch);
+        self.warmup.apply(features, self.partial_window)
+    }
+}
+
+impl WindowedExtractor for KeystrokeExtractor {
+    fn add_raw_event(&mut self, event: RawEvent) {
+        // Focus changes arrive on the same channel and only set the window's context
+        if event.event_type == "app" {
+            self.focus.observe(&event, self.window_seconds as f64 * 2.0);
+            return;
+        }
+        if event.event_type != "keystroke" {
+            return;
+        }
+        if let (Some(key), Some(kind)) = (event.key, event.event) {
+            // Events without a known position keep their logical name
+            let key = match (self.key_identity, event.code.and_then(keymap::key_at_code)) {
+                _ if self.privacy_mode => keymap::privacy_key(&key).to_string(),
+                (KeyIdentity::Position, Some(position)) => position.to_string(),
+                _ => key,
+            };
+            self.add_event(event.ts as f64 / 1_000_000.0, key, kind);
+        }
+    }
+    
+    fn extract_windows_at(&mut self, now: f64) -> Vec<serde_json::Value> {
+        self.extract_features_at(now).into_iter().collect()
+    }
+}
+
+impl LiveExtractor for KeystrokeExtractor {
+    type Window = KeystrokeWindow;
+    
+    fn from_config(config: &Config) -> Self {
+        KeystrokeExtractor::from_config(config)
+    }
+    
+    fn feature_names() -> &'static [&'static str] {
+        KeystrokeFeatureCalculator::feature_names()
+    }
+    
+    fn buffered_events(&self) -> usize {
+        KeystrokeExtractor::buffered_events(self)
+    }
+    
+    fn dump_buffer(&self) -> serde_json::Value {
+        KeystrokeExtractor::dump_buffer(self)
+    }
+    
+    fn cleanup_old_events(&mut self) {
+        KeystrokeExtractor::cleanup_old_events(self)
+    }
+    
+    fn begin_window(&mut self, now: f64) -> KeystrokeWindow {
+        KeystrokeExtractor::begin_window(self, now)
+    }
+    
+    fn compute_window(window: KeystrokeWindow) -> Option<serde_json::Value> {
+        window.compute()
+    }
+    
+    fn finish_window(&mut self, features: Option<serde_json::Value>) -> Option<serde_json::Value> {
+        KeystrokeExtractor::finish_window(self, features)
+    }
+}
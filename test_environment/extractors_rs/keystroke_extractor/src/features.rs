@@ -1,6 +1,17 @@
-use std::collections::{HashMap, VecDeque};
+use crate::record::{KeystrokeFeatureRecord, DIGRAPH_RANK_BUCKETS};
+use common::{stats, OutlierTrim, Summary};
+pub use common::keymap::{key_category, modifier_key, normalize_key, KeyCategory, Modifier};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use serde_json::json;
 
+/// Dwell and flight time summaries worked out ahead of the window, e.g. kept up
+/// incrementally, used in place of recomputing them from the window's events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingSummaries {
+    pub dwell: Summary,
+    pub flight: Summary,
+}
+
 #[derive(Debug, Clone)]
 pub struct KeystrokeEvent {
     pub timestamp: f64,
@@ -8,83 +19,326 @@ pub struct KeystrokeEvent {
     pub event_type: String, // "press" or "release"
 }
 
+/// Canonical feature order for model input
+const FEATURE_NAMES: &[&str] = KeystrokeFeatureRecord::FEATURE_NAMES;
+
+// `feature_names()` is the documented feature set, no more and no less
+const _: () = assert!(FEATURE_NAMES.len() == crate::record::FEATURE_COUNT);
+
+/// Idle time between keypresses that ends a typing session
+pub const DEFAULT_SESSION_GAP_SECONDS: f64 = 2.0;
+
+/// Most frequent English bigrams, tracked by default as `digraph_<pair>_mean`
+pub const DEFAULT_TARGET_DIGRAPHS: &[&str] = &["th", "he", "in", "er", "an", "re", "on", "at", "en", "nd"];
+
+/// Dwell times (ms) at or above this are a missed release, not a key hold
+pub const MAX_DWELL_MS: f64 = 1000.0;
+
+/// Gaps (ms) between presses at or above this are a pause, not a flight time
+pub const MAX_FLIGHT_MS: f64 = 2000.0;
+
+/// Longest modifier hold (ms) counted towards `chord_hold_mean`; longer holds are a
+/// stuck key or a missed release
+const MAX_CHORD_HOLD_MS: f64 = 5000.0;
+
+/// Features of the typing rhythm, all padded when a window has no press intervals
+const RHYTHM_FEATURES: &[&str] = &[
+    "rhythm_consistency", "burst_frequency", "pause_frequency", "avg_burst_speed",
+    "avg_pause_duration", "rhythm_variation", "typing_speed_wpm", "rhythm_stability",
+    "interval_entropy", "interval_mode",
+];
+
+/// Width (ms) of the press interval histogram bins behind `interval_entropy` and
+/// `interval_mode`; finer than this is mostly timer and scheduling jitter
+const INTERVAL_BIN_MS: f64 = 25.0;
+
+/// Keys typed after a correction whose press intervals make up `retype_burst_speed`
+const RETYPE_KEYS: usize = 5;
+
+/// Longest gap (ms) between presses of one key that can be OS auto-repeat; repeat
+/// rates are 20-50 Hz, faster than anyone re-presses a key
+const AUTOREPEAT_MAX_INTERVAL_MS: f64 = 50.0;
+
+/// How much (ms) consecutive gaps in an auto-repeat run may differ; the OS repeats
+/// at a fixed rate, so only scheduling jitter is allowed
+const AUTOREPEAT_JITTER_MS: f64 = 10.0;
+
+/// Longest wait (ms) before the OS starts repeating a held key
+const AUTOREPEAT_MAX_DELAY_MS: f64 = 1000.0;
+
+/// Steady repeats needed before a run of presses counts as auto-repeat
+const AUTOREPEAT_MIN_REPEATS: usize = 2;
+
+/// Which hand types a key on a standard QWERTY layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+/// QWERTY hand map keyed by normalized key name (see `normalize_key`)
+const LEFT_HAND_KEYS: &[&str] = &[
+    "`", "1", "2", "3", "4", "5",
+    "q", "w", "e", "r", "t",
+    "a", "s", "d", "f", "g",
+    "z", "x", "c", "v", "b",
+    "backquote", "tab", "capslock", "shiftleft", "controlleft", "alt",
+];
+const RIGHT_HAND_KEYS: &[&str] = &[
+    "6", "7", "8", "9", "0", "-", "=",
+    "y", "u", "i", "o", "p", "[", "]", "\\",
+    "h", "j", "k", "l", ";", "'",
+    "n", "m", ",", ".", "/",
+    "minus", "equal", "leftbracket", "rightbracket", "backslash",
+    "semicolon", "quote", "comma", "dot", "slash",
+    "return", "backspace", "shiftright", "controlright", "altgr",
+];
+
+/// Hand a key belongs to, or `None` for keys either hand uses (space, arrows, ...)
+pub fn key_hand(key: &str) -> Option<Hand> {
+    let key = normalize_key(key);
+    if LEFT_HAND_KEYS.contains(&key.as_str()) {
+        Some(Hand::Left)
+    } else if RIGHT_HAND_KEYS.contains(&key.as_str()) {
+        Some(Hand::Right)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone)]
 pub struct KeystrokeFeatureCalculator {
     window_seconds: u64,
+    dev_mode: bool,
+    target_digraphs: Vec<String>,
+    session_gap_seconds: f64,
+    outlier_trim: OutlierTrim,
+    collapse_autorepeat: bool,
 }
 
 impl KeystrokeFeatureCalculator {
     pub fn new(window_seconds: u64) -> Self {
-        KeystrokeFeatureCalculator { window_seconds }
+        KeystrokeFeatureCalculator {
+            window_seconds,
+            dev_mode: false,
+            target_digraphs: DEFAULT_TARGET_DIGRAPHS.iter().map(|d| d.to_string()).collect(),
+            session_gap_seconds: DEFAULT_SESSION_GAP_SECONDS,
+            outlier_trim: OutlierTrim::None,
+            collapse_autorepeat: false,
+        }
+    }
+    
+    /// Mark emitted features as dev-mode self-testing data
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.dev_mode = dev_mode;
+        self
+    }
+    
+    /// Letter pairs to emit a dedicated `digraph_<pair>_mean` latency for
+    pub fn with_target_digraphs(mut self, digraphs: Vec<String>) -> Self {
+        self.target_digraphs = digraphs.into_iter().map(|d| d.to_lowercase()).collect();
+        self
+    }
+    
+    /// Idle gap (seconds without a keypress) that splits typing sessions
+    pub fn with_session_gap(mut self, gap_seconds: f64) -> Self {
+        self.session_gap_seconds = gap_seconds;
+        self
+    }
+    
+    /// Trim outlying dwell, flight and rhythm intervals after the hard caps
+    pub fn with_outlier_trim(mut self, outlier_trim: OutlierTrim) -> Self {
+        self.outlier_trim = outlier_trim;
+        self
+    }
+    
+    /// Fold each auto-repeat run into its first press before computing timing features
+    pub fn with_collapse_autorepeat(mut self, collapse_autorepeat: bool) -> Self {
+        self.collapse_autorepeat = collapse_autorepeat;
+        self
+    }
+    
+    /// Names of the per-pair digraph features, which depend on the configured targets
+    pub fn digraph_feature_names(&self) -> Vec<String> {
+        self.target_digraphs
+            .iter()
+            .map(|pair| format!("digraph_{}_mean", pair))
+            .collect()
+    }
+    
+    /// Names of every fixed feature, in canonical column order
+    pub fn feature_names() -> &'static [&'static str] {
+        FEATURE_NAMES
     }
     
-    /// Extract 140 keystroke features from events
+    /// Flatten extracted features into `feature_names()` order, 0.0 for missing keys
+    pub fn to_vector(features: &serde_json::Value) -> Vec<f64> {
+        FEATURE_NAMES
+            .iter()
+            .map(|name| features.get(*name).and_then(|v| v.as_f64()).unwrap_or(0.0))
+            .collect()
+    }
+    
+    /// Extract the `FEATURE_COUNT` keystroke features, the target digraphs and the
+    /// window metadata from events
     pub fn extract_features(
         &self,
         events: &[KeystrokeEvent],
         current_time: f64,
     ) -> Option<serde_json::Value> {
+        self.extract_features_with(events, current_time, None)
+    }
+    
+    /// `extract_features`, taking the dwell and flight statistics from `timings` if given
+    pub fn extract_features_with(
+        &self,
+        events: &[KeystrokeEvent],
+        current_time: f64,
+        timings: Option<TimingSummaries>,
+    ) -> Option<serde_json::Value> {
+        let record = self.extract_record_with(events, current_time, timings)?;
+        let features = record.to_json();
+        debug_assert_eq!(
+            features.as_object().map_or(0, |fields| fields.len()),
+            KeystrokeFeatureRecord::field_count() + record.target_digraphs.len(),
+            "keystroke window doesn't have the documented keys"
+        );
+        Some(features)
+    }
+    
+    /// Feature record for the window ending at `current_time`; `None` below 10 events
+    pub fn extract_record(
+        &self,
+        events: &[KeystrokeEvent],
+        current_time: f64,
+    ) -> Option<KeystrokeFeatureRecord> {
+        self.extract_record_with(events, current_time, None)
+    }
+    
+    /// `extract_record`, taking the dwell and flight statistics from `timings` if given
+    pub fn extract_record_with(
+        &self,
+        events: &[KeystrokeEvent],
+        current_time: f64,
+        timings: Option<TimingSummaries>,
+    ) -> Option<KeystrokeFeatureRecord> {
         let cutoff_time = current_time - self.window_seconds as f64;
-        let recent: Vec<&KeystrokeEvent> = events
+        let mut recent: Vec<&KeystrokeEvent> = events
             .iter()
-            .filter(|e| e.timestamp > cutoff_time)
+            .filter(|e| e.timestamp > cutoff_time && e.timestamp <= current_time)
             .collect();
         
         if recent.len() < 10 {
             return None;
         }
         
-        let mut features = HashMap::new();
+        let mut record = KeystrokeFeatureRecord::default();
+        
+        // Share of presses that are auto-repeat (1 feature); collapsing leaves one
+        // press per held key, held until the run's final release
+        let repeats = autorepeat_events(&recent);
+        let presses = recent.iter().filter(|e| e.event_type == "press").count();
+        let repeat_presses = recent
+            .iter()
+            .zip(&repeats)
+            .filter(|(e, &repeat)| repeat && e.event_type == "press")
+            .count();
+        record.autorepeat_fraction = repeat_presses as f64 / presses.max(1) as f64;
+        if self.collapse_autorepeat {
+            recent = recent
+                .into_iter()
+                .zip(repeats)
+                .filter(|(_, repeat)| !repeat)
+                .map(|(e, _)| e)
+                .collect();
+        }
+        
+        let timings = timings.unwrap_or_else(|| TimingSummaries {
+            dwell: Summary::of(&self.calculate_dwell_times(&recent)),
+            flight: Summary::of(&self.calculate_flight_times(&recent)),
+        });
         
-        // Calculate dwell times (8 features)
-        let dwell_times = self.calculate_dwell_times(&recent);
-        if !dwell_times.is_empty() {
-            features.insert("dwell_mean".to_string(), self.mean(&dwell_times));
-            features.insert("dwell_std".to_string(), self.std_dev(&dwell_times));
-            features.insert("dwell_min".to_string(), self.min(&dwell_times));
-            features.insert("dwell_max".to_string(), self.max(&dwell_times));
-            features.insert("dwell_median".to_string(), self.median(&dwell_times));
-            features.insert("dwell_q25".to_string(), self.percentile(&dwell_times, 25));
-            features.insert("dwell_q75".to_string(), self.percentile(&dwell_times, 75));
-            features.insert("dwell_range".to_string(), self.max(&dwell_times) - self.min(&dwell_times));
+        // Dwell time statistics (8 features)
+        let dwell = timings.dwell;
+        if dwell.count == 0 {
+            record.mark_padded(|name| is_summary_feature(name, "dwell_"));
+        } else {
+            record.dwell_mean = dwell.mean;
+            record.dwell_std = dwell.std_dev;
+            record.dwell_min = dwell.min;
+            record.dwell_max = dwell.max;
+            record.dwell_median = dwell.median;
+            record.dwell_q25 = dwell.q25;
+            record.dwell_q75 = dwell.q75;
+            record.dwell_range = dwell.max - dwell.min;
         }
         
-        // Calculate flight times (8 features)
-        let flight_times = self.calculate_flight_times(&recent);
-        if !flight_times.is_empty() {
-            features.insert("flight_mean".to_string(), self.mean(&flight_times));
-            features.insert("flight_std".to_string(), self.std_dev(&flight_times));
-            features.insert("flight_min".to_string(), self.min(&flight_times));
-            features.insert("flight_max".to_string(), self.max(&flight_times));
-            features.insert("flight_median".to_string(), self.median(&flight_times));
-            features.insert("flight_q25".to_string(), self.percentile(&flight_times, 25));
-            features.insert("flight_q75".to_string(), self.percentile(&flight_times, 75));
-            features.insert("flight_range".to_string(), self.max(&flight_times) - self.min(&flight_times));
+        // Flight time statistics (8 features)
+        let flight = timings.flight;
+        if flight.count == 0 {
+            record.mark_padded(|name| is_summary_feature(name, "flight_"));
+        } else {
+            record.flight_mean = flight.mean;
+            record.flight_std = flight.std_dev;
+            record.flight_min = flight.min;
+            record.flight_max = flight.max;
+            record.flight_median = flight.median;
+            record.flight_q25 = flight.q25;
+            record.flight_q75 = flight.q75;
+            record.flight_range = flight.max - flight.min;
         }
         
-        // Calculate digraph features (20 features)
+        // Calculate digraph latency rank buckets (20 features)
         let digraphs = self.calculate_digraphs(&recent);
-        for (i, digraph) in digraphs.iter().enumerate().take(20) {
-            features.insert(format!("digraph_{}_mean", i), *digraph);
+        if digraphs.is_empty() {
+            record.mark_padded(|name| name.starts_with("digraph_rank_"));
         }
-        // Pad with zeros
-        for i in digraphs.len()..20 {
-            features.insert(format!("digraph_{}_mean", i), 0.0);
+        for (field, digraph) in record.digraph_ranks_mut().into_iter().zip(digraphs) {
+            *field = digraph;
         }
         
-        // Calculate error patterns (4 features)
-        let errors = self.calculate_error_patterns(&recent);
-        features.extend(errors);
+        // Calculate per-pair digraph features (one per target digraph)
+        record.target_digraphs = self.calculate_pair_digraphs(&recent);
+        
+        // Calculate trigraph features (3 features)
+        self.calculate_trigraphs(&recent, &mut record);
+        
+        // Calculate per-hand timing split (8 features)
+        self.calculate_hand_split(&recent, &mut record);
+        
+        // Calculate error patterns and correction timing (6 features)
+        self.calculate_error_patterns(&recent, &mut record);
+        
+        // Calculate rhythm features (10 features)
+        self.calculate_rhythm(&recent, &mut record);
+        
+        // Calculate typing session features (4 features)
+        self.calculate_sessions(&recent, self.session_gap_seconds, &mut record);
         
-        // Calculate rhythm features (8 features)
-        let rhythm = self.calculate_rhythm(&recent);
-        features.extend(rhythm);
+        // Calculate key category distribution (5 features)
+        self.calculate_key_categories(&recent, &mut record);
+        
+        // Calculate modifier chord features (3 features)
+        self.calculate_chords(&recent, &mut record);
+        
+        // Calculate key rollover features (3 features)
+        self.calculate_rollover(&recent, &mut record);
         
         // Add metadata
-        let total_keys = recent.iter().filter(|e| e.event_type == "press").count() as f64;
-        features.insert("total_keys".to_string(), total_keys);
-        features.insert("dev_mode".to_string(), 0.0);
+        record.total_keys = recent.iter().filter(|e| e.event_type == "press").count() as f64;
+        record.dev_mode = if self.dev_mode { 1.0 } else { 0.0 };
+        
+        // How densely the window was actually sampled, so consumers can spot machines
+        // (or gaps) whose event rate differs from what a model was trained on
+        let timestamps: Vec<f64> = recent.iter().map(|e| e.timestamp).collect();
+        record.observed_event_rate = stats::event_rate(&timestamps);
+        record.window_coverage = stats::coverage(&timestamps, self.window_seconds as f64);
+        
+        // The exact (window_start, window_end] span these features cover
+        record.window_start = cutoff_time;
+        record.window_end = current_time;
         
-        Some(serde_json::to_value(features).unwrap())
+        Some(record)
     }
     
     fn calculate_dwell_times(&self, events: &[&KeystrokeEvent]) -> Vec<f64> {
@@ -97,14 +351,14 @@ impl KeystrokeFeatureCalculator {
             } else if event.event_type == "release" {
                 if let Some(press_time) = key_presses.get(&event.key) {
                     let dwell = (event.timestamp - press_time) * 1000.0;
-                    if dwell > 0.0 && dwell < 1000.0 {
+                    if dwell > 0.0 && dwell < MAX_DWELL_MS {
                         times.push(dwell);
                     }
                     key_presses.remove(&event.key);
                 }
             }
         }
-        times
+        self.outlier_trim.apply(times)
     }
     
     fn calculate_flight_times(&self, events: &[&KeystrokeEvent]) -> Vec<f64> {
@@ -117,13 +371,20 @@ impl KeystrokeFeatureCalculator {
         
         for i in 0..presses.len().saturating_sub(1) {
             let flight = (presses[i + 1] - presses[i]) * 1000.0;
-            if flight > 0.0 && flight < 2000.0 {
+            if flight > 0.0 && flight < MAX_FLIGHT_MS {
                 times.push(flight);
             }
         }
-        times
+        self.outlier_trim.apply(times)
     }
     
+    /// Press-to-press latencies sorted and split into `DIGRAPH_RANK_BUCKETS` equal
+    /// shares, fastest first, each reduced to its mean
+    ///
+    /// Bucket `i` always covers the same share of the distribution, however many
+    /// latencies the window holds, so the features line up across windows. With
+    /// fewer latencies than buckets, neighbouring buckets repeat the same latency;
+    /// with none at all, the result is empty.
     fn calculate_digraphs(&self, events: &[&KeystrokeEvent]) -> Vec<f64> {
         let mut times = Vec::new();
         let presses: Vec<&KeystrokeEvent> = events
@@ -138,37 +399,232 @@ impl KeystrokeFeatureCalculator {
                 times.push(time_diff);
             }
         }
-        times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        times.truncate(20);
-        times
+        if times.is_empty() {
+            return Vec::new();
+        }
+        times.sort_by(|a, b| a.total_cmp(b));
+        
+        let n = times.len();
+        (0..DIGRAPH_RANK_BUCKETS)
+            .map(|i| {
+                let start = (i * n / DIGRAPH_RANK_BUCKETS).min(n - 1);
+                let end = ((i + 1) * n / DIGRAPH_RANK_BUCKETS).max(start + 1);
+                stats::mean(&times[start..end])
+            })
+            .collect()
+    }
+    
+    /// Mean press-to-press latency for each target letter pair, 0.0 if it didn't occur
+    fn calculate_pair_digraphs(&self, events: &[&KeystrokeEvent]) -> BTreeMap<String, f64> {
+        let mut latencies: HashMap<String, Vec<f64>> = HashMap::new();
+        let presses: Vec<&KeystrokeEvent> = events
+            .iter()
+            .filter(|e| e.event_type == "press")
+            .copied()
+            .collect();
+        
+        for pair in presses.windows(2) {
+            let latency = (pair[1].timestamp - pair[0].timestamp) * 1000.0;
+            if latency <= 0.0 || latency >= 2000.0 {
+                continue;
+            }
+            let digraph = format!("{}{}", normalize_key(&pair[0].key), normalize_key(&pair[1].key));
+            if self.target_digraphs.contains(&digraph) {
+                latencies.entry(digraph).or_default().push(latency);
+            }
+        }
+        
+        self.target_digraphs
+            .iter()
+            .map(|pair| {
+                let mean = latencies.get(pair).map(|times| stats::mean(times)).unwrap_or(0.0);
+                (format!("digraph_{}_mean", pair), mean)
+            })
+            .collect()
+    }
+    
+    /// Summarize latencies spanning three consecutive key presses
+    fn calculate_trigraphs(&self, events: &[&KeystrokeEvent], record: &mut KeystrokeFeatureRecord) {
+        let mut times = Vec::new();
+        let presses: Vec<f64> = events
+            .iter()
+            .filter(|e| e.event_type == "press")
+            .map(|e| e.timestamp)
+            .collect();
+        
+        for i in 0..presses.len().saturating_sub(2) {
+            let first = (presses[i + 1] - presses[i]) * 1000.0;
+            let second = (presses[i + 2] - presses[i + 1]) * 1000.0;
+            if first > 0.0 && first < 2000.0 && second > 0.0 && second < 2000.0 {
+                times.push(first + second);
+            }
+        }
+        
+        // Left at zero without any trigraphs
+        if times.is_empty() {
+            record.mark_padded(|name| name.starts_with("trigraph_"));
+            return;
+        }
+        
+        record.trigraph_mean = stats::mean(&times);
+        record.trigraph_std = stats::std_dev(&times);
+        record.trigraph_median = stats::median(&times);
     }
     
-    fn calculate_error_patterns(&self, events: &[&KeystrokeEvent]) -> HashMap<String, f64> {
-        let mut features = HashMap::new();
+    /// Split dwell times by hand and flight times by hand transition
+    fn calculate_hand_split(
+        &self,
+        events: &[&KeystrokeEvent],
+        record: &mut KeystrokeFeatureRecord,
+    ) {
+        let mut dwell_left = Vec::new();
+        let mut dwell_right = Vec::new();
+        let mut key_presses: HashMap<String, f64> = HashMap::new();
+        
+        for event in events {
+            if event.event_type == "press" {
+                key_presses.insert(event.key.clone(), event.timestamp);
+            } else if event.event_type == "release" {
+                if let Some(press_time) = key_presses.remove(&event.key) {
+                    let dwell = (event.timestamp - press_time) * 1000.0;
+                    if dwell > 0.0 && dwell < 1000.0 {
+                        match key_hand(&event.key) {
+                            Some(Hand::Left) => dwell_left.push(dwell),
+                            Some(Hand::Right) => dwell_right.push(dwell),
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        
+        // Flight times bucketed by (from hand, to hand)
+        let mut flights: HashMap<(Hand, Hand), Vec<f64>> = HashMap::new();
+        let presses: Vec<&KeystrokeEvent> = events
+            .iter()
+            .filter(|e| e.event_type == "press")
+            .copied()
+            .collect();
+        
+        for pair in presses.windows(2) {
+            let flight = (pair[1].timestamp - pair[0].timestamp) * 1000.0;
+            if flight <= 0.0 || flight >= 2000.0 {
+                continue;
+            }
+            if let (Some(from), Some(to)) = (key_hand(&pair[0].key), key_hand(&pair[1].key)) {
+                flights.entry((from, to)).or_default().push(flight);
+            }
+        }
+        
+        record.dwell_left_mean = stats::mean(&dwell_left);
+        record.dwell_left_std = stats::std_dev(&dwell_left);
+        record.dwell_right_mean = stats::mean(&dwell_right);
+        record.dwell_right_std = stats::std_dev(&dwell_right);
+        
+        for (field, from, to) in [
+            (&mut record.flight_ll_mean, Hand::Left, Hand::Left),
+            (&mut record.flight_lr_mean, Hand::Left, Hand::Right),
+            (&mut record.flight_rl_mean, Hand::Right, Hand::Left),
+            (&mut record.flight_rr_mean, Hand::Right, Hand::Right),
+        ] {
+            *field = flights.get(&(from, to)).map(|times| stats::mean(times)).unwrap_or(0.0);
+        }
+        
+        let unseen_transitions: Vec<&str> = [
+            ("flight_ll_mean", Hand::Left, Hand::Left),
+            ("flight_lr_mean", Hand::Left, Hand::Right),
+            ("flight_rl_mean", Hand::Right, Hand::Left),
+            ("flight_rr_mean", Hand::Right, Hand::Right),
+        ]
+        .into_iter()
+        .filter(|(_, from, to)| !flights.contains_key(&(*from, *to)))
+        .map(|(name, _, _)| name)
+        .collect();
+        record.mark_padded(|name| {
+            (dwell_left.is_empty() && name.starts_with("dwell_left_"))
+                || (dwell_right.is_empty() && name.starts_with("dwell_right_"))
+                || unseen_transitions.contains(&name)
+        });
+    }
+    
+    fn calculate_error_patterns(
+        &self,
+        events: &[&KeystrokeEvent],
+        record: &mut KeystrokeFeatureRecord,
+    ) {
         let total_keys = events.iter().filter(|e| e.event_type == "press").count();
         let backspace_count = events
             .iter()
-            .filter(|e| e.event_type == "press" && (e.key.contains("BackSpace") || e.key.contains("Delete")))
+            .filter(|e| e.event_type == "press" && is_correction_key(&e.key))
             .count();
         
-        features.insert(
-            "backspace_frequency".to_string(),
-            backspace_count as f64 / total_keys.max(1) as f64,
-        );
-        features.insert("backspace_count".to_string(), backspace_count as f64);
-        features.insert(
-            "correction_rate".to_string(),
-            backspace_count as f64 / (total_keys - backspace_count).max(1) as f64,
-        );
-        features.insert(
-            "clean_typing_ratio".to_string(),
-            (total_keys - backspace_count) as f64 / total_keys.max(1) as f64,
-        );
-        features
+        record.backspace_frequency = backspace_count as f64 / total_keys.max(1) as f64;
+        record.backspace_count = backspace_count as f64;
+        let clean_keys = total_keys - backspace_count;
+        record.correction_rate = backspace_count as f64 / clean_keys.max(1) as f64;
+        record.clean_typing_ratio = clean_keys as f64 / total_keys.max(1) as f64;
+        
+        self.calculate_correction_timing(events, record);
+    }
+    
+    /// How quickly mistakes are noticed and fixed
+    ///
+    /// A correction is a run of Backspace/Delete presses. Its latency runs from the
+    /// press of the key before it, the one taken as the mistake, to the first
+    /// correcting press; the re-typing burst is the press intervals of up to
+    /// `RETYPE_KEYS` keys typed after it, cut at the first pause. Modifiers are left
+    /// out so a Shift between the typo and the fix doesn't stand in for the typo.
+    fn calculate_correction_timing(
+        &self,
+        events: &[&KeystrokeEvent],
+        record: &mut KeystrokeFeatureRecord,
+    ) {
+        let presses: Vec<&KeystrokeEvent> = events
+            .iter()
+            .filter(|e| e.event_type == "press" && modifier_key(&e.key).is_none())
+            .copied()
+            .collect();
+        
+        let mut latencies = Vec::new();
+        let mut retype_intervals = Vec::new();
+        for (i, pair) in presses.windows(2).enumerate() {
+            let (before, after) = (pair[0], pair[1]);
+            match (is_correction_key(&before.key), is_correction_key(&after.key)) {
+                (false, true) => {
+                    let latency = (after.timestamp - before.timestamp) * 1000.0;
+                    if latency > 0.0 && latency < MAX_FLIGHT_MS {
+                        latencies.push(latency);
+                    }
+                }
+                (true, false) => {
+                    let retyped: Vec<f64> = presses[i + 1..]
+                        .iter()
+                        .take(RETYPE_KEYS)
+                        .take_while(|e| !is_correction_key(&e.key))
+                        .map(|e| e.timestamp)
+                        .collect();
+                    retype_intervals.extend(
+                        retyped
+                            .windows(2)
+                            .map(|keys| (keys[1] - keys[0]) * 1000.0)
+                            .take_while(|&interval| interval > 0.0 && interval < MAX_FLIGHT_MS),
+                    );
+                }
+                _ => {}
+            }
+        }
+        
+        record.correction_latency_mean = stats::mean(&latencies);
+        record.retype_burst_speed = stats::mean(&retype_intervals);
+        if latencies.is_empty() {
+            record.mark_padded(|name| name == "correction_latency_mean");
+        }
+        if retype_intervals.is_empty() {
+            record.mark_padded(|name| name == "retype_burst_speed");
+        }
     }
     
-    fn calculate_rhythm(&self, events: &[&KeystrokeEvent]) -> HashMap<String, f64> {
-        let mut features = HashMap::new();
+    fn calculate_rhythm(&self, events: &[&KeystrokeEvent], record: &mut KeystrokeFeatureRecord) {
         let mut intervals = Vec::new();
         let presses: Vec<f64> = events
             .iter()
@@ -182,71 +638,306 @@ impl KeystrokeFeatureCalculator {
                 intervals.push(interval);
             }
         }
+        let intervals = self.outlier_trim.apply(intervals);
         
         if intervals.is_empty() {
-            for i in 0..8 {
-                features.insert(format!("rhythm_{}", i), 0.0);
-            }
-            return features;
+            record.mark_padded(|name| RHYTHM_FEATURES.contains(&name));
+            return;
         }
         
-        let burst_threshold = self.median(&intervals);
+        let burst_threshold = stats::median(&intervals);
         let bursts: Vec<f64> = intervals.iter().filter(|&&i| i < burst_threshold).copied().collect();
         let pauses: Vec<f64> = intervals.iter().filter(|&&i| i >= burst_threshold).copied().collect();
+        // Even intervals put everything at or above the median
+        if bursts.is_empty() {
+            record.mark_padded(|name| name == "avg_burst_speed");
+        }
         
-        features.insert(
-            "rhythm_consistency".to_string(),
-            1.0 - (self.std_dev(&intervals) / self.mean(&intervals).max(1.0)),
-        );
-        features.insert("burst_frequency".to_string(), bursts.len() as f64 / intervals.len() as f64);
-        features.insert("pause_frequency".to_string(), pauses.len() as f64 / intervals.len() as f64);
-        features.insert("avg_burst_speed".to_string(), if bursts.is_empty() { 0.0 } else { self.mean(&bursts) });
-        features.insert("avg_pause_duration".to_string(), if pauses.is_empty() { 0.0 } else { self.mean(&pauses) });
-        features.insert("rhythm_variation".to_string(), self.std_dev(&intervals));
-        features.insert("typing_speed_wpm".to_string(), 60000.0 / self.mean(&intervals).max(1.0) / 5.0);
-        features.insert("rhythm_stability".to_string(), 1.0 / (1.0 + self.variance(&intervals)));
-        features
-    }
-    
-    // Utility statistics functions
-    fn mean(&self, values: &[f64]) -> f64 {
-        if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+        record.rhythm_consistency =
+            1.0 - (stats::std_dev(&intervals) / stats::mean(&intervals).max(1.0));
+        record.burst_frequency = bursts.len() as f64 / intervals.len() as f64;
+        record.pause_frequency = pauses.len() as f64 / intervals.len() as f64;
+        record.avg_burst_speed = stats::mean(&bursts);
+        record.avg_pause_duration = stats::mean(&pauses);
+        record.rhythm_variation = stats::std_dev(&intervals);
+        record.typing_speed_wpm = 60000.0 / stats::mean(&intervals).max(1.0) / 5.0;
+        record.rhythm_stability = 1.0 / (1.0 + stats::variance(&intervals));
+        
+        // Shape of the interval histogram: a metronomic typist fills one bin, an
+        // irregular one spreads over many. Timing only, so nothing about what was typed.
+        let mut bins: BTreeMap<u64, usize> = BTreeMap::new();
+        for interval in &intervals {
+            *bins.entry((interval / INTERVAL_BIN_MS) as u64).or_default() += 1;
+        }
+        let total = intervals.len() as f64;
+        record.interval_entropy = bins
+            .values()
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        // Ties go to the shortest interval; the mode is the bin's centre
+        let (modal_bin, _) = bins
+            .iter()
+            .fold((0, 0), |best, (&bin, &count)| if count > best.1 { (bin, count) } else { best });
+        record.interval_mode = (modal_bin as f64 + 0.5) * INTERVAL_BIN_MS;
     }
     
-    fn std_dev(&self, values: &[f64]) -> f64 {
-        let mean = self.mean(values);
-        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
-        variance.sqrt()
+    /// Split presses into bursts separated by more than `gap_seconds` of idle time
+    fn calculate_sessions(
+        &self,
+        events: &[&KeystrokeEvent],
+        gap_seconds: f64,
+        record: &mut KeystrokeFeatureRecord,
+    ) {
+        let presses: Vec<f64> = events
+            .iter()
+            .filter(|e| e.event_type == "press")
+            .map(|e| e.timestamp)
+            .collect();
+        
+        if presses.is_empty() {
+            record.mark_padded(|name| name.starts_with("session_"));
+            return;
+        }
+        
+        let mut session_keys = vec![1usize];
+        let mut gaps = Vec::new();
+        for pair in presses.windows(2) {
+            let gap = pair[1] - pair[0];
+            if gap > gap_seconds {
+                gaps.push(gap * 1000.0);
+                session_keys.push(1);
+            } else if let Some(keys) = session_keys.last_mut() {
+                *keys += 1;
+            }
+        }
+        
+        let lengths: Vec<f64> = session_keys.iter().map(|&k| k as f64).collect();
+        record.session_count = lengths.len() as f64;
+        record.session_mean_keys = stats::mean(&lengths);
+        record.session_gap_mean = stats::mean(&gaps);
+        record.session_longest_burst = stats::max(&lengths);
+        // One session has no gap between sessions
+        if gaps.is_empty() {
+            record.mark_padded(|name| name == "session_gap_mean");
+        }
     }
     
-    fn variance(&self, values: &[f64]) -> f64 {
-        let mean = self.mean(values);
-        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+    /// Share of categorized presses in each `KeyCategory`; modifiers aren't counted
+    fn calculate_key_categories(
+        &self,
+        events: &[&KeystrokeEvent],
+        record: &mut KeystrokeFeatureRecord,
+    ) {
+        let mut counts: HashMap<KeyCategory, usize> = HashMap::new();
+        for event in events.iter().filter(|e| e.event_type == "press") {
+            if let Some(category) = key_category(&event.key) {
+                *counts.entry(category).or_insert(0) += 1;
+            }
+        }
+        
+        if counts.is_empty() {
+            record.mark_padded(|name| name.starts_with("frac_"));
+        }
+        let total = counts.values().sum::<usize>().max(1) as f64;
+        for (field, category) in [
+            (&mut record.frac_alpha, KeyCategory::Alpha),
+            (&mut record.frac_digit, KeyCategory::Digit),
+            (&mut record.frac_symbol, KeyCategory::Symbol),
+            (&mut record.frac_nav, KeyCategory::Navigation),
+            (&mut record.frac_space, KeyCategory::Whitespace),
+        ] {
+            *field = counts.get(&category).copied().unwrap_or(0) as f64 / total;
+        }
     }
     
-    fn min(&self, values: &[f64]) -> f64 {
-        values.iter().cloned().fold(f64::INFINITY, f64::min)
+    /// Shortcut chords: presses made while Ctrl, Alt or Cmd was held
+    ///
+    /// A chord is the set of held modifiers (Shift included, so Ctrl+Shift+T differs
+    /// from Ctrl+T) plus the key pressed. Its hold time runs from the shortcut
+    /// modifier's press to its release, and only counts if a key was pressed meanwhile.
+    fn calculate_chords(&self, events: &[&KeystrokeEvent], record: &mut KeystrokeFeatureRecord) {
+        // Held modifier -> (press time, whether a key was pressed while it was held)
+        let mut held: BTreeMap<Modifier, (f64, bool)> = BTreeMap::new();
+        let mut chords: BTreeSet<(Vec<Modifier>, String)> = BTreeSet::new();
+        let mut holds = Vec::new();
+        let mut keys = 0usize;
+        let mut chorded_keys = 0usize;
+        
+        for event in events {
+            match (event.event_type.as_str(), modifier_key(&event.key)) {
+                // Auto-repeat re-sends the press; the hold started at the first one
+                ("press", Some(modifier)) => {
+                    held.entry(modifier).or_insert((event.timestamp, false));
+                }
+                ("release", Some(modifier)) => {
+                    if let Some((press_time, true)) = held.remove(&modifier) {
+                        let hold = (event.timestamp - press_time) * 1000.0;
+                        if hold > 0.0 && hold < MAX_CHORD_HOLD_MS {
+                            holds.push(hold);
+                        }
+                    }
+                }
+                ("press", None) => {
+                    keys += 1;
+                    if !held.keys().any(|m| m.is_shortcut()) {
+                        continue;
+                    }
+                    chorded_keys += 1;
+                    chords.insert((held.keys().copied().collect(), normalize_key(&event.key)));
+                    for (_, chorded) in held.values_mut() {
+                        *chorded = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        
+        record.modifier_key_fraction = chorded_keys as f64 / keys.max(1) as f64;
+        record.chord_distinct_count = chords.len() as f64;
+        record.chord_hold_mean = stats::mean(&holds);
+        if holds.is_empty() {
+            record.mark_padded(|name| name == "chord_hold_mean");
+        }
     }
     
-    fn max(&self, values: &[f64]) -> f64 {
-        values.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+    /// Key rollover: the next key pressed before the previous one is released
+    ///
+    /// Holds run from a key's press to its release and are taken in press order;
+    /// modifiers are left out, since Shift held through a letter is a chord, not
+    /// rollover. Each hold overlaps the next if that was pressed before it ended,
+    /// by the time both keys were down. Press-to-press flight times can't show
+    /// this; it's the habit of fast touch-typists.
+    fn calculate_rollover(&self, events: &[&KeystrokeEvent], record: &mut KeystrokeFeatureRecord) {
+        let mut pressed: HashMap<&str, f64> = HashMap::new();
+        let mut holds: Vec<(f64, f64)> = Vec::new();
+        for event in events {
+            if modifier_key(&event.key).is_some() {
+                continue;
+            }
+            match event.event_type.as_str() {
+                // Auto-repeat re-sends the press; the hold started at the first one
+                "press" => {
+                    pressed.entry(event.key.as_str()).or_insert(event.timestamp);
+                }
+                "release" => {
+                    if let Some(press_time) = pressed.remove(event.key.as_str()) {
+                        let hold = (event.timestamp - press_time) * 1000.0;
+                        if hold > 0.0 && hold < MAX_DWELL_MS {
+                            holds.push((press_time, event.timestamp));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        holds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        
+        if holds.len() < 2 {
+            record.mark_padded(|name| name.starts_with("rollover_"));
+            return;
+        }
+        
+        let overlaps: Vec<f64> = holds
+            .windows(2)
+            .map(|pair| (pair[0].1.min(pair[1].1) - pair[1].0) * 1000.0)
+            .filter(|&overlap| overlap > 0.0)
+            .collect();
+        record.rollover_count = overlaps.len() as f64;
+        record.rollover_fraction = overlaps.len() as f64 / (holds.len() - 1) as f64;
+        record.rollover_overlap_mean = stats::mean(&overlaps);
+        if overlaps.is_empty() {
+            record.mark_padded(|name| name == "rollover_overlap_mean");
+        }
     }
+}
+
+/// Whether `key` deletes text: Backspace (`BackSpace` on X11) or Delete
+fn is_correction_key(key: &str) -> bool {
+    key.contains("Backspace") || key.contains("BackSpace") || key.contains("Delete")
+}
+
+/// Whether `name` is one of the eight summary statistics under `prefix`, e.g.
+/// `dwell_q25` but not the per-hand `dwell_left_mean`
+fn is_summary_feature(name: &str, prefix: &str) -> bool {
+    matches!(
+        name.strip_prefix(prefix),
+        Some("mean" | "std" | "min" | "max" | "median" | "q25" | "q75" | "range")
+    )
+}
+
+/// Which events are OS auto-repeat from a held key: every press of a run after its
+/// first, plus the releases of that key in between (X11 may send one before each
+/// repeat), so dropping them leaves one press held for the whole run
+///
+/// A run is consecutive presses of one key: the first gap may be the OS repeat
+/// delay, every later one is under `AUTOREPEAT_MAX_INTERVAL_MS` and within
+/// `AUTOREPEAT_JITTER_MS` of the one before.
+fn autorepeat_events(events: &[&KeystrokeEvent]) -> Vec<bool> {
+    let mut repeats = vec![false; events.len()];
+    // Indices of the run's presses, its latest fast gap and how many fast gaps it has
+    let mut run: Vec<usize> = Vec::new();
+    let mut last_gap: Option<f64> = None;
+    let mut fast_gaps = 0;
     
-    fn median(&self, values: &[f64]) -> f64 {
-        let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let mid = sorted.len() / 2;
-        if sorted.len() % 2 == 0 {
-            (sorted[mid - 1] + sorted[mid]) / 2.0
-        } else {
-            sorted[mid]
+    for (i, event) in events.iter().enumerate() {
+        if event.event_type != "press" {
+            continue;
         }
+        let gap = run
+            .last()
+            .filter(|&&last| events[last].key == event.key)
+            .map(|&last| (event.timestamp - events[last].timestamp) * 1000.0);
+        let continues = match gap {
+            None => false,
+            Some(gap) if run.len() == 1 => gap > 0.0 && gap <= AUTOREPEAT_MAX_DELAY_MS,
+            Some(gap) => {
+                gap > 0.0
+                    && gap < AUTOREPEAT_MAX_INTERVAL_MS
+                    && !matches!(last_gap, Some(last) if (gap - last).abs() > AUTOREPEAT_JITTER_MS)
+            }
+        };
+        
+        match gap {
+            Some(gap) if continues => {
+                if gap < AUTOREPEAT_MAX_INTERVAL_MS {
+                    last_gap = Some(gap);
+                    fast_gaps += 1;
+                }
+            }
+            _ => {
+                mark_autorepeat_run(events, &run, fast_gaps, &mut repeats);
+                run.clear();
+                last_gap = None;
+                fast_gaps = 0;
+            }
+        }
+        run.push(i);
     }
-    
-    fn percentile(&self, values: &[f64], p: usize) -> f64 {
-        let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let idx = (sorted.len() * p / 100).max(0).min(sorted.len() - 1);
-        sorted[idx]
+    mark_autorepeat_run(events, &run, fast_gaps, &mut repeats);
+    repeats
+}
+
+/// Mark everything of the run's key after its first press, if it repeated enough
+fn mark_autorepeat_run(
+    events: &[&KeystrokeEvent],
+    run: &[usize],
+    fast_gaps: usize,
+    repeats: &mut [bool],
+) {
+    let (Some(&first), Some(&last)) = (run.first(), run.last()) else {
+        return;
+    };
+    if fast_gaps < AUTOREPEAT_MIN_REPEATS {
+        return;
+    }
+    let key = &events[first].key;
+    let span = first + 1..=last;
+    for (event, repeat) in events[span.clone()].iter().zip(&mut repeats[span]) {
+        if event.key == *key {
+            *repeat = true;
+        }
     }
 }
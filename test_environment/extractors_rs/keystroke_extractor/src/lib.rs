@@ -1,5 +1,7 @@
 pub mod extractor;
 pub mod features;
+pub mod record;
 
-pub use extractor::KeystrokeExtractor;
+pub use extractor::{KeystrokeExtractor, KeystrokeWindow};
 pub use features::KeystrokeFeatureCalculator;
+pub use record::KeystrokeFeatureRecord;
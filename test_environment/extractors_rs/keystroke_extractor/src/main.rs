@@ -1,47 +1,41 @@
 use keystroke_extractor::KeystrokeExtractor;
-use common::{init_logging, AppContext};
-use tokio::time::{interval, Duration};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{info, error};
+use common::{
+    extract_offline, init_logging, run_live, AppContext, Config, LiveModality, UserSessions,
+};
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     init_logging();
     info!("Keystroke Extractor starting");
     
-    let ctx = AppContext::new().await?;
-    let config = ctx.config.clone();
+    let config = Config::load()?;
     
-    let mut extractor = KeystrokeExtractor::new(
-        config.window_seconds,
-        config.update_interval,
-    );
-    
-    let mut update_interval = interval(Duration::from_secs(config.update_interval));
-    let mut cleanup_interval = interval(Duration::from_secs(60));
-    
-    info!("Keystroke Extractor initialized and ready");
-    
-    // Example: simulate keystroke events for testing
-    loop {
-        tokio::select! {
-            _ = update_interval.tick() => {
-                if let Some(features) = extractor.extract_features() {
-                    info!("Extracted keystroke features");
-                    
-                    // Publish to Redis
-                    if let Err(e) = ctx.redis.publish_features(
-                        "seclyzer:features:keystroke",
-                        &features,
-                    ).await {
-                        error!("Failed to publish features: {}", e);
-                    }
-                }
-            }
-            _ = cleanup_interval.tick() => {
-                extractor.cleanup_old_events();
-                info!("Cleaned up old events");
-            }
-        }
+    // Recorded events are extracted on event time and printed; no Redis or InfluxDB needed
+    if let Some(mut source) = config.event_source.open_local().await? {
+        let extractor_config = config.clone();
+        let mut extractors = UserSessions::new(config.user_id.clone(), move |_| {
+            KeystrokeExtractor::from_config(&extractor_config)
+        });
+        let windows = extract_offline(
+            source.as_mut(),
+            &mut extractors,
+            config.hop_seconds(),
+            &mut std::io::stdout(),
+        )
+        .await?;
+        info!("Extracted {} feature windows from {:?}", windows, config.event_source);
+        return Ok(());
     }
+    
+    let ctx = AppContext::from_config(config).await?;
+    run_live::<KeystrokeExtractor>(
+        ctx,
+        LiveModality {
+            name: "keystroke",
+            collector: "keyboard",
+            title: "Keystroke",
+        },
+    )
+    .await
 }
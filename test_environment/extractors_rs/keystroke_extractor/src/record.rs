@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+/// Number of `digraph_rank_<i>_mean` buckets the sorted digraph latencies are split into
+pub const DIGRAPH_RANK_BUCKETS: usize = 20;
+
+/// Model features per window, as documented; the build fails if the record disagrees
+pub const FEATURE_COUNT: usize = 81;
+
+common::feature_record! {
+    /// One window of keystroke features
+    pub struct KeystrokeFeatureRecord {
+        schema_version 5;
+        features {
+            dwell_mean, dwell_std, dwell_min, dwell_max,
+            dwell_median, dwell_q25, dwell_q75, dwell_range,
+            flight_mean, flight_std, flight_min, flight_max,
+            flight_median, flight_q25, flight_q75, flight_range,
+            digraph_rank_0_mean, digraph_rank_1_mean, digraph_rank_2_mean, digraph_rank_3_mean,
+            digraph_rank_4_mean, digraph_rank_5_mean, digraph_rank_6_mean, digraph_rank_7_mean,
+            digraph_rank_8_mean, digraph_rank_9_mean, digraph_rank_10_mean, digraph_rank_11_mean,
+            digraph_rank_12_mean, digraph_rank_13_mean, digraph_rank_14_mean, digraph_rank_15_mean,
+            digraph_rank_16_mean, digraph_rank_17_mean, digraph_rank_18_mean, digraph_rank_19_mean,
+            trigraph_mean, trigraph_std, trigraph_median,
+            dwell_left_mean, dwell_left_std, dwell_right_mean, dwell_right_std,
+            flight_ll_mean, flight_lr_mean, flight_rl_mean, flight_rr_mean,
+            backspace_frequency, backspace_count, correction_rate, clean_typing_ratio,
+            correction_latency_mean, retype_burst_speed,
+            rhythm_consistency, burst_frequency, pause_frequency, avg_burst_speed,
+            avg_pause_duration, rhythm_variation, typing_speed_wpm, rhythm_stability,
+            interval_entropy, interval_mode,
+            session_count, session_mean_keys, session_gap_mean, session_longest_burst,
+            frac_alpha, frac_digit, frac_symbol, frac_nav, frac_space,
+            modifier_key_fraction, chord_distinct_count, chord_hold_mean,
+            autorepeat_fraction,
+            rollover_count, rollover_fraction, rollover_overlap_mean,
+            total_keys, dev_mode,
+        }
+        metadata {
+            observed_event_rate, window_coverage, window_start, window_end,
+        }
+        /// `digraph_<pair>_mean` for each configured target pair; the pairs are
+        /// configurable, so these can't be fixed fields
+        #[serde(flatten)]
+        pub target_digraphs: BTreeMap<String, f64>,
+    }
+}
+
+const _: () = assert!(KeystrokeFeatureRecord::feature_count() == FEATURE_COUNT);
+
+impl KeystrokeFeatureRecord {
+    /// The `digraph_rank_<i>_mean` fields, fastest bucket first
+    pub fn digraph_ranks_mut(&mut self) -> [&mut f64; DIGRAPH_RANK_BUCKETS] {
+        [
+            &mut self.digraph_rank_0_mean,
+            &mut self.digraph_rank_1_mean,
+            &mut self.digraph_rank_2_mean,
+            &mut self.digraph_rank_3_mean,
+            &mut self.digraph_rank_4_mean,
+            &mut self.digraph_rank_5_mean,
+            &mut self.digraph_rank_6_mean,
+            &mut self.digraph_rank_7_mean,
+            &mut self.digraph_rank_8_mean,
+            &mut self.digraph_rank_9_mean,
+            &mut self.digraph_rank_10_mean,
+            &mut self.digraph_rank_11_mean,
+            &mut self.digraph_rank_12_mean,
+            &mut self.digraph_rank_13_mean,
+            &mut self.digraph_rank_14_mean,
+            &mut self.digraph_rank_15_mean,
+            &mut self.digraph_rank_16_mean,
+            &mut self.digraph_rank_17_mean,
+            &mut self.digraph_rank_18_mean,
+            &mut self.digraph_rank_19_mean,
+        ]
+    }
+}
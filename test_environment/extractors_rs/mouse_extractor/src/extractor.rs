@@ -1,21 +1,140 @@
 use crate::features::{MouseEvent, MouseFeatureCalculator};
+use common::{
+    Config, FocusTracker, IdleRecord, LiveExtractor, MouseFeatureProfile, RawEvent, WindowWarmup,
+    WindowedExtractor,
+};
 use std::collections::VecDeque;
+use tracing::warn;
+
+/// Seconds between window ends unless `with_hop_seconds` says otherwise
+pub const DEFAULT_HOP_SECONDS: u64 = 5;
+
+/// Events buffered unless `with_buffer_size` says otherwise
+pub const DEFAULT_BUFFER_SIZE: usize = 50_000;
 
 pub struct MouseExtractor {
     events: VecDeque<MouseEvent>,
     feature_calculator: MouseFeatureCalculator,
     window_seconds: u64,
+    hop_seconds: u64,
+    last_window_end: Option<f64>,
+    idle_records: bool,
+    buffer_size: usize,
+    /// Whether the last insert evicted an event still inside the window
+    saturated: bool,
+    /// Focused app and recent app switches, from focus events
+    focus: FocusTracker,
+    /// What happens to windows that start before `first_event`
+    warmup: WindowWarmup,
+    /// Timestamp of the first event added, from which windows are complete
+    first_event: Option<f64>,
+    /// Moves closer than this (raw pixels) to the last kept move are dropped
+    min_move_distance: f64,
+    /// Position of the last move kept in the buffer
+    last_move: Option<(f64, f64)>,
 }
 
 impl MouseExtractor {
     pub fn new(window_seconds: u64) -> Self {
         MouseExtractor {
-            events: VecDeque::with_capacity(50000),
+            events: VecDeque::with_capacity(DEFAULT_BUFFER_SIZE),
             feature_calculator: MouseFeatureCalculator::new(window_seconds),
             window_seconds,
+            hop_seconds: DEFAULT_HOP_SECONDS,
+            last_window_end: None,
+            idle_records: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            saturated: false,
+            focus: FocusTracker::new(),
+            warmup: WindowWarmup::Off,
+            first_event: None,
+            min_move_distance: 0.0,
+            last_move: None,
+        }
+    }
+    
+    /// An extractor with every setting taken from `config`
+    pub fn from_config(config: &Config) -> Self {
+        let extractor = MouseExtractor::new(config.window_seconds)
+            .with_dev_mode(config.dev_mode)
+            .with_min_events(config.mouse_min_events)
+            .with_degraded_mode(config.mouse_degraded_mode)
+            .with_feature_profile(config.mouse_feature_profile)
+            .with_hop_seconds(config.hop_seconds())
+            .with_idle_records(config.emit_idle_records)
+            .with_window_warmup(config.window_warmup)
+            .with_buffer_size(config.mouse_buffer_size)
+            .with_min_move_distance(config.min_move_distance_px);
+        match (config.screen_width, config.screen_height) {
+            (Some(width), Some(height)) => extractor.with_screen_size(width as f64, height as f64),
+            _ => extractor,
         }
     }
     
+    /// Mark emitted features as dev-mode self-testing data
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.feature_calculator = self.feature_calculator.with_dev_mode(dev_mode);
+        self
+    }
+    
+    /// Minimum events in the window before features are emitted normally
+    pub fn with_min_events(mut self, min_events: usize) -> Self {
+        self.feature_calculator = self.feature_calculator.with_min_events(min_events);
+        self
+    }
+    
+    /// Emit partial features flagged `insufficient_data` below `min_events`
+    pub fn with_degraded_mode(mut self, degraded_mode: bool) -> Self {
+        self.feature_calculator = self.feature_calculator.with_degraded_mode(degraded_mode);
+        self
+    }
+    
+    /// Normalize positions by screen size before computing features
+    pub fn with_screen_size(mut self, width: f64, height: f64) -> Self {
+        self.feature_calculator = self.feature_calculator.with_screen_size(width, height);
+        self
+    }
+    
+    /// Publish the `compact` or `minimal` feature subset instead of every feature
+    pub fn with_feature_profile(mut self, profile: MouseFeatureProfile) -> Self {
+        self.feature_calculator = self.feature_calculator.with_feature_profile(profile);
+        self
+    }
+    
+    /// Seconds between the ends of consecutive feature windows
+    pub fn with_hop_seconds(mut self, hop_seconds: u64) -> Self {
+        self.hop_seconds = hop_seconds;
+        self
+    }
+    
+    /// Return an `IdleRecord` instead of `None` when a window is too sparse for features
+    pub fn with_idle_records(mut self, idle_records: bool) -> Self {
+        self.idle_records = idle_records;
+        self
+    }
+    
+    /// Flag or suppress windows that reach back before the first event, since they
+    /// cover less than `window_seconds` of activity
+    pub fn with_window_warmup(mut self, warmup: WindowWarmup) -> Self {
+        self.warmup = warmup;
+        self
+    }
+    
+    /// Most events kept at once; the oldest are dropped beyond it
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self.events = VecDeque::with_capacity(buffer_size);
+        self
+    }
+    
+    /// Drop moves that land within `pixels` of the last kept move, so sub-pixel and
+    /// 1-2px jitter reads as a stationary cursor; slow drift still gets through once
+    /// it adds up to `pixels`
+    pub fn with_min_move_distance(mut self, pixels: f64) -> Self {
+        self.min_move_distance = pixels;
+        self
+    }
+    
     /// Add a mouse event to the buffer
     pub fn add_event(
         &mut self,
@@ -26,9 +145,24 @@ impl MouseExtractor {
         button: Option<String>,
         scroll_delta: Option<f64>,
     ) {
-        if self.events.len() >= 50000 {
-            self.events.pop_front();
+        self.first_event.get_or_insert(timestamp);
+        if let ("move", Some(x), Some(y)) = (event_type.as_str(), x, y) {
+            if self.is_jitter(x, y) {
+                return;
+            }
+            self.last_move = Some((x, y));
+        }
+        
+        let window_start = timestamp - self.window_seconds as f64;
+        let evicted_in_window = self.events.len() >= self.buffer_size
+            && self.events.pop_front().is_some_and(|e| e.timestamp > window_start);
+        if evicted_in_window && !self.saturated {
+            warn!(
+                "Mouse buffer full ({} events); dropping events still inside the window",
+                self.buffer_size
+            );
         }
+        self.saturated = evicted_in_window;
         
         self.events.push_back(MouseEvent {
             timestamp,
@@ -40,15 +174,87 @@ impl MouseExtractor {
         });
     }
     
-    /// Extract features from current buffer
-    pub fn extract_features(&self) -> Option<serde_json::Value> {
-        let events: Vec<MouseEvent> = self.events.iter().cloned().collect();
+    /// Whether a move to `(x, y)` is too short to count as movement
+    fn is_jitter(&self, x: f64, y: f64) -> bool {
+        self.min_move_distance > 0.0
+            && self
+                .last_move
+                .is_some_and(|(last_x, last_y)| (x - last_x).hypot(y - last_y) < self.min_move_distance)
+    }
+    
+    /// Number of events currently buffered
+    pub fn buffered_events(&self) -> usize {
+        self.events.len()
+    }
+    
+    /// The buffered events, oldest first, as raw mouse events (fields the event
+    /// didn't have are left out)
+    pub fn dump_buffer(&self) -> serde_json::Value {
+        self.events
+            .iter()
+            .map(|event| {
+                let mut raw = serde_json::json!({
+                    "type": "mouse",
+                    "ts": (event.timestamp * 1_000_000.0).round() as u64,
+                    "event": event.event_type,
+                });
+                let optional = [
+                    ("x", event.x.map(serde_json::Value::from)),
+                    ("y", event.y.map(serde_json::Value::from)),
+                    ("button", event.button.as_deref().map(serde_json::Value::from)),
+                    ("scroll_delta", event.scroll_delta.map(serde_json::Value::from)),
+                ];
+                for (field, value) in optional {
+                    if let Some(value) = value {
+                        raw[field] = value;
+                    }
+                }
+                raw
+            })
+            .collect()
+    }
+    
+    /// Extract features for the next sliding window ending around now
+    pub fn extract_features(&mut self) -> Option<serde_json::Value> {
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs_f64();
         
-        self.feature_calculator.extract_features(&events, current_time)
+        self.extract_features_at(current_time)
+    }
+    
+    /// Extract features for the window one hop after the previous one
+    ///
+    /// Consecutive windows overlap by exactly `window_seconds - hop_seconds`; if `now`
+    /// has drifted more than a hop from that schedule (e.g. after a stall) the
+    /// windows re-anchor at `now`.
+    pub fn extract_features_at(&mut self, now: f64) -> Option<serde_json::Value> {
+        self.begin_window(now).compute()
+    }
+    
+    /// Advance the window schedule to `now` and snapshot what the window needs, so
+    /// `MouseWindow::compute` can run off the async runtime
+    pub fn begin_window(&mut self, now: f64) -> MouseWindow {
+        let hop = self.hop_seconds as f64;
+        let window_end = match self.last_window_end {
+            Some(last) if (now - (last + hop)).abs() < hop => last + hop,
+            _ => now,
+        };
+        self.last_window_end = Some(window_end);
+        let window_start = window_end - self.window_seconds as f64;
+        
+        MouseWindow {
+            events: self.events.iter().cloned().collect(),
+            window_end,
+            window_seconds: self.window_seconds,
+            idle_records: self.idle_records,
+            feature_calculator: self.feature_calculator.clone(),
+            active_app: self.focus.active_app().map(str::to_string),
+            contained_app_switch: self.focus.contained_switch(window_start, window_end),
+            warmup: self.warmup,
+            partial_window: !self.first_event.is_some_and(|first| window_start >= first),
+        }
     }
     
     /// Clear old events outside the window
@@ -69,3 +275,103 @@ impl MouseExtractor {
         }
     }
 }
+
+/// One window's events and settings, detached from the extractor
+pub struct MouseWindow {
+    events: Vec<MouseEvent>,
+    window_end: f64,
+    window_seconds: u64,
+    idle_records: bool,
+    feature_calculator: MouseFeatureCalculator,
+    active_app: Option<String>,
+    contained_app_switch: Option<bool>,
+    warmup: WindowWarmup,
+    /// Whether the window starts before the first event the extractor saw
+    partial_window: bool,
+}
+
+impl MouseWindow {
+    /// Features for the window, or an `IdleRecord` when it's too sparse and idle records are on,
+    /// tagged with the `active_app` focused when the window closed and whether focus
+    /// switched apps during it; a window started before the first event is then
+    /// flagged or dropped as `WindowWarmup` says
+    pub fn compute(self) -> Option<serde_json::Value> {
+        let features = self.feature_calculator.extract_features(&self.events, self.window_end);
+        let mut features = match features {
+            Some(features) => features,
+            None if self.idle_records => {
+                let window_start = self.window_end - self.window_seconds as f64;
+                let event_count = self
+                    .events
+                    .iter()
+                    .filter(|e| e.timestamp > window_start && e.timestamp <= self.window_end)
+                    .count();
+                serde_json::to_value(IdleRecord::new(event_count, window_start, self.window_end))
+                    .ok()?
+            }
+            None => return None,
+        };
+        
+        FocusTracker::tag(&mut features, self.active_app, self.contained_app_switch);
+        self.warmup.apply(features, self.partial_window)
+    }
+}
+
+impl WindowedExtractor for MouseExtractor {
+    fn add_raw_event(&mut self, event: RawEvent) {
+        // Focus events don't feed the features, only the context windows are tagged with
+        if event.event_type == "app" {
+            self.focus.observe(&event, self.window_seconds as f64 * 2.0);
+            return;
+        }
+        if event.event_type != "mouse" {
+            return;
+        }
+        if let Some(kind) = event.event {
+            self.add_event(
+                event.ts as f64 / 1_000_000.0,
+                event.x,
+                event.y,
+                kind,
+                event.button,
+                event.scroll_delta,
+            );
+        }
+    }
+    
+    fn extract_windows_at(&mut self, now: f64) -> Vec<serde_json::Value> {
+        self.extract_features_at(now).into_iter().collect()
+    }
+}
+
+impl LiveExtractor for MouseExtractor {
+    type Window = MouseWindow;
+    
+    fn from_config(config: &Config) -> Self {
+        MouseExtractor::from_config(config)
+    }
+    
+    fn feature_names() -> &'static [&'static str] {
+        MouseFeatureCalculator::feature_names()
+    }
+    
+    fn buffered_events(&self) -> usize {
+        MouseExtractor::buffered_events(self)
+    }
+    
+    fn dump_buffer(&self) -> serde_json::Value {
+        MouseExtractor::dump_buffer(self)
+    }
+    
+    fn cleanup_old_events(&mut self) {
+        MouseExtractor::cleanup_old_events(self)
+    }
+    
+    fn begin_window(&mut self, now: f64) -> MouseWindow {
+        MouseExtractor::begin_window(self, now)
+    }
+    
+    fn compute_window(window: MouseWindow) -> Option<serde_json::Value> {
+        window.compute()
+    }
+}
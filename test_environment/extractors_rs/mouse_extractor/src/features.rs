@@ -1,3 +1,5 @@
+use crate::record::{self, MouseFeatureRecord};
+use common::{stats, MouseFeatureProfile};
 use std::collections::HashMap;
 use std::f64::consts::PI;
 
@@ -11,37 +13,197 @@ pub struct MouseEvent {
     pub scroll_delta: Option<f64>,
 }
 
+/// One button held down, from press to release (seconds)
+struct ButtonInterval<'a> {
+    button: &'a str,
+    start: f64,
+    end: f64,
+}
+
+impl ButtonInterval<'_> {
+    fn dwell_ms(&self) -> f64 {
+        (self.end - self.start) * 1000.0
+    }
+    
+    fn overlaps(&self, other: &ButtonInterval) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Canonical feature order for model input
+const FEATURE_NAMES: &[&str] = MouseFeatureRecord::FEATURE_NAMES;
+
+// `feature_names()` is the documented feature set, no more and no less
+const _: () = assert!(FEATURE_NAMES.len() == record::FEATURE_COUNT);
+
+/// Features computed from the window's movement path, padded together without one
+fn is_movement_feature(name: &str) -> bool {
+    (name.starts_with("move_") && !name.starts_with("move_frac_"))
+        || matches!(name, "velocity_peak_ratio" | "high_speed_fraction" | "flick_count")
+}
+
+/// Features computed from the window's clicks, padded together without any
+fn is_click_feature(name: &str) -> bool {
+    name.starts_with("click_") && !name.starts_with("click_frac_")
+}
+
+/// Events needed in the window before features are considered reliable
+pub const DEFAULT_MIN_EVENTS: usize = 50;
+
+/// Screen width the pixel-based outlier limits were tuned on; with normalized
+/// coordinates they're rescaled against it so every resolution filters alike
+const REFERENCE_WIDTH: f64 = 1920.0;
+
+/// How far back before a click its approach movement is searched
+const APPROACH_SECONDS: f64 = 1.0;
+
+/// Travel (reference pixels) a reversal must exceed to count, so jitter isn't a correction
+const CORRECTION_THRESHOLD: f64 = 2.0;
+
+/// Speed (reference pixels/s) below which the cursor counts as stationary
+const PAUSE_SPEED: f64 = 20.0;
+
+/// Speed (reference pixels/s) above which movement counts as a flick rather than a precise move
+const FLICK_SPEED: f64 = 2000.0;
+
+/// How far back before a click the stationary dwell is looked for
+const DWELL_LOOKBACK_SECONDS: f64 = 2.0;
+
+/// Shortest and longest stop within movement counted as a micro-pause; longer is idling
+const MICRO_PAUSE_SECONDS: (f64, f64) = (0.05, 0.5);
+
+/// Speed coefficient of variation at or above which movement counts as fully human
+const HUMAN_SPEED_CV: f64 = 0.25;
+
+/// Mean heading change (radians) at or above which a path counts as fully human
+const HUMAN_ANGLE_JITTER: f64 = 0.2;
+
+#[derive(Clone)]
 pub struct MouseFeatureCalculator {
     window_seconds: u64,
+    dev_mode: bool,
+    min_events: usize,
+    degraded_mode: bool,
+    screen_size: Option<(f64, f64)>,
+    profile: MouseFeatureProfile,
 }
 
 impl MouseFeatureCalculator {
     pub fn new(window_seconds: u64) -> Self {
-        MouseFeatureCalculator { window_seconds }
+        MouseFeatureCalculator {
+            window_seconds,
+            dev_mode: false,
+            min_events: DEFAULT_MIN_EVENTS,
+            degraded_mode: false,
+            screen_size: None,
+            profile: MouseFeatureProfile::Full,
+        }
     }
     
-    /// Extract 38 mouse features from events
+    /// Mark emitted features as dev-mode self-testing data
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.dev_mode = dev_mode;
+        self
+    }
+    
+    /// Minimum events in the window before features are emitted normally
+    pub fn with_min_events(mut self, min_events: usize) -> Self {
+        self.min_events = min_events;
+        self
+    }
+    
+    /// Below `min_events`, emit whatever can be computed flagged `insufficient_data`
+    /// instead of returning `None`
+    pub fn with_degraded_mode(mut self, degraded_mode: bool) -> Self {
+        self.degraded_mode = degraded_mode;
+        self
+    }
+    
+    /// Normalize positions into `[0, 1]` by screen size so features are resolution independent
+    pub fn with_screen_size(mut self, width: f64, height: f64) -> Self {
+        self.screen_size = Some((width, height));
+        self
+    }
+    
+    /// Publish only the features of `profile` (see `record::profile_features`)
+    pub fn with_feature_profile(mut self, profile: MouseFeatureProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+    
+    /// Map a raw position into the feature coordinate space
+    fn normalize(&self, x: f64, y: f64) -> (f64, f64) {
+        match self.screen_size {
+            Some((width, height)) => (x / width, y / height),
+            None => (x, y),
+        }
+    }
+    
+    /// Size of one reference pixel in the feature coordinate space
+    fn pixel_unit(&self) -> f64 {
+        if self.screen_size.is_some() {
+            1.0 / REFERENCE_WIDTH
+        } else {
+            1.0
+        }
+    }
+    
+    /// Names of every emitted model feature, in canonical column order
+    pub fn feature_names() -> &'static [&'static str] {
+        FEATURE_NAMES
+    }
+    
+    /// Flatten extracted features into `feature_names()` order, 0.0 for missing keys
+    pub fn to_vector(features: &serde_json::Value) -> Vec<f64> {
+        FEATURE_NAMES
+            .iter()
+            .map(|name| features.get(*name).and_then(|v| v.as_f64()).unwrap_or(0.0))
+            .collect()
+    }
+    
+    /// Extract the `FEATURE_COUNT` mouse features and the window metadata from events
     pub fn extract_features(
         &self,
         events: &[MouseEvent],
         current_time: f64,
     ) -> Option<serde_json::Value> {
+        let features = self.extract_record(events, current_time)?.to_json();
+        debug_assert_eq!(
+            features.as_object().map_or(0, |fields| fields.len()),
+            MouseFeatureRecord::field_count(),
+            "mouse window doesn't have the documented keys"
+        );
+        Some(record::restrict_to_profile(features, self.profile))
+    }
+    
+    /// Feature record for the window ending at `current_time`; `None` if the window
+    /// has too few events and degraded mode is off
+    pub fn extract_record(
+        &self,
+        events: &[MouseEvent],
+        current_time: f64,
+    ) -> Option<MouseFeatureRecord> {
         let cutoff_time = current_time - self.window_seconds as f64;
         let recent: Vec<&MouseEvent> = events
             .iter()
-            .filter(|e| e.timestamp > cutoff_time)
+            .filter(|e| e.timestamp > cutoff_time && e.timestamp <= current_time)
             .collect();
         
-        if recent.len() < 50 {
+        let insufficient_data = recent.len() < self.min_events;
+        if insufficient_data && !self.degraded_mode {
             return None;
         }
         
-        let mut features = HashMap::new();
+        let mut record = MouseFeatureRecord::default();
         
         // Separate events by type
         let movements: Vec<&MouseEvent> = recent
             .iter()
-            .filter(|e| e.event_type == "move" && e.x.is_some())
+            .filter(|e| {
+                e.event_type == "move"
+                    && e.x.is_some_and(f64::is_finite)
+                    && e.y.is_some_and(f64::is_finite)
+            })
             .copied()
             .collect();
         
@@ -59,51 +221,84 @@ impl MouseFeatureCalculator {
         
         // Calculate movement features (20 features)
         if movements.len() > 2 {
-            let movement_features = self.calculate_movement_features(&movements);
-            features.extend(movement_features);
+            self.calculate_movement_features(&movements, &mut record);
         } else {
-            for i in 0..20 {
-                features.insert(format!("move_{}", i), 0.0);
-            }
+            record.mark_padded(is_movement_feature);
+        }
+        
+        // Score how scripted the movement looks (1 feature)
+        match self.calculate_bot_likelihood(&movements) {
+            Some(likelihood) => record.bot_likelihood = likelihood,
+            None => record.mark_padded(|name| name == "bot_likelihood"),
         }
         
-        // Calculate click features (10 features)
+        // Calculate click features (10 features, plus spread and per-button dwell)
         if !clicks.is_empty() {
-            let click_features = self.calculate_click_features(&clicks);
-            features.extend(click_features);
+            self.calculate_click_features(&clicks, &mut record);
         } else {
-            for i in 0..10 {
-                features.insert(format!("click_{}", i), 0.0);
-            }
+            record.mark_padded(is_click_feature);
         }
         
+        // Overshoot and corrective submovements approaching clicks (3 features)
+        self.calculate_submovements(&movements, &clicks, &mut record);
+        
+        // Stationary dwell before clicks and micro-pauses within movement (2 features)
+        self.calculate_pauses(&movements, &clicks, &mut record);
+        
         // Calculate scroll features (8 features)
         if !scrolls.is_empty() {
-            let scroll_features = self.calculate_scroll_features(&scrolls);
-            features.extend(scroll_features);
+            self.calculate_scroll_features(&scrolls, &mut record);
+        } else {
+            record.mark_padded(|name| name.starts_with("scroll_"));
+        }
+        
+        // Share of movement and clicks in each screen quadrant (8 features); without the
+        // screen size there's no telling where the middle of the screen is
+        if self.screen_size.is_some() {
+            self.calculate_quadrants(&movements, &clicks, &mut record);
         } else {
-            for i in 0..8 {
-                features.insert(format!("scroll_{}", i), 0.0);
+            record.mark_padded(|name| name.starts_with("move_frac_") || name.starts_with("click_frac_"));
+        }
+        
+        record.dev_mode = if self.dev_mode { 1.0 } else { 0.0 };
+        record.insufficient_data = if insufficient_data { 1.0 } else { 0.0 };
+        
+        // A degenerate window must never put NaN/Inf into InfluxDB or the model
+        for value in record.values_mut() {
+            if !value.is_finite() {
+                *value = 0.0;
             }
         }
         
-        features.insert("dev_mode".to_string(), 0.0);
+        // How densely the window was actually sampled, so consumers can spot machines
+        // (or gaps) whose event rate differs from what a model was trained on
+        let timestamps: Vec<f64> = recent.iter().map(|e| e.timestamp).collect();
+        record.observed_event_rate = stats::event_rate(&timestamps);
+        record.window_coverage = stats::coverage(&timestamps, self.window_seconds as f64);
+        
+        // The exact (window_start, window_end] span these features cover
+        record.window_start = cutoff_time;
+        record.window_end = current_time;
         
-        Some(serde_json::to_value(features).unwrap())
+        Some(record)
     }
     
-    fn calculate_movement_features(&self, movements: &[&MouseEvent]) -> HashMap<String, f64> {
-        let mut features = HashMap::new();
-        
-        let x: Vec<f64> = movements.iter().filter_map(|e| e.x).collect();
-        let y: Vec<f64> = movements.iter().filter_map(|e| e.y).collect();
+    fn calculate_movement_features(
+        &self,
+        movements: &[&MouseEvent],
+        record: &mut MouseFeatureRecord,
+    ) {
+
+        let (x, y): (Vec<f64>, Vec<f64>) = movements
+            .iter()
+            .filter_map(|e| Some(self.normalize(e.x?, e.y?)))
+            .unzip();
         let t: Vec<f64> = movements.iter().map(|e| e.timestamp).collect();
+        let unit = self.pixel_unit();
         
         if x.len() < 2 || y.len() < 2 {
-            for i in 0..20 {
-                features.insert(format!("move_{}", i), 0.0);
-            }
-            return features;
+            record.mark_padded(is_movement_feature);
+            return;
         }
         
         // Calculate distances
@@ -122,25 +317,30 @@ impl MouseFeatureCalculator {
             dt.push(delta.max(0.001));
         }
         
-        // Velocity (pixels/second)
-        let velocities: Vec<f64> = distances
+        // Velocity (pixels or screen fractions per second), paired with the midpoint time of its interval
+        // so filtering out samples doesn't misalign the later derivatives
+        let velocity_samples: Vec<(f64, f64)> = distances
             .iter()
             .zip(&dt)
-            .map(|(d, dt)| d / dt)
-            .filter(|v| v < &10000.0)
+            .enumerate()
+            .map(|(i, (d, dt))| (d / dt, (t[i] + t[i + 1]) / 2.0))
+            .filter(|(v, _)| *v < 10000.0 * unit)
             .collect();
+        let velocities: Vec<f64> = velocity_samples.iter().map(|(v, _)| *v).collect();
         
         // Acceleration
-        let mut accelerations = Vec::new();
-        if velocities.len() > 1 {
-            for i in 0..velocities.len() - 1 {
-                let dv = velocities[i + 1] - velocities[i];
-                let accel = dv / dt[i];
-                if accel.abs() < 100000.0 {
-                    accelerations.push(accel);
+        let mut acceleration_samples = Vec::new();
+        if velocity_samples.len() > 1 {
+            for i in 0..velocity_samples.len() - 1 {
+                let (v0, t0) = velocity_samples[i];
+                let (v1, t1) = velocity_samples[i + 1];
+                let accel = (v1 - v0) / (t1 - t0).max(0.001);
+                if accel.abs() < 100000.0 * unit {
+                    acceleration_samples.push((accel, (t0 + t1) / 2.0));
                 }
             }
         }
+        let accelerations: Vec<f64> = acceleration_samples.iter().map(|(a, _)| *a).collect();
         
         // Direction changes (angles)
         let mut angle_changes = Vec::new();
@@ -160,87 +360,94 @@ impl MouseFeatureCalculator {
         // Curvature
         let total_distance: f64 = distances.iter().sum();
         let straight_distance = ((x[x.len() - 1] - x[0]).powi(2) + (y[y.len() - 1] - y[0]).powi(2)).sqrt();
-        let curvature = 1.0 - (straight_distance / total_distance.max(1.0));
+        let curvature = 1.0 - (straight_distance / total_distance.max(unit));
         
         // Jerk
         let mut jerk = Vec::new();
-        if accelerations.len() > 1 {
-            for i in 0..accelerations.len() - 1 {
-                let da = accelerations[i + 1] - accelerations[i];
-                let j = da / dt[i];
-                if j.abs() < 1000000.0 {
+        if acceleration_samples.len() > 1 {
+            for i in 0..acceleration_samples.len() - 1 {
+                let (a0, t0) = acceleration_samples[i];
+                let (a1, t1) = acceleration_samples[i + 1];
+                let j = (a1 - a0) / (t1 - t0).max(0.001);
+                if j.abs() < 1000000.0 * unit {
                     jerk.push(j);
                 }
             }
         }
         
         // Populate features
-        features.insert("move_0".to_string(), self.mean(&velocities)); // velocity mean
-        features.insert("move_1".to_string(), self.std_dev(&velocities)); // velocity std
-        features.insert("move_2".to_string(), self.max(&velocities)); // velocity max
-        features.insert("move_3".to_string(), self.median(&velocities)); // velocity median
+        record.move_0 = stats::mean(&velocities); // velocity mean
+        record.move_1 = stats::std_dev(&velocities); // velocity std
+        record.move_2 = stats::max(&velocities); // velocity max
+        record.move_3 = stats::median(&velocities); // velocity median
         
-        features.insert("move_4".to_string(), self.mean(&accelerations.iter().map(|a| a.abs()).collect::<Vec<_>>())); // accel mean
-        features.insert("move_5".to_string(), self.std_dev(&accelerations)); // accel std
-        features.insert("move_6".to_string(), self.max(&accelerations.iter().map(|a| a.abs()).collect::<Vec<_>>())); // accel max
+        record.move_4 = stats::mean(&accelerations.iter().map(|a| a.abs()).collect::<Vec<_>>()); // accel mean
+        record.move_5 = stats::std_dev(&accelerations); // accel std
+        record.move_6 = stats::max(&accelerations.iter().map(|a| a.abs()).collect::<Vec<_>>()); // accel max
         
-        features.insert("move_7".to_string(), curvature);
-        features.insert("move_8".to_string(), self.mean(&angle_changes)); // angle change mean
-        features.insert("move_9".to_string(), self.std_dev(&angle_changes)); // angle change std
+        record.move_7 = curvature;
+        record.move_8 = stats::mean(&angle_changes); // angle change mean
+        record.move_9 = stats::std_dev(&angle_changes); // angle change std
         
-        features.insert("move_10".to_string(), self.mean(&jerk.iter().map(|j| j.abs()).collect::<Vec<_>>())); // jerk mean
-        features.insert("move_11".to_string(), self.std_dev(&jerk)); // jerk std
+        record.move_10 = stats::mean(&jerk.iter().map(|j| j.abs()).collect::<Vec<_>>()); // jerk mean
+        record.move_11 = stats::std_dev(&jerk); // jerk std
         
-        features.insert("move_12".to_string(), total_distance); // total distance
-        features.insert("move_13".to_string(), straight_distance); // straight distance
-        features.insert("move_14".to_string(), total_distance / movements.len() as f64); // avg distance per sample
+        record.move_12 = total_distance; // total distance
+        record.move_13 = straight_distance; // straight distance
+        record.move_14 = total_distance / movements.len() as f64; // avg distance per sample
         
         let idle_count = dt.iter().filter(|&&d| d > 0.1).count();
-        features.insert("move_15".to_string(), idle_count as f64 / dt.len() as f64); // idle fraction
-        features.insert("move_16".to_string(), self.mean(&dt)); // mean time between samples
-        features.insert("move_17".to_string(), self.std_dev(&dt)); // std time between samples
+        record.move_15 = idle_count as f64 / dt.len() as f64; // idle fraction
+        record.move_16 = stats::mean(&dt); // mean time between samples
+        record.move_17 = stats::std_dev(&dt); // std time between samples
         
-        features.insert("move_18".to_string(), straight_distance / total_distance.max(1.0)); // efficiency
-        features.insert("move_19".to_string(), movements.len() as f64 / self.window_seconds as f64); // movement frequency
+        record.move_18 = straight_distance / total_distance.max(unit); // efficiency
+        record.move_19 = movements.len() as f64 / self.window_seconds as f64; // movement frequency
         
-        features
+        // Speed profile shape: flicks peak far above their mean, precise moves stay level
+        let velocity_mean = stats::mean(&velocities);
+        if velocity_mean > 0.0 {
+            record.velocity_peak_ratio = stats::max(&velocities) / velocity_mean;
+        } else {
+            record.mark_padded(|name| name == "velocity_peak_ratio");
+        }
+        let flick_speed = FLICK_SPEED * unit;
+        let steps: Vec<(f64, f64)> = distances
+            .iter()
+            .zip(&dt)
+            .map(|(d, dt)| (d / dt, *dt))
+            .filter(|(v, _)| *v < 10000.0 * unit)
+            .collect();
+        let sampled_time: f64 = steps.iter().map(|(_, dt)| dt).sum();
+        let high_speed_time: f64 = steps
+            .iter()
+            .filter(|(v, _)| *v > flick_speed)
+            .map(|(_, dt)| dt)
+            .sum();
+        record.high_speed_fraction = high_speed_time / sampled_time.max(0.001);
+        // A flick is a run of consecutive steps above the flick speed
+        let mut flick_count = 0;
+        let mut in_flick = false;
+        for (v, _) in &steps {
+            let fast = *v > flick_speed;
+            if fast && !in_flick {
+                flick_count += 1;
+            }
+            in_flick = fast;
+        }
+        record.flick_count = flick_count as f64;
     }
     
-    fn calculate_click_features(&self, clicks: &[&MouseEvent]) -> HashMap<String, f64> {
-        let mut features = HashMap::new();
-        
+    fn calculate_click_features(&self, clicks: &[&MouseEvent], record: &mut MouseFeatureRecord) {
+
         let presses: Vec<&MouseEvent> = clicks
             .iter()
             .filter(|e| e.event_type == "press")
             .copied()
             .collect();
         
-        let releases: Vec<&MouseEvent> = clicks
-            .iter()
-            .filter(|e| e.event_type == "release")
-            .copied()
-            .collect();
-        
-        // Click durations
-        let mut click_durations = Vec::new();
-        let mut press_times: HashMap<String, f64> = HashMap::new();
-        
-        for press in &presses {
-            if let Some(button) = &press.button {
-                press_times.insert(button.clone(), press.timestamp);
-            }
-        }
-        
-        for release in &releases {
-            if let Some(button) = &release.button {
-                if let Some(&press_time) = press_times.get(button) {
-                    let duration = (release.timestamp - press_time) * 1000.0;
-                    if duration > 0.0 && duration < 5000.0 {
-                        click_durations.push(duration);
-                    }
-                }
-            }
-        }
+        let intervals = self.button_intervals(clicks);
+        let click_durations: Vec<f64> = intervals.iter().map(|i| i.dwell_ms()).collect();
         
         // Count by button
         let left_clicks = presses.iter().filter(|c| c.button.as_ref().map_or(false, |b| b == "Left")).count();
@@ -257,87 +464,432 @@ impl MouseFeatureCalculator {
             }
         }
         
-        features.insert("click_0".to_string(), self.mean(&click_durations));
-        features.insert("click_1".to_string(), self.std_dev(&click_durations));
-        features.insert("click_2".to_string(), left_clicks as f64);
-        features.insert("click_3".to_string(), right_clicks as f64);
-        features.insert("click_4".to_string(), middle_clicks as f64);
+        if click_durations.is_empty() {
+            record.mark_padded(|name| matches!(name, "click_0" | "click_1" | "click_9"));
+        }
+        record.click_0 = stats::mean(&click_durations);
+        record.click_1 = stats::std_dev(&click_durations);
+        record.click_2 = left_clicks as f64;
+        record.click_3 = right_clicks as f64;
+        record.click_4 = middle_clicks as f64;
         
         let total_clicks = left_clicks + right_clicks + middle_clicks;
-        features.insert("click_5".to_string(), left_clicks as f64 / total_clicks.max(1) as f64);
-        features.insert("click_6".to_string(), double_clicks as f64);
-        features.insert("click_7".to_string(), double_clicks as f64 / presses.len().max(1) as f64);
-        features.insert("click_8".to_string(), presses.len() as f64 / self.window_seconds as f64);
-        features.insert("click_9".to_string(), self.median(&click_durations));
+        record.click_5 = left_clicks as f64 / total_clicks.max(1) as f64;
+        record.click_6 = double_clicks as f64;
+        record.click_7 = double_clicks as f64 / presses.len().max(1) as f64;
+        record.click_8 = presses.len() as f64 / self.window_seconds as f64;
+        record.click_9 = stats::median(&click_durations);
+        
+        self.calculate_click_spread(&presses, record);
+        self.calculate_button_dwell(&intervals, record);
+    }
+    
+    /// Press-to-release intervals in time order, pairing each release with the
+    /// open press of the same button; presses never released are left out
+    fn button_intervals<'a>(&self, clicks: &[&'a MouseEvent]) -> Vec<ButtonInterval<'a>> {
+        let mut sorted = clicks.to_vec();
+        sorted.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
         
-        features
+        let mut open: HashMap<&str, f64> = HashMap::new();
+        let mut intervals = Vec::new();
+        for event in sorted {
+            let Some(button) = event.button.as_deref() else {
+                continue;
+            };
+            match event.event_type.as_str() {
+                "press" => {
+                    open.insert(button, event.timestamp);
+                }
+                "release" => {
+                    if let Some(start) = open.remove(button) {
+                        let interval = ButtonInterval { button, start, end: event.timestamp };
+                        let dwell = interval.dwell_ms();
+                        if dwell > 0.0 && dwell < 5000.0 {
+                            intervals.push(interval);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        intervals
     }
     
-    fn calculate_scroll_features(&self, scrolls: &[&MouseEvent]) -> HashMap<String, f64> {
-        let mut features = HashMap::new();
+    /// Mean dwell per button and the share of presses held down together with a
+    /// different button (chords), which casual clickers almost never produce
+    fn calculate_button_dwell(
+        &self,
+        intervals: &[ButtonInterval],
+        record: &mut MouseFeatureRecord,
+    ) {
+        let mut unheld = Vec::new();
+        for (field, name, button) in [
+            (&mut record.click_dwell_left, "click_dwell_left", "Left"),
+            (&mut record.click_dwell_right, "click_dwell_right", "Right"),
+            (&mut record.click_dwell_middle, "click_dwell_middle", "Middle"),
+        ] {
+            let dwells: Vec<f64> = intervals
+                .iter()
+                .filter(|i| i.button == button)
+                .map(|i| i.dwell_ms())
+                .collect();
+            if dwells.is_empty() {
+                unheld.push(name);
+            }
+            *field = stats::mean(&dwells);
+        }
+        if intervals.is_empty() {
+            unheld.push("click_chord_fraction");
+        }
+        record.mark_padded(|name| unheld.contains(&name));
         
-        let deltas: Vec<f64> = scrolls
+        let chorded = intervals
             .iter()
-            .filter_map(|e| e.scroll_delta)
+            .filter(|a| intervals.iter().any(|b| a.button != b.button && a.overlaps(b)))
+            .count();
+        record.click_chord_fraction = chorded as f64 / intervals.len().max(1) as f64;
+    }
+    
+    /// Overshoot and corrective submovements in the approach to each click
+    ///
+    /// The approach is the movement in the `APPROACH_SECONDS` before a press (or
+    /// since the previous press), projected onto the line from where it started to
+    /// where the click landed. Going past the click point is an overshoot; every
+    /// zero-crossing of the velocity along that line after the primary movement
+    /// starts a corrective submovement.
+    fn calculate_submovements(
+        &self,
+        movements: &[&MouseEvent],
+        clicks: &[&MouseEvent],
+        record: &mut MouseFeatureRecord,
+    ) {
+        let threshold = CORRECTION_THRESHOLD * self.pixel_unit();
+        let mut overshoots = Vec::new();
+        let mut submovements = 0;
+        let mut previous_press = f64::NEG_INFINITY;
+        
+        for press in clicks.iter().filter(|e| e.event_type == "press") {
+            let start = previous_press.max(press.timestamp - APPROACH_SECONDS);
+            previous_press = press.timestamp;
+            let mut path: Vec<(f64, f64)> = movements
+                .iter()
+                .filter(|e| e.timestamp > start && e.timestamp <= press.timestamp)
+                .filter_map(|e| Some(self.normalize(e.x?, e.y?)))
+                .collect();
+            if let (Some(x), Some(y)) = (press.x, press.y) {
+                path.push(self.normalize(x, y));
+            }
+            let (Some(&origin), Some(&target)) = (path.first(), path.last()) else {
+                continue;
+            };
+            let (dx, dy) = (target.0 - origin.0, target.1 - origin.1);
+            let distance = dx.hypot(dy);
+            if path.len() < 3 || distance <= threshold {
+                continue;
+            }
+            
+            // Progress towards the click point along the direction of travel
+            let along: Vec<f64> = path
+                .iter()
+                .map(|p| ((p.0 - origin.0) * dx + (p.1 - origin.1) * dy) / distance)
+                .collect();
+            let furthest = stats::max(&along);
+            if furthest > distance + threshold {
+                overshoots.push(furthest - distance);
+            }
+            
+            // A reversal only counts once the pointer has backed off the last extreme by the threshold
+            let mut forward = true;
+            let mut extreme = along[0];
+            for &position in &along[1..] {
+                let retreat = if forward { extreme - position } else { position - extreme };
+                if retreat > threshold {
+                    forward = !forward;
+                    extreme = position;
+                    submovements += 1;
+                } else if retreat < 0.0 {
+                    extreme = position;
+                }
+            }
+        }
+        
+        record.overshoot_count = overshoots.len() as f64;
+        record.overshoot_distance_mean = stats::mean(&overshoots);
+        if overshoots.is_empty() {
+            record.mark_padded(|name| name == "overshoot_distance_mean");
+        }
+        record.submovement_count = submovements as f64;
+    }
+    
+    /// Time the cursor sat still before each click, and short stops within movement
+    ///
+    /// A step between consecutive samples is moving if it's faster than `PAUSE_SPEED`;
+    /// moves are only reported while the cursor moves, so a gap between samples is a
+    /// stop too. Dwell before a click runs from the end of the last moving step (the
+    /// step to where the click landed included) to the press; a click with no moving
+    /// step in the lookback isn't counted. A micro-pause is the stop between two moving
+    /// steps, if it lasts within `MICRO_PAUSE_SECONDS`.
+    fn calculate_pauses(
+        &self,
+        movements: &[&MouseEvent],
+        clicks: &[&MouseEvent],
+        record: &mut MouseFeatureRecord,
+    ) {
+        let pause_speed = PAUSE_SPEED * self.pixel_unit();
+        let is_moving = |(t0, (x0, y0)): (f64, (f64, f64)), (t1, (x1, y1)): (f64, (f64, f64))| {
+            t1 > t0 && (x1 - x0).hypot(y1 - y0) / (t1 - t0) > pause_speed
+        };
+        let samples: Vec<(f64, (f64, f64))> = movements
+            .iter()
+            .filter_map(|e| Some((e.timestamp, self.normalize(e.x?, e.y?))))
             .collect();
         
-        if deltas.is_empty() {
-            for i in 0..8 {
-                features.insert(format!("scroll_{}", i), 0.0);
+        let mut micro_pauses = 0;
+        let mut last_moving_end = None;
+        for step in samples.windows(2) {
+            if !is_moving(step[0], step[1]) {
+                continue;
+            }
+            if let Some(end) = last_moving_end {
+                let pause = step[0].0 - end;
+                if pause >= MICRO_PAUSE_SECONDS.0 && pause <= MICRO_PAUSE_SECONDS.1 {
+                    micro_pauses += 1;
+                }
             }
-            return features;
+            last_moving_end = Some(step[1].0);
         }
         
-        let up_scrolls: Vec<f64> = deltas.iter().filter(|&&d| d > 0.0).copied().collect();
-        let down_scrolls: Vec<f64> = deltas.iter().filter(|&&d| d < 0.0).copied().collect();
+        let mut dwells = Vec::new();
+        let mut previous_press = f64::NEG_INFINITY;
+        for press in clicks.iter().filter(|e| e.event_type == "press") {
+            let start = previous_press.max(press.timestamp - DWELL_LOOKBACK_SECONDS);
+            previous_press = press.timestamp;
+            let mut approach: Vec<(f64, (f64, f64))> = samples
+                .iter()
+                .filter(|(t, _)| *t > start && *t <= press.timestamp)
+                .copied()
+                .collect();
+            if let (Some(x), Some(y)) = (press.x, press.y) {
+                approach.push((press.timestamp, self.normalize(x, y)));
+            }
+            let stopped_at = approach
+                .windows(2)
+                .rev()
+                .find(|step| is_moving(step[0], step[1]))
+                .map(|step| step[1].0);
+            if let Some(stopped_at) = stopped_at {
+                dwells.push((press.timestamp - stopped_at) * 1000.0);
+            }
+        }
         
-        let times: Vec<f64> = scrolls.iter().map(|e| e.timestamp).collect();
-        let mut intervals = Vec::new();
-        for i in 0..times.len().saturating_sub(1) {
-            intervals.push(times[i + 1] - times[i]);
+        record.dwell_before_click_mean = stats::mean(&dwells);
+        if dwells.is_empty() {
+            record.mark_padded(|name| name == "dwell_before_click_mean");
         }
+        record.micro_pause_count = micro_pauses as f64;
+    }
+    
+    /// 0-1 score of how synthetic the movement looks; `None` under three steps
+    ///
+    /// Averages three signals that human jitter keeps low: constant speed,
+    /// dead-straight headings and identical step lengths between samples.
+    fn calculate_bot_likelihood(&self, movements: &[&MouseEvent]) -> Option<f64> {
+        let samples: Vec<(f64, f64, f64)> = movements
+            .iter()
+            .filter_map(|e| {
+                let (x, y) = self.normalize(e.x?, e.y?);
+                Some((x, y, e.timestamp))
+            })
+            .collect();
         
-        features.insert("scroll_0".to_string(), self.mean(&deltas.iter().map(|d| d.abs()).collect::<Vec<_>>()));
-        features.insert("scroll_1".to_string(), self.std_dev(&deltas));
-        features.insert("scroll_2".to_string(), up_scrolls.len() as f64);
-        features.insert("scroll_3".to_string(), down_scrolls.len() as f64);
-        features.insert("scroll_4".to_string(), up_scrolls.len() as f64 / deltas.len() as f64);
-        features.insert("scroll_5".to_string(), scrolls.len() as f64 / self.window_seconds as f64);
-        features.insert("scroll_6".to_string(), self.mean(&intervals));
-        features.insert("scroll_7".to_string(), self.std_dev(&intervals));
+        // Ignore samples where the pointer didn't move; they carry no heading
+        let steps: Vec<(f64, f64, f64)> = samples
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0, w[1].1 - w[0].1, (w[1].2 - w[0].2).max(0.001)))
+            .filter(|(dx, dy, _)| dx.hypot(*dy) > 0.0)
+            .collect();
+        if steps.len() < 3 {
+            return None;
+        }
         
-        features
+        let distances: Vec<f64> = steps.iter().map(|(dx, dy, _)| dx.hypot(*dy)).collect();
+        let speeds: Vec<f64> = steps.iter().map(|(dx, dy, dt)| dx.hypot(*dy) / dt).collect();
+        let speed_cv = stats::std_dev(&speeds) / stats::mean(&speeds).max(f64::EPSILON);
+        let constant_speed = 1.0 - (speed_cv / HUMAN_SPEED_CV).min(1.0);
+        
+        let heading_changes: Vec<f64> = steps
+            .windows(2)
+            .map(|w| {
+                let diff = (w[1].1.atan2(w[1].0) - w[0].1.atan2(w[0].0)).abs();
+                diff.min(2.0 * PI - diff)
+            })
+            .collect();
+        let straight_path = 1.0 - (stats::mean(&heading_changes) / HUMAN_ANGLE_JITTER).min(1.0);
+        
+        let tolerance = 1e-6 * self.pixel_unit();
+        let identical_steps = distances
+            .windows(2)
+            .filter(|w| (w[1] - w[0]).abs() < tolerance)
+            .count() as f64
+            / (distances.len() - 1) as f64;
+        
+        Some((constant_speed + straight_path + identical_steps) / 3.0)
     }
     
-    // Utility statistics
-    fn mean(&self, values: &[f64]) -> f64 {
-        if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+    /// Where clicks land: centroid, per-axis variance and 2-cluster separation
+    fn calculate_click_spread(&self, presses: &[&MouseEvent], record: &mut MouseFeatureRecord) {
+        let points: Vec<(f64, f64)> = presses
+            .iter()
+            .filter_map(|e| Some(self.normalize(e.x?, e.y?)))
+            .collect();
+        
+        if points.is_empty() {
+            record.mark_padded(|name| name.starts_with("click_centroid_") || name.starts_with("click_var_"));
+        }
+        if points.len() < 4 {
+            record.mark_padded(|name| name == "click_separation");
+        }
+        let x: Vec<f64> = points.iter().map(|p| p.0).collect();
+        let y: Vec<f64> = points.iter().map(|p| p.1).collect();
+        record.click_centroid_x = stats::mean(&x);
+        record.click_centroid_y = stats::mean(&y);
+        record.click_var_x = stats::std_dev(&x).powi(2);
+        record.click_var_y = stats::std_dev(&y).powi(2);
+        record.click_separation = self.two_cluster_separation(&points);
     }
     
-    fn std_dev(&self, values: &[f64]) -> f64 {
-        if values.len() < 2 { return 0.0; }
-        let mean = self.mean(values);
-        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
-        variance.sqrt()
+    /// Fractions of movement samples and click presses in each quadrant of the screen
+    fn calculate_quadrants(
+        &self,
+        movements: &[&MouseEvent],
+        clicks: &[&MouseEvent],
+        record: &mut MouseFeatureRecord,
+    ) {
+        let presses: Vec<&MouseEvent> = clicks
+            .iter()
+            .filter(|e| e.event_type == "press")
+            .copied()
+            .collect();
+        
+        match self.quadrant_fractions(movements) {
+            Some(fractions) => {
+                [
+                    record.move_frac_top_left,
+                    record.move_frac_top_right,
+                    record.move_frac_bottom_left,
+                    record.move_frac_bottom_right,
+                ] = fractions
+            }
+            None => record.mark_padded(|name| name.starts_with("move_frac_")),
+        }
+        match self.quadrant_fractions(&presses) {
+            Some(fractions) => {
+                [
+                    record.click_frac_top_left,
+                    record.click_frac_top_right,
+                    record.click_frac_bottom_left,
+                    record.click_frac_bottom_right,
+                ] = fractions
+            }
+            None => record.mark_padded(|name| name.starts_with("click_frac_")),
+        }
     }
     
-    fn min(&self, values: &[f64]) -> f64 {
-        values.iter().cloned().fold(f64::INFINITY, f64::min)
+    /// Share of positioned events in the top-left, top-right, bottom-left and
+    /// bottom-right quadrants, in that order; `None` when none have a position
+    fn quadrant_fractions(&self, events: &[&MouseEvent]) -> Option<[f64; 4]> {
+        let mut counts = [0usize; 4];
+        for (x, y) in events.iter().filter_map(|e| Some(self.normalize(e.x?, e.y?))) {
+            counts[usize::from(x >= 0.5) + 2 * usize::from(y >= 0.5)] += 1;
+        }
+        let total = counts.iter().sum::<usize>();
+        (total > 0).then(|| counts.map(|count| count as f64 / total as f64))
     }
     
-    fn max(&self, values: &[f64]) -> f64 {
-        values.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+    /// Share of click-position variance explained by splitting into two clusters
+    /// (2-means): near 1.0 for two tight groups, lower for one blob or uniform spread
+    fn two_cluster_separation(&self, points: &[(f64, f64)]) -> f64 {
+        if points.len() < 4 {
+            return 0.0;
+        }
+        
+        let dist2 = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+        let centroid = |group: &[(f64, f64)]| {
+            let n = group.len() as f64;
+            (
+                group.iter().map(|p| p.0).sum::<f64>() / n,
+                group.iter().map(|p| p.1).sum::<f64>() / n,
+            )
+        };
+        
+        let overall = centroid(points);
+        let total_ss: f64 = points.iter().map(|&p| dist2(p, overall)).sum();
+        if total_ss <= 0.0 {
+            return 0.0;
+        }
+        
+        // Seed with the point farthest from the centroid and the point farthest from that
+        let farthest_from = |origin: (f64, f64)| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| dist2(*a, origin).partial_cmp(&dist2(*b, origin)).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap_or(origin)
+        };
+        let mut a = farthest_from(overall);
+        let mut b = farthest_from(a);
+
+        let mut within_ss = total_ss;
+        for _ in 0..10 {
+            let (group_a, group_b): (Vec<_>, Vec<_>) =
+                points.iter().partition(|&&p| dist2(p, a) <= dist2(p, b));
+            if group_a.is_empty() || group_b.is_empty() {
+                return 0.0;
+            }
+            a = centroid(&group_a);
+            b = centroid(&group_b);
+            within_ss = group_a.iter().map(|&p| dist2(p, a)).sum::<f64>()
+                + group_b.iter().map(|&p| dist2(p, b)).sum::<f64>();
+        }
+        
+        1.0 - within_ss / total_ss
     }
     
-    fn median(&self, values: &[f64]) -> f64 {
-        if values.is_empty() { return 0.0; }
-        let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let mid = sorted.len() / 2;
-        if sorted.len() % 2 == 0 {
-            (sorted[mid - 1] + sorted[mid]) / 2.0
-        } else {
-            sorted[mid]
+    fn calculate_scroll_features(&self, scrolls: &[&MouseEvent], record: &mut MouseFeatureRecord) {
+
+        // Zero deltas (e.g. horizontal-only wheel events) carry no vertical scroll
+        let deltas: Vec<f64> = scrolls
+            .iter()
+            .filter_map(|e| e.scroll_delta)
+            .filter(|d| *d != 0.0 && d.is_finite())
+            .collect();
+        
+        if deltas.is_empty() {
+            record.mark_padded(|name| name.starts_with("scroll_"));
+            return;
         }
+        
+        let up_scrolls: Vec<f64> = deltas.iter().filter(|&&d| d > 0.0).copied().collect();
+        let down_scrolls: Vec<f64> = deltas.iter().filter(|&&d| d < 0.0).copied().collect();
+        
+        // High-resolution devices split one gesture into many small deltas, so the
+        // direction ratio weighs by distance scrolled rather than event count
+        let up_magnitude: f64 = up_scrolls.iter().sum();
+        let total_magnitude: f64 = deltas.iter().map(|d| d.abs()).sum();
+        
+        let times: Vec<f64> = scrolls.iter().map(|e| e.timestamp).collect();
+        let mut intervals = Vec::new();
+        for i in 0..times.len().saturating_sub(1) {
+            intervals.push(times[i + 1] - times[i]);
+        }
+        
+        record.scroll_0 = stats::mean(&deltas.iter().map(|d| d.abs()).collect::<Vec<_>>());
+        record.scroll_1 = stats::std_dev(&deltas);
+        record.scroll_2 = up_scrolls.len() as f64;
+        record.scroll_3 = down_scrolls.len() as f64;
+        record.scroll_4 = up_magnitude / total_magnitude;
+        record.scroll_5 = scrolls.len() as f64 / self.window_seconds as f64;
+        record.scroll_6 = stats::mean(&intervals);
+        record.scroll_7 = stats::std_dev(&intervals);
     }
 }
@@ -1,5 +1,7 @@
 pub mod extractor;
 pub mod features;
+pub mod record;
 
 pub use extractor::MouseExtractor;
 pub use features::MouseFeatureCalculator;
+pub use record::MouseFeatureRecord;
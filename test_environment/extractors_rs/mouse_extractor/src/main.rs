@@ -1,41 +1,44 @@
 use mouse_extractor::MouseExtractor;
-use common::{init_logging, AppContext};
-use tokio::time::{interval, Duration};
-use tracing::{info, error};
+use common::{
+    extract_offline, init_logging, run_live, AppContext, Config, LiveModality, UserSessions,
+};
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     init_logging();
     info!("Mouse Extractor starting");
     
-    let ctx = AppContext::new().await?;
-    let config = ctx.config.clone();
-    
-    let mut extractor = MouseExtractor::new(config.window_seconds);
-    
-    let mut update_interval = interval(Duration::from_secs(config.update_interval));
-    let mut cleanup_interval = interval(Duration::from_secs(60));
-    
-    info!("Mouse Extractor initialized and ready");
+    let config = Config::load()?;
+    if let (Some(width), Some(height)) = (config.screen_width, config.screen_height) {
+        info!("Normalizing mouse coordinates to a {}x{} screen", width, height);
+    }
     
-    loop {
-        tokio::select! {
-            _ = update_interval.tick() => {
-                if let Some(features) = extractor.extract_features() {
-                    info!("Extracted mouse features");
-                    
-                    if let Err(e) = ctx.redis.publish_features(
-                        "seclyzer:features:mouse",
-                        &features,
-                    ).await {
-                        error!("Failed to publish features: {}", e);
-                    }
-                }
-            }
-            _ = cleanup_interval.tick() => {
-                extractor.cleanup_old_events();
-                info!("Cleaned up old events");
-            }
-        }
+    // Recorded events are extracted on event time and printed; no Redis or InfluxDB needed
+    if let Some(mut source) = config.event_source.open_local().await? {
+        let extractor_config = config.clone();
+        let mut extractors = UserSessions::new(config.user_id.clone(), move |_| {
+            MouseExtractor::from_config(&extractor_config)
+        });
+        let windows = extract_offline(
+            source.as_mut(),
+            &mut extractors,
+            config.hop_seconds(),
+            &mut std::io::stdout(),
+        )
+        .await?;
+        info!("Extracted {} feature windows from {:?}", windows, config.event_source);
+        return Ok(());
     }
+    
+    let ctx = AppContext::from_config(config).await?;
+    run_live::<MouseExtractor>(
+        ctx,
+        LiveModality {
+            name: "mouse",
+            collector: "mouse",
+            title: "Mouse",
+        },
+    )
+    .await
 }
@@ -0,0 +1,133 @@
+use common::MouseFeatureProfile;
+
+/// Model features per window, as documented; the build fails if the record disagrees
+pub const FEATURE_COUNT: usize = 65;
+
+common::feature_record! {
+    /// One window of mouse features
+    pub struct MouseFeatureRecord {
+        schema_version 2;
+        features {
+            move_0, move_1, move_2, move_3, move_4,
+            move_5, move_6, move_7, move_8, move_9,
+            move_10, move_11, move_12, move_13, move_14,
+            move_15, move_16, move_17, move_18, move_19,
+            click_0, click_1, click_2, click_3, click_4,
+            click_5, click_6, click_7, click_8, click_9,
+            click_centroid_x, click_centroid_y, click_var_x, click_var_y,
+            click_separation,
+            click_dwell_left, click_dwell_right, click_dwell_middle, click_chord_fraction,
+            overshoot_count, overshoot_distance_mean, submovement_count,
+            dwell_before_click_mean, micro_pause_count,
+            velocity_peak_ratio, high_speed_fraction, flick_count,
+            scroll_0, scroll_1, scroll_2, scroll_3,
+            scroll_4, scroll_5, scroll_6, scroll_7,
+            move_frac_top_left, move_frac_top_right,
+            move_frac_bottom_left, move_frac_bottom_right,
+            click_frac_top_left, click_frac_top_right,
+            click_frac_bottom_left, click_frac_bottom_right,
+            bot_likelihood,
+            dev_mode,
+        }
+        metadata {
+            insufficient_data, observed_event_rate, window_coverage, window_start, window_end,
+        }
+    }
+}
+
+const _: () = assert!(MouseFeatureRecord::feature_count() == FEATURE_COUNT);
+
+/// Model features a `compact` record keeps, in `FEATURE_NAMES` order
+pub const COMPACT_FEATURES: &[&str] = &[
+    "move_0", "move_1", "move_2", "move_3", "move_4",
+    "move_5", "move_6", "move_7", "move_8", "move_9",
+    "move_12", "move_15", "move_18", "move_19",
+    "click_0", "click_1", "click_5", "click_6", "click_8", "click_9",
+    "overshoot_count", "dwell_before_click_mean",
+    "velocity_peak_ratio", "high_speed_fraction",
+    "scroll_0", "scroll_2", "scroll_3", "scroll_5",
+    "bot_likelihood",
+    "dev_mode",
+];
+
+/// Model features a `minimal` record keeps, in `FEATURE_NAMES` order
+pub const MINIMAL_FEATURES: &[&str] = &[
+    "move_0", "move_4", "move_18",
+    "click_0", "click_8",
+    "scroll_5",
+    "bot_likelihood",
+    "dev_mode",
+];
+
+const _: () = assert!(is_feature_subset(COMPACT_FEATURES) && is_feature_subset(MINIMAL_FEATURES));
+
+/// Whether every name is a model feature, so a renamed feature can't silently
+/// drop out of a profile
+const fn is_feature_subset(names: &[&str]) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        let mut found = false;
+        let mut j = 0;
+        while j < MouseFeatureRecord::FEATURE_NAMES.len() {
+            found |= str_eq(names[i], MouseFeatureRecord::FEATURE_NAMES[j]);
+            j += 1;
+        }
+        if !found {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Model features a record published under `profile` carries, in `FEATURE_NAMES` order
+pub fn profile_features(profile: MouseFeatureProfile) -> &'static [&'static str] {
+    match profile {
+        MouseFeatureProfile::Full => MouseFeatureRecord::FEATURE_NAMES,
+        MouseFeatureProfile::Compact => COMPACT_FEATURES,
+        MouseFeatureProfile::Minimal => MINIMAL_FEATURES,
+    }
+}
+
+/// A published record cut down to `profile`: model features outside it are removed
+/// (from `padded_features` too) and the rest tagged with `feature_profile`; a `full`
+/// record is returned as is
+pub fn restrict_to_profile(
+    mut features: serde_json::Value,
+    profile: MouseFeatureProfile,
+) -> serde_json::Value {
+    if profile == MouseFeatureProfile::Full {
+        return features;
+    }
+    let kept = profile_features(profile);
+    let dropped =
+        |name: &str| MouseFeatureRecord::FEATURE_NAMES.contains(&name) && !kept.contains(&name);
+    if let Some(fields) = features.as_object_mut() {
+        fields.retain(|name, _| !dropped(name));
+        if let Some(serde_json::Value::Array(padded)) = fields.get_mut("padded_features") {
+            padded.retain(|name| name.as_str().is_some_and(|name| !dropped(name)));
+        }
+        let name = match profile {
+            MouseFeatureProfile::Full => "full",
+            MouseFeatureProfile::Compact => "compact",
+            MouseFeatureProfile::Minimal => "minimal",
+        };
+        fields.insert("feature_profile".to_string(), name.into());
+    }
+    features
+}
@@ -0,0 +1,186 @@
+use anyhow::{bail, Context, Result};
+use common::{
+    event_bus, extract_offline, init_logging, Config, IdleRecord, InfluxClient,
+    MemoryEventSource, RawEvent, RedisClient, UserSessions, WindowedExtractor,
+};
+use keystroke_extractor::KeystrokeExtractor;
+use mouse_extractor::MouseExtractor;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tracing::info;
+
+const USAGE: &str = "usage: replay <events.jsonl> [--speed <factor>] [--to-influx]\n\
+    \n\
+    --speed 1     replay at the recorded pace (default)\n\
+    --speed 2     replay twice as fast\n\
+    --speed 0     replay as fast as possible\n\
+    --to-influx   extract features from the file and write them to InfluxDB\n\
+    \x20             at their windows' timestamps instead of publishing the events";
+
+struct Args {
+    path: PathBuf,
+    speed: f64,
+    /// Backfill features into InfluxDB rather than replaying over the bus
+    to_influx: bool,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut path = None;
+    let mut speed: f64 = 1.0;
+    let mut to_influx = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let value = args.next().context("--speed needs a value")?;
+                speed = value
+                    .parse()
+                    .with_context(|| format!("Invalid --speed {:?}", value))?;
+                if !speed.is_finite() || speed < 0.0 {
+                    bail!("--speed must be a non-negative number (got {})", speed);
+                }
+            }
+            "--to-influx" => to_influx = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => bail!("unexpected argument {:?}\n{}", arg, USAGE),
+        }
+    }
+    let path = path.with_context(|| format!("missing event file\n{}", USAGE))?;
+    Ok(Args {
+        path,
+        speed,
+        to_influx,
+    })
+}
+
+/// Parse a newline-delimited JSON log of `RawEvent`s, skipping blank lines
+fn read_events(path: &Path) -> Result<Vec<RawEvent>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid event", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Pause between two recorded timestamps (microseconds) scaled by `speed`
+fn replay_delay(prev_ts: u64, ts: u64, speed: f64) -> Duration {
+    if speed == 0.0 {
+        return Duration::ZERO;
+    }
+    let gap_secs = ts.saturating_sub(prev_ts) as f64 / 1_000_000.0;
+    Duration::from_secs_f64(gap_secs / speed)
+}
+
+/// Run `events` through `extractor` window by window, as offline extraction does, and
+/// queue each record at its window's end; returns the number of points queued
+async fn backfill<E: WindowedExtractor>(
+    influx: &InfluxClient,
+    modality: &str,
+    mut extractor: E,
+    events: &[RawEvent],
+    hop_seconds: u64,
+) -> Result<usize> {
+    let mut source = MemoryEventSource::from(events.to_vec());
+    let mut records = Vec::new();
+    extract_offline(&mut source, &mut extractor, hop_seconds, &mut records).await?;
+
+    let mut points = 0;
+    for line in String::from_utf8(records)?.lines() {
+        let record: serde_json::Value = serde_json::from_str(line)?;
+        let window_end = record["window_end"]
+            .as_f64()
+            .context("feature record without window_end")?;
+        let measurement = if IdleRecord::is_idle(&record) {
+            format!("{}_activity", modality)
+        } else {
+            format!("{}_features", modality)
+        };
+        let timestamp_ns = (window_end * 1e9).round() as i64;
+        influx
+            .queue_features_at(&measurement, &record, timestamp_ns)
+            .await?;
+        points += 1;
+    }
+    Ok(points)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    init_logging();
+    let args = parse_args()?;
+
+    let config = Config::load()?;
+    if args.to_influx {
+        let events = read_events(&args.path)?;
+        let influx = InfluxClient::new(&config).await?;
+        let hop = config.hop_seconds();
+        // Each user's events are windowed separately and their points tagged with them
+        let keystroke_config = config.clone();
+        let keystroke = backfill(
+            &influx,
+            "keystroke",
+            UserSessions::new(config.user_id.clone(), move |_| {
+                KeystrokeExtractor::from_config(&keystroke_config)
+            }),
+            &events,
+            hop,
+        )
+        .await?;
+        let mouse_config = config.clone();
+        let mouse = backfill(
+            &influx,
+            "mouse",
+            UserSessions::new(config.user_id.clone(), move |_| {
+                MouseExtractor::from_config(&mouse_config)
+            }),
+            &events,
+            hop,
+        )
+        .await?;
+        influx.flush().await?;
+        info!(
+            "Backfilled {} keystroke and {} mouse points from {} events in {}",
+            keystroke,
+            mouse,
+            events.len(),
+            args.path.display()
+        );
+        return Ok(());
+    }
+
+    let redis = Arc::new(RedisClient::new(&config).await?);
+    let bus = event_bus::connect(&config, redis).await?;
+    let events = read_events(&args.path)?;
+    let event_channel = config.event_channel();
+    info!(
+        "Replaying {} events from {} to {} at speed {}",
+        events.len(),
+        args.path.display(),
+        event_channel,
+        args.speed
+    );
+
+    let mut prev_ts = None;
+    for event in &events {
+        if let Some(prev_ts) = prev_ts {
+            sleep(replay_delay(prev_ts, event.ts, args.speed)).await;
+        }
+        prev_ts = Some(event.ts);
+        bus.publish(&event_channel, &serde_json::to_value(event)?).await?;
+    }
+
+    info!("Replayed {} events", events.len());
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+/// Combines the latest score from each modality into one fused score
+#[derive(Debug, Default)]
+pub struct ScoreFusion {
+    latest: HashMap<String, f64>,
+}
+
+impl ScoreFusion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a modality's newest score and return the fused score
+    pub fn update(&mut self, modality: &str, score: f64) -> f64 {
+        self.latest.insert(modality.to_string(), score);
+        self.fused()
+    }
+
+    /// Mean of the latest per-modality scores, 0.0 before any arrive
+    pub fn fused(&self) -> f64 {
+        if self.latest.is_empty() {
+            0.0
+        } else {
+            self.latest.values().sum::<f64>() / self.latest.len() as f64
+        }
+    }
+
+    pub fn latest(&self) -> &HashMap<String, f64> {
+        &self.latest
+    }
+}
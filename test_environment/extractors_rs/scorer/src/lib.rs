@@ -0,0 +1,10 @@
+pub mod fusion;
+pub mod linear;
+
+pub use fusion::ScoreFusion;
+pub use linear::{load_models, LinearScorer};
+
+/// Turns one modality's feature map into an authentication score
+pub trait Scorer: Send + Sync {
+    fn score(&self, features: &serde_json::Value) -> f64;
+}
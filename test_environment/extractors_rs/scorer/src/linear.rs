@@ -0,0 +1,63 @@
+use crate::Scorer;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use common::record::SCHEMA_VERSION_FIELD;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// `bias + Σ weight·feature`; features missing from the map count as 0.0
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LinearScorer {
+    #[serde(default)]
+    pub bias: f64,
+    pub weights: HashMap<String, f64>,
+    /// Feature `schema_version` the weights were fit on; other versions aren't scored
+    #[serde(default)]
+    pub schema_version: Option<u32>,
+}
+
+impl LinearScorer {
+    pub fn new(bias: f64, weights: HashMap<String, f64>) -> Self {
+        LinearScorer { bias, weights, schema_version: None }
+    }
+
+    /// Only score features carrying this `schema_version`
+    pub fn with_schema_version(mut self, version: u32) -> Self {
+        self.schema_version = Some(version);
+        self
+    }
+
+    /// Whether `features` come from the feature set the model was fit on; always
+    /// true for a model without a `schema_version`
+    pub fn accepts(&self, features: &serde_json::Value) -> bool {
+        let Some(version) = self.schema_version else {
+            return true;
+        };
+        features.get(SCHEMA_VERSION_FIELD).and_then(|v| v.as_u64()) == Some(version as u64)
+    }
+
+    /// Load a single model: `{"bias": 0.1, "weights": {"dwell_mean": 0.02, ...}}`,
+    /// optionally with the `"schema_version"` it was fit on
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+impl Scorer for LinearScorer {
+    fn score(&self, features: &serde_json::Value) -> f64 {
+        self.weights.iter().fold(self.bias, |acc, (name, weight)| {
+            let value = features.get(name).and_then(|v| v.as_f64()).unwrap_or(0.0);
+            acc + weight * value
+        })
+    }
+}
+
+/// Load one linear model per modality from a file keyed by modality name
+pub fn load_models(path: &Path) -> Result<HashMap<String, LinearScorer>> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+}
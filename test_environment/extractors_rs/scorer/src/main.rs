@@ -0,0 +1,103 @@
+use common::record::SCHEMA_VERSION_FIELD;
+use common::{
+    init_logging, shutdown_signal, tag_user, Config, IdleRecord, RedisClient, UserSessions,
+    USER_ID_FIELD,
+};
+use scorer::{load_models, ScoreFusion, Scorer};
+use serde_json::json;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_logging();
+    info!("Scorer starting");
+
+    // Scoring only needs Redis, so skip AppContext and its InfluxDB check
+    let config = Config::load()?;
+    let redis = RedisClient::new(&config).await?;
+    let models = load_models(Path::new(&config.scorer_model_path))?;
+    info!(
+        "Loaded models for {:?} from {}",
+        models.keys().collect::<Vec<_>>(),
+        config.scorer_model_path
+    );
+
+    let feature_prefix = config.feature_channel_prefix();
+    let score_channel = config.score_channel();
+    let mut features = redis.psubscribe(&format!("{}*", feature_prefix)).await?;
+    // Each user's modalities are fused with each other, never with another user's
+    let mut fusions = UserSessions::new(None, |_| ScoreFusion::new());
+
+    info!("Scorer initialized and ready");
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some((channel, payload)) = features.recv() => {
+                // Only plain modality channels; `:dev` and `:scores` sub-channels are skipped
+                let Some(modality) = channel.strip_prefix(feature_prefix.as_str()) else {
+                    continue;
+                };
+                if modality.contains(':') {
+                    continue;
+                }
+                let Some(model) = models.get(modality) else {
+                    continue;
+                };
+                let features: serde_json::Value = match serde_json::from_str(&payload) {
+                    Ok(features) => features,
+                    Err(e) => {
+                        warn!("Skipping malformed features on {}: {}", channel, e);
+                        continue;
+                    }
+                };
+                // Idle windows have no behaviour to score
+                if IdleRecord::is_idle(&features) {
+                    continue;
+                }
+
+                // Weights fit on another feature set would give a meaningless score
+                if !model.accepts(&features) {
+                    warn!(
+                        "Skipping {} features with schema_version {} (model expects {:?})",
+                        modality,
+                        features.get(SCHEMA_VERSION_FIELD).unwrap_or(&json!(null)),
+                        model.schema_version
+                    );
+                    continue;
+                }
+
+                let score = model.score(&features);
+                let user = features[USER_ID_FIELD].as_str();
+                let fusion = fusions.session(user);
+                let fused_score = fusion.update(modality, score);
+                let mut message = json!({
+                    "modality": modality,
+                    "score": score,
+                    "fused_score": fused_score,
+                    "scores": fusion.latest(),
+                    "ts": unix_now(),
+                });
+                tag_user(&mut message, user);
+                if let Err(e) = redis.publish_features(&score_channel, &message).await {
+                    error!("Failed to publish score: {}", e);
+                }
+            }
+        }
+    }
+
+    info!("Scorer stopped cleanly");
+    Ok(())
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
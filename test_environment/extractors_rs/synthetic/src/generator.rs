@@ -0,0 +1,374 @@
+use crate::profile::{Params, Profile, Typing};
+use common::RawEvent;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Screen the mouse stream moves across, in pixels
+const SCREEN: (f64, f64) = (1920.0, 1080.0);
+
+/// Gap between mouse samples while the cursor moves (125 Hz, a common polling rate)
+const SAMPLE_INTERVAL: f64 = 0.008;
+
+/// Chance a prose keystroke is a wrong letter that gets backspaced
+const TYPO_PROBABILITY: f64 = 0.03;
+
+/// Chance a movement key is the same as the previous one
+const REPEAT_PROBABILITY: f64 = 0.5;
+
+/// Shortest gap between releasing a key and pressing it again
+const REPRESS_GAP: f64 = 0.03;
+
+/// Letters likely to follow each letter in English text; a space ends the word
+const SUCCESSORS: [&str; 26] = [
+    "nrtslcd ", "eloua", "oheatk", " eiao", " rsndaltc", " oiaeu", " eho", "eaiot ", "ntscolr",
+    "ou", "e i", "eilaoy ", "eaoip", " gdtesa", "nfruwm ", "reaol", "u", "eoaist ", " teis",
+    "h eoia", "rnstl", "ei", "aheio", "pt", " o", "e",
+];
+
+/// Letters words start with
+const WORD_STARTS: &str = "tawsohibcmfpd";
+
+/// Longest word before a space is forced
+const MAX_WORD_LEN: usize = 9;
+
+/// Movement and action keys, each weighted by how often it's picked
+const MOVEMENT_KEYS: &[(&str, u32)] = &[
+    ("KeyW", 8),
+    ("KeyA", 4),
+    ("KeyS", 3),
+    ("KeyD", 4),
+    ("Space", 2),
+    ("ShiftLeft", 2),
+    ("KeyE", 1),
+    ("KeyR", 1),
+    ("Num1", 1),
+    ("Num2", 1),
+];
+
+/// Deterministic source of keystroke, mouse and app events
+///
+/// Each stream has its own seeded RNG, so the same seed produces the same events
+/// however the timeline is stepped through with `advance`.
+pub struct Generator {
+    params: Params,
+    start_us: u64,
+    dev_mode: bool,
+    user_id: Option<String>,
+    keyboard: KeyboardStream,
+    mouse: MouseStream,
+    apps: AppStream,
+    /// Generated events at or past the last `advance` horizon, by seconds since the start
+    pending: Vec<(f64, RawEvent)>,
+}
+
+impl Generator {
+    /// Events for `profile` from `seed`, stamped from `start_us` (Unix microseconds)
+    pub fn new(profile: Profile, seed: u64, start_us: u64) -> Self {
+        let rng = |stream: u64| StdRng::seed_from_u64(seed.wrapping_mul(3).wrapping_add(stream));
+        Generator {
+            params: profile.params(),
+            start_us,
+            dev_mode: false,
+            user_id: None,
+            keyboard: KeyboardStream {
+                rng: rng(0),
+                t: 0.0,
+                last_letter: None,
+                word_len: 0,
+                last_key: None,
+                released_at: HashMap::new(),
+            },
+            mouse: MouseStream {
+                rng: rng(1),
+                t: 0.0,
+                position: (SCREEN.0 / 2.0, SCREEN.1 / 2.0),
+            },
+            apps: AppStream {
+                rng: rng(2),
+                t: 0.0,
+                current: None,
+            },
+            pending: Vec::new(),
+        }
+    }
+
+    /// Tag events as dev-mode self-testing data
+    pub fn with_dev_mode(mut self, dev_mode: bool) -> Self {
+        self.dev_mode = dev_mode;
+        self
+    }
+
+    /// Stamp events with the user they belong to
+    pub fn with_user_id(mut self, user_id: Option<String>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    /// Every event before `until` seconds after the start not returned yet, in timestamp order
+    pub fn advance(&mut self, until: f64) -> Vec<RawEvent> {
+        self.keyboard.fill(until, &self.params, &mut self.pending);
+        self.mouse.fill(until, &self.params, &mut self.pending);
+        self.apps.fill(until, &self.params, &mut self.pending);
+
+        self.pending.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let ready = self.pending.partition_point(|(t, _)| *t < until);
+        self.pending
+            .drain(..ready)
+            .map(|(t, mut event)| {
+                event.ts = self.start_us + (t * 1_000_000.0).round() as u64;
+                event.dev_mode = self.dev_mode;
+                event.user_id = self.user_id.clone();
+                event
+            })
+            .collect()
+    }
+}
+
+/// A `RawEvent` with only its type set; `ts` is filled in by `Generator::advance`
+fn blank(event_type: &str) -> RawEvent {
+    RawEvent {
+        event_type: event_type.to_string(),
+        ts: 0,
+        key: None,
+        code: None,
+        event: None,
+        x: None,
+        y: None,
+        button: None,
+        scroll_delta: None,
+        app_name: None,
+        window_class: None,
+        dev_mode: false,
+        user_id: None,
+    }
+}
+
+/// Normally distributed sample (Box-Muller)
+fn normal(rng: &mut StdRng, (mean, sd): (f64, f64)) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    mean + sd * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+fn uniform(rng: &mut StdRng, (low, high): (f64, f64)) -> f64 {
+    rng.gen_range(low..=high)
+}
+
+struct KeyboardStream {
+    rng: StdRng,
+    t: f64,
+    last_letter: Option<u8>,
+    /// Letters typed since the last space
+    word_len: usize,
+    last_key: Option<&'static str>,
+    /// When each key was last released, so a key isn't pressed again while held
+    released_at: HashMap<String, f64>,
+}
+
+impl KeyboardStream {
+    /// Type bursts of keys, each followed by a pause, until `until`
+    fn fill(&mut self, until: f64, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        while self.t < until {
+            let keys = self.rng.gen_range(params.burst_keys.0..=params.burst_keys.1);
+            for _ in 0..keys {
+                match params.typing {
+                    Typing::Prose => self.type_letter(params, out),
+                    Typing::Movement => {
+                        let key = self.movement_key();
+                        self.stroke(key.to_string(), params, out);
+                    }
+                }
+            }
+            self.t += uniform(&mut self.rng, params.burst_pause);
+        }
+    }
+
+    /// The next letter from the Markov chain, occasionally mistyped and corrected first
+    fn type_letter(&mut self, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        let letter = self.next_letter();
+        if letter != b' ' && self.rng.gen_bool(TYPO_PROBABILITY) {
+            let typo = self.rng.gen_range(b'a'..=b'z');
+            self.stroke(key_name(typo), params, out);
+            self.stroke("Backspace".to_string(), params, out);
+        }
+        self.stroke(key_name(letter), params, out);
+    }
+
+    fn next_letter(&mut self) -> u8 {
+        let next = match self.last_letter {
+            None | Some(b' ') => pick(&mut self.rng, WORD_STARTS.as_bytes()),
+            Some(_) if self.word_len >= MAX_WORD_LEN => b' ',
+            Some(letter) => pick(&mut self.rng, SUCCESSORS[(letter - b'a') as usize].as_bytes()),
+        };
+        self.word_len = if next == b' ' { 0 } else { self.word_len + 1 };
+        self.last_letter = Some(next);
+        next
+    }
+
+    fn movement_key(&mut self) -> &'static str {
+        if let Some(key) = self.last_key.filter(|_| self.rng.gen_bool(REPEAT_PROBABILITY)) {
+            return key;
+        }
+        let total: u32 = MOVEMENT_KEYS.iter().map(|(_, weight)| weight).sum();
+        let mut roll = self.rng.gen_range(0..total);
+        let key = MOVEMENT_KEYS
+            .iter()
+            .find(|(_, weight)| {
+                let hit = roll < *weight;
+                roll = roll.saturating_sub(*weight);
+                hit
+            })
+            .map_or("KeyW", |(key, _)| key);
+        self.last_key = Some(key);
+        key
+    }
+
+    /// Press and release `key`, then move on by one flight time
+    fn stroke(&mut self, key: String, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        let pressed = match self.released_at.get(&key) {
+            Some(released) => self.t.max(released + REPRESS_GAP),
+            None => self.t,
+        };
+        let dwell_ms = normal(&mut self.rng, params.dwell_ms).clamp(30.0, 1500.0);
+        let released = pressed + dwell_ms / 1000.0;
+        for (t, event) in [(pressed, "press"), (released, "release")] {
+            let mut raw = blank("keystroke");
+            raw.key = Some(key.clone());
+            raw.event = Some(event.to_string());
+            out.push((t, raw));
+        }
+        self.released_at.insert(key, released);
+        self.t = pressed + normal(&mut self.rng, params.flight_ms).clamp(20.0, 1000.0) / 1000.0;
+    }
+}
+
+fn pick(rng: &mut StdRng, choices: &[u8]) -> u8 {
+    choices[rng.gen_range(0..choices.len())]
+}
+
+/// rdev's name for a letter or the space bar, as the keyboard collector reports it
+fn key_name(letter: u8) -> String {
+    if letter == b' ' {
+        "Space".to_string()
+    } else {
+        format!("Key{}", letter.to_ascii_uppercase() as char)
+    }
+}
+
+struct MouseStream {
+    rng: StdRng,
+    t: f64,
+    position: (f64, f64),
+}
+
+impl MouseStream {
+    /// Move to random targets, sometimes clicking or scrolling, until `until`
+    fn fill(&mut self, until: f64, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        while self.t < until {
+            self.move_to_target(params, out);
+            if self.rng.gen_bool(params.click_probability) {
+                self.click(out);
+            }
+            if self.rng.gen_bool(params.scroll_probability) {
+                self.scroll(out);
+            }
+            self.t += uniform(&mut self.rng, params.move_pause);
+        }
+    }
+
+    /// A minimum-jerk reach whose duration follows Fitts' law, bowed slightly off the
+    /// straight line and landing near (not exactly on) the target centre
+    fn move_to_target(&mut self, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        let (x0, y0) = self.position;
+        let width = params.target_width;
+        let target = (
+            (self.rng.gen_range(0.0..SCREEN.0) + normal(&mut self.rng, (0.0, width / 4.0)))
+                .clamp(0.0, SCREEN.0 - 1.0),
+            (self.rng.gen_range(0.0..SCREEN.1) + normal(&mut self.rng, (0.0, width / 4.0)))
+                .clamp(0.0, SCREEN.1 - 1.0),
+        );
+        let (dx, dy) = (target.0 - x0, target.1 - y0);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance < 1.0 {
+            return;
+        }
+        let duration = params.fitts_a + params.fitts_b * (distance / width + 1.0).log2();
+        let samples = (duration / SAMPLE_INTERVAL).ceil().max(1.0) as usize;
+        let bow = normal(&mut self.rng, (0.0, 0.05 * distance));
+        let (nx, ny) = (-dy / distance, dx / distance);
+
+        for i in 1..=samples {
+            let tau = i as f64 / samples as f64;
+            let s = 10.0 * tau.powi(3) - 15.0 * tau.powi(4) + 6.0 * tau.powi(5);
+            let off = bow * (PI * tau).sin();
+            let x = x0 + s * dx + off * nx + normal(&mut self.rng, (0.0, 0.5));
+            let y = y0 + s * dy + off * ny + normal(&mut self.rng, (0.0, 0.5));
+            let mut raw = blank("mouse");
+            raw.event = Some("move".to_string());
+            raw.x = Some(x.clamp(0.0, SCREEN.0 - 1.0).round());
+            raw.y = Some(y.clamp(0.0, SCREEN.1 - 1.0).round());
+            out.push((self.t + i as f64 * SAMPLE_INTERVAL, raw));
+        }
+        self.t += samples as f64 * SAMPLE_INTERVAL;
+        self.position = target;
+    }
+
+    fn click(&mut self, out: &mut Vec<(f64, RawEvent)>) {
+        let button = if self.rng.gen_bool(0.05) { "Right" } else { "Left" };
+        let pressed = self.t + self.rng.gen_range(0.1..0.3);
+        let released = pressed + self.rng.gen_range(0.08..0.15);
+        for (t, event) in [(pressed, "press"), (released, "release")] {
+            let mut raw = blank("mouse");
+            raw.event = Some(event.to_string());
+            raw.button = Some(button.to_string());
+            raw.x = Some(self.position.0.round());
+            raw.y = Some(self.position.1.round());
+            out.push((t, raw));
+        }
+        self.t = released;
+    }
+
+    fn scroll(&mut self, out: &mut Vec<(f64, RawEvent)>) {
+        let direction = if self.rng.gen_bool(0.8) { -1.0 } else { 1.0 };
+        for _ in 0..self.rng.gen_range(3..=8) {
+            self.t += self.rng.gen_range(0.03..0.08);
+            let mut raw = blank("mouse");
+            raw.event = Some("scroll".to_string());
+            raw.scroll_delta = Some(direction);
+            out.push((self.t, raw));
+        }
+    }
+}
+
+struct AppStream {
+    rng: StdRng,
+    t: f64,
+    current: Option<usize>,
+}
+
+impl AppStream {
+    /// Focus a different app each time the current one's dwell runs out
+    fn fill(&mut self, until: f64, params: &Params, out: &mut Vec<(f64, RawEvent)>) {
+        while self.t < until {
+            let next = match self.current {
+                // The first app in the list is the main one and always comes first
+                None => 0,
+                Some(current) if params.apps.len() > 1 => {
+                    let other = self.rng.gen_range(0..params.apps.len() - 1);
+                    if other >= current { other + 1 } else { other }
+                }
+                Some(current) => current,
+            };
+            let (app_name, window_class) = params.apps[next];
+            let mut raw = blank("app");
+            raw.event = Some("focus".to_string());
+            raw.app_name = Some(app_name.to_string());
+            raw.window_class = Some(window_class.to_string());
+            out.push((self.t, raw));
+            self.current = Some(next);
+            self.t += uniform(&mut self.rng, params.app_dwell);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod generator;
+pub mod profile;
+
+pub use generator::Generator;
+pub use profile::Profile;
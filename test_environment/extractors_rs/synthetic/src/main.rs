@@ -0,0 +1,126 @@
+use anyhow::{bail, Context, Result};
+use common::{event_bus, init_logging, Config, RedisClient};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use synthetic::{Generator, Profile};
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::info;
+
+const USAGE: &str = "usage: synthetic [--profile <name>] [--rate <factor>] [--duration <seconds>]\n\
+    \x20                [--seed <n>] [--stdout]\n\
+    \n\
+    --profile office|gaming|idle   kind of activity to imitate (default office)\n\
+    --rate 1         generate in real time (default)\n\
+    --rate 10        generate ten simulated seconds per second\n\
+    --rate 0         generate as fast as possible\n\
+    --duration N     stop after N simulated seconds (default: run until stopped)\n\
+    --seed N         same seed, same events (default 0)\n\
+    --stdout         print JSON lines instead of publishing to the event channel";
+
+/// Simulated time generated per step; events are published in order within it
+const STEP_SECONDS: f64 = 1.0;
+
+struct Args {
+    profile: Profile,
+    rate: f64,
+    duration: Option<f64>,
+    seed: u64,
+    stdout: bool,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        profile: Profile::Office,
+        rate: 1.0,
+        duration: None,
+        seed: 0,
+        stdout: false,
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().with_context(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--profile" => args.profile = value()?.parse()?,
+            "--rate" => {
+                let value = value()?;
+                args.rate = value.parse().with_context(|| format!("Invalid --rate {:?}", value))?;
+                if !args.rate.is_finite() || args.rate < 0.0 {
+                    bail!("--rate must be a non-negative number (got {})", args.rate);
+                }
+            }
+            "--duration" => {
+                let value = value()?;
+                let duration: f64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid --duration {:?}", value))?;
+                if !duration.is_finite() || duration <= 0.0 {
+                    bail!("--duration must be a positive number (got {})", duration);
+                }
+                args.duration = Some(duration);
+            }
+            "--seed" => {
+                let value = value()?;
+                args.seed = value.parse().with_context(|| format!("Invalid --seed {:?}", value))?;
+            }
+            "--stdout" => args.stdout = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => bail!("unexpected argument {:?}\n{}", arg, USAGE),
+        }
+    }
+    Ok(args)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    init_logging();
+    let args = parse_args()?;
+    let config = Config::load()?;
+
+    let bus = if args.stdout {
+        None
+    } else {
+        let redis = Arc::new(RedisClient::new(&config).await?);
+        Some(event_bus::connect(&config, redis).await?)
+    };
+    let event_channel = config.event_channel();
+
+    let start_us = SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64;
+    let mut generator = Generator::new(args.profile, args.seed, start_us)
+        .with_dev_mode(config.dev_mode)
+        .with_user_id(config.user_id.clone());
+    info!(
+        "Generating {:?} events (seed {}) at rate {} to {}",
+        args.profile,
+        args.seed,
+        args.rate,
+        if args.stdout { "stdout" } else { &event_channel }
+    );
+
+    let started = Instant::now();
+    let mut out = std::io::stdout().lock();
+    let mut generated = 0;
+    let end = args.duration.unwrap_or(f64::INFINITY);
+    let mut horizon = 0.0;
+    while horizon < end {
+        horizon = (horizon + STEP_SECONDS).min(end);
+        for event in generator.advance(horizon) {
+            if args.rate > 0.0 {
+                let offset = (event.ts - start_us) as f64 / 1_000_000.0 / args.rate;
+                sleep_until(started + Duration::from_secs_f64(offset)).await;
+            }
+            match &bus {
+                Some(bus) => bus.publish(&event_channel, &serde_json::to_value(&event)?).await?,
+                None => writeln!(out, "{}", serde_json::to_string(&event)?)?,
+            }
+            generated += 1;
+        }
+        out.flush()?;
+    }
+
+    info!("Generated {} events", generated);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use std::str::FromStr;
+
+/// Kind of user activity the generator imitates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Prose typing, precise pointing with clicks and scrolling, regular app switches
+    Office,
+    /// Held movement keys, fast flicks and rapid clicks, one app in focus
+    Gaming,
+    /// Occasional keys and mouse nudges with long gaps
+    Idle,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "office" => Ok(Profile::Office),
+            "gaming" => Ok(Profile::Gaming),
+            "idle" => Ok(Profile::Idle),
+            other => anyhow::bail!("unknown profile {:?} (expected office, gaming or idle)", other),
+        }
+    }
+}
+
+/// How the keyboard stream picks its keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Typing {
+    /// Letters from a character Markov chain, words separated by spaces, with typos
+    Prose,
+    /// Movement and action keys, often the same one again
+    Movement,
+}
+
+/// Distribution parameters behind a profile; times in seconds unless noted
+#[derive(Clone, Debug)]
+pub struct Params {
+    pub typing: Typing,
+    /// Key hold time (ms), mean and standard deviation
+    pub dwell_ms: (f64, f64),
+    /// Press-to-press gap within a burst (ms), mean and standard deviation
+    pub flight_ms: (f64, f64),
+    /// Keys per burst of typing
+    pub burst_keys: (usize, usize),
+    /// Gap between typing bursts
+    pub burst_pause: (f64, f64),
+    /// Fitts' law intercept and slope: movement time = a + b * log2(distance / width + 1)
+    pub fitts_a: f64,
+    pub fitts_b: f64,
+    /// Width (px) of the targets the cursor moves to
+    pub target_width: f64,
+    /// Stillness between mouse moves
+    pub move_pause: (f64, f64),
+    /// Chance a move ends in a click
+    pub click_probability: f64,
+    /// Chance of a scroll burst after a move
+    pub scroll_probability: f64,
+    /// Time an app keeps focus
+    pub app_dwell: (f64, f64),
+    /// `(app_name, window_class)` pairs to switch between
+    pub apps: &'static [(&'static str, &'static str)],
+}
+
+const OFFICE_APPS: &[(&str, &str)] = &[
+    ("firefox", "Navigator"),
+    ("code", "Code"),
+    ("slack", "Slack"),
+    ("gnome-terminal", "Gnome-terminal"),
+];
+
+const GAMING_APPS: &[(&str, &str)] = &[("steam_app_570", "dota2"), ("discord", "discord")];
+
+impl Profile {
+    pub fn params(self) -> Params {
+        match self {
+            Profile::Office => Params {
+                typing: Typing::Prose,
+                dwell_ms: (95.0, 20.0),
+                flight_ms: (165.0, 45.0),
+                burst_keys: (20, 80),
+                burst_pause: (0.5, 4.0),
+                fitts_a: 0.10,
+                fitts_b: 0.15,
+                target_width: 24.0,
+                move_pause: (0.3, 2.0),
+                click_probability: 0.6,
+                scroll_probability: 0.15,
+                app_dwell: (20.0, 90.0),
+                apps: OFFICE_APPS,
+            },
+            Profile::Gaming => Params {
+                typing: Typing::Movement,
+                dwell_ms: (220.0, 120.0),
+                flight_ms: (90.0, 40.0),
+                burst_keys: (10, 40),
+                burst_pause: (0.2, 1.5),
+                fitts_a: 0.05,
+                fitts_b: 0.07,
+                target_width: 60.0,
+                move_pause: (0.05, 0.4),
+                click_probability: 0.5,
+                scroll_probability: 0.02,
+                app_dwell: (120.0, 600.0),
+                apps: GAMING_APPS,
+            },
+            Profile::Idle => Params {
+                typing: Typing::Prose,
+                dwell_ms: (100.0, 25.0),
+                flight_ms: (200.0, 60.0),
+                burst_keys: (1, 5),
+                burst_pause: (10.0, 40.0),
+                fitts_a: 0.12,
+                fitts_b: 0.18,
+                target_width: 24.0,
+                move_pause: (5.0, 20.0),
+                click_probability: 0.2,
+                scroll_probability: 0.05,
+                app_dwell: (60.0, 300.0),
+                apps: OFFICE_APPS,
+            },
+        }
+    }
+}